@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Benchmarks of the process events handling stages: decoding the netlink
+//! messages, resolving the executed files and formatting the events.
+
+use std::ffi::OsString;
+
+use copes::{
+    io::connector::{self, RawMessage},
+    solver::{self, PCmdLine, PEvent, PExe, Quoting, Resolvers, WineExecutables, PID},
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+/// Returns a netlink message with an exec event of the process `pid`, as sent
+/// by the process events connector.
+fn exec_message(pid: u32) -> RawMessage {
+    // nlmsghdr: len, type (NLMSG_DONE), flags, seq, pid
+    let mut data = Vec::new();
+    data.extend(76u32.to_ne_bytes());
+    data.extend(3u16.to_ne_bytes());
+    data.extend(0u16.to_ne_bytes());
+    data.extend(0u32.to_ne_bytes());
+    data.extend(0u32.to_ne_bytes());
+    // cn_msg: id (CN_IDX_PROC, CN_VAL_PROC), seq, ack, len, flags
+    data.extend(1u32.to_ne_bytes());
+    data.extend(1u32.to_ne_bytes());
+    data.extend(0u32.to_ne_bytes());
+    data.extend(0u32.to_ne_bytes());
+    data.extend(40u16.to_ne_bytes());
+    data.extend(0u16.to_ne_bytes());
+    // proc_event: what (PROC_EVENT_EXEC), cpu, timestamp_ns, pid, tgid
+    data.extend(2u32.to_ne_bytes());
+    data.extend(0u32.to_ne_bytes());
+    data.extend(123_456_789u64.to_ne_bytes());
+    data.extend(pid.to_ne_bytes());
+    data.extend(pid.to_ne_bytes());
+    data.resize(76, 0);
+    RawMessage::from(data)
+}
+
+fn cmdline(args: &[&str]) -> PCmdLine {
+    PCmdLine::from(args.iter().map(OsString::from).collect::<Vec<_>>())
+}
+
+fn decode(c: &mut Criterion) {
+    let message = exec_message(1234);
+
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Bytes(message.as_bytes().len() as u64));
+    group.bench_function("event", |b| {
+        b.iter(|| connector::decode_event(black_box(message.as_bytes())))
+    });
+    group.bench_function("headers", |b| b.iter(|| black_box(&message).headers()));
+    group.finish();
+}
+
+fn resolve(c: &mut Criterion) {
+    let resolvers = Resolvers::with_builtin(WineExecutables::default());
+    let native = cmdline(&["/usr/bin/game", "--fullscreen"]);
+    let wine = cmdline(&[
+        "C:\\windows\\system32\\start.exe",
+        "/unix",
+        "/home/user/Games/Control/Control.exe",
+    ]);
+    let wine_exe = PExe::from(OsString::from("wine64-preloader"));
+
+    let mut group = c.benchmark_group("resolve");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("native", |b| {
+        b.iter(|| {
+            let pexe = PExe::from(OsString::from("game"));
+            solver::get_process_executed_file(pexe, black_box(&native), &resolvers)
+        })
+    });
+    group.bench_function("wine", |b| {
+        b.iter(|| solver::get_process_executed_file(wine_exe.clone(), black_box(&wine), &resolvers))
+    });
+    group.finish();
+}
+
+fn format(c: &mut Criterion) {
+    let event = PEvent::exec(PID::from(1234));
+    let cmdline = cmdline(&["/usr/bin/game", "--name", "My Game", "--fullscreen"]);
+
+    let mut group = c.benchmark_group("format");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("event", |b| {
+        b.iter(|| format!("{} game", black_box(&event)))
+    });
+    group.bench_function("event_with_cmdline", |b| {
+        b.iter(|| {
+            format!(
+                "{} game {}",
+                black_box(&event),
+                black_box(&cmdline).display(Quoting::Shell)
+            )
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, resolve, format);
+criterion_main!(benches);
@@ -6,10 +6,41 @@ extern crate bindgen;
 use std::env;
 use std::path::PathBuf;
 
+/// Architectures with checked-in bindings, on `src/io/connector/cnproc`.
+const VENDORED_BINDINGS_ARCHS: [&str; 2] = ["x86_64", "aarch64"];
+
 fn main() {
     println!("cargo:rerun-if-changed=cnproc_wrapper.h");
 
-    let cnproc_bindings = bindgen::Builder::default()
+    // The process events connector is only available on Linux.
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("linux") {
+        return;
+    }
+
+    // The checked-in bindings don't need the target kernel headers, which
+    // are usually missing on cross builds.
+    if env::var_os("CARGO_FEATURE_BINDINGS_VENDORED").is_some() {
+        let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+        if !VENDORED_BINDINGS_ARCHS.contains(&arch.as_str()) {
+            panic!(
+                "No vendored cnproc bindings for the {} architecture, build without the bindings-vendored feature",
+                arch
+            );
+        }
+        return;
+    }
+
+    let mut builder = bindgen::Builder::default();
+    // Kernel headers are installed along the glibc ones, so the
+    // architecture specific ones (`asm/...`) are not found on the musl
+    // include paths. Look for them on the glibc multiarch directory as a
+    // last resort.
+    if env::var("CARGO_CFG_TARGET_ENV").as_deref() == Ok("musl") {
+        let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+        builder = builder.clang_args(["-idirafter", &format!("/usr/include/{}-linux-gnu", arch)]);
+    }
+
+    let cnproc_bindings = builder
         .header("cnproc_wrapper.h")
         .parse_callbacks(Box::new(bindgen::CargoCallbacks))
         .use_core()
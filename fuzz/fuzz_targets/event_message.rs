@@ -0,0 +1,15 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Decodes arbitrary bytes as datagrams of process events messages.
+
+#![no_main]
+
+use copes::io::connector::{decode_event, messages};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for message in messages(data).flatten() {
+        let _ = decode_event(message);
+    }
+});
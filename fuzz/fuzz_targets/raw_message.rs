@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Decodes arbitrary bytes as netlink messages received by the process events
+//! connector.
+
+#![no_main]
+
+use copes::io::connector::RawMessage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let message = RawMessage::from(data.to_vec());
+    let _ = message.headers();
+    let _ = message.to_string();
+});
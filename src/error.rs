@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Error type of the library.
+
+use std::{fmt, io, path::PathBuf, time::Duration};
+
+#[cfg(target_os = "linux")]
+use crate::io::proc;
+use crate::solver::PID;
+
+/// A specialized [`Result`](std::result::Result) type for the library
+/// operations.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned by the library operations.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The process events connector couldn't be set up.
+    NetlinkSetup(io::Error),
+    /// A message couldn't be received from the process events connector.
+    SocketRecv(io::Error),
+    /// A file of a process couldn't be read.
+    ProcRead {
+        pid: PID,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A file of a process doesn't have the expected contents.
+    ParseError {
+        pid: PID,
+        path: PathBuf,
+        reason: &'static str,
+    },
+    /// A message received from the process events connector is malformed.
+    MalformedMessage { reason: &'static str },
+    /// The process events connector reported an error.
+    Netlink(io::Error),
+    /// Process events were lost, as they couldn't be delivered in time.
+    EventsLost,
+    /// The process events connector was set up again after failing, taking
+    /// the given number of `attempts`. The process events sent during the
+    /// `downtime` were lost.
+    Reconnected { attempts: u32, downtime: Duration },
+    /// Any other I/O error.
+    Io(io::Error),
+}
+
+impl Error {
+    /// Returns the I/O error that caused this error, if any.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            Error::NetlinkSetup(error)
+            | Error::SocketRecv(error)
+            | Error::ProcRead { source: error, .. }
+            | Error::Netlink(error)
+            | Error::Io(error) => Some(error),
+            Error::ParseError { .. }
+            | Error::MalformedMessage { .. }
+            | Error::EventsLost
+            | Error::Reconnected { .. } => None,
+        }
+    }
+
+    /// Returns the kind of the I/O error that caused this error, or
+    /// [`io::ErrorKind::InvalidData`] for errors not caused by I/O errors.
+    pub fn kind(&self) -> io::ErrorKind {
+        self.io_error()
+            .map_or(io::ErrorKind::InvalidData, io::Error::kind)
+    }
+
+    /// Returns `true` when this error was caused by reading the files of a
+    /// process that no longer exists.
+    pub fn is_process_gone(&self) -> bool {
+        match self {
+            // The files of the processes are only read on Linux
+            #[cfg(target_os = "linux")]
+            Error::ProcRead { source, .. } => proc::is_process_gone(source),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` when this error reports that process events were lost.
+    pub fn is_events_lost(&self) -> bool {
+        match self {
+            Error::EventsLost | Error::Reconnected { .. } => true,
+            Error::SocketRecv(error) => error.raw_os_error() == Some(libc::ENOBUFS),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` when this error was caused by a failure of the process
+    /// events connector socket, which must be set up again to keep receiving
+    /// process events.
+    pub fn is_connection_broken(&self) -> bool {
+        match self {
+            Error::SocketRecv(error) => {
+                error.raw_os_error() != Some(libc::ENOBUFS)
+                    && !matches!(
+                        error.kind(),
+                        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+                    )
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NetlinkSetup(_) => write!(f, "Couldn't set up the process events connector"),
+            Error::SocketRecv(_) => write!(f, "Couldn't receive process events"),
+            Error::ProcRead { pid, path, .. } => {
+                write!(f, "Couldn't read {} of process {}", path.display(), pid)
+            }
+            Error::ParseError { pid, path, reason } => write!(
+                f,
+                "Couldn't parse {} of process {}: {}",
+                path.display(),
+                pid,
+                reason
+            ),
+            Error::MalformedMessage { reason } => {
+                write!(f, "Received a malformed process events message: {}", reason)
+            }
+            Error::Netlink(_) => write!(f, "The process events connector reported an error"),
+            Error::EventsLost => write!(f, "Process events were lost"),
+            Error::Reconnected { attempts, downtime } => write!(
+                f,
+                "Reconnected to the process events connector after {} attempts, process \
+                 events of the last {:.1}s were lost",
+                attempts,
+                downtime.as_secs_f64()
+            ),
+            Error::Io(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::NetlinkSetup(error)
+            | Error::SocketRecv(error)
+            | Error::ProcRead { source: error, .. }
+            | Error::Netlink(error) => Some(error),
+            Error::Io(error) => error.source(),
+            Error::ParseError { .. }
+            | Error::MalformedMessage { .. }
+            | Error::EventsLost
+            | Error::Reconnected { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::*;
+
+    fn proc_read_error(kind: io::ErrorKind) -> Error {
+        Error::ProcRead {
+            pid: PID::from(1234),
+            path: PathBuf::from("/proc/1234/exe"),
+            source: io::Error::from(kind),
+        }
+    }
+
+    #[test]
+    fn error_display_gives_context() {
+        let error = proc_read_error(io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            "Couldn't read /proc/1234/exe of process 1234",
+            error.to_string()
+        );
+        assert!(error.source().is_some());
+
+        let error = Error::ParseError {
+            pid: PID::from(1234),
+            path: PathBuf::from("/proc/1234/stat"),
+            reason: "no command name",
+        };
+        assert_eq!(
+            "Couldn't parse /proc/1234/stat of process 1234: no command name",
+            error.to_string()
+        );
+        assert!(error.source().is_none());
+    }
+
+    #[test]
+    fn error_kind_is_the_io_error_kind() {
+        assert_eq!(
+            io::ErrorKind::PermissionDenied,
+            proc_read_error(io::ErrorKind::PermissionDenied).kind()
+        );
+        assert_eq!(
+            io::ErrorKind::WouldBlock,
+            Error::SocketRecv(io::Error::from(io::ErrorKind::WouldBlock)).kind()
+        );
+        let error = Error::ParseError {
+            pid: PID::from(1234),
+            path: PathBuf::from("/proc/1234/status"),
+            reason: "no user ID",
+        };
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+
+        let error = Error::MalformedMessage {
+            reason: "truncated exec event",
+        };
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!(
+            "Received a malformed process events message: truncated exec event",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn error_is_process_gone_only_for_missing_process_files() {
+        assert!(proc_read_error(io::ErrorKind::NotFound).is_process_gone());
+        assert!(!proc_read_error(io::ErrorKind::PermissionDenied).is_process_gone());
+        assert!(!Error::SocketRecv(io::Error::from(io::ErrorKind::NotFound)).is_process_gone());
+    }
+
+    #[test]
+    fn error_is_events_lost_for_overruns() {
+        assert!(Error::EventsLost.is_events_lost());
+        assert!(Error::SocketRecv(io::Error::from_raw_os_error(libc::ENOBUFS)).is_events_lost());
+        assert!(!Error::SocketRecv(io::Error::from_raw_os_error(libc::EBADF)).is_events_lost());
+        assert!(!Error::Netlink(io::Error::from_raw_os_error(libc::ENOBUFS)).is_events_lost());
+    }
+
+    #[test]
+    fn error_is_connection_broken_for_socket_failures() {
+        assert!(Error::SocketRecv(io::Error::from_raw_os_error(libc::EBADF)).is_connection_broken());
+        assert!(
+            !Error::SocketRecv(io::Error::from_raw_os_error(libc::ENOBUFS)).is_connection_broken()
+        );
+        assert!(
+            !Error::SocketRecv(io::Error::from(io::ErrorKind::Interrupted)).is_connection_broken()
+        );
+        assert!(!Error::EventsLost.is_connection_broken());
+
+        let error = Error::Reconnected {
+            attempts: 2,
+            downtime: Duration::from_millis(1500),
+        };
+        assert!(error.is_events_lost());
+        assert_eq!(
+            "Reconnected to the process events connector after 2 attempts, process events of \
+             the last 1.5s were lost",
+            error.to_string()
+        );
+    }
+}
@@ -0,0 +1,556 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Process events filter expressions.
+//!
+//! Expressions combine predicates on the process events with `&&`, `||`, `!`
+//! and parentheses. The `exec` and `exit` predicates match the event kind,
+//! while the `exe`, `path`, `cmdline`, `cgroup` and `uid` fields are compared
+//! with values:
+//!
+//! ```text
+//! exec && (exe =~ "\\.exe$") && uid != 0
+//! exit || cgroup =~ "^/user.slice/"
+//! (path =~ "^/usr/games/" || uid == 1000) && !exe == "steam"
+//! ```
+//!
+//! Text fields support the `==`, `!=`, `=~` and `!~` operators, the last two
+//! matching regular expressions. The `uid` field supports the `==`, `!=`, `<`,
+//! `<=`, `>` and `>=` operators. Predicates on unknown fields (like the `uid`
+//! of processes that exited before it was read) never match.
+
+use regex::Regex;
+
+/// Kind of a process event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Exec,
+    Exit,
+}
+
+/// Process event fields used by filter predicates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Exe,
+    /// Full path of the process executable.
+    Path,
+    CmdLine,
+    Uid,
+    Cgroup,
+}
+
+/// Process event data evaluated by a [`Filter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event<'a> {
+    pub kind: EventKind,
+    pub exe: &'a str,
+    pub path: Option<&'a str>,
+    pub cmdline: &'a str,
+    pub uid: Option<u32>,
+    pub cgroup: Option<&'a str>,
+}
+
+/// A compiled filter expression.
+#[derive(Debug, Clone)]
+pub struct Filter(Expr);
+
+impl Filter {
+    /// Attempts to compile the filter `expression`.
+    ///
+    /// # Errors
+    ///
+    /// An error describing the problem is returned when the expression is
+    /// invalid.
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            next: 0,
+            end: expression.chars().count() + 1,
+        };
+
+        let expr = parser.or()?;
+        match parser.peek() {
+            Some((position, token)) => Err(format!(
+                "Unexpected {} at position {}",
+                token.describe(),
+                position
+            )),
+            None => Ok(Filter(expr)),
+        }
+    }
+
+    /// Returns `true` when `event` matches the filter.
+    pub fn matches(&self, event: &Event) -> bool {
+        self.0.matches(event)
+    }
+
+    /// Returns `true` when the filter has predicates on `field`.
+    pub fn uses(&self, field: Field) -> bool {
+        self.0.uses(field)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Kind(EventKind),
+    Text(Field, TextPredicate),
+    Uid(CmpOp, u32),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum TextPredicate {
+    Equal(String),
+    NotEqual(String),
+    Matches(Regex),
+    NotMatches(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Equal,
+    NotEqual,
+    Matches,
+    NotMatches,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Expr {
+    fn matches(&self, event: &Event) -> bool {
+        match self {
+            Expr::Kind(kind) => event.kind == *kind,
+            Expr::Text(field, predicate) => {
+                let value = match field {
+                    Field::Exe => Some(event.exe),
+                    Field::Path => event.path,
+                    Field::CmdLine => Some(event.cmdline),
+                    Field::Cgroup => event.cgroup,
+                    Field::Uid => None,
+                };
+                value.is_some_and(|value| predicate.matches(value))
+            }
+            Expr::Uid(op, uid) => event.uid.is_some_and(|value| match op {
+                CmpOp::Equal => value == *uid,
+                CmpOp::NotEqual => value != *uid,
+                CmpOp::Less => value < *uid,
+                CmpOp::LessEqual => value <= *uid,
+                CmpOp::Greater => value > *uid,
+                CmpOp::GreaterEqual => value >= *uid,
+                CmpOp::Matches | CmpOp::NotMatches => false,
+            }),
+            Expr::Not(expr) => !expr.matches(event),
+            Expr::And(lhs, rhs) => lhs.matches(event) && rhs.matches(event),
+            Expr::Or(lhs, rhs) => lhs.matches(event) || rhs.matches(event),
+        }
+    }
+
+    fn uses(&self, field: Field) -> bool {
+        match self {
+            Expr::Kind(_) => false,
+            Expr::Text(text_field, _) => *text_field == field,
+            Expr::Uid(..) => field == Field::Uid,
+            Expr::Not(expr) => expr.uses(field),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.uses(field) || rhs.uses(field),
+        }
+    }
+}
+
+impl TextPredicate {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            TextPredicate::Equal(text) => value == text,
+            TextPredicate::NotEqual(text) => value != text,
+            TextPredicate::Matches(regex) => regex.is_match(value),
+            TextPredicate::NotMatches(regex) => !regex.is_match(value),
+        }
+    }
+}
+
+impl CmpOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CmpOp::Equal => "==",
+            CmpOp::NotEqual => "!=",
+            CmpOp::Matches => "=~",
+            CmpOp::NotMatches => "!~",
+            CmpOp::Less => "<",
+            CmpOp::LessEqual => "<=",
+            CmpOp::Greater => ">",
+            CmpOp::GreaterEqual => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Op(CmpOp),
+    Ident(String),
+    Text(String),
+    Number(u64),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::LParen => "`(`".to_string(),
+            Token::RParen => "`)`".to_string(),
+            Token::And => "`&&`".to_string(),
+            Token::Or => "`||`".to_string(),
+            Token::Not => "`!`".to_string(),
+            Token::Op(op) => format!("`{}`", op.as_str()),
+            Token::Ident(ident) => format!("`{}`", ident),
+            Token::Text(text) => format!("\"{}\"", text),
+            Token::Number(number) => format!("`{}`", number),
+        }
+    }
+}
+
+/// Splits `expression` into tokens, along with their positions (starting
+/// at 1).
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().enumerate().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let position = index + 1;
+        let mut next_is = |expected: char| chars.next_if(|(_, c)| *c == expected).is_some();
+
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '=' if next_is('=') => Token::Op(CmpOp::Equal),
+            '=' if next_is('~') => Token::Op(CmpOp::Matches),
+            '!' if next_is('=') => Token::Op(CmpOp::NotEqual),
+            '!' if next_is('~') => Token::Op(CmpOp::NotMatches),
+            '!' => Token::Not,
+            '<' if next_is('=') => Token::Op(CmpOp::LessEqual),
+            '<' => Token::Op(CmpOp::Less),
+            '>' if next_is('=') => Token::Op(CmpOp::GreaterEqual),
+            '>' => Token::Op(CmpOp::Greater),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => text.push(c),
+                            None => return Err(unterminated_string(position)),
+                        },
+                        Some((_, c)) => text.push(c),
+                        None => return Err(unterminated_string(position)),
+                    }
+                }
+                Token::Text(text)
+            }
+            c if c.is_ascii_digit() => {
+                let mut number = String::from(c);
+                while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    number.push(c);
+                }
+                Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| format!("Invalid number at position {}", position))?,
+                )
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut ident = String::from(c);
+                while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric()) {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            c => return Err(format!("Unexpected `{}` at position {}", c, position)),
+        };
+        tokens.push((position, token));
+    }
+
+    Ok(tokens)
+}
+
+fn unterminated_string(position: usize) -> String {
+    format!("Unterminated string at position {}", position)
+}
+
+/// Recursive descent parser of filter expressions.
+///
+/// `||` has lower precedence than `&&`, which has lower precedence than `!`.
+struct Parser<'a> {
+    tokens: &'a [(usize, Token)],
+    next: usize,
+    /// Position reported for errors at the end of the expression.
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.next)
+    }
+
+    fn take(&mut self) -> Result<(usize, Token), String> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or_else(|| format!("Unexpected end of expression at position {}", self.end))?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn take_if(&mut self, expected: &Token) -> bool {
+        let found = self.peek().is_some_and(|(_, token)| token == expected);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.take_if(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.take_if(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.take_if(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+
+        match self.take()? {
+            (_, Token::LParen) => {
+                let expr = self.or()?;
+                match self.take()? {
+                    (_, Token::RParen) => Ok(expr),
+                    (position, token) => Err(format!(
+                        "Expected `)` but found {} at position {}",
+                        token.describe(),
+                        position
+                    )),
+                }
+            }
+            (_, Token::Ident(ident)) if ident == "exec" => Ok(Expr::Kind(EventKind::Exec)),
+            (_, Token::Ident(ident)) if ident == "exit" => Ok(Expr::Kind(EventKind::Exit)),
+            (position, Token::Ident(ident)) => {
+                let field = match ident.as_str() {
+                    "exe" => Field::Exe,
+                    "path" => Field::Path,
+                    "cmdline" => Field::CmdLine,
+                    "uid" => Field::Uid,
+                    "cgroup" => Field::Cgroup,
+                    _ => {
+                        return Err(format!(
+                            "Unknown field `{}` at position {}",
+                            ident, position
+                        ))
+                    }
+                };
+                self.predicate(field)
+            }
+            (position, token) => Err(format!(
+                "Unexpected {} at position {}",
+                token.describe(),
+                position
+            )),
+        }
+    }
+
+    fn predicate(&mut self, field: Field) -> Result<Expr, String> {
+        let (op_position, op) = match self.take()? {
+            (position, Token::Op(op)) => (position, op),
+            (position, token) => {
+                return Err(format!(
+                    "Expected an operator but found {} at position {}",
+                    token.describe(),
+                    position
+                ))
+            }
+        };
+        let (position, value) = self.take()?;
+
+        match (field, op, value) {
+            (Field::Uid, CmpOp::Matches | CmpOp::NotMatches, _) => Err(format!(
+                "Operator `{}` can't be used with `uid` at position {}",
+                op.as_str(),
+                op_position
+            )),
+            (Field::Uid, op, Token::Number(uid)) => u32::try_from(uid)
+                .map(|uid| Expr::Uid(op, uid))
+                .map_err(|_| format!("Invalid uid at position {}", position)),
+            (Field::Uid, _, token) => Err(format!(
+                "Expected a number but found {} at position {}",
+                token.describe(),
+                position
+            )),
+            (field, op, Token::Text(text)) => {
+                let regex = || {
+                    Regex::new(&text).map_err(|e| {
+                        format!("Invalid regular expression at position {}: {}", position, e)
+                    })
+                };
+                let predicate = match op {
+                    CmpOp::Equal => TextPredicate::Equal(text.clone()),
+                    CmpOp::NotEqual => TextPredicate::NotEqual(text.clone()),
+                    CmpOp::Matches => TextPredicate::Matches(regex()?),
+                    CmpOp::NotMatches => TextPredicate::NotMatches(regex()?),
+                    op => {
+                        return Err(format!(
+                            "Operator `{}` can only be used with `uid` at position {}",
+                            op.as_str(),
+                            op_position
+                        ))
+                    }
+                };
+                Ok(Expr::Text(field, predicate))
+            }
+            (_, _, token) => Err(format!(
+                "Expected a string but found {} at position {}",
+                token.describe(),
+                position
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: EventKind, exe: &'static str) -> Event<'static> {
+        Event {
+            kind,
+            exe,
+            path: Some("/usr/games/game"),
+            cmdline: "",
+            uid: Some(1000),
+            cgroup: Some("/user.slice/user-1000.slice"),
+        }
+    }
+
+    #[test]
+    fn filter_matches_event_kind_and_fields() {
+        let filter = Filter::parse(r#"exec && (exe =~ "\\.exe$") && uid != 0"#).unwrap();
+
+        assert!(filter.matches(&event(EventKind::Exec, "game.exe")));
+        assert!(!filter.matches(&event(EventKind::Exit, "game.exe")));
+        assert!(!filter.matches(&event(EventKind::Exec, "game")));
+        assert!(!filter.matches(&Event {
+            uid: Some(0),
+            ..event(EventKind::Exec, "game.exe")
+        }));
+    }
+
+    #[test]
+    fn filter_applies_operators_precedence() {
+        let filter = Filter::parse(r#"exit || exe == "a" && !cgroup =~ "^/user""#).unwrap();
+
+        assert!(filter.matches(&event(EventKind::Exit, "b")));
+        assert!(!filter.matches(&event(EventKind::Exec, "a")));
+        assert!(filter.matches(&Event {
+            cgroup: Some("/system.slice"),
+            ..event(EventKind::Exec, "a")
+        }));
+    }
+
+    #[test]
+    fn filter_predicates_on_unknown_fields_do_not_match() {
+        let filter = Filter::parse("uid >= 1000").unwrap();
+        assert!(filter.matches(&event(EventKind::Exec, "a")));
+        assert!(!filter.matches(&Event {
+            uid: None,
+            ..event(EventKind::Exec, "a")
+        }));
+
+        let filter = Filter::parse(r#"cgroup != "/""#).unwrap();
+        assert!(!filter.matches(&Event {
+            cgroup: None,
+            ..event(EventKind::Exec, "a")
+        }));
+    }
+
+    #[test]
+    fn filter_combines_path_and_other_fields() {
+        let filter =
+            Filter::parse(r#"(path =~ "^/usr/games/" || uid == 0) && !exe == "steam""#).unwrap();
+
+        assert!(filter.matches(&event(EventKind::Exec, "game")));
+        assert!(!filter.matches(&event(EventKind::Exec, "steam")));
+        assert!(filter.matches(&Event {
+            path: Some("/usr/bin/game"),
+            uid: Some(0),
+            ..event(EventKind::Exec, "game")
+        }));
+        assert!(!filter.matches(&Event {
+            path: None,
+            ..event(EventKind::Exec, "game")
+        }));
+        assert!(filter.uses(Field::Path));
+    }
+
+    #[test]
+    fn filter_reports_used_fields() {
+        let filter = Filter::parse(r#"exec && !(uid == 0 || cmdline =~ "--x")"#).unwrap();
+
+        assert!(filter.uses(Field::Uid));
+        assert!(filter.uses(Field::CmdLine));
+        assert!(!filter.uses(Field::Exe));
+        assert!(!filter.uses(Field::Path));
+        assert!(!filter.uses(Field::Cgroup));
+    }
+
+    #[test]
+    fn filter_parse_fails_on_invalid_expressions() {
+        assert_eq!(
+            Err("Unexpected end of expression at position 8".to_string()),
+            Filter::parse("exec &&").map(|_| ())
+        );
+        assert_eq!(
+            Err("Unknown field `user` at position 1".to_string()),
+            Filter::parse("user == 0").map(|_| ())
+        );
+        assert_eq!(
+            Err("Expected a number but found \"root\" at position 8".to_string()),
+            Filter::parse(r#"uid == "root""#).map(|_| ())
+        );
+        assert_eq!(
+            Err("Operator `<` can only be used with `uid` at position 5".to_string()),
+            Filter::parse(r#"exe < "a""#).map(|_| ())
+        );
+        assert_eq!(
+            Err("Expected `)` but found `exit` at position 7".to_string()),
+            Filter::parse("(exec exit)").map(|_| ())
+        );
+        assert!(Filter::parse(r#"exe =~ "(""#).is_err());
+        assert_eq!(
+            Err("Unterminated string at position 8".to_string()),
+            Filter::parse(r#"exe == "a"#).map(|_| ())
+        );
+        assert_eq!(
+            Err("Unterminated string at position 8".to_string()),
+            Filter::parse(r#"exe == "abc\"#).map(|_| ())
+        );
+    }
+}
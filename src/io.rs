@@ -3,7 +3,45 @@
 
 //! Input / Output utilities and related modules.
 
+#[cfg(target_os = "linux")]
+pub mod clock;
+pub mod config;
+#[cfg(target_os = "linux")]
 pub mod connector;
+#[cfg(unix)]
+pub mod control;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(all(windows, feature = "etw"))]
+pub mod etw;
+#[cfg(unix)]
+pub mod exe_list;
+#[cfg(unix)]
+pub mod hash;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub mod kqueue;
+#[cfg(all(unix, feature = "otlp"))]
+pub mod otlp;
+#[cfg(target_os = "linux")]
+pub mod probe;
+#[cfg(target_os = "linux")]
 pub mod proc;
-
-mod socket;
+pub mod receiver;
+pub mod rotating_file;
+#[cfg(unix)]
+pub mod security;
+#[cfg(unix)]
+pub mod signal;
+#[cfg(unix)]
+pub mod sink;
+#[cfg(target_os = "linux")]
+pub mod socket;
+pub mod source;
+pub mod steam;
+#[cfg(all(unix, feature = "systemd"))]
+pub mod systemd;
+#[cfg(unix)]
+pub mod terminal;
+#[cfg(unix)]
+pub mod users;
+pub mod waker;
@@ -0,0 +1,307 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Clocks for the timestamps of the process events and the lifetimes of the
+//! processes.
+//!
+//! The kernel timestamps the process events with the monotonic clock, which
+//! stops while the system is suspended, so processes running across a
+//! suspend seem to live shorter than they did. The boot time clock keeps
+//! counting while suspended, and the realtime clock is the wall clock, which
+//! can be set at any time.
+//!
+//! Wall clock times are formatted as dates with a [`TimeFormat`].
+
+use std::{
+    ffi::CString,
+    fmt, mem,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// Format of the dates in UTC when no other format is given, RFC 3339 with
+/// microseconds.
+pub const DEFAULT_UTC_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S.%fZ";
+
+/// Maximum length of a formatted date.
+const MAX_TIME_LENGTH: usize = 4096;
+
+/// A system clock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Clock {
+    /// Time since boot, without the time the system was suspended.
+    #[default]
+    Monotonic,
+    /// Wall clock time, since the Unix epoch.
+    Realtime,
+    /// Time since boot, including the time the system was suspended.
+    Boottime,
+}
+
+impl Clock {
+    /// Returns the current time of the clock, since its epoch.
+    pub fn time(self) -> Duration {
+        let id = match self {
+            Clock::Monotonic => libc::CLOCK_MONOTONIC,
+            Clock::Realtime => libc::CLOCK_REALTIME,
+            Clock::Boottime => libc::CLOCK_BOOTTIME,
+        };
+        // Safety: `libc::timespec` is a C structure, so it's safe to
+        // initialize it to zero. Calling the ffi function with a pointer to
+        // it is safe.
+        let time = unsafe {
+            let mut time: libc::timespec = mem::zeroed();
+            // It only fails with invalid clocks.
+            libc::clock_gettime(id, &mut time);
+            time
+        };
+        Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+    }
+
+    /// Converts a `timestamp` of the monotonic clock, like the ones of the
+    /// process events records, to this clock.
+    ///
+    /// The current offset between both clocks is used, so timestamps from
+    /// before the last suspend or clock change are off by its duration.
+    pub fn convert_monotonic(self, timestamp: Duration) -> Duration {
+        match self {
+            Clock::Monotonic => timestamp,
+            clock => {
+                let monotonic = Clock::Monotonic.time();
+                let time = clock.time();
+                (timestamp + time).saturating_sub(monotonic)
+            }
+        }
+    }
+}
+
+impl FromStr for Clock {
+    type Err = String;
+
+    /// Parses the `mono`, `real` and `boottime` clock names.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mono" => Ok(Clock::Monotonic),
+            "real" => Ok(Clock::Realtime),
+            "boottime" => Ok(Clock::Boottime),
+            _ => Err(format!(
+                "Unknown clock '{}', expected mono, real or boottime",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Clock::Monotonic => write!(f, "mono"),
+            Clock::Realtime => write!(f, "real"),
+            Clock::Boottime => write!(f, "boottime"),
+        }
+    }
+}
+
+/// Instants that advance along with a clock, so the durations measured with
+/// them follow that clock instead of the monotonic one.
+///
+/// They can only be compared with other instants of the same
+/// `ClockInstants`.
+#[derive(Debug, Clone, Copy)]
+pub struct ClockInstants {
+    clock: Clock,
+    origin: Instant,
+    origin_time: Duration,
+}
+
+impl ClockInstants {
+    /// Creates instants that advance along with `clock`.
+    pub fn new(clock: Clock) -> Self {
+        ClockInstants {
+            clock,
+            origin: Instant::now(),
+            origin_time: clock.time(),
+        }
+    }
+
+    /// Returns the clock the instants advance along with.
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    /// Returns the current instant.
+    pub fn now(&self) -> Instant {
+        let time = self.clock.time();
+        match time.checked_sub(self.origin_time) {
+            Some(elapsed) => self.origin + elapsed,
+            // The realtime clock was set back.
+            None => self
+                .origin
+                .checked_sub(self.origin_time - time)
+                .unwrap_or(self.origin),
+        }
+    }
+}
+
+/// Format of the wall clock times as dates, on the local time zone or in
+/// UTC.
+///
+/// The format is given with the `strftime` conversion specifications, like
+/// `%Y-%m-%d %H:%M:%S`, plus `%f` for the microseconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeFormat {
+    format: String,
+    utc: bool,
+}
+
+impl TimeFormat {
+    /// Creates a time format from the `strftime` `format`, formatting the
+    /// dates in UTC instead of in the local time zone when `utc` is `true`.
+    pub fn new(format: impl Into<String>, utc: bool) -> Self {
+        TimeFormat {
+            format: format.into(),
+            utc,
+        }
+    }
+
+    /// Formats the wall clock `time`, since the Unix epoch. An empty string is
+    /// returned when the date is too long, or out of range.
+    pub fn format(&self, time: Duration) -> String {
+        let format = microseconds_format(&self.format, time.subsec_micros());
+        let Ok(format) = CString::new(format) else {
+            return String::new();
+        };
+        let Ok(seconds) = libc::time_t::try_from(time.as_secs()) else {
+            return String::new();
+        };
+
+        // Safety: `libc::tm` is a C structure, so it's safe to initialize it
+        // to zero. Calling the ffi functions with pointers to it and to the
+        // seconds is safe.
+        let mut date: libc::tm = unsafe { mem::zeroed() };
+        let date = unsafe {
+            match self.utc {
+                true => libc::gmtime_r(&seconds, &mut date),
+                false => libc::localtime_r(&seconds, &mut date),
+            }
+        };
+        if date.is_null() {
+            return String::new();
+        }
+
+        // `strftime` returns zero both for empty dates and for too small
+        // buffers, so the buffer grows until the limit.
+        let mut buffer = vec![0u8; 64];
+        loop {
+            // Safety: Calling the ffi function with a pointer to buffer is
+            // safe as it has the given size. The format is null terminated
+            // and the date was initialized above.
+            let length = unsafe {
+                libc::strftime(
+                    buffer.as_mut_ptr() as *mut libc::c_char,
+                    buffer.len(),
+                    format.as_ptr(),
+                    date,
+                )
+            };
+            if length > 0 || buffer.len() >= MAX_TIME_LENGTH {
+                buffer.truncate(length);
+                return String::from_utf8_lossy(&buffer).into_owned();
+            }
+            buffer.resize(buffer.len() * 2, 0);
+        }
+    }
+}
+
+/// Replaces the `%f` conversion specifications of the `strftime` `format`
+/// with the `microseconds`.
+fn microseconds_format(format: &str, microseconds: u32) -> String {
+    let mut replaced = String::with_capacity(format.len());
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            replaced.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('f') => replaced.push_str(&format!("{:06}", microseconds)),
+            Some(specifier) => {
+                replaced.push('%');
+                replaced.push(specifier);
+            }
+            None => replaced.push('%'),
+        }
+    }
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clocks_are_parsed_from_their_names() {
+        for clock in [Clock::Monotonic, Clock::Realtime, Clock::Boottime] {
+            assert_eq!(Ok(clock), clock.to_string().parse());
+        }
+        assert!("tai".parse::<Clock>().is_err());
+    }
+
+    #[test]
+    fn boot_time_includes_the_monotonic_time() {
+        let monotonic = Clock::Monotonic.time();
+        assert!(Clock::Boottime.time() >= monotonic);
+        assert!(Clock::Boottime.convert_monotonic(monotonic) >= monotonic);
+        assert_eq!(monotonic, Clock::Monotonic.convert_monotonic(monotonic));
+    }
+
+    #[test]
+    fn monotonic_timestamps_are_converted_to_the_wall_clock() {
+        let monotonic = Clock::Monotonic.time();
+        let realtime = Clock::Realtime.convert_monotonic(monotonic);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        assert!(now.max(realtime) - now.min(realtime) < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn times_are_formatted_as_dates() {
+        let time = Duration::new(1_700_000_000, 123_456_789);
+        assert_eq!(
+            "2023-11-14T22:13:20.123456Z",
+            TimeFormat::new(DEFAULT_UTC_TIME_FORMAT, true).format(time)
+        );
+        assert_eq!(
+            "22:13 +0000 100%f",
+            TimeFormat::new("%H:%M %z 100%%f", true).format(time)
+        );
+        assert_eq!("", TimeFormat::new("", true).format(time));
+    }
+
+    #[test]
+    fn local_dates_are_shifted_by_the_time_zone_offset() {
+        let time = Duration::from_secs(1_700_000_000);
+        let local = TimeFormat::new("%s %z", false).format(time);
+        // `%s` is the local time converted back to seconds since the epoch.
+        assert!(local.starts_with("1700000000 "), "{}", local);
+    }
+
+    #[test]
+    fn microseconds_are_only_formatted_on_conversion_specifications() {
+        assert_eq!("12.000042", microseconds_format("12.%f", 42));
+        assert_eq!("%%f %H %", microseconds_format("%%f %H %", 42));
+    }
+
+    #[test]
+    fn clock_instants_advance_along_with_the_clock() {
+        let instants = ClockInstants::new(Clock::Boottime);
+        assert_eq!(Clock::Boottime, instants.clock());
+
+        let start = instants.now();
+        std::thread::sleep(Duration::from_millis(10));
+        let elapsed = instants.now().duration_since(start);
+        assert!(elapsed >= Duration::from_millis(10));
+        assert!(elapsed < Duration::from_secs(5));
+    }
+}
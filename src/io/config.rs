@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Utilities to read configuration files.
+//!
+//! Configuration files set default values for the command line options. Each
+//! line contains a long option name and its value, in the form `option =
+//! value`. Flags are enabled with `option = true`, or just `option`. Empty
+//! lines and lines starting with `#` are ignored.
+//!
+//! ```text
+//! # Hide wine services
+//! hide-wine-services = true
+//!
+//! # Options that can be repeated are set on multiple lines
+//! wine = wine-tkg
+//! wine = /opt/wine-custom/bin/wine-custom
+//! ```
+
+use std::{ffi::OsString, fs, io, path::Path};
+
+/// Attempts to read the command line arguments defined on the configuration
+/// file at `path`.
+///
+/// # Errors
+///
+/// If this function encounters any form of I/O error or the file has an
+/// invalid line, an error variant will be returned.
+pub fn args_reader(path: &Path) -> io::Result<Vec<OsString>> {
+    parse_args(&fs::read_to_string(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn parse_args(data: &str) -> Result<Vec<OsString>, String> {
+    let mut args = Vec::new();
+
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (option, value) = match line.split_once('=') {
+            Some((option, value)) => (option.trim(), Some(unquote(value.trim()))),
+            None => (line, None),
+        };
+        if option.is_empty() || option.starts_with('-') || option.contains(char::is_whitespace) {
+            return Err(format!("Invalid option on line {}: {}", number + 1, line));
+        }
+
+        match value {
+            Some("false") => {}
+            Some("true") | None => args.push(OsString::from(format!("--{}", option))),
+            Some(value) => {
+                args.push(OsString::from(format!("--{}", option)));
+                args.push(OsString::from(value));
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_args_returns_options_with_values() {
+        let data = "wine = wine-tkg\nwine=\"/opt/wine custom/bin/wine\"\n";
+        assert_eq!(
+            Ok(vec![
+                OsString::from("--wine"),
+                OsString::from("wine-tkg"),
+                OsString::from("--wine"),
+                OsString::from("/opt/wine custom/bin/wine"),
+            ]),
+            parse_args(data)
+        );
+    }
+
+    #[test]
+    fn parse_args_returns_enabled_flags() {
+        let data = "helpers = true\nsteam\nno-color = false\n";
+        assert_eq!(
+            Ok(vec![OsString::from("--helpers"), OsString::from("--steam")]),
+            parse_args(data)
+        );
+    }
+
+    #[test]
+    fn parse_args_ignores_comments_and_empty_lines() {
+        let data = "# comment\n\n   # indented comment\nsteam\n";
+        assert_eq!(Ok(vec![OsString::from("--steam")]), parse_args(data));
+    }
+
+    #[test]
+    fn parse_args_returns_error_on_invalid_lines() {
+        assert!(parse_args("--steam").is_err());
+        assert!(parse_args("= value").is_err());
+        assert!(parse_args("no color = true").is_err());
+    }
+}
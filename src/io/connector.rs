@@ -7,282 +7,434 @@
 //! [process events connector]: https://github.com/torvalds/linux/commit/9f46080c41d5f3f7c00b4e169ba4b0b2865258bf
 
 use std::{
+    cell::Cell,
+    collections::VecDeque,
     io,
-    mem::{self, offset_of},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
     time::Duration,
 };
 
 use crate::{
-    io::socket::Socket,
-    solver::{PEvent, PID},
+    io::{
+        socket::{self, Socket},
+        source::ProcessEventSource,
+        waker::{self, Readiness, Waker},
+    },
+    solver::{PEvent, PEventRecord},
+    Error, Result,
 };
 
 #[macro_use]
 mod cnproc;
+mod filter;
+mod message;
+mod raw;
+
+use filter::FilterBuilder;
+pub use message::{decode_event, messages, Messages};
+pub use raw::RawMessage;
+
+/// Size of the buffer used to receive raw messages, big enough for any
+/// message sent by the connector.
+const RAW_MESSAGE_BUFFER_SIZE: usize = 4096;
+
+/// Process events monitored by a [`ProcessEventsConnector`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MonitoredEvents {
+    /// Exec and exit events.
+    #[default]
+    All,
+    /// Only exec events.
+    Exec,
+    /// Only exit events.
+    Exit,
+}
+
+/// Default maximum time that the events iterator waits for new events.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
 
 /// A connector to monitor process events.
-pub struct ProcessEventsConnector(Socket);
+pub struct ProcessEventsConnector {
+    socket: Socket,
+    events: Option<MonitoredEvents>,
+    timeout: Cell<Duration>,
+    waker: Option<Waker>,
+    paused: Cell<bool>,
+}
 
 impl ProcessEventsConnector {
     /// Attempts to create a new `ProcessEventsConnector` instance.
     ///
     /// # Errors
     ///
-    /// If this function encounters any form of I/O error, an error variant will
-    /// be returned.
-    pub fn try_new() -> Result<Self, io::Error> {
+    /// Returns an [`Error::NetlinkSetup`] error when the connector can't be
+    /// set up.
+    pub fn try_new() -> Result<Self> {
+        ProcessEventsConnector::try_new_with(MonitoredEvents::All)
+    }
+
+    /// Attempts to create a new `ProcessEventsConnector` instance that only
+    /// receives the given `events`. Other events are dropped by the kernel.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NetlinkSetup`] error when the connector can't be
+    /// set up.
+    pub fn try_new_with(events: MonitoredEvents) -> Result<Self> {
+        ProcessEventsConnector::create(Some(events))
+    }
+
+    /// Attempts to create a new `ProcessEventsConnector` instance without
+    /// socket filter, receiving every message sent to the socket. Use
+    /// [`ProcessEventsConnector::raw_messages`] to read them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NetlinkSetup`] error when the connector can't be
+    /// set up.
+    pub fn try_new_raw() -> Result<Self> {
+        ProcessEventsConnector::create(None)
+    }
+
+    /// Returns an iterator over the raw messages received by the connector.
+    ///
+    /// Like the events iterator, it yields `None` when no messages are
+    /// received within the connector timeout.
+    pub fn raw_messages(&self) -> RawIter<'_> {
+        RawIter(self)
+    }
+
+    /// Returns an iterator over the received events, along with the CPU and
+    /// time at which the kernel reported them.
+    ///
+    /// Like the events iterator, it yields `None` when no events are received
+    /// within the connector timeout, or once its waker is woken up.
+    pub fn records(&self) -> RecordIter<'_> {
+        RecordIter {
+            connector: self,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Attempts to set up the connector again, on a new socket that receives
+    /// the same events with the same timeout. Use it to keep receiving
+    /// process events after a [broken connection] error.
+    ///
+    /// The process events sent while the connector is being set up again are
+    /// lost. A paused connector stays paused.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NetlinkSetup`] error when the connector can't be
+    /// set up. The connector is left unchanged then.
+    ///
+    /// [broken connection]: Error::is_connection_broken
+    pub fn reconnect(&mut self) -> Result<()> {
+        let mut connector = ProcessEventsConnector::create(self.events)?;
+        connector.set_timeout(self.timeout.get())?;
+        connector.waker = self.waker.take();
+        if self.is_paused() {
+            connector.pause()?;
+        }
+        *self = connector;
+
+        Ok(())
+    }
+
+    fn create(events: Option<MonitoredEvents>) -> Result<Self> {
+        ProcessEventsConnector::setup(events).map_err(Error::NetlinkSetup)
+    }
+
+    fn setup(events: Option<MonitoredEvents>) -> io::Result<Self> {
         let socket = Socket::try_new(
             libc::PF_NETLINK,
             libc::SOCK_DGRAM,
             cnproc::NETLINK_CONNECTOR as libc::c_int,
+            false,
         )?;
 
-        let listener = ProcessEventsConnector(socket)
-            .timeout(Duration::from_secs(3))?
-            .install_filter()?
-            .bind()?;
+        let mut connector = ProcessEventsConnector {
+            socket,
+            events,
+            timeout: Cell::new(DEFAULT_TIMEOUT),
+            waker: None,
+            paused: Cell::new(false),
+        }
+        .timeout(DEFAULT_TIMEOUT)?;
+        if let Some(events) = events {
+            connector = connector.install_filter(events)?;
+        }
+        let listener = connector.bind()?;
         listener.subscribe_to_proc_events(true)?;
 
         Ok(listener)
     }
 
     /// Setups the socket bindings.
-    fn bind(self) -> Result<ProcessEventsConnector, io::Error> {
-        // Safety: `libc::sockaddr_nl` is a C structure, so it's safe to
-        // initialize it with zeros.
-        let mut address = unsafe { mem::zeroed::<libc::sockaddr_nl>() };
-        address.nl_pid = 0; // let the kernel handle its value
-        address.nl_family = libc::AF_NETLINK as _;
-        address.nl_groups = cnproc::CN_IDX_PROC;
-
-        // Safety: Calling `Socket::bind` ffi method with a pointer to address
-        // is safe at this point, now that the structure has been allocated and
-        // properly initialized.
-        unsafe {
-            self.0.bind(
-                &address as *const _ as *const _,
-                mem::size_of_val(&address) as _,
-            )?
-        };
+    fn bind(self) -> io::Result<ProcessEventsConnector> {
+        // Let the kernel handle the port ID
+        let address = socket::netlink_address(0, cnproc::CN_IDX_PROC);
+        self.socket.bind_netlink(&address)?;
 
         Ok(self)
     }
 
     /// Setups the socket data receiving timeout.
-    fn timeout(self, duration: Duration) -> Result<ProcessEventsConnector, io::Error> {
-        let duration = libc::timeval {
-            tv_sec: duration.as_secs().clamp(0, i64::MAX as u64) as i64,
-            tv_usec: 0,
-        };
+    fn timeout(self, duration: Duration) -> io::Result<ProcessEventsConnector> {
+        self.socket.set_timeout(duration)?;
+        Ok(self)
+    }
 
-        // Safety: Calling `Socket::set_option` ffi method with a pointer to
-        // duration is safe at this point, now that the structure has been
-        // allocated properly initialized.
-        unsafe {
-            self.0.set_option(
-                libc::SOL_SOCKET,
-                libc::SO_RCVTIMEO,
-                &duration as *const _ as *const _,
-                mem::size_of_val(&duration) as _,
-            )?
-        };
+    /// Sets the maximum time that the events iterator waits for new events.
+    /// When no events are received within this time, the iterator yields
+    /// `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NetlinkSetup`] error when the timeout can't be set.
+    pub fn set_timeout(&self, duration: Duration) -> Result<()> {
+        self.socket
+            .set_timeout(duration)
+            .map_err(Error::NetlinkSetup)?;
+        self.timeout.set(duration);
 
-        Ok(self)
+        Ok(())
     }
 
-    /// Setups the socket filter.
-    fn install_filter(self) -> Result<ProcessEventsConnector, io::Error> {
-        use cnproc::*;
-        use libc::{c_uint, c_ushort};
-
-        type ExecProcEvent = proc_event__bindgen_ty_1_exec_proc_event;
-        type ExitProcEvent = proc_event__bindgen_ty_1_exit_proc_event;
-
-        #[rustfmt::skip]
-        let mut filter = [
-            // Check message from kernel.
-            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, offset_of!(nlmsghdr, nlmsg_pid)),
-            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, 0, 1, 0),
-            bpf_stmt!(BPF_RET | BPF_K, 0x0),
-
-            // Check message type NLMSG_DONE.
-            bpf_stmt!(BPF_LD | BPF_H | BPF_ABS, offset_of!(nlmsghdr, nlmsg_type)),
-            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_ushort::to_be(NLMSG_DONE as c_ushort), 1, 0),
-            bpf_stmt!(BPF_RET | BPF_K, 0x0),
-
-            // Check proc connector event CN_IDX_PROC.
-            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
-                                                offset_of!(cn_msg, id) +
-                                                offset_of!(cb_id, idx)),
-            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_uint::to_be(CN_IDX_PROC), 1, 0),
-            bpf_stmt!(BPF_RET | BPF_K, 0x0),
-
-            // Check proc connector event CN_VAL_PROC.
-            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
-                                                offset_of!(cn_msg, id) +
-                                                offset_of!(cb_id, val)),
-            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_uint::to_be(CN_VAL_PROC), 1, 0),
-            bpf_stmt!(BPF_RET | BPF_K, 0x0),
-
-            // Accept exec messages from processes.
-            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
-                                                offset_of!(cn_msg, data) +
-                                                offset_of!(proc_event, what)),
-            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_uint::to_be(PROCESS_EVENT_EXEC), 0, 6),
-
-            // Processes have process_pid == process_tgid (thread group leaders).
-            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
-                                                offset_of!(cn_msg, data) +
-                                                offset_of!(proc_event, event_data) +
-                                                offset_of!(ExecProcEvent, process_pid)),
-            bpf_stmt!(BPF_ST, 0),
-            bpf_stmt!(BPF_LDX | BPF_W | BPF_MEM, 0),
-            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
-                                                offset_of!(cn_msg, data) +
-                                                offset_of!(proc_event, event_data) +
-                                                offset_of!(ExecProcEvent, process_tgid)),
-            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_X, 0, 0, 9),
-            bpf_stmt!(BPF_RET | BPF_K, 0xffffffff),
-
-            // Accept exit messages from processes.
-            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
-                                                offset_of!(cn_msg, data) +
-                                                offset_of!(proc_event, what)),
-            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_uint::to_be(PROCESS_EVENT_EXIT), 0, 6),
-
-            // Processes have process_pid == process_tgid
-            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
-                                                offset_of!(cn_msg, data) +
-                                                offset_of!(proc_event, event_data) +
-                                                offset_of!(ExitProcEvent, process_pid)),
-            bpf_stmt!(BPF_ST, 0),
-            bpf_stmt!(BPF_LDX | BPF_W | BPF_MEM, 0),
-            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
-                                                offset_of!(cn_msg, data) +
-                                                offset_of!(proc_event, event_data) +
-                                                offset_of!(ExitProcEvent, process_tgid)),
-            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_X, 0, 0, 1),
-            bpf_stmt!(BPF_RET | BPF_K, 0xffffffff),
-
-            // Drop any other messages.
-            bpf_stmt!(BPF_RET | BPF_K, 0x0),
-        ];
-        // Safety: `cnproc::sock_fprog` is a C structure, so it's safe to
-        // initialize it with zeros.
-        let mut fprog = unsafe { mem::zeroed::<sock_fprog>() };
-        fprog.filter = filter.as_mut_ptr();
-        fprog.len = filter.len() as _;
-
-        // Safety: Calling `Socket::set_option` ffi method with a pointer to
-        // fprog is safe at this point, now that the structure and all the
-        // related data have been allocated and properly initialized.
-        unsafe {
-            self.0.set_option(
-                libc::SOL_SOCKET,
-                libc::SO_ATTACH_FILTER,
-                &fprog as *const _ as *const _,
-                mem::size_of_val(&fprog) as _,
-            )?
-        };
+    /// Sets the `waker` that interrupts the waits for new events. Once it's
+    /// woken up, the iterators yield `None` at once.
+    pub fn set_waker(&mut self, waker: Waker) {
+        self.waker = Some(waker);
+    }
 
-        Ok(self)
+    /// Returns `true` when the waker of the connector was woken up.
+    pub fn is_woken(&self) -> bool {
+        self.waker.as_ref().is_some_and(Waker::is_woken)
     }
 
-    /// Subscribe and unsubscribe to proc events.
-    fn subscribe_to_proc_events(&self, subscribe: bool) -> io::Result<()> {
-        use cnproc::*;
+    /// Stops receiving process events until the connector is resumed, while
+    /// keeping its socket. The kernel stops reporting process events when no
+    /// other program listens to them.
+    ///
+    /// Process events received before pausing the connector can still be
+    /// read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NetlinkSetup`] error when the connector can't be
+    /// paused.
+    pub fn pause(&self) -> Result<()> {
+        if self.is_paused() {
+            return Ok(());
+        }
+
+        self.subscribe_to_proc_events(false)
+            .and_then(|_| {
+                self.socket
+                    .set_netlink_membership(cnproc::CN_IDX_PROC, false)
+            })
+            .map_err(Error::NetlinkSetup)?;
+        self.paused.set(true);
 
-        const MSG_SIZE: usize =
-            nlmsg_length(mem::size_of::<cn_msg>() + mem::size_of::<proc_cn_mcast_op>());
-        let mut msg_buffer = [0u8; MSG_SIZE];
-        let msg = msg_buffer.as_mut_ptr() as *mut nlcn_msg<cn_msg>;
+        Ok(())
+    }
 
-        // Safety: Dereferencing msg is safe in this context as it doesn't
-        // outlives msg_buffer and, being the later a fixed size array, there is
-        // no re-allocations that could invalidate the pointer.
+    /// Starts receiving process events again after pausing the connector.
+    /// The process events sent while it was paused are lost.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::NetlinkSetup`] error when the connector can't be
+    /// resumed.
+    pub fn resume(&self) -> Result<()> {
+        if !self.is_paused() {
+            return Ok(());
+        }
+
+        self.socket
+            .set_netlink_membership(cnproc::CN_IDX_PROC, true)
+            .and_then(|_| self.subscribe_to_proc_events(true))
+            .map_err(Error::NetlinkSetup)?;
+        self.paused.set(false);
+
+        Ok(())
+    }
+
+    /// Returns `true` when the connector is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.get()
+    }
+
+    /// Receives a datagram into `buffer`, returning its size.
+    ///
+    /// Fails with an [`io::ErrorKind::WouldBlock`] error when no datagram is
+    /// received within the timeout, or the waker is woken up.
+    fn receive(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        let flags = match &self.waker {
+            Some(waker) => match waker::wait_readable(self.as_fd(), waker, self.timeout.get())? {
+                Readiness::Readable => libc::MSG_DONTWAIT,
+                Readiness::Woken | Readiness::TimedOut => {
+                    return Err(io::ErrorKind::WouldBlock.into())
+                }
+            },
+            None => 0,
+        };
+
+        // Safety: Calling `Socket::receive` ffi method with a pointer to
+        // buffer is safe at this point as the buffer has the given length.
         unsafe {
-            (*msg).nl_hdr.0.nlmsg_pid = 0; // the kernel is handling its value
-            (*msg).nl_hdr.0.nlmsg_type = libc::NLMSG_DONE as _;
-            (*msg).nl_hdr.0.nlmsg_len = MSG_SIZE as _;
-
-            (*msg).cn_msg.id.idx = cnproc::CN_IDX_PROC;
-            (*msg).cn_msg.id.val = cnproc::CN_VAL_PROC;
-            (*msg).cn_msg.len = mem::size_of::<proc_cn_mcast_op>() as _;
-
-            // Safety: Dereferencing (*msg).cn_msg.data as a proc_cn_mcast_op
-            // type is safe in this context as it points to memory allocated on
-            // the msg_buffer.
-            let data = (*msg).cn_msg.data.as_mut_ptr() as *mut proc_cn_mcast_op;
-            *data = if subscribe {
-                proc_cn_mcast_op_PROC_CN_MCAST_LISTEN
-            } else {
-                proc_cn_mcast_op_PROC_CN_MCAST_IGNORE
-            };
+            self.socket
+                .receive(buffer.as_mut_ptr() as *mut _, buffer.len(), flags)
+        }
+    }
+
+    /// Setups the socket filter, accepting only the `events` messages.
+    fn install_filter(self, events: MonitoredEvents) -> io::Result<ProcessEventsConnector> {
+        let filter = match events {
+            MonitoredEvents::All => FilterBuilder::new().accept_exec().accept_exit(),
+            MonitoredEvents::Exec => FilterBuilder::new().accept_exec(),
+            MonitoredEvents::Exit => FilterBuilder::new().accept_exit(),
         }
+        .build();
+        self.socket.attach_filter(&filter)?;
+
+        Ok(self)
+    }
+
+    /// Subscribe and unsubscribe to proc events.
+    fn subscribe_to_proc_events(&self, subscribe: bool) -> io::Result<()> {
+        let msg = message::subscription_message(subscribe);
 
         // Safety: Calling `Socket::send` ffi method with a pointer to msg is
-        // safe at this point as the needed data has been allocated and properly
-        // initialized.
-        unsafe { self.0.send(msg as *const _, MSG_SIZE, 0)? };
+        // safe at this point as it points to an initialized buffer of the
+        // given size.
+        unsafe { self.socket.send(msg.as_ptr() as *const _, msg.len(), 0)? };
 
         Ok(())
     }
 }
 
+impl ProcessEventSource for ProcessEventsConnector {
+    fn records(&self) -> Box<dyn Iterator<Item = Result<PEventRecord>> + '_> {
+        Box::new(ProcessEventsConnector::records(self))
+    }
+
+    fn set_timeout(&self, duration: Duration) -> Result<()> {
+        ProcessEventsConnector::set_timeout(self, duration)
+    }
+
+    fn set_waker(&mut self, waker: Waker) {
+        ProcessEventsConnector::set_waker(self, waker)
+    }
+
+    fn is_woken(&self) -> bool {
+        ProcessEventsConnector::is_woken(self)
+    }
+
+    fn pause(&self) -> Result<()> {
+        ProcessEventsConnector::pause(self)
+    }
+
+    fn resume(&self) -> Result<()> {
+        ProcessEventsConnector::resume(self)
+    }
+
+    fn is_paused(&self) -> bool {
+        ProcessEventsConnector::is_paused(self)
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        ProcessEventsConnector::reconnect(self)
+    }
+}
+
+impl AsFd for ProcessEventsConnector {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.as_fd()
+    }
+}
+
+impl AsRawFd for ProcessEventsConnector {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
 impl Drop for ProcessEventsConnector {
     fn drop(&mut self) {
+        // Paused connectors are already unsubscribed
+        if self.is_paused() {
+            return;
+        }
+
         if let Err(e) = self.subscribe_to_proc_events(false) {
             log::error!("An error occur while unsubscribing from proc events: {}", e);
         }
     }
 }
 
-pub struct Iter<'a>(&'a ProcessEventsConnector);
+pub struct Iter<'a>(RecordIter<'a>);
 
 impl<'a> Iterator for Iter<'a> {
-    type Item = io::Result<PEvent>;
+    type Item = Result<PEvent>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        use cnproc::*;
+        self.0
+            .next()
+            .map(|record| record.map(|record| record.event))
+    }
+}
+
+/// Iterator over the event records received by a connector.
+///
+/// A single datagram can hold several netlink messages. Their events are
+/// yielded one by one, before receiving the next datagram.
+pub struct RecordIter<'a> {
+    connector: &'a ProcessEventsConnector,
+    pending: VecDeque<Result<PEventRecord>>,
+}
 
-        const MSG_SIZE: usize =
-            nlmsg_length(mem::size_of::<cn_msg>() + mem::size_of::<proc_event>());
-        let mut msg_buffer = [0u8; MSG_SIZE];
+impl<'a> Iterator for RecordIter<'a> {
+    type Item = Result<PEventRecord>;
 
-        // Safety: Calling `Socket::receive` ffi method with a pointer to
-        // msg_buffer is safe at this point as the buffer has enough memory to
-        // hold the message.
-        if let Err(error) = unsafe {
-            self.0
-                 .0
-                .receive(msg_buffer.as_mut_ptr() as *mut _, MSG_SIZE, 0)
-        } {
-            let result = match error.kind() {
-                io::ErrorKind::WouldBlock => None,
-                _ => Some(Err(error)),
-            };
-            return result;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(record) = self.pending.pop_front() {
+            return Some(record);
         }
 
-        let msg = msg_buffer.as_ptr() as *const nlcn_msg<cn_msg>;
+        // Messages are received on a buffer bigger than the process event
+        // bindings, so messages from kernels with bigger process events are
+        // not truncated.
+        let mut msg_buffer = [0u8; RAW_MESSAGE_BUFFER_SIZE];
 
-        // Safety: Dereferencing msg is safe in this context as it doesn't
-        // outlives msg_buffer and, being the later a fixed size array, there is
-        // no re-allocations that could invalidate the pointer. Dereferencing
-        // (*msg).cn_msg.data as a proc_cn_mcast_op type is also safe as it
-        // points to memory allocated on the msg_buffer.
-        unsafe {
-            let event = (*msg).cn_msg.data.as_ptr() as *const proc_event;
-
-            match (*event).what {
-                PROCESS_EVENT_EXEC => Some(Ok(PEvent::Exec(PID::from(
-                    (*event).event_data.exec.process_pid,
-                )))),
-                PROCESS_EVENT_EXIT => Some(Ok(PEvent::Exit(PID::from(
-                    (*event).event_data.exit.process_pid,
-                )))),
-                _ => None,
+        let size = match self.connector.receive(&mut msg_buffer) {
+            Ok(size) => size,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => return None,
+            Err(error) => return Some(Err(Error::SocketRecv(error))),
+        };
+
+        let records = message::messages(&msg_buffer[..size])
+            .filter_map(|message| message.and_then(message::decode_event).transpose());
+        self.pending.extend(records);
+        self.pending.pop_front()
+    }
+}
+
+pub struct RawIter<'a>(&'a ProcessEventsConnector);
+
+impl<'a> Iterator for RawIter<'a> {
+    type Item = Result<RawMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buffer = vec![0u8; RAW_MESSAGE_BUFFER_SIZE];
+
+        match self.0.receive(&mut buffer) {
+            Ok(size) => {
+                buffer.truncate(size);
+                Some(Ok(RawMessage::from(buffer)))
             }
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => None,
+            Err(error) => Some(Err(Error::SocketRecv(error))),
         }
     }
 }
@@ -292,6 +444,6 @@ impl<'a> IntoIterator for &'a ProcessEventsConnector {
     type IntoIter = Iter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        Iter(self)
+        Iter(self.records())
     }
 }
@@ -11,16 +11,21 @@
     deref_nullptr
 )]
 
+#[cfg(not(feature = "bindings-vendored"))]
 include!(concat!(env!("OUT_DIR"), "/cnproc_bindings.rs"));
 
-#[repr(C, align(4))] // NLMSG_ALIGNTO == 4
-pub struct nlmsghdr_aligned<T>(pub T);
+/// Checked-in bindings of the target architecture, used instead of the ones
+/// generated from the system headers with the `bindings-vendored` feature.
+#[cfg(any(
+    feature = "bindings-vendored",
+    all(test, any(target_arch = "x86_64", target_arch = "aarch64"))
+))]
+#[cfg_attr(target_arch = "x86_64", path = "cnproc/x86_64.rs")]
+#[cfg_attr(target_arch = "aarch64", path = "cnproc/aarch64.rs")]
+mod vendored;
 
-#[repr(C)]
-pub struct nlcn_msg<T> {
-    pub nl_hdr: nlmsghdr_aligned<libc::nlmsghdr>,
-    pub cn_msg: T,
-}
+#[cfg(feature = "bindings-vendored")]
+pub use vendored::*;
 
 /// Creates a [sock_filter] jump rule.
 ///
@@ -51,7 +56,8 @@ macro_rules! bpf_stmt {
 }
 
 #[inline]
-const fn nlmsg_align(len: usize) -> usize {
+/// Rounds a netlink message length up to the netlink messages alignment.
+pub const fn nlmsg_align(len: usize) -> usize {
     (len + NLMSG_ALIGNTO as usize - 1) & !(NLMSG_ALIGNTO as usize - 1)
 }
 
@@ -71,6 +77,8 @@ pub const fn nlmsg_length(len: usize) -> usize {
 mod tests {
     use std::mem::offset_of;
 
+    use libc::c_uint;
+
     use super::*;
 
     // for testing convenience
@@ -83,6 +91,90 @@ mod tests {
         }
     }
 
+    /// Fails when the vendored bindings no longer match the generated ones,
+    /// so they must be updated.
+    #[test]
+    #[cfg(all(
+        not(feature = "bindings-vendored"),
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    fn vendored_bindings_match_the_generated_ones() {
+        use std::mem::{align_of, size_of};
+
+        macro_rules! assert_same_values {
+            ($($name: ident),+) => {
+                $(assert_eq!($name as u64, vendored::$name as u64, stringify!($name));)+
+            };
+        }
+        macro_rules! assert_same_layout {
+            ($ty: ident $(, $field: ident)*) => {
+                assert_eq!(size_of::<$ty>(), size_of::<vendored::$ty>(), stringify!($ty));
+                assert_eq!(align_of::<$ty>(), align_of::<vendored::$ty>(), stringify!($ty));
+                $(assert_eq!(
+                    offset_of!($ty, $field),
+                    offset_of!(vendored::$ty, $field),
+                    concat!(stringify!($ty), ".", stringify!($field))
+                );)*
+            };
+        }
+
+        assert_same_values!(
+            NETLINK_CONNECTOR,
+            NETLINK_ADD_MEMBERSHIP,
+            NETLINK_DROP_MEMBERSHIP,
+            NLMSG_ALIGNTO,
+            NLMSG_NOOP,
+            NLMSG_ERROR,
+            NLMSG_DONE,
+            NLMSG_OVERRUN,
+            CN_IDX_PROC,
+            CN_VAL_PROC,
+            BPF_LD,
+            BPF_LDX,
+            BPF_ST,
+            BPF_JMP,
+            BPF_RET,
+            BPF_W,
+            BPF_H,
+            BPF_ABS,
+            BPF_MEM,
+            BPF_JEQ,
+            BPF_K,
+            BPF_X,
+            proc_cn_mcast_op_PROC_CN_MCAST_LISTEN,
+            proc_cn_mcast_op_PROC_CN_MCAST_IGNORE,
+            PROCESS_EVENT_EXEC,
+            PROCESS_EVENT_EXIT
+        );
+        assert_same_layout!(sock_filter, code, jt, jf, k);
+        assert_same_layout!(
+            nlmsghdr,
+            nlmsg_len,
+            nlmsg_type,
+            nlmsg_flags,
+            nlmsg_seq,
+            nlmsg_pid
+        );
+        assert_same_layout!(cb_id, idx, val);
+        assert_same_layout!(cn_msg, id, seq, ack, len, flags, data);
+        assert_same_layout!(proc_cn_mcast_op);
+        assert_same_layout!(proc_event, what, cpu, timestamp_ns, event_data);
+        assert_same_layout!(
+            proc_event__bindgen_ty_1_exec_proc_event,
+            process_pid,
+            process_tgid
+        );
+        assert_same_layout!(
+            proc_event__bindgen_ty_1_exit_proc_event,
+            process_pid,
+            process_tgid,
+            exit_code,
+            exit_signal,
+            parent_pid,
+            parent_tgid
+        );
+    }
+
     #[test]
     fn bpf_jump_expansion() {
         assert_eq!(
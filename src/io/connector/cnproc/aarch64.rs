@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+// Bindings of the `cnproc_wrapper.h` items used by the crate on aarch64,
+// following the bindgen output. The
+// `vendored_bindings_match_the_generated_ones` test checks them against the
+// bindings generated from the system headers.
+
+use libc::*;
+
+pub const NETLINK_CONNECTOR: u32 = 11;
+pub const NETLINK_ADD_MEMBERSHIP: u32 = 1;
+pub const NETLINK_DROP_MEMBERSHIP: u32 = 2;
+pub const NLMSG_ALIGNTO: u32 = 4;
+pub const NLMSG_NOOP: u32 = 1;
+pub const NLMSG_ERROR: u32 = 2;
+pub const NLMSG_DONE: u32 = 3;
+pub const NLMSG_OVERRUN: u32 = 4;
+pub const CN_IDX_PROC: u32 = 1;
+pub const CN_VAL_PROC: u32 = 1;
+pub const BPF_LD: u32 = 0;
+pub const BPF_LDX: u32 = 1;
+pub const BPF_ST: u32 = 2;
+pub const BPF_JMP: u32 = 5;
+pub const BPF_RET: u32 = 6;
+pub const BPF_W: u32 = 0;
+pub const BPF_H: u32 = 8;
+pub const BPF_ABS: u32 = 32;
+pub const BPF_MEM: u32 = 96;
+pub const BPF_JEQ: u32 = 16;
+pub const BPF_K: u32 = 0;
+pub const BPF_X: u32 = 8;
+pub type __u8 = c_uchar;
+pub type __u16 = c_ushort;
+pub type __u32 = c_uint;
+pub type __u64 = c_ulonglong;
+pub type __kernel_pid_t = c_int;
+#[repr(C)]
+#[derive(Default)]
+pub struct __IncompleteArrayField<T>(::core::marker::PhantomData<T>, [T; 0]);
+impl<T> ::core::fmt::Debug for __IncompleteArrayField<T> {
+    fn fmt(&self, fmt: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+        fmt.write_str("__IncompleteArrayField")
+    }
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct sock_filter {
+    pub code: __u16,
+    pub jt: __u8,
+    pub jf: __u8,
+    pub k: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct nlmsghdr {
+    pub nlmsg_len: __u32,
+    pub nlmsg_type: __u16,
+    pub nlmsg_flags: __u16,
+    pub nlmsg_seq: __u32,
+    pub nlmsg_pid: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct cb_id {
+    pub idx: __u32,
+    pub val: __u32,
+}
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct cn_msg {
+    pub id: cb_id,
+    pub seq: __u32,
+    pub ack: __u32,
+    pub len: __u16,
+    pub flags: __u16,
+    pub data: __IncompleteArrayField<__u8>,
+}
+pub const proc_cn_mcast_op_PROC_CN_MCAST_LISTEN: proc_cn_mcast_op = 1;
+pub const proc_cn_mcast_op_PROC_CN_MCAST_IGNORE: proc_cn_mcast_op = 2;
+pub type proc_cn_mcast_op = c_uint;
+#[repr(C)]
+#[repr(align(8))]
+#[derive(Copy, Clone)]
+pub struct proc_event {
+    pub what: c_uint,
+    pub cpu: __u32,
+    pub timestamp_ns: __u64,
+    pub event_data: proc_event__bindgen_ty_1,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union proc_event__bindgen_ty_1 {
+    pub exec: proc_event__bindgen_ty_1_exec_proc_event,
+    pub exit: proc_event__bindgen_ty_1_exit_proc_event,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct proc_event__bindgen_ty_1_exec_proc_event {
+    pub process_pid: __kernel_pid_t,
+    pub process_tgid: __kernel_pid_t,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct proc_event__bindgen_ty_1_exit_proc_event {
+    pub process_pid: __kernel_pid_t,
+    pub process_tgid: __kernel_pid_t,
+    pub exit_code: __u32,
+    pub exit_signal: __u32,
+    pub parent_pid: __kernel_pid_t,
+    pub parent_tgid: __kernel_pid_t,
+}
+pub const PROCESS_EVENT_EXEC: c_uint = 2;
+pub const PROCESS_EVENT_EXIT: c_uint = 2147483648;
@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Builder of the [socket filter] program that drops the unwanted messages
+//! in the kernel.
+//!
+//! [socket filter]: https://www.kernel.org/doc/Documentation/networking/filter.txt
+
+use std::mem::offset_of;
+
+use libc::{c_uint, c_ushort, sock_filter};
+
+use super::cnproc::*;
+
+type ExecProcEvent = proc_event__bindgen_ty_1_exec_proc_event;
+type ExitProcEvent = proc_event__bindgen_ty_1_exit_proc_event;
+
+/// Builds a socket filter program accepting process events connector
+/// messages of the selected process events.
+///
+/// Each accepted event is checked by an independent block of rules that
+/// jumps to the next block when the message doesn't match, so blocks can be
+/// added in any order. Messages not accepted by any block are dropped.
+#[derive(Debug)]
+pub struct FilterBuilder(Vec<sock_filter>);
+
+impl FilterBuilder {
+    /// Creates a `FilterBuilder` whose filter only lets through the process
+    /// events connector messages sent by the kernel.
+    pub fn new() -> Self {
+        #[rustfmt::skip]
+        let rules = vec![
+            // Check message from kernel.
+            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, offset_of!(nlmsghdr, nlmsg_pid)),
+            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, 0, 1, 0),
+            bpf_stmt!(BPF_RET | BPF_K, 0x0),
+
+            // Accept error and overrun messages, so they are reported.
+            // Otherwise, check message type NLMSG_DONE.
+            bpf_stmt!(BPF_LD | BPF_H | BPF_ABS, offset_of!(nlmsghdr, nlmsg_type)),
+            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_ushort::to_be(NLMSG_ERROR as c_ushort), 3, 0),
+            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_ushort::to_be(NLMSG_OVERRUN as c_ushort), 2, 0),
+            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_ushort::to_be(NLMSG_DONE as c_ushort), 2, 0),
+            bpf_stmt!(BPF_RET | BPF_K, 0x0),
+            bpf_stmt!(BPF_RET | BPF_K, 0xffffffff),
+
+            // Check proc connector event CN_IDX_PROC.
+            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
+                                                offset_of!(cn_msg, id) +
+                                                offset_of!(cb_id, idx)),
+            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_uint::to_be(CN_IDX_PROC), 1, 0),
+            bpf_stmt!(BPF_RET | BPF_K, 0x0),
+
+            // Check proc connector event CN_VAL_PROC.
+            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
+                                                offset_of!(cn_msg, id) +
+                                                offset_of!(cb_id, val)),
+            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_uint::to_be(CN_VAL_PROC), 1, 0),
+            bpf_stmt!(BPF_RET | BPF_K, 0x0),
+        ];
+
+        FilterBuilder(rules)
+    }
+
+    /// Accepts exec messages from processes.
+    pub fn accept_exec(self) -> Self {
+        self.accept_process_event(
+            PROCESS_EVENT_EXEC,
+            offset_of!(ExecProcEvent, process_pid),
+            offset_of!(ExecProcEvent, process_tgid),
+        )
+    }
+
+    /// Accepts exit messages from processes.
+    pub fn accept_exit(self) -> Self {
+        self.accept_process_event(
+            PROCESS_EVENT_EXIT,
+            offset_of!(ExitProcEvent, process_pid),
+            offset_of!(ExitProcEvent, process_tgid),
+        )
+    }
+
+    /// Returns the filter rules. Messages not accepted by the previous rules
+    /// are dropped.
+    pub fn build(mut self) -> Vec<sock_filter> {
+        self.0.push(bpf_stmt!(BPF_RET | BPF_K, 0x0));
+        self.0
+    }
+
+    /// Accepts the `what` event messages from processes, whose event data
+    /// has the process pid and tgid at `pid_offset` and `tgid_offset`.
+    fn accept_process_event(mut self, what: c_uint, pid_offset: usize, tgid_offset: usize) -> Self {
+        let event_data =
+            nlmsg_length(0) + offset_of!(cn_msg, data) + offset_of!(proc_event, event_data);
+
+        #[rustfmt::skip]
+        let rules = [
+            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, nlmsg_length(0) +
+                                                offset_of!(cn_msg, data) +
+                                                offset_of!(proc_event, what)),
+            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_K, c_uint::to_be(what), 0, 6),
+
+            // Processes have process_pid == process_tgid (thread group leaders).
+            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, event_data + pid_offset),
+            bpf_stmt!(BPF_ST, 0),
+            bpf_stmt!(BPF_LDX | BPF_W | BPF_MEM, 0),
+            bpf_stmt!(BPF_LD | BPF_W | BPF_ABS, event_data + tgid_offset),
+            bpf_jump!(BPF_JMP | BPF_JEQ | BPF_X, 0, 0, 1),
+            bpf_stmt!(BPF_RET | BPF_K, 0xffffffff),
+        ];
+        self.0.extend(rules);
+
+        self
+    }
+}
+
+impl Default for FilterBuilder {
+    fn default() -> Self {
+        FilterBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_RULES: usize = 15;
+    const EVENT_RULES: usize = 8;
+
+    #[test]
+    fn filter_builder_adds_a_block_for_each_accepted_event() {
+        assert_eq!(HEADER_RULES + 1, FilterBuilder::new().build().len());
+        assert_eq!(
+            HEADER_RULES + EVENT_RULES + 1,
+            FilterBuilder::new().accept_exec().build().len()
+        );
+        assert_eq!(
+            HEADER_RULES + 2 * EVENT_RULES + 1,
+            FilterBuilder::new()
+                .accept_exec()
+                .accept_exit()
+                .build()
+                .len()
+        );
+    }
+
+    #[test]
+    fn filter_builder_accepts_error_and_overrun_messages() {
+        let filter = FilterBuilder::new().build();
+        let accept = filter
+            .iter()
+            .position(|rule| rule.code == (BPF_RET | BPF_K) as c_ushort && rule.k == 0xffffffff)
+            .unwrap();
+
+        for ty in [NLMSG_ERROR, NLMSG_OVERRUN] {
+            let (i, rule) = filter
+                .iter()
+                .enumerate()
+                .find(|(_, rule)| rule.k == c_ushort::to_be(ty as c_ushort) as c_uint)
+                .unwrap();
+            // Jumps are relative to the next rule
+            assert_eq!(accept, i + 1 + rule.jt as usize);
+        }
+    }
+
+    #[test]
+    fn filter_builder_event_blocks_jump_to_the_next_block() {
+        let filter = FilterBuilder::new().accept_exit().accept_exec().build();
+
+        for block in [HEADER_RULES, HEADER_RULES + EVENT_RULES] {
+            let rules = &filter[block..block + EVENT_RULES];
+            // Jumps are relative to the next rule
+            assert_eq!(EVENT_RULES, 2 + rules[1].jf as usize);
+            assert_eq!(EVENT_RULES, 7 + rules[6].jf as usize);
+        }
+        assert_eq!(
+            c_uint::to_be(PROCESS_EVENT_EXIT),
+            filter[HEADER_RULES + 1].k
+        );
+        assert_eq!(
+            c_uint::to_be(PROCESS_EVENT_EXEC),
+            filter[HEADER_RULES + EVENT_RULES + 1].k
+        );
+        assert_eq!((BPF_RET | BPF_K) as c_ushort, filter[filter.len() - 1].code);
+        assert_eq!(0, filter[filter.len() - 1].k);
+    }
+}
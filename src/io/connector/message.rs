@@ -0,0 +1,443 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Decoding and encoding of the netlink messages exchanged with the process
+//! events connector.
+//!
+//! Messages are handled as byte slices. Every field is bounds checked before
+//! being read, so malformed messages are rejected instead of being read past
+//! their end or through misaligned pointers.
+
+use std::{
+    io,
+    mem::{offset_of, size_of},
+};
+
+use super::cnproc::{
+    cb_id, cn_msg, nlmsg_align, nlmsg_length, nlmsghdr, proc_cn_mcast_op,
+    proc_cn_mcast_op_PROC_CN_MCAST_IGNORE, proc_cn_mcast_op_PROC_CN_MCAST_LISTEN, proc_event,
+    proc_event__bindgen_ty_1_exec_proc_event, proc_event__bindgen_ty_1_exit_proc_event,
+    CN_IDX_PROC, CN_VAL_PROC, PROCESS_EVENT_EXEC, PROCESS_EVENT_EXIT,
+};
+use crate::{
+    solver::{PEvent, PEventRecord, PExitStatus, PID},
+    Error, Result,
+};
+
+type ExecProcEvent = proc_event__bindgen_ty_1_exec_proc_event;
+type ExitProcEvent = proc_event__bindgen_ty_1_exit_proc_event;
+
+/// Size of the message used to subscribe to the process events.
+pub const SUBSCRIPTION_MESSAGE_SIZE: usize =
+    nlmsg_length(size_of::<cn_msg>() + size_of::<proc_cn_mcast_op>());
+
+/// Iterator over the netlink messages of a datagram, following their
+/// lengths like the `NLMSG_OK` and `NLMSG_NEXT` macros do.
+///
+/// Iteration stops after yielding an error for a message with an invalid
+/// length, as the next messages can't be located.
+#[derive(Debug)]
+pub struct Messages<'a>(&'a [u8]);
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Result<&'a [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+
+        let data = std::mem::take(&mut self.0);
+        let Some(len) = read_u32(data, offset_of!(nlmsghdr, nlmsg_len)) else {
+            return Some(Err(malformed("truncated netlink header")));
+        };
+        let len = len as usize;
+        if len < nlmsg_length(0) || len > data.len() {
+            return Some(Err(malformed("invalid netlink message length")));
+        }
+
+        self.0 = data.get(nlmsg_align(len)..).unwrap_or_default();
+        Some(Ok(&data[..len]))
+    }
+}
+
+/// Returns an iterator over the netlink messages of a `datagram`.
+pub fn messages(datagram: &[u8]) -> Messages<'_> {
+    Messages(datagram)
+}
+
+/// Decodes the exec or exit event of a netlink `message` received from the
+/// process events connector, along with the CPU and time at which the kernel
+/// reported it.
+///
+/// Returns `None` for other process events, for messages of other
+/// connectors and for acknowledgment messages.
+///
+/// # Errors
+///
+/// Returns an [`Error::MalformedMessage`] error when the message is
+/// truncated or its lengths are inconsistent, an [`Error::Netlink`] error for
+/// error messages and an [`Error::EventsLost`] error for overrun messages.
+pub fn decode_event(message: &[u8]) -> Result<Option<PEventRecord>> {
+    let payload = netlink_payload(message)?;
+    let ty = read_u16(message, offset_of!(nlmsghdr, nlmsg_type)).unwrap_or_default();
+    match ty as libc::c_int {
+        libc::NLMSG_ERROR => return netlink_error(payload).map(|_| None),
+        libc::NLMSG_OVERRUN => return Err(Error::EventsLost),
+        libc::NLMSG_NOOP => return Ok(None),
+        _ => {}
+    }
+
+    let Some(data) = connector_data(payload)? else {
+        return Ok(None);
+    };
+
+    let event_data = offset_of!(proc_event, event_data);
+    let (Some(what), Some(cpu), Some(timestamp_ns)) = (
+        read_u32(data, offset_of!(proc_event, what)),
+        read_u32(data, offset_of!(proc_event, cpu)),
+        read_u64(data, offset_of!(proc_event, timestamp_ns)),
+    ) else {
+        return Err(malformed("truncated process event header"));
+    };
+
+    let event = match what {
+        PROCESS_EVENT_EXEC => {
+            let exec = data
+                .get(event_data..event_data + size_of::<ExecProcEvent>())
+                .ok_or_else(|| malformed("truncated exec event"))?;
+            PEvent::Exec {
+                pid: read_pid(exec, offset_of!(ExecProcEvent, process_pid)),
+                tgid: read_pid(exec, offset_of!(ExecProcEvent, process_tgid)),
+                parent_pid: None,
+                timestamp: timestamp_ns,
+            }
+        }
+        PROCESS_EVENT_EXIT => {
+            let exit = data
+                .get(event_data..event_data + size_of::<ExitProcEvent>())
+                .ok_or_else(|| malformed("truncated exit event"))?;
+            let exit_code = read_u32(exit, offset_of!(ExitProcEvent, exit_code));
+            PEvent::Exit {
+                pid: read_pid(exit, offset_of!(ExitProcEvent, process_pid)),
+                exit_code: PExitStatus::from(exit_code.unwrap_or_default()),
+                exit_signal: read_u32(exit, offset_of!(ExitProcEvent, exit_signal))
+                    .unwrap_or_default(),
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(PEventRecord {
+        event,
+        cpu,
+        timestamp_ns,
+    }))
+}
+
+/// Encodes the message to `subscribe` to the process events, or to
+/// unsubscribe from them.
+pub fn subscription_message(subscribe: bool) -> [u8; SUBSCRIPTION_MESSAGE_SIZE] {
+    let mut message = [0u8; SUBSCRIPTION_MESSAGE_SIZE];
+
+    // The port ID (nlmsg_pid) is left to zero, as the kernel is handling it.
+    write_u32(
+        &mut message,
+        offset_of!(nlmsghdr, nlmsg_len),
+        SUBSCRIPTION_MESSAGE_SIZE as u32,
+    );
+    write_u16(
+        &mut message,
+        offset_of!(nlmsghdr, nlmsg_type),
+        libc::NLMSG_DONE as u16,
+    );
+
+    let cn = nlmsg_length(0);
+    write_u32(
+        &mut message,
+        cn + offset_of!(cn_msg, id) + offset_of!(cb_id, idx),
+        CN_IDX_PROC,
+    );
+    write_u32(
+        &mut message,
+        cn + offset_of!(cn_msg, id) + offset_of!(cb_id, val),
+        CN_VAL_PROC,
+    );
+    write_u16(
+        &mut message,
+        cn + offset_of!(cn_msg, len),
+        size_of::<proc_cn_mcast_op>() as u16,
+    );
+
+    let op = match subscribe {
+        true => proc_cn_mcast_op_PROC_CN_MCAST_LISTEN,
+        false => proc_cn_mcast_op_PROC_CN_MCAST_IGNORE,
+    };
+    write_u32(&mut message, cn + size_of::<cn_msg>(), op);
+
+    message
+}
+
+/// Returns the payload of the netlink `message`, after validating its
+/// header.
+fn netlink_payload(message: &[u8]) -> Result<&[u8]> {
+    let len = read_u32(message, offset_of!(nlmsghdr, nlmsg_len))
+        .ok_or_else(|| malformed("truncated netlink header"))? as usize;
+    if len < nlmsg_length(0) {
+        return Err(malformed("netlink message length shorter than its header"));
+    }
+
+    message
+        .get(nlmsg_length(0)..len)
+        .ok_or_else(|| malformed("netlink message length exceeds the received data"))
+}
+
+/// Returns the error reported by the payload of an error message. Error
+/// messages without error are acknowledgments.
+fn netlink_error(payload: &[u8]) -> Result<()> {
+    // The payload is a nlmsgerr structure, starting with the negated errno.
+    let error = read_u32(payload, 0).ok_or_else(|| malformed("truncated error message"))? as i32;
+    match error {
+        0 => Ok(()),
+        error => Err(Error::Netlink(io::Error::from_raw_os_error(-error))),
+    }
+}
+
+/// Returns the data of the connector message on a netlink `payload`, or
+/// `None` when the message is not from the process events connector.
+fn connector_data(payload: &[u8]) -> Result<Option<&[u8]>> {
+    let (Some(idx), Some(val), Some(len)) = (
+        read_u32(payload, offset_of!(cn_msg, id) + offset_of!(cb_id, idx)),
+        read_u32(payload, offset_of!(cn_msg, id) + offset_of!(cb_id, val)),
+        read_u16(payload, offset_of!(cn_msg, len)),
+    ) else {
+        return Err(malformed("truncated connector header"));
+    };
+    if idx != CN_IDX_PROC || val != CN_VAL_PROC {
+        return Ok(None);
+    }
+
+    let data = size_of::<cn_msg>();
+    payload
+        .get(data..data + len as usize)
+        .map(Some)
+        .ok_or_else(|| malformed("connector data length exceeds the netlink message"))
+}
+
+fn malformed(reason: &'static str) -> Error {
+    Error::MalformedMessage { reason }
+}
+
+/// Reads a PID from `data`, which must have been bounds checked already.
+fn read_pid(data: &[u8], offset: usize) -> PID {
+    PID::from_raw(read_u32(data, offset).unwrap_or_default() as i32)
+}
+
+pub(super) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_ne_bytes(bytes.try_into().ok()?))
+}
+
+pub(super) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+}
+
+pub(super) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    Some(u64::from_ne_bytes(bytes.try_into().ok()?))
+}
+
+fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_ne_bytes());
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a message of the process events connector with the given
+    /// process event `what` and `event_data`.
+    fn event_message(what: u32, event_data: &[u32]) -> Vec<u8> {
+        let mut event = Vec::new();
+        event.extend(what.to_ne_bytes());
+        event.extend(3u32.to_ne_bytes());
+        event.extend(123_456_789u64.to_ne_bytes());
+        event.extend(event_data.iter().flat_map(|value| value.to_ne_bytes()));
+        event.resize(size_of::<proc_event>(), 0);
+
+        let mut message = Vec::new();
+        let len = nlmsg_length(size_of::<cn_msg>() + event.len());
+        message.extend((len as u32).to_ne_bytes());
+        message.extend((libc::NLMSG_DONE as u16).to_ne_bytes());
+        message.extend(0u16.to_ne_bytes());
+        message.extend(0u32.to_ne_bytes());
+        message.extend(0u32.to_ne_bytes());
+        message.extend(CN_IDX_PROC.to_ne_bytes());
+        message.extend(CN_VAL_PROC.to_ne_bytes());
+        message.extend(0u32.to_ne_bytes());
+        message.extend(0u32.to_ne_bytes());
+        message.extend((event.len() as u16).to_ne_bytes());
+        message.extend(0u16.to_ne_bytes());
+        message.extend(event);
+        message
+    }
+
+    #[test]
+    fn decode_event_decodes_exec_events() {
+        let message = event_message(PROCESS_EVENT_EXEC, &[1234, 1230]);
+        assert_eq!(
+            Some(PEventRecord {
+                event: PEvent::Exec {
+                    pid: PID::from(1234),
+                    tgid: PID::from(1230),
+                    parent_pid: None,
+                    timestamp: 123_456_789,
+                },
+                cpu: 3,
+                timestamp_ns: 123_456_789,
+            }),
+            decode_event(&message).unwrap()
+        );
+    }
+
+    #[test]
+    fn decode_event_decodes_exit_events() {
+        let message = event_message(PROCESS_EVENT_EXIT, &[1234, 1234, 7 << 8, 17]);
+        let record = decode_event(&message).unwrap().unwrap();
+        match record.event {
+            PEvent::Exit {
+                pid,
+                exit_code,
+                exit_signal,
+            } => {
+                assert_eq!(PID::from(1234), pid);
+                assert_eq!(Some(7), exit_code.code());
+                assert_eq!(17, exit_signal);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn decode_event_skips_other_events_and_connectors() {
+        let message = event_message(0x00000001, &[1, 1, 1234, 1234]);
+        assert!(decode_event(&message).unwrap().is_none());
+
+        let mut message = event_message(PROCESS_EVENT_EXEC, &[1234, 1234]);
+        let idx = nlmsg_length(0) + offset_of!(cn_msg, id) + offset_of!(cb_id, idx);
+        write_u32(&mut message, idx, CN_IDX_PROC + 1);
+        assert!(decode_event(&message).unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_event_rejects_malformed_messages() {
+        let message = event_message(PROCESS_EVENT_EXIT, &[1234, 1234, 0, 17]);
+
+        for len in [0, 4, nlmsg_length(0) + 4, message.len() - 1] {
+            let error = decode_event(&message[..len]).unwrap_err();
+            assert!(matches!(error, Error::MalformedMessage { .. }), "{}", len);
+        }
+
+        let mut short = message.clone();
+        write_u32(&mut short, 0, 8);
+        assert!(decode_event(&short).is_err());
+
+        let mut truncated_event = message.clone();
+        write_u16(
+            &mut truncated_event,
+            nlmsg_length(0) + offset_of!(cn_msg, len),
+            20,
+        );
+        assert!(decode_event(&truncated_event).is_err());
+    }
+
+    /// Returns a netlink message with the given `ty` and `payload`.
+    fn netlink_message(ty: libc::c_int, payload: &[u8]) -> Vec<u8> {
+        let mut message = Vec::new();
+        message.extend((nlmsg_length(payload.len()) as u32).to_ne_bytes());
+        message.extend((ty as u16).to_ne_bytes());
+        message.extend(0u16.to_ne_bytes());
+        message.extend(0u32.to_ne_bytes());
+        message.extend(0u32.to_ne_bytes());
+        message.extend(payload);
+        message
+    }
+
+    #[test]
+    fn decode_event_reports_errors_and_overruns() {
+        let error = netlink_message(libc::NLMSG_ERROR, &(-libc::ENOBUFS).to_ne_bytes());
+        match decode_event(&error) {
+            Err(Error::Netlink(error)) => assert_eq!(Some(libc::ENOBUFS), error.raw_os_error()),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        let ack = netlink_message(libc::NLMSG_ERROR, &0i32.to_ne_bytes());
+        assert!(decode_event(&ack).unwrap().is_none());
+
+        let truncated = netlink_message(libc::NLMSG_ERROR, &[]);
+        assert!(matches!(
+            decode_event(&truncated),
+            Err(Error::MalformedMessage { .. })
+        ));
+
+        let overrun = netlink_message(libc::NLMSG_OVERRUN, &[]);
+        assert!(matches!(decode_event(&overrun), Err(Error::EventsLost)));
+
+        let noop = netlink_message(libc::NLMSG_NOOP, &[]);
+        assert!(decode_event(&noop).unwrap().is_none());
+    }
+
+    #[test]
+    fn messages_iterates_the_messages_of_a_datagram() {
+        let exec = event_message(PROCESS_EVENT_EXEC, &[1234, 1234]);
+        let exit = event_message(PROCESS_EVENT_EXIT, &[1234, 1234, 0, 17]);
+        let mut datagram = exec.clone();
+        datagram.resize(nlmsg_align(exec.len()), 0);
+        datagram.extend(&exit);
+
+        let messages = messages(&datagram).collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(vec![&exec[..], &exit[..]], messages);
+        assert_eq!(0, super::messages(&[]).count());
+    }
+
+    #[test]
+    fn messages_stops_on_invalid_lengths() {
+        let exec = event_message(PROCESS_EVENT_EXEC, &[1234, 1234]);
+        let mut datagram = exec.clone();
+        datagram.extend(&exec[..8]);
+
+        let mut messages = messages(&datagram);
+        assert_eq!(Some(&exec[..]), messages.next().map(Result::unwrap));
+        assert!(messages.next().unwrap().is_err());
+        assert!(messages.next().is_none());
+
+        let mut zero_length = exec.clone();
+        write_u32(&mut zero_length, 0, 0);
+        let mut messages = super::messages(&zero_length);
+        assert!(messages.next().unwrap().is_err());
+        assert!(messages.next().is_none());
+    }
+
+    #[test]
+    fn subscription_message_encodes_the_operation() {
+        let message = subscription_message(true);
+        let op = nlmsg_length(0) + size_of::<cn_msg>();
+        assert_eq!(
+            Some(SUBSCRIPTION_MESSAGE_SIZE as u32),
+            read_u32(&message, 0)
+        );
+        assert_eq!(
+            Some(proc_cn_mcast_op_PROC_CN_MCAST_LISTEN),
+            read_u32(&message, op)
+        );
+        assert_eq!(
+            Some(proc_cn_mcast_op_PROC_CN_MCAST_IGNORE),
+            read_u32(&subscription_message(false), op)
+        );
+    }
+}
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Raw netlink messages received by the process events connector, used to
+//! debug the connector.
+
+use std::{
+    fmt::{self, Write},
+    mem::{offset_of, size_of},
+};
+
+use super::{
+    cnproc::{cb_id, cn_msg, nlmsg_length, nlmsghdr, proc_event},
+    message::{read_u16, read_u32, read_u64},
+};
+
+/// Process event names, by their `what` value on `proc_event`.
+///
+/// The values are part of the kernel ABI, but the names generated for them
+/// differ between kernel header versions, so they are listed here.
+const PROC_EVENT_NAMES: [(u32, &str); 11] = [
+    (0x00000000, "NONE"),
+    (0x00000001, "FORK"),
+    (0x00000002, "EXEC"),
+    (0x00000004, "UID"),
+    (0x00000040, "GID"),
+    (0x00000080, "SID"),
+    (0x00000100, "PTRACE"),
+    (0x00000200, "COMM"),
+    (0x20000000, "NONZERO_EXIT"),
+    (0x40000000, "COREDUMP"),
+    (0x80000000, "EXIT"),
+];
+
+/// Number of bytes on each line of a hex dump.
+const HEX_DUMP_WIDTH: usize = 16;
+
+/// A netlink message, as received from the socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawMessage(Vec<u8>);
+
+impl RawMessage {
+    /// Returns the bytes of the message.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns a description of the netlink, connector and process event
+    /// headers of the message, one header per line. Headers that don't fit
+    /// on the message are not described.
+    pub fn headers(&self) -> String {
+        let mut headers = String::new();
+        let data = &self.0;
+
+        let (Some(len), Some(ty), Some(flags), Some(seq), Some(pid)) = (
+            read_u32(data, offset_of!(nlmsghdr, nlmsg_len)),
+            read_u16(data, offset_of!(nlmsghdr, nlmsg_type)),
+            read_u16(data, offset_of!(nlmsghdr, nlmsg_flags)),
+            read_u32(data, offset_of!(nlmsghdr, nlmsg_seq)),
+            read_u32(data, offset_of!(nlmsghdr, nlmsg_pid)),
+        ) else {
+            return "truncated netlink header".to_string();
+        };
+        let _ = write!(
+            headers,
+            "nlmsghdr: len={} type={} flags={:#x} seq={} pid={}",
+            len,
+            netlink_message_type(ty),
+            flags,
+            seq,
+            pid
+        );
+
+        let cn = nlmsg_length(0);
+        let (Some(idx), Some(val), Some(seq), Some(ack), Some(len), Some(flags)) = (
+            read_u32(data, cn + offset_of!(cn_msg, id) + offset_of!(cb_id, idx)),
+            read_u32(data, cn + offset_of!(cn_msg, id) + offset_of!(cb_id, val)),
+            read_u32(data, cn + offset_of!(cn_msg, seq)),
+            read_u32(data, cn + offset_of!(cn_msg, ack)),
+            read_u16(data, cn + offset_of!(cn_msg, len)),
+            read_u16(data, cn + offset_of!(cn_msg, flags)),
+        ) else {
+            return headers;
+        };
+        let _ = write!(
+            headers,
+            "\ncn_msg: idx={} val={} seq={} ack={} len={} flags={:#x}",
+            idx, val, seq, ack, len, flags
+        );
+
+        let event = cn + size_of::<cn_msg>();
+        let (Some(what), Some(cpu), Some(timestamp)) = (
+            read_u32(data, event + offset_of!(proc_event, what)),
+            read_u32(data, event + offset_of!(proc_event, cpu)),
+            read_u64(data, event + offset_of!(proc_event, timestamp_ns)),
+        ) else {
+            return headers;
+        };
+        let name = PROC_EVENT_NAMES
+            .iter()
+            .find(|(value, _)| *value == what)
+            .map_or("UNKNOWN", |(_, name)| name);
+        let _ = write!(
+            headers,
+            "\nproc_event: what={}({:#x}) cpu={} timestamp_ns={}",
+            name, what, cpu, timestamp
+        );
+
+        headers
+    }
+}
+
+impl From<Vec<u8>> for RawMessage {
+    fn from(value: Vec<u8>) -> Self {
+        RawMessage(value)
+    }
+}
+
+impl fmt::Display for RawMessage {
+    /// Formats the message headers followed by a hex dump of the message.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.headers())?;
+        write!(f, "{}", hex_dump(&self.0))
+    }
+}
+
+/// Returns a hex dump of `data`, with the offset, the bytes in hexadecimal
+/// and the printable ASCII characters of each line.
+fn hex_dump(data: &[u8]) -> String {
+    data.chunks(HEX_DUMP_WIDTH)
+        .enumerate()
+        .map(|(line, bytes)| {
+            let hex = bytes
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = bytes
+                .iter()
+                .map(|byte| match byte.is_ascii_graphic() || *byte == b' ' {
+                    true => *byte as char,
+                    false => '.',
+                })
+                .collect::<String>();
+            format!(
+                "{:04x}  {:<width$}  |{}|",
+                line * HEX_DUMP_WIDTH,
+                hex,
+                ascii,
+                width = HEX_DUMP_WIDTH * 3 - 1
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn netlink_message_type(ty: u16) -> String {
+    let name = match ty as libc::c_int {
+        libc::NLMSG_NOOP => "NLMSG_NOOP",
+        libc::NLMSG_ERROR => "NLMSG_ERROR",
+        libc::NLMSG_DONE => "NLMSG_DONE",
+        libc::NLMSG_OVERRUN => "NLMSG_OVERRUN",
+        _ => return ty.to_string(),
+    };
+    format!("{}({})", name, ty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exec_message() -> Vec<u8> {
+        let mut data = Vec::new();
+        // nlmsghdr
+        data.extend(52u32.to_ne_bytes());
+        data.extend((libc::NLMSG_DONE as u16).to_ne_bytes());
+        data.extend(0u16.to_ne_bytes());
+        data.extend(7u32.to_ne_bytes());
+        data.extend(0u32.to_ne_bytes());
+        // cn_msg
+        data.extend(1u32.to_ne_bytes());
+        data.extend(1u32.to_ne_bytes());
+        data.extend(7u32.to_ne_bytes());
+        data.extend(0u32.to_ne_bytes());
+        data.extend(16u16.to_ne_bytes());
+        data.extend(0u16.to_ne_bytes());
+        // proc_event
+        data.extend(2u32.to_ne_bytes());
+        data.extend(3u32.to_ne_bytes());
+        data.extend(1000u64.to_ne_bytes());
+        data
+    }
+
+    #[test]
+    fn raw_message_headers_are_decoded() {
+        let message = RawMessage::from(exec_message());
+        assert_eq!(
+            "nlmsghdr: len=52 type=NLMSG_DONE(3) flags=0x0 seq=7 pid=0\n\
+             cn_msg: idx=1 val=1 seq=7 ack=0 len=16 flags=0x0\n\
+             proc_event: what=EXEC(0x2) cpu=3 timestamp_ns=1000",
+            message.headers()
+        );
+    }
+
+    #[test]
+    fn raw_message_headers_skip_truncated_headers() {
+        let mut data = exec_message();
+        data.truncate(nlmsg_length(0) + 4);
+        assert_eq!(
+            "nlmsghdr: len=52 type=NLMSG_DONE(3) flags=0x0 seq=7 pid=0",
+            RawMessage::from(data).headers()
+        );
+        assert_eq!(
+            "truncated netlink header",
+            RawMessage::from(vec![0; 4]).headers()
+        );
+    }
+
+    #[test]
+    fn hex_dump_shows_offsets_bytes_and_ascii() {
+        let data = b"0123456789abcdef\x00\xffz";
+        assert_eq!(
+            "0000  30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  |0123456789abcdef|\n\
+             0010  00 ff 7a                                         |..z|",
+            hex_dump(data)
+        );
+    }
+}
@@ -0,0 +1,459 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Control socket, to adjust a running monitor without restarting it.
+//!
+//! Clients connect to a Unix stream socket and send a single command line,
+//! like `add-filter exe == "steam"` or `stats`. The monitor answers with a
+//! status line, `ok` or `error`, followed by a free text message, and closes
+//! the connection.
+//!
+//! The connections are accepted on a worker thread, which reads and parses
+//! the commands, so the monitor only has to take the [`Request`]s from time to
+//! time with [`ControlSocket::try_requests`] and reply to them.
+
+use std::{
+    fmt,
+    fs::{self, DirBuilder, Permissions},
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::{
+        fs::{DirBuilderExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+/// Maximum length of a command line, in bytes.
+const MAX_COMMAND_LENGTH: u64 = 64 * 1024;
+
+/// Time given to the clients to send their command, and to read the reply.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time the clients wait for the reply. The monitor takes the requests
+/// between the process events, so it may take a while.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reply to a command: a message on success, or an error message.
+pub type Reply = Result<String, String>;
+
+/// A command sent through the control socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Only report the process events matching a filter expression too.
+    AddFilter(String),
+    /// Remove a filter expression added before.
+    RemoveFilter(String),
+    /// Replace the filter expressions added before with a new one.
+    SetFilter(String),
+    /// Remove the filter expressions added before.
+    ClearFilters,
+    /// List the filter expressions.
+    Filters,
+    /// Toggle the display of the process command lines.
+    ToggleCmdline,
+    /// Return the monitor statistics.
+    Stats,
+    /// Pause monitoring.
+    Pause,
+    /// Resume monitoring.
+    Resume,
+    /// Pause monitoring, or resume it when paused.
+    TogglePause,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    /// Parses a command line: a command name, followed by its argument when
+    /// it takes one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match s.trim().split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (s.trim(), ""),
+        };
+        let expression = || match argument {
+            "" => Err(format!("The {} command needs a filter expression", name)),
+            argument => Ok(argument.to_string()),
+        };
+        let command = match name {
+            "add-filter" => Command::AddFilter(expression()?),
+            "remove-filter" => Command::RemoveFilter(expression()?),
+            "set-filter" => Command::SetFilter(expression()?),
+            "clear-filters" => Command::ClearFilters,
+            "filters" => Command::Filters,
+            "cmdline" => Command::ToggleCmdline,
+            "stats" => Command::Stats,
+            "pause" => Command::Pause,
+            "resume" => Command::Resume,
+            "toggle-pause" => Command::TogglePause,
+            "" => return Err("Empty command".to_string()),
+            name => return Err(format!("Unknown command '{}'", name)),
+        };
+        match (&command, argument) {
+            (Command::AddFilter(_) | Command::RemoveFilter(_) | Command::SetFilter(_), _)
+            | (_, "") => Ok(command),
+            _ => Err(format!("The {} command takes no arguments", name)),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::AddFilter(expression) => write!(f, "add-filter {}", expression),
+            Command::RemoveFilter(expression) => write!(f, "remove-filter {}", expression),
+            Command::SetFilter(expression) => write!(f, "set-filter {}", expression),
+            Command::ClearFilters => write!(f, "clear-filters"),
+            Command::Filters => write!(f, "filters"),
+            Command::ToggleCmdline => write!(f, "cmdline"),
+            Command::Stats => write!(f, "stats"),
+            Command::Pause => write!(f, "pause"),
+            Command::Resume => write!(f, "resume"),
+            Command::TogglePause => write!(f, "toggle-pause"),
+        }
+    }
+}
+
+/// A command received through the control socket, waiting for its reply.
+#[derive(Debug)]
+pub struct Request {
+    command: Command,
+    stream: UnixStream,
+}
+
+impl Request {
+    /// Returns the requested command.
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    /// Sends the `reply` to the client, closing the connection.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, like when the client
+    /// is gone, an error variant will be returned.
+    pub fn reply(mut self, reply: Reply) -> io::Result<()> {
+        write_reply(&mut self.stream, reply)
+    }
+}
+
+/// A listening control socket. The socket file is removed when the
+/// `ControlSocket` is dropped.
+#[derive(Debug)]
+pub struct ControlSocket {
+    path: PathBuf,
+    listener: UnixListener,
+    requests: Option<Receiver<Request>>,
+}
+
+impl ControlSocket {
+    /// Attempts to create a control socket at `path`, only accessible by the
+    /// user running the process.
+    ///
+    /// A leftover socket file, from a process that didn't remove it, is
+    /// replaced. The connections aren't accepted until [`start`] is called.
+    ///
+    /// [`start`]: ControlSocket::start
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::AddrInUse`] error when another process is
+    /// listening on `path`, or any other I/O error creating the socket.
+    pub fn bind(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let listener = match bind_private(&path) {
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+                match UnixStream::connect(&path) {
+                    Ok(_) => return Err(e),
+                    Err(e) if e.kind() != io::ErrorKind::ConnectionRefused => return Err(e),
+                    Err(_) => {}
+                }
+                fs::remove_file(&path)?;
+                bind_private(&path)?
+            }
+            result => result?,
+        };
+
+        Ok(ControlSocket {
+            path,
+            listener,
+            requests: None,
+        })
+    }
+
+    /// Returns the path of the socket file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Starts accepting connections on a worker thread. It has no effect when
+    /// they are already accepted.
+    ///
+    /// The worker thread is left running once the socket is dropped, as it
+    /// waits for connections that no longer come.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    pub fn start(&mut self) -> io::Result<()> {
+        if self.requests.is_some() {
+            return Ok(());
+        }
+
+        let listener = self.listener.try_clone()?;
+        let (sender, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("control".to_string())
+            .spawn(move || accept(listener, sender))?;
+        self.requests = Some(receiver);
+
+        Ok(())
+    }
+
+    /// Returns the requests received since the last call, without waiting.
+    pub fn try_requests(&self) -> Vec<Request> {
+        self.requests
+            .as_ref()
+            .map(|requests| requests.try_iter().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::error!(
+                "Couldn't remove control socket {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Binds a socket at `path` that is only accessible by the user running the
+/// process.
+///
+/// The socket is bound on a private directory next to `path`, where nobody
+/// else can connect to it, and linked to `path` once its permissions are
+/// restricted. Returns an [`io::ErrorKind::AddrInUse`] error when `path`
+/// already exists.
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let dir = path.with_file_name(format!(".{}.{}", name, process::id()));
+    DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let temporary = dir.join("socket");
+    let listener = UnixListener::bind(&temporary).and_then(|listener| {
+        fs::set_permissions(&temporary, Permissions::from_mode(0o600))?;
+        match fs::hard_link(&temporary, path) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                Err(io::Error::new(io::ErrorKind::AddrInUse, e))
+            }
+            result => result.map(|_| listener),
+        }
+    });
+    // The socket is still reachable through `path`
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        log::warn!("Couldn't remove {}: {}", dir.display(), e);
+    }
+    listener
+}
+
+/// Sends the `command` to the control socket at `path`, and returns its
+/// reply.
+///
+/// # Errors
+///
+/// If this function encounters any form of I/O error, like when no process
+/// listens on `path`, an error variant will be returned.
+pub fn send(path: &Path, command: &Command) -> io::Result<Reply> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    stream.set_write_timeout(Some(REPLY_TIMEOUT))?;
+    writeln!(stream, "{}", command)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    let (status, message) = reply.split_once('\n').unwrap_or((&reply, ""));
+    let message = message.trim_end().to_string();
+    match status {
+        "ok" => Ok(Ok(message)),
+        "error" => Ok(Err(message)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid control socket reply",
+        )),
+    }
+}
+
+/// Accepts the connections on `listener`, and sends their requests to the
+/// monitor until it stops taking them.
+fn accept(listener: UnixListener, requests: Sender<Request>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::error!("Couldn't accept a control socket connection: {}", e);
+                continue;
+            }
+        };
+        match read_command(&stream) {
+            Ok(Ok(command)) => {
+                if requests.send(Request { command, stream }).is_err() {
+                    break;
+                }
+            }
+            Ok(Err(e)) => {
+                if let Err(e) = write_reply(&mut &stream, Err(e)) {
+                    log::warn!("Couldn't reply to a control socket client: {}", e);
+                }
+            }
+            Err(e) => log::warn!("Couldn't read a control socket command: {}", e),
+        }
+    }
+}
+
+/// Reads and parses the command line sent through `stream`.
+fn read_command(stream: &UnixStream) -> io::Result<Result<Command, String>> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut line = String::new();
+    BufReader::new(stream.take(MAX_COMMAND_LENGTH)).read_line(&mut line)?;
+    Ok(line.parse())
+}
+
+/// Writes the `reply` status line, followed by its message.
+fn write_reply(stream: &mut impl Write, reply: Reply) -> io::Result<()> {
+    let (status, message) = match reply {
+        Ok(message) => ("ok", message),
+        Err(message) => ("error", message),
+    };
+    writeln!(stream, "{}\n{}", status, message.trim_end())?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn socket_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("copes-{}-{}.ctl", name, process::id()))
+    }
+
+    #[test]
+    fn commands_are_parsed_from_their_lines() {
+        assert_eq!(
+            Ok(Command::AddFilter("exe == \"steam\"".to_string())),
+            " add-filter  exe == \"steam\" \n".parse()
+        );
+        assert_eq!(
+            Ok(Command::RemoveFilter("uid != 0".to_string())),
+            "remove-filter uid != 0".parse()
+        );
+        assert_eq!(Ok(Command::ToggleCmdline), "cmdline\n".parse());
+        assert_eq!(Ok(Command::Stats), "stats".parse::<Command>());
+
+        assert!("add-filter".parse::<Command>().is_err());
+        assert!("pause now".parse::<Command>().is_err());
+        assert!("restart".parse::<Command>().is_err());
+        assert!("".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn commands_are_formatted_as_parsed() {
+        for command in [
+            Command::AddFilter("exec && uid == 1000".to_string()),
+            Command::SetFilter("exit".to_string()),
+            Command::ClearFilters,
+            Command::Filters,
+            Command::Pause,
+            Command::Resume,
+            Command::TogglePause,
+        ] {
+            assert_eq!(Ok(command.clone()), command.to_string().parse());
+        }
+    }
+
+    #[test]
+    fn control_socket_replies_to_the_sent_commands() {
+        let path = socket_path("replies");
+        let mut socket = ControlSocket::bind(&path).unwrap();
+        assert_eq!(
+            0o600,
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777
+        );
+        let name = path.file_name().unwrap().to_string_lossy();
+        assert!(!path
+            .with_file_name(format!(".{}.{}", name, process::id()))
+            .exists());
+        socket.start().unwrap();
+
+        let client_path = path.clone();
+        let client = thread::spawn(move || {
+            (
+                send(&client_path, &Command::Stats).unwrap(),
+                send(&client_path, &Command::Pause).unwrap(),
+            )
+        });
+        let mut replies = vec![
+            Ok("2 processes\n  steam".to_string()),
+            Err("Paused".to_string()),
+        ];
+        while !replies.is_empty() {
+            for request in socket.try_requests() {
+                request.reply(replies.remove(0)).unwrap();
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            (
+                Ok("2 processes\n  steam".to_string()),
+                Err("Paused".to_string())
+            ),
+            client.join().unwrap()
+        );
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn control_socket_rejects_invalid_commands() {
+        let path = socket_path("invalid");
+        let mut socket = ControlSocket::bind(&path).unwrap();
+        socket.start().unwrap();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(b"restart\n").unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        assert_eq!("error\nUnknown command 'restart'\n", reply);
+        assert!(socket.try_requests().is_empty());
+    }
+
+    #[test]
+    fn control_socket_replaces_leftover_socket_files() {
+        let path = socket_path("leftover");
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let socket = ControlSocket::bind(&path).unwrap();
+        assert_eq!(
+            io::ErrorKind::AddrInUse,
+            ControlSocket::bind(&path).unwrap_err().kind()
+        );
+        drop(socket);
+    }
+}
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Utilities to run the program as a daemon.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Detaches the process from its terminal and runs it in the background.
+///
+/// The process is forked twice, so it's no longer a session leader and can't
+/// acquire a controlling terminal. The working directory is changed to `/`,
+/// the standard input and output are redirected to `/dev/null` and the
+/// standard error is appended to the file at `log`, or redirected to
+/// `/dev/null` when no `log` is given.
+///
+/// The parent processes exit without running destructors, so resources
+/// shared with the daemon (like sockets) are left untouched.
+///
+/// Must be called before any thread is started, as threads don't survive
+/// the forks. On Linux, it fails when other threads are already running.
+///
+/// # Errors
+///
+/// If this function encounters any form of I/O error, an error variant will be
+/// returned.
+pub fn daemonize(log: Option<&Path>) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    if thread_count().is_ok_and(|count| count > 1) {
+        return Err(io::Error::other(
+            "other threads are running, and they wouldn't survive the forks",
+        ));
+    }
+
+    let null = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    let log = match log {
+        Some(path) => OpenOptions::new().create(true).append(true).open(path)?,
+        None => null.try_clone()?,
+    };
+
+    // Safety: The process is single threaded at this point, so it's safe to
+    // fork it. The parent processes exit right away.
+    unsafe {
+        if check(libc::fork())? > 0 {
+            libc::_exit(0);
+        }
+        check(libc::setsid())?;
+        if check(libc::fork())? > 0 {
+            libc::_exit(0);
+        }
+    }
+
+    env::set_current_dir("/")?;
+
+    // Safety: Calling `dup2` with valid file descriptors is safe.
+    unsafe {
+        check(libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO))?;
+        check(libc::dup2(null.as_raw_fd(), libc::STDOUT_FILENO))?;
+        check(libc::dup2(log.as_raw_fd(), libc::STDERR_FILENO))?;
+    }
+
+    Ok(())
+}
+
+/// A file holding the PID of the running process. The file is removed when
+/// the `PidFile` is dropped.
+#[derive(Debug)]
+pub struct PidFile(PathBuf);
+
+impl PidFile {
+    /// Attempts to write the PID of the process to the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn create(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        fs::write(&path, format!("{}\n", process::id()))?;
+        Ok(PidFile(path))
+    }
+
+    /// Returns the path of the PID file.
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.0) {
+            log::error!("Couldn't remove PID file {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Returns the number of threads of the process.
+#[cfg(target_os = "linux")]
+fn thread_count() -> io::Result<usize> {
+    Ok(fs::read_dir("/proc/self/task")?.count())
+}
+
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    match result {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pid_file_holds_the_process_pid_until_dropped() {
+        let path = env::temp_dir().join(format!("copes-pid-{}", process::id()));
+
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(
+            format!("{}\n", process::id()),
+            fs::read_to_string(pid_file.path()).unwrap()
+        );
+
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn daemonize_fails_when_threads_are_running() {
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || receiver.recv());
+        assert!(thread_count().unwrap() > 1);
+
+        let error = daemonize(None).unwrap_err();
+        assert_eq!(io::ErrorKind::Other, error.kind());
+
+        drop(sender);
+        thread.join().unwrap().unwrap_err();
+    }
+}
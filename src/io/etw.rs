@@ -0,0 +1,553 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Process events source for Windows, using [Event Tracing for Windows].
+//!
+//! A real-time trace session is started with the
+//! `Microsoft-Windows-Kernel-Process` provider enabled, which reports the
+//! start and stop of every process. The session events are consumed on a
+//! background thread, and yielded as exec and exit events.
+//!
+//! Starting trace sessions needs administrator rights, or being member of
+//! the Performance Log Users group.
+//!
+//! [Event Tracing for Windows]: https://learn.microsoft.com/en-us/windows/win32/etw/about-event-tracing
+
+use std::{
+    cell::Cell,
+    io, mem, process, ptr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        OnceLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use windows_sys::{
+    core::GUID,
+    Win32::{
+        Foundation::{ERROR_ALREADY_EXISTS, ERROR_SUCCESS},
+        System::{
+            Diagnostics::Etw::{
+                CloseTrace, ControlTraceW, EnableTraceEx2, OpenTraceW, ProcessTrace, StartTraceW,
+                CONTROLTRACE_HANDLE, EVENT_CONTROL_CODE_ENABLE_PROVIDER, EVENT_RECORD,
+                EVENT_TRACE_CONTROL_STOP, EVENT_TRACE_LOGFILEW, EVENT_TRACE_PROPERTIES,
+                EVENT_TRACE_REAL_TIME_MODE, PROCESS_TRACE_MODE_EVENT_RECORD,
+                PROCESS_TRACE_MODE_REAL_TIME, TRACE_LEVEL_INFORMATION, WNODE_FLAG_TRACED_GUID,
+            },
+            Performance::QueryPerformanceFrequency,
+        },
+    },
+};
+
+use crate::{
+    io::{source::ProcessEventSource, waker::Waker},
+    solver::{PEvent, PEventRecord, PExitStatus, PID},
+    Error, Result,
+};
+
+/// The `Microsoft-Windows-Kernel-Process` provider.
+const KERNEL_PROCESS_PROVIDER: GUID = GUID::from_u128(0x22fb2cd6_0e7b_422b_a0c7_2fad1fd0e716);
+
+/// Keyword of the process start and stop events of the provider
+/// (`WINEVENT_KEYWORD_PROCESS`).
+const PROCESS_KEYWORD: u64 = 0x10;
+
+/// ID of the process start events.
+const PROCESS_START_EVENT: u16 = 1;
+
+/// ID of the process stop events.
+const PROCESS_STOP_EVENT: u16 = 2;
+
+/// Timestamps of the session events, in query performance counter units.
+const QPC_CLIENT_CONTEXT: u32 = 1;
+
+/// Default maximum time that the records iterator waits for new events.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Maximum time waited for new events before checking whether the waker was
+/// woken up.
+const WAKER_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Maximum length of the session names, in UTF-16 units.
+const MAX_SESSION_NAME_LEN: usize = 64;
+
+/// Properties of a trace session, followed by the session name.
+#[repr(C)]
+struct SessionProperties {
+    properties: EVENT_TRACE_PROPERTIES,
+    name: [u16; MAX_SESSION_NAME_LEN],
+}
+
+/// A source of process events using Event Tracing for Windows.
+///
+/// The exit codes are truncated to their lower byte, like on Unix. The
+/// events don't have the process threads or the parent of the exited
+/// processes, so their `tgid` is the process ID.
+pub struct EtwEventSource {
+    session: Session,
+    events: Receiver<PEventRecord>,
+    timeout: Cell<Duration>,
+    waker: Option<Waker>,
+    paused: Cell<bool>,
+}
+
+impl EtwEventSource {
+    /// Attempts to create a new `EtwEventSource` instance, starting its
+    /// trace session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Io`] error when the trace session can't be
+    /// started, like when the process doesn't have the needed rights.
+    pub fn try_new() -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let session = Session::start(sender).map_err(Error::Io)?;
+
+        Ok(EtwEventSource {
+            session,
+            events,
+            timeout: Cell::new(DEFAULT_TIMEOUT),
+            waker: None,
+            paused: Cell::new(false),
+        })
+    }
+
+    /// Sets the maximum time that the records iterator waits for new events.
+    /// When no events are received within this time, the iterator yields
+    /// `None`. A zero `duration` waits forever.
+    pub fn set_timeout(&self, duration: Duration) {
+        self.timeout.set(duration);
+    }
+
+    /// Sets the `waker` that interrupts the waits for new events. Once it's
+    /// woken up, the iterator yields `None` shortly after.
+    pub fn set_waker(&mut self, waker: Waker) {
+        self.waker = Some(waker);
+    }
+
+    /// Returns `true` when the waker of the source was woken up.
+    pub fn is_woken(&self) -> bool {
+        self.waker.as_ref().is_some_and(Waker::is_woken)
+    }
+
+    /// Stops reporting process events until the source is resumed. The
+    /// trace session keeps running meanwhile.
+    ///
+    /// Process events received before pausing the source can still be read.
+    pub fn pause(&self) {
+        self.paused.set(true);
+    }
+
+    /// Starts reporting process events again after pausing the source. The
+    /// process events happening while it was paused are lost.
+    pub fn resume(&self) {
+        self.paused.set(false);
+    }
+
+    /// Returns `true` when the source is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.get()
+    }
+
+    /// Returns an iterator over the received event records, along with the
+    /// CPU and time at which the events were logged.
+    ///
+    /// The iterator yields `None` when no events are received within the
+    /// source timeout, or once its waker is woken up. When the trace
+    /// session stops, it yields an [`Error::SocketRecv`] error.
+    pub fn records(&self) -> RecordIter<'_> {
+        RecordIter(self)
+    }
+
+    /// Attempts to start the trace session again, with the same timeout.
+    /// The process events happening meanwhile are lost. A paused source
+    /// stays paused.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Io`] error when the trace session can't be
+    /// started. The source is left unchanged then.
+    pub fn reconnect(&mut self) -> Result<()> {
+        let mut source = EtwEventSource::try_new()?;
+        source.set_timeout(self.timeout.get());
+        source.waker = self.waker.take();
+        source.paused.set(self.is_paused());
+        *self = source;
+
+        Ok(())
+    }
+}
+
+impl ProcessEventSource for EtwEventSource {
+    fn records(&self) -> Box<dyn Iterator<Item = Result<PEventRecord>> + '_> {
+        Box::new(EtwEventSource::records(self))
+    }
+
+    fn set_timeout(&self, duration: Duration) -> Result<()> {
+        EtwEventSource::set_timeout(self, duration);
+        Ok(())
+    }
+
+    fn set_waker(&mut self, waker: Waker) {
+        EtwEventSource::set_waker(self, waker)
+    }
+
+    fn is_woken(&self) -> bool {
+        EtwEventSource::is_woken(self)
+    }
+
+    fn pause(&self) -> Result<()> {
+        EtwEventSource::pause(self);
+        Ok(())
+    }
+
+    fn resume(&self) -> Result<()> {
+        EtwEventSource::resume(self);
+        Ok(())
+    }
+
+    fn is_paused(&self) -> bool {
+        EtwEventSource::is_paused(self)
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        EtwEventSource::reconnect(self)
+    }
+}
+
+/// Iterator over the event records received by an [`EtwEventSource`].
+pub struct RecordIter<'a>(&'a EtwEventSource);
+
+impl<'a> Iterator for RecordIter<'a> {
+    type Item = Result<PEventRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let timeout = self.0.timeout.get();
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.0.is_woken() {
+                return None;
+            }
+
+            let wait = match timeout {
+                Duration::ZERO => WAKER_CHECK_INTERVAL,
+                _ => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if remaining > Duration::ZERO => {
+                        remaining.min(WAKER_CHECK_INTERVAL)
+                    }
+                    _ => return None,
+                },
+            };
+            match self.0.events.recv_timeout(wait) {
+                // Events logged while paused are dropped.
+                Ok(_) if self.0.is_paused() => continue,
+                Ok(record) => return Some(Ok(record)),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => {
+                    let error = io::Error::new(io::ErrorKind::BrokenPipe, "Trace session stopped");
+                    return Some(Err(Error::SocketRecv(error)));
+                }
+            }
+        }
+    }
+}
+
+/// A real-time trace session, with the thread consuming its events.
+struct Session {
+    handle: CONTROLTRACE_HANDLE,
+    properties: Box<SessionProperties>,
+    consumer: Option<JoinHandle<()>>,
+}
+
+impl Session {
+    /// Attempts to start a trace session, sending its process events to
+    /// `sender`.
+    fn start(sender: Sender<PEventRecord>) -> io::Result<Self> {
+        // Sessions are set up again before stopping the former ones, so
+        // each one has its own name.
+        static SESSIONS: AtomicU32 = AtomicU32::new(0);
+        let name = format!(
+            "copes-{}-{}",
+            process::id(),
+            SESSIONS.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut properties = session_properties(&name);
+        let mut handle = CONTROLTRACE_HANDLE { Value: 0 };
+
+        // Safety: Calling the ffi function with pointers to handle, name and
+        // properties is safe as they point to initialized values, and the
+        // properties have room for the session name.
+        let mut result = unsafe {
+            StartTraceW(
+                &mut handle,
+                properties.name.as_ptr(),
+                &mut properties.properties,
+            )
+        };
+        if result == ERROR_ALREADY_EXISTS {
+            // A session of a former process with the same ID wasn't stopped
+            stop_session(&name);
+            properties = session_properties(&name);
+            // Safety: As above.
+            result = unsafe {
+                StartTraceW(
+                    &mut handle,
+                    properties.name.as_ptr(),
+                    &mut properties.properties,
+                )
+            };
+        }
+        check(result)?;
+
+        let mut session = Session {
+            handle,
+            properties,
+            consumer: None,
+        };
+        // Safety: Calling the ffi function with a pointer to the provider ID
+        // is safe as it points to an initialized GUID. The enable parameters
+        // are optional.
+        check(unsafe {
+            EnableTraceEx2(
+                session.handle,
+                &KERNEL_PROCESS_PROVIDER,
+                EVENT_CONTROL_CODE_ENABLE_PROVIDER,
+                TRACE_LEVEL_INFORMATION as u8,
+                PROCESS_KEYWORD,
+                0,
+                0,
+                ptr::null(),
+            )
+        })?;
+        session.consumer = Some(consume(&session.properties, sender)?);
+
+        Ok(session)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // Stopping the session ends the consumer thread.
+        // Safety: Calling the ffi function with a pointer to the session
+        // properties is safe as they have room for the session name.
+        unsafe {
+            ControlTraceW(
+                self.handle,
+                ptr::null(),
+                &mut self.properties.properties,
+                EVENT_TRACE_CONTROL_STOP,
+            )
+        };
+        if let Some(consumer) = self.consumer.take() {
+            let _ = consumer.join();
+        }
+    }
+}
+
+/// Returns the properties of a real-time session named `name`.
+fn session_properties(name: &str) -> Box<SessionProperties> {
+    // Safety: The properties are plain C structs, for which zeroed memory is
+    // a valid value.
+    let mut properties: Box<SessionProperties> = Box::new(unsafe { mem::zeroed() });
+    properties.properties.Wnode.BufferSize = mem::size_of::<SessionProperties>() as u32;
+    properties.properties.Wnode.Flags = WNODE_FLAG_TRACED_GUID;
+    properties.properties.Wnode.ClientContext = QPC_CLIENT_CONTEXT;
+    properties.properties.LogFileMode = EVENT_TRACE_REAL_TIME_MODE;
+    properties.properties.LoggerNameOffset = mem::size_of::<EVENT_TRACE_PROPERTIES>() as u32;
+    for (unit, code) in properties
+        .name
+        .iter_mut()
+        .zip(name.encode_utf16().take(MAX_SESSION_NAME_LEN - 1))
+    {
+        *unit = code;
+    }
+    properties
+}
+
+/// Stops the session named `name`, ignoring any error.
+fn stop_session(name: &str) {
+    let mut properties = session_properties(name);
+    // Safety: Calling the ffi function with pointers to the session name and
+    // properties is safe as they point to initialized values.
+    unsafe {
+        ControlTraceW(
+            CONTROLTRACE_HANDLE { Value: 0 },
+            properties.name.as_ptr(),
+            &mut properties.properties,
+            EVENT_TRACE_CONTROL_STOP,
+        )
+    };
+}
+
+/// Attempts to start consuming the events of the session with the given
+/// `properties` on a background thread, sending the process events to
+/// `sender`. The thread ends when the session is stopped.
+fn consume(
+    properties: &SessionProperties,
+    sender: Sender<PEventRecord>,
+) -> io::Result<JoinHandle<()>> {
+    let mut name = properties.name;
+    let context = Box::into_raw(Box::new(sender));
+    // Safety: The log file is a plain C struct, for which zeroed memory is a
+    // valid value.
+    let mut logfile: EVENT_TRACE_LOGFILEW = unsafe { mem::zeroed() };
+    logfile.LoggerName = name.as_mut_ptr();
+    logfile.Anonymous1.ProcessTraceMode =
+        PROCESS_TRACE_MODE_REAL_TIME | PROCESS_TRACE_MODE_EVENT_RECORD;
+    logfile.Anonymous2.EventRecordCallback = Some(handle_event);
+    logfile.Context = context as *mut _;
+
+    // Safety: Calling the ffi function with a pointer to logfile is safe as
+    // it points to an initialized value, and the session name it points to
+    // outlives the call.
+    let handle = unsafe { OpenTraceW(&mut logfile) };
+    if handle.Value == u64::MAX {
+        // Safety: The sender was leaked above, and the callback never runs.
+        drop(unsafe { Box::from_raw(context) });
+        return Err(io::Error::last_os_error());
+    }
+
+    let context = context as usize;
+    thread::Builder::new()
+        .name("etw consumer".to_string())
+        .spawn(move || {
+            let handles = [handle];
+            // Safety: Calling the ffi functions with a pointer to handles is
+            // safe as it points to an array of the given length. The time
+            // range is optional.
+            unsafe {
+                ProcessTrace(
+                    handles.as_ptr(),
+                    handles.len() as u32,
+                    ptr::null(),
+                    ptr::null(),
+                );
+                CloseTrace(handle);
+            }
+            // Safety: The callback doesn't run after closing the trace, so
+            // the sender can be freed.
+            drop(unsafe { Box::from_raw(context as *mut Sender<PEventRecord>) });
+        })
+}
+
+/// Sends the process event of the session `record` to the sender on its
+/// context. Only the kernel process provider is enabled on the session.
+unsafe extern "system" fn handle_event(record: *mut EVENT_RECORD) {
+    // Safety: The record and its user data are valid during the callback,
+    // and its context is the sender given on `consume`.
+    let (record, sender) = unsafe {
+        let record = &*record;
+        (
+            record,
+            &*(record.UserContext as *const Sender<PEventRecord>),
+        )
+    };
+    // Safety: The user data has the given length.
+    let data = unsafe {
+        std::slice::from_raw_parts(record.UserData as *const u8, record.UserDataLength as usize)
+    };
+    let timestamp = qpc_to_ns(record.EventHeader.TimeStamp as u64);
+    let Some(event) = decode_event(record.EventHeader.EventDescriptor.Id, data, timestamp) else {
+        return;
+    };
+    let _ = sender.send(PEventRecord {
+        event,
+        // Safety: Every variant of the union is an integer.
+        cpu: u32::from(unsafe { record.BufferContext.Anonymous.ProcessorIndex }),
+        timestamp_ns: timestamp,
+    });
+}
+
+/// Decodes the process event with the given `id` and payload `data` of the
+/// `Microsoft-Windows-Kernel-Process` provider, logged at `timestamp`.
+///
+/// Returns `None` for other events and truncated payloads.
+fn decode_event(id: u16, data: &[u8], timestamp: u64) -> Option<PEvent> {
+    let u32_at = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+
+    // The payloads start with the process ID and its creation time.
+    let pid = PID::from_raw(u32_at(0)?);
+    match id {
+        PROCESS_START_EVENT => Some(PEvent::Exec {
+            pid,
+            tgid: pid,
+            parent_pid: Some(PID::from_raw(u32_at(12)?)),
+            timestamp,
+        }),
+        // Followed by the exit time and the exit code.
+        PROCESS_STOP_EVENT => Some(PEvent::Exit {
+            pid,
+            exit_code: PExitStatus::from((u32_at(20)? & 0xff) << 8),
+            exit_signal: 0,
+        }),
+        _ => None,
+    }
+}
+
+/// Converts the query performance counter `value` to nanoseconds since
+/// boot.
+fn qpc_to_ns(value: u64) -> u64 {
+    static FREQUENCY: OnceLock<u64> = OnceLock::new();
+    let frequency = *FREQUENCY.get_or_init(|| {
+        let mut frequency = 0;
+        // Safety: Calling the ffi function with a pointer to frequency is
+        // safe as it points to an initialized integer.
+        unsafe { QueryPerformanceFrequency(&mut frequency) };
+        frequency.max(1) as u64
+    });
+    (value as u128 * 1_000_000_000 / frequency as u128) as u64
+}
+
+/// Returns an error for the Win32 error code `result`, if any.
+fn check(result: u32) -> io::Result<()> {
+    match result {
+        ERROR_SUCCESS => Ok(()),
+        error => Err(io::Error::from_raw_os_error(error as i32)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(fields: &[(usize, u32)]) -> Vec<u8> {
+        let mut data = vec![0u8; 32];
+        for (offset, value) in fields {
+            data[*offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn decode_event_decodes_process_start_and_stop() {
+        assert_eq!(
+            Some(PEvent::Exec {
+                pid: PID::from(1234),
+                tgid: PID::from(1234),
+                parent_pid: Some(PID::from(1000)),
+                timestamp: 5,
+            }),
+            decode_event(PROCESS_START_EVENT, &payload(&[(0, 1234), (12, 1000)]), 5)
+        );
+        assert_eq!(
+            Some(PEvent::Exit {
+                pid: PID::from(1234),
+                exit_code: PExitStatus::from(3 << 8),
+                exit_signal: 0,
+            }),
+            decode_event(PROCESS_STOP_EVENT, &payload(&[(0, 1234), (20, 3)]), 5)
+        );
+    }
+
+    #[test]
+    fn decode_event_ignores_other_events_and_truncated_payloads() {
+        assert_eq!(None, decode_event(3, &payload(&[(0, 1234)]), 5));
+        assert_eq!(None, decode_event(PROCESS_STOP_EVENT, &[0u8; 8], 5));
+    }
+}
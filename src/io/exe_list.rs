@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Lists of executables, to verify the executed files against allowlists
+//! and blocklists.
+//!
+//! Each line of a list file is an absolute executable path, a directory
+//! path ending with `/` that matches every executable inside it, or the
+//! SHA-256 digest of an executable as 64 hexadecimal digits. Empty lines and
+//! lines starting with `#` are ignored.
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::hash::Digest;
+
+/// List of executables, by path, directory or digest.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExeList {
+    paths: HashSet<PathBuf>,
+    dirs: Vec<PathBuf>,
+    digests: HashSet<Digest>,
+}
+
+impl ExeList {
+    /// Attempts to read the list from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::InvalidData`] error when a line isn't an
+    /// absolute path nor a digest, or any other I/O error reading the file.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        ExeList::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Attempts to parse a `list`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error message when a line isn't an absolute path nor a
+    /// digest.
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let mut exe_list = ExeList::default();
+        for (number, line) in list.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Ok(digest) = line.parse::<Digest>() {
+                exe_list.digests.insert(digest);
+            } else if line.starts_with('/') && line.ends_with('/') {
+                exe_list.dirs.push(PathBuf::from(line));
+            } else if line.starts_with('/') {
+                exe_list.paths.insert(PathBuf::from(line));
+            } else {
+                return Err(format!(
+                    "line {}: '{}' is not an absolute path nor a SHA-256 digest",
+                    number + 1,
+                    line
+                ));
+            }
+        }
+        Ok(exe_list)
+    }
+
+    /// Returns `true` when the list has digests, so the executables must be
+    /// hashed to match them.
+    pub fn has_digests(&self) -> bool {
+        !self.digests.is_empty()
+    }
+
+    /// Returns `true` when the executable with the given `path` or `digest`
+    /// is on the list.
+    pub fn contains(&self, path: Option<&Path>, digest: Option<&Digest>) -> bool {
+        path.is_some_and(|path| {
+            self.paths.contains(path) || self.dirs.iter().any(|dir| path.starts_with(dir))
+        }) || digest.is_some_and(|digest| self.digests.contains(digest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "4add4bb89d8ca0e3b1bd861130ddd7ae0fd9617a8055de0a38c8d2ca1ac95723";
+
+    #[test]
+    fn exe_list_contains_paths_dirs_and_digests() {
+        let list = ExeList::parse(&format!(
+            "# Allowed programs\n/usr/bin/game\n\n/opt/games/\n  {}\n",
+            DIGEST.to_uppercase()
+        ))
+        .unwrap();
+        let digest = DIGEST.parse::<Digest>().unwrap();
+
+        assert!(list.has_digests());
+        assert!(list.contains(Some(Path::new("/usr/bin/game")), None));
+        assert!(list.contains(Some(Path::new("/opt/games/bin/game")), None));
+        assert!(list.contains(Some(Path::new("/tmp/sleep")), Some(&digest)));
+        assert!(list.contains(None, Some(&digest)));
+        assert!(!list.contains(Some(Path::new("/usr/bin/game2")), None));
+        assert!(!list.contains(None, None));
+    }
+
+    #[test]
+    fn exe_list_rejects_relative_paths() {
+        assert_eq!(
+            Err("line 2: 'game' is not an absolute path nor a SHA-256 digest".to_string()),
+            ExeList::parse("/usr/bin/game\ngame\n")
+        );
+    }
+}
@@ -0,0 +1,289 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Hashing of the executed files.
+//!
+//! Executables are hashed on a worker thread, so hashing big files doesn't
+//! delay the processing of the process events. The digests are cached by
+//! file identity, so each executable is only hashed once while it's not
+//! modified.
+//!
+//! The files are only hashed with the `hash` feature, which uses the `sha2`
+//! crate.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io,
+    os::unix::fs::MetadataExt,
+    str::FromStr,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+#[cfg(feature = "hash")]
+use std::io::Read;
+
+#[cfg(feature = "hash")]
+use sha2::{Digest as _, Sha256};
+
+/// Maximum number of cached digests. The cache is cleared once it's full.
+const MAX_CACHED_DIGESTS: usize = 4096;
+
+/// Size of the chunks the files are read in.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// SHA-256 digest of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Digest([u8; 32]);
+
+impl fmt::Display for Digest {
+    /// Formats the digest as lowercase hexadecimal digits.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl FromStr for Digest {
+    type Err = String;
+
+    /// Parses a digest from 64 hexadecimal digits, in any case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{}' is not a SHA-256 digest", s);
+        if s.len() != 64 || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let mut digest = [0; 32];
+        for (byte, digits) in digest.iter_mut().zip(s.as_bytes().chunks_exact(2)) {
+            let digits = std::str::from_utf8(digits).map_err(|_| invalid())?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        }
+        Ok(Digest(digest))
+    }
+}
+
+/// Identifier of a hashing job, to match its result.
+pub type JobId = u64;
+
+/// Outcome of a request to hash a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hash {
+    /// The file digest was cached.
+    Ready(Digest),
+    /// The file is bigger than the maximum size, so it isn't hashed.
+    TooLarge,
+    /// The file is being hashed by the job with the given ID.
+    Pending(JobId),
+}
+
+/// Identity of the contents of a file: its device, inode, size and
+/// modification time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FileId(u64, u64, u64, i64, i64);
+
+/// Hashes executable files on a worker thread.
+#[derive(Debug)]
+pub struct ExeHasher {
+    jobs: Sender<(JobId, File)>,
+    results: Receiver<(JobId, io::Result<Digest>)>,
+    max_size: u64,
+    cache: HashMap<FileId, Digest>,
+    pending: HashMap<JobId, FileId>,
+    next_job: JobId,
+}
+
+impl ExeHasher {
+    /// Attempts to start the worker thread of an `ExeHasher` that hashes the
+    /// files up to `max_size` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the worker thread can't be started, or an
+    /// [`io::ErrorKind::Unsupported`] error without the `hash` feature.
+    pub fn spawn(max_size: u64) -> io::Result<Self> {
+        if !cfg!(feature = "hash") {
+            return Err(unsupported());
+        }
+
+        let (jobs, jobs_receiver) = mpsc::channel::<(JobId, File)>();
+        let (results_sender, results) = mpsc::channel();
+        thread::Builder::new()
+            .name("exe-hasher".to_string())
+            .spawn(move || {
+                for (job, file) in jobs_receiver {
+                    if results_sender.send((job, sha256_file(file))).is_err() {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(ExeHasher {
+            jobs,
+            results,
+            max_size,
+            cache: HashMap::new(),
+            pending: HashMap::new(),
+            next_job: 0,
+        })
+    }
+
+    /// Requests to hash the open `file`. Files being hashed by a pending job
+    /// are not hashed again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file metadata can't be read, or when the
+    /// worker thread stopped.
+    pub fn hash(&mut self, file: File) -> io::Result<Hash> {
+        let metadata = file.metadata()?;
+        if metadata.len() > self.max_size {
+            return Ok(Hash::TooLarge);
+        }
+
+        let id = FileId(
+            metadata.dev(),
+            metadata.ino(),
+            metadata.len(),
+            metadata.mtime(),
+            metadata.mtime_nsec(),
+        );
+        if let Some(digest) = self.cache.get(&id) {
+            return Ok(Hash::Ready(*digest));
+        }
+        if let Some((job, _)) = self.pending.iter().find(|(_, pending)| **pending == id) {
+            return Ok(Hash::Pending(*job));
+        }
+
+        let job = self.next_job;
+        self.next_job += 1;
+        self.jobs
+            .send((job, file))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Hashing thread stopped"))?;
+        self.pending.insert(job, id);
+        Ok(Hash::Pending(job))
+    }
+
+    /// Returns `true` when there are pending jobs.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Returns the results of the completed jobs, without waiting.
+    pub fn try_completed(&mut self) -> Vec<(JobId, io::Result<Digest>)> {
+        let results = self.results.try_iter().collect::<Vec<_>>();
+        results
+            .into_iter()
+            .map(|(job, result)| self.complete(job, result))
+            .collect()
+    }
+
+    /// Waits up to `timeout` for the result of the next completed job.
+    pub fn wait_completed(&mut self, timeout: Duration) -> Option<(JobId, io::Result<Digest>)> {
+        match self.results.recv_timeout(timeout) {
+            Ok((job, result)) => Some(self.complete(job, result)),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    fn complete(&mut self, job: JobId, result: io::Result<Digest>) -> (JobId, io::Result<Digest>) {
+        if let (Some(id), Ok(digest)) = (self.pending.remove(&job), result.as_ref()) {
+            if self.cache.len() >= MAX_CACHED_DIGESTS {
+                self.cache.clear();
+            }
+            self.cache.insert(id, *digest);
+        }
+        (job, result)
+    }
+}
+
+/// Returns the SHA-256 digest of the contents of `file`.
+#[cfg(feature = "hash")]
+fn sha256_file(mut file: File) -> io::Result<Digest> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => return Ok(Digest(hasher.finalize().into())),
+            Ok(size) => hasher.update(&buffer[..size]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Files can't be hashed without the `hash` feature.
+#[cfg(not(feature = "hash"))]
+fn sha256_file(_file: File) -> io::Result<Digest> {
+    Err(unsupported())
+}
+
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "built without the hash feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_is_parsed_from_hexadecimal_digits() {
+        let hex = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(hex, hex.parse::<Digest>().unwrap().to_string());
+        assert_eq!(
+            hex,
+            hex.to_uppercase().parse::<Digest>().unwrap().to_string()
+        );
+        assert!("ba78".parse::<Digest>().is_err());
+        assert!(hex.replace('b', "g").parse::<Digest>().is_err());
+        assert!(format!("+{}", &hex[1..]).parse::<Digest>().is_err());
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn exe_hasher_hashes_files_once() {
+        use std::{env, fs, process};
+
+        let path = env::temp_dir().join(format!("copes-hash-{}", process::id()));
+        fs::write(&path, "abc").unwrap();
+        let mut hasher = ExeHasher::spawn(1024).unwrap();
+
+        let job = match hasher.hash(File::open(&path).unwrap()).unwrap() {
+            Hash::Pending(job) => job,
+            hash => panic!("Unexpected {:?}", hash),
+        };
+        assert_eq!(
+            Hash::Pending(job),
+            hasher.hash(File::open(&path).unwrap()).unwrap()
+        );
+        let (completed, digest) = hasher.wait_completed(Duration::from_secs(10)).unwrap();
+        assert_eq!(job, completed);
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            digest.unwrap().to_string()
+        );
+        assert!(!hasher.has_pending());
+        assert!(matches!(
+            hasher.hash(File::open(&path).unwrap()).unwrap(),
+            Hash::Ready(_)
+        ));
+
+        let mut hasher = ExeHasher::spawn(2).unwrap();
+        assert_eq!(
+            Hash::TooLarge,
+            hasher.hash(File::open(&path).unwrap()).unwrap()
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(not(feature = "hash"))]
+    #[test]
+    fn exe_hasher_is_unsupported_without_hash_feature() {
+        assert_eq!(
+            io::ErrorKind::Unsupported,
+            ExeHasher::spawn(1024).unwrap_err().kind()
+        );
+    }
+}
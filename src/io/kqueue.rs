@@ -0,0 +1,520 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Process events source for macOS and FreeBSD, using `kqueue`.
+//!
+//! These platforms don't have the process events connector. Instead,
+//! `kqueue` reports the exec, fork and exit of the processes registered on
+//! it with the `EVFILT_PROC` filter. Every running process is registered when
+//! the source is set up.
+//!
+//! On FreeBSD, the kernel registers the forked processes along with their
+//! parent (`NOTE_TRACK`). macOS doesn't, so the new processes are registered
+//! when their parent forks. A process forked and executing a file right away
+//! can do it before being registered. Then, its exec event is reported when
+//! it's registered, as its executable is not the one of its parent.
+//!
+//! The process information is read with `libproc` on macOS, and from the
+//! `kern.proc` sysctl nodes on FreeBSD.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    io, mem,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    path::PathBuf,
+    ptr,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    io::{source::ProcessEventSource, waker::Waker},
+    solver::{PEvent, PEventRecord, PExitStatus, PID},
+    Error, Result,
+};
+
+#[cfg(target_os = "macos")]
+mod libproc;
+#[cfg(target_os = "freebsd")]
+mod sysctl;
+
+#[cfg(target_os = "macos")]
+use libproc::{list_pids, parent_pid};
+#[cfg(target_os = "macos")]
+pub use libproc::{process_name, process_path};
+#[cfg(target_os = "freebsd")]
+use sysctl::{list_pids, parent_pid};
+#[cfg(target_os = "freebsd")]
+pub use sysctl::{process_cmdline, process_path};
+
+/// Default maximum time that the records iterator waits for new events.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Number of `kqueue` events received at once.
+const EVENTS_BUFFER_SIZE: usize = 64;
+
+/// Process events reported for every registered process.
+#[cfg(target_os = "macos")]
+const PROC_NOTES: u32 = libc::NOTE_EXEC | libc::NOTE_EXIT | libc::NOTE_EXITSTATUS | libc::NOTE_FORK;
+/// Process events reported for every registered process. The exit status is
+/// always reported.
+#[cfg(target_os = "freebsd")]
+const PROC_NOTES: u32 = libc::NOTE_EXEC | libc::NOTE_EXIT | libc::NOTE_TRACK;
+
+/// A source of process events using `kqueue`.
+///
+/// The exec events don't have the parent process ID when the parent exits
+/// before it's read. On macOS, the exec events of processes executing files
+/// several times before being registered are reported once.
+pub struct KqueueEventSource {
+    kqueue: OwnedFd,
+    /// Executables of the registered processes.
+    processes: RefCell<HashMap<libc::pid_t, Option<PathBuf>>>,
+    pending: RefCell<VecDeque<PEventRecord>>,
+    timeout: Cell<Duration>,
+    waker: Option<Waker>,
+    paused: Cell<bool>,
+}
+
+impl KqueueEventSource {
+    /// Attempts to create a new `KqueueEventSource` instance, registering
+    /// every running process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Io`] error when the `kqueue` can't be set up.
+    pub fn try_new() -> Result<Self> {
+        KqueueEventSource::setup().map_err(Error::Io)
+    }
+
+    fn setup() -> io::Result<Self> {
+        // Safety: It's safe to call the ffi function in this context as it
+        // won't produce undefined behaviour on the Rust side upon a failure.
+        // The returned file descriptor is open and owned by nobody else.
+        let kqueue = unsafe {
+            match libc::kqueue() {
+                -1 => return Err(io::Error::last_os_error()),
+                fd => OwnedFd::from_raw_fd(fd),
+            }
+        };
+        let source = KqueueEventSource {
+            kqueue,
+            processes: RefCell::new(HashMap::new()),
+            pending: RefCell::new(VecDeque::new()),
+            timeout: Cell::new(DEFAULT_TIMEOUT),
+            waker: None,
+            paused: Cell::new(false),
+        };
+        source.register_new_processes(None)?;
+
+        Ok(source)
+    }
+
+    /// Sets the maximum time that the records iterator waits for new events.
+    /// When no events are received within this time, the iterator yields
+    /// `None`. A zero `duration` waits forever.
+    pub fn set_timeout(&self, duration: Duration) {
+        self.timeout.set(duration);
+    }
+
+    /// Sets the `waker` that interrupts the waits for new events. Once it's
+    /// woken up, the iterator yields `None` at once.
+    pub fn set_waker(&mut self, waker: Waker) {
+        self.waker = Some(waker);
+    }
+
+    /// Returns `true` when the waker of the source was woken up.
+    pub fn is_woken(&self) -> bool {
+        self.waker.as_ref().is_some_and(Waker::is_woken)
+    }
+
+    /// Stops reporting process events until the source is resumed. The
+    /// processes are still registered meanwhile, so their events are
+    /// reported again once resumed.
+    ///
+    /// Process events received before pausing the source can still be read.
+    pub fn pause(&self) {
+        self.paused.set(true);
+    }
+
+    /// Starts reporting process events again after pausing the source. The
+    /// process events happening while it was paused are lost.
+    pub fn resume(&self) {
+        self.paused.set(false);
+    }
+
+    /// Returns `true` when the source is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.get()
+    }
+
+    /// Returns an iterator over the received event records. The records
+    /// have the time at which the events were received, and CPU `0`, as
+    /// `kqueue` doesn't report them.
+    ///
+    /// The iterator yields `None` when no events are received within the
+    /// source timeout, or once its waker is woken up.
+    pub fn records(&self) -> RecordIter<'_> {
+        RecordIter(self)
+    }
+
+    /// Attempts to set up the source again, on a new `kqueue` with the same
+    /// timeout. The process events happening meanwhile are lost. A paused
+    /// source stays paused.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Io`] error when the `kqueue` can't be set up. The
+    /// source is left unchanged then.
+    pub fn reconnect(&mut self) -> Result<()> {
+        let mut source = KqueueEventSource::try_new()?;
+        source.set_timeout(self.timeout.get());
+        source.waker = self.waker.take();
+        source.paused.set(self.is_paused());
+        *self = source;
+
+        Ok(())
+    }
+
+    /// Registers the running processes that are not registered yet. When the
+    /// events are received at `timestamp`, exec events are queued for the
+    /// new processes that don't run the executable of their parent.
+    fn register_new_processes(&self, timestamp: Option<u64>) -> io::Result<()> {
+        for pid in list_pids()? {
+            if self.processes.borrow().contains_key(&pid) {
+                continue;
+            }
+            match self.register(pid) {
+                Ok(()) => (),
+                // The process already exited
+                Err(error) if error.raw_os_error() == Some(libc::ESRCH) => continue,
+                Err(error) => return Err(error),
+            }
+
+            let exe = process_path(PID::from_raw(pid));
+            let parent_pid = parent_pid(pid);
+            if let Some(timestamp) = timestamp {
+                let parent_exe = parent_pid
+                    .and_then(|ppid| self.processes.borrow().get(&ppid).cloned())
+                    .flatten();
+                if exe.is_some() && exe != parent_exe {
+                    self.queue(PEvent::Exec {
+                        pid: PID::from_raw(pid),
+                        tgid: PID::from_raw(pid),
+                        parent_pid: parent_pid.map(PID::from),
+                        timestamp,
+                    });
+                }
+            }
+            self.processes.borrow_mut().insert(pid, exe);
+        }
+
+        Ok(())
+    }
+
+    /// Registers the process `pid` on the `kqueue`.
+    fn register(&self, pid: libc::pid_t) -> io::Result<()> {
+        let change = kevent(pid as libc::uintptr_t, libc::EVFILT_PROC, PROC_NOTES);
+        // Safety: Calling the ffi function with a pointer to change is safe
+        // as it points to a single initialized event.
+        match unsafe {
+            libc::kevent(
+                self.kqueue.as_raw_fd(),
+                &change,
+                1,
+                ptr::null_mut(),
+                0,
+                ptr::null(),
+            )
+        } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Waits up to `timeout` for `kqueue` events, queueing the process events
+    /// among them. A zero `timeout` waits forever.
+    ///
+    /// Returns `false` when no process events are received within `timeout`,
+    /// like when the waker is woken up.
+    fn receive(&self, timeout: Duration) -> io::Result<bool> {
+        // Registering the waker again on each wait keeps it registered after
+        // the source is set up again.
+        let changes: Vec<_> = self
+            .waker
+            .iter()
+            .map(|waker| {
+                kevent(
+                    waker.as_fd().as_raw_fd() as libc::uintptr_t,
+                    libc::EVFILT_READ,
+                    0,
+                )
+            })
+            .collect();
+        let timeout = (timeout != Duration::ZERO).then(|| libc::timespec {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_nsec: timeout.subsec_nanos() as libc::c_long,
+        });
+        // Safety: `kevent` is a plain C struct, for which zeroed memory is
+        // a valid value.
+        let mut events: [libc::kevent; EVENTS_BUFFER_SIZE] = unsafe { mem::zeroed() };
+
+        // Safety: Calling the ffi function with pointers to changes, events
+        // and timeout is safe as they point to initialized values of the
+        // given lengths.
+        let count = match unsafe {
+            libc::kevent(
+                self.kqueue.as_raw_fd(),
+                changes.as_ptr(),
+                changes.len() as libc::c_int,
+                events.as_mut_ptr(),
+                events.len() as libc::c_int,
+                timeout
+                    .as_ref()
+                    .map_or(ptr::null(), |timeout| timeout as *const _),
+            )
+        } {
+            -1 => return Err(io::Error::last_os_error()),
+            count => count as usize,
+        };
+
+        self.handle_all(&events[..count], monotonic_now())
+    }
+
+    /// Queues the process events among the `kqueue` `events`, received at
+    /// `timestamp`.
+    ///
+    /// Returns `true` when any process event is received. The events after
+    /// the one of the waker are handled too, as they are already removed from
+    /// the `kqueue`.
+    fn handle_all(&self, events: &[libc::kevent], timestamp: u64) -> io::Result<bool> {
+        let mut received = false;
+        for event in events {
+            if event.flags & libc::EV_ERROR != 0 {
+                match event.data as libc::c_int {
+                    // The process exited before it was registered
+                    libc::ESRCH => continue,
+                    errno => return Err(io::Error::from_raw_os_error(errno)),
+                }
+            }
+            // The events of other filters are the waker ones, checked by the
+            // records iterator with `is_woken`.
+            if event.filter != libc::EVFILT_PROC {
+                continue;
+            }
+
+            received = true;
+            self.handle(event, timestamp)?;
+        }
+
+        Ok(received)
+    }
+
+    /// Queues the process events reported by the `kqueue` `event`, received
+    /// at `timestamp`.
+    fn handle(&self, event: &libc::kevent, timestamp: u64) -> io::Result<()> {
+        let pid = event.ident as libc::pid_t;
+        #[cfg(target_os = "macos")]
+        if event.fflags & libc::NOTE_FORK != 0 {
+            self.register_new_processes(Some(timestamp))?;
+        }
+        #[cfg(target_os = "freebsd")]
+        if event.fflags & libc::NOTE_CHILD != 0 {
+            let exe = process_path(PID::from_raw(pid));
+            self.processes.borrow_mut().insert(pid, exe);
+        }
+        // The forked process couldn't be registered along with its parent
+        #[cfg(target_os = "freebsd")]
+        if event.fflags & libc::NOTE_TRACKERR != 0 {
+            self.register_new_processes(Some(timestamp))?;
+        }
+        if event.fflags & libc::NOTE_EXEC != 0 {
+            let exe = process_path(PID::from_raw(pid));
+            self.processes.borrow_mut().insert(pid, exe);
+            self.queue(PEvent::Exec {
+                pid: PID::from_raw(pid),
+                tgid: PID::from_raw(pid),
+                parent_pid: parent_pid(pid).map(PID::from),
+                timestamp,
+            });
+        }
+        if event.fflags & libc::NOTE_EXIT != 0 {
+            self.processes.borrow_mut().remove(&pid);
+            self.queue(PEvent::Exit {
+                pid: PID::from_raw(pid),
+                exit_code: PExitStatus::from(event.data as u32),
+                exit_signal: libc::SIGCHLD as u32,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Queues the `event`, unless the source is paused.
+    fn queue(&self, event: PEvent) {
+        if self.is_paused() {
+            return;
+        }
+
+        let timestamp_ns = match event {
+            PEvent::Exec { timestamp, .. } => timestamp,
+            PEvent::Exit { .. } => monotonic_now(),
+        };
+        self.pending.borrow_mut().push_back(PEventRecord {
+            event,
+            cpu: 0,
+            timestamp_ns,
+        });
+    }
+}
+
+impl ProcessEventSource for KqueueEventSource {
+    fn records(&self) -> Box<dyn Iterator<Item = Result<PEventRecord>> + '_> {
+        Box::new(KqueueEventSource::records(self))
+    }
+
+    fn set_timeout(&self, duration: Duration) -> Result<()> {
+        KqueueEventSource::set_timeout(self, duration);
+        Ok(())
+    }
+
+    fn set_waker(&mut self, waker: Waker) {
+        KqueueEventSource::set_waker(self, waker)
+    }
+
+    fn is_woken(&self) -> bool {
+        KqueueEventSource::is_woken(self)
+    }
+
+    fn pause(&self) -> Result<()> {
+        KqueueEventSource::pause(self);
+        Ok(())
+    }
+
+    fn resume(&self) -> Result<()> {
+        KqueueEventSource::resume(self);
+        Ok(())
+    }
+
+    fn is_paused(&self) -> bool {
+        KqueueEventSource::is_paused(self)
+    }
+
+    fn reconnect(&mut self) -> Result<()> {
+        KqueueEventSource::reconnect(self)
+    }
+}
+
+impl AsFd for KqueueEventSource {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.kqueue.as_fd()
+    }
+}
+
+/// Iterator over the event records received by a [`KqueueEventSource`].
+pub struct RecordIter<'a>(&'a KqueueEventSource);
+
+impl<'a> Iterator for RecordIter<'a> {
+    type Item = Result<PEventRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let timeout = self.0.timeout.get();
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(record) = self.0.pending.borrow_mut().pop_front() {
+                return Some(Ok(record));
+            }
+            if self.0.is_woken() {
+                return None;
+            }
+
+            // Forks and paused events don't queue any records, so wait
+            // again for the rest of the timeout.
+            let remaining = match timeout {
+                Duration::ZERO => Duration::ZERO,
+                _ => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if remaining > Duration::ZERO => remaining,
+                    _ => return None,
+                },
+            };
+            match self.0.receive(remaining) {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(error) => return Some(Err(Error::SocketRecv(error))),
+            }
+        }
+    }
+}
+
+/// Returns a `kevent` change adding the `filter` for `ident`, reporting the
+/// given `notes`.
+fn kevent(ident: libc::uintptr_t, filter: i16, notes: u32) -> libc::kevent {
+    // Safety: `kevent` is a plain C struct, for which zeroed memory is a
+    // valid value. Its fields differ between platforms.
+    let mut change: libc::kevent = unsafe { mem::zeroed() };
+    change.ident = ident;
+    change.filter = filter;
+    change.flags = libc::EV_ADD;
+    change.fflags = notes;
+    change
+}
+
+/// Returns the time since boot, in nanoseconds, like the timestamps of the
+/// process events connector.
+fn monotonic_now() -> u64 {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // Safety: Calling the ffi function with a pointer to time is safe as it
+    // points to an initialized timespec.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    #[test]
+    fn kqueue_source_reports_child_exec_and_exit() {
+        let source = KqueueEventSource::try_new().unwrap();
+        source.set_timeout(Duration::from_secs(5));
+
+        let mut child = Command::new("/bin/sleep").arg("0.1").spawn().unwrap();
+        let pid = PID::from_raw(child.id() as i32);
+        let mut exec = false;
+        for record in source.records() {
+            match record.unwrap().event {
+                PEvent::Exec { pid: event_pid, .. } if event_pid == pid => exec = true,
+                PEvent::Exit {
+                    pid: event_pid,
+                    exit_code,
+                    ..
+                } if event_pid == pid => {
+                    assert_eq!(PExitStatus::from(0), exit_code);
+                    break;
+                }
+                _ => (),
+            }
+        }
+        child.wait().unwrap();
+        assert!(exec);
+    }
+
+    #[test]
+    fn kqueue_source_handles_process_events_after_the_waker_one() {
+        let source = KqueueEventSource::try_new().unwrap();
+        let waker = kevent(0, libc::EVFILT_READ, 0);
+        let exit = kevent(999_999, libc::EVFILT_PROC, libc::NOTE_EXIT);
+
+        assert!(source.handle_all(&[waker, exit], monotonic_now()).unwrap());
+        let record = source.pending.borrow_mut().pop_front().unwrap();
+        assert!(matches!(
+            record.event,
+            PEvent::Exit { pid, .. } if pid == PID::from_raw(999_999)
+        ));
+    }
+}
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Process information of macOS, read with `libproc`.
+
+use std::{ffi::OsString, io, mem, os::unix::ffi::OsStringExt, path::PathBuf, ptr};
+
+use crate::solver::PID;
+
+/// Returns the path of the executable run by the process `pid`, read with
+/// `libproc`, or `None` when the process doesn't exist or it can't be read.
+pub fn process_path(pid: PID) -> Option<PathBuf> {
+    let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    // Safety: Calling the ffi function with a pointer to buffer is safe as it
+    // has the given length.
+    let size = unsafe {
+        libc::proc_pidpath(
+            pid.as_raw(),
+            buffer.as_mut_ptr() as *mut _,
+            buffer.len() as u32,
+        )
+    };
+    (size > 0).then(|| {
+        buffer.truncate(size as usize);
+        PathBuf::from(OsString::from_vec(buffer))
+    })
+}
+
+/// Returns the name of the process `pid`, read with `libproc`, or `None`
+/// when the process doesn't exist or it can't be read.
+pub fn process_name(pid: PID) -> Option<OsString> {
+    // Names are truncated to `2 * MAXCOMLEN` bytes.
+    let mut buffer = vec![0u8; 2 * libc::MAXCOMLEN + 1];
+    // Safety: Calling the ffi function with a pointer to buffer is safe as it
+    // has the given length.
+    let size = unsafe {
+        libc::proc_name(
+            pid.as_raw(),
+            buffer.as_mut_ptr() as *mut _,
+            buffer.len() as u32,
+        )
+    };
+    (size > 0).then(|| {
+        buffer.truncate(size as usize);
+        OsString::from_vec(buffer)
+    })
+}
+
+/// Returns the parent process ID of the process `pid`, or `None` when the
+/// process doesn't exist.
+pub(super) fn parent_pid(pid: libc::pid_t) -> Option<libc::pid_t> {
+    // Safety: `proc_bsdinfo` is a plain C struct, for which zeroed memory is
+    // a valid value.
+    let mut info: libc::proc_bsdinfo = unsafe { mem::zeroed() };
+    let size = mem::size_of::<libc::proc_bsdinfo>() as libc::c_int;
+    // Safety: Calling the ffi function with a pointer to info is safe as it
+    // points to a struct of the given size.
+    let result = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            libc::PROC_PIDTBSDINFO,
+            0,
+            &mut info as *mut _ as *mut _,
+            size,
+        )
+    };
+    (result == size).then_some(info.pbi_ppid as libc::pid_t)
+}
+
+/// Returns the IDs of the running processes.
+pub(super) fn list_pids() -> io::Result<Vec<libc::pid_t>> {
+    // The number of processes can grow between calls, so leave some room.
+    // Safety: Calling the ffi function with a null buffer is safe, as it
+    // only returns the number of processes then.
+    let count = match unsafe { libc::proc_listallpids(ptr::null_mut(), 0) } {
+        -1 => return Err(io::Error::last_os_error()),
+        count => count as usize + 64,
+    };
+    let mut pids = vec![0 as libc::pid_t; count];
+    // Safety: Calling the ffi function with a pointer to pids is safe as it
+    // has the given size.
+    let count = match unsafe {
+        libc::proc_listallpids(
+            pids.as_mut_ptr() as *mut _,
+            (pids.len() * mem::size_of::<libc::pid_t>()) as libc::c_int,
+        )
+    } {
+        -1 => return Err(io::Error::last_os_error()),
+        count => count as usize,
+    };
+    pids.truncate(count);
+
+    Ok(pids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn process_path_and_name_of_the_current_process() {
+        let pid = PID::from_raw(std::process::id() as i32);
+        assert_eq!(
+            std::env::current_exe().unwrap().canonicalize().ok(),
+            process_path(pid).map(|path| path.canonicalize().unwrap())
+        );
+        assert!(process_name(pid).is_some());
+    }
+
+    #[test]
+    fn list_pids_lists_the_current_process() {
+        let pid = std::process::id() as libc::pid_t;
+        assert!(list_pids().unwrap().contains(&pid));
+        assert_eq!(
+            Some(std::os::unix::process::parent_id() as libc::pid_t),
+            parent_pid(pid)
+        );
+    }
+}
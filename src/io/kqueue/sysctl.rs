@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Process information of FreeBSD, read from the `kern.proc` sysctl nodes.
+
+use std::{ffi::OsString, io, mem, os::unix::ffi::OsStringExt, path::PathBuf, ptr};
+
+use crate::solver::{PCmdLine, PID};
+
+/// Returns the path of the executable run by the process `pid`, read from
+/// `kern.proc.pathname`, or `None` when the process doesn't exist or it
+/// can't be read.
+pub fn process_path(pid: PID) -> Option<PathBuf> {
+    let mut path = kern_proc(libc::KERN_PROC_PATHNAME, pid.as_raw()).ok()?;
+    // The path is null terminated.
+    path.pop();
+    (!path.is_empty()).then(|| PathBuf::from(OsString::from_vec(path)))
+}
+
+/// Returns the command line of the process `pid`, read from
+/// `kern.proc.args`, or `None` when the process doesn't exist or it can't
+/// be read.
+pub fn process_cmdline(pid: PID) -> Option<PCmdLine> {
+    let args = kern_proc(libc::KERN_PROC_ARGS, pid.as_raw()).ok()?;
+    let mut args = args
+        .split(|byte| *byte == b'\0')
+        .map(|arg| OsString::from_vec(arg.to_vec()))
+        .collect::<Vec<_>>();
+    // The arguments are null terminated, and rewritten command lines are
+    // padded with null bytes.
+    while args.last().is_some_and(|arg| arg.is_empty()) {
+        args.pop();
+    }
+    Some(PCmdLine::from(args))
+}
+
+/// Returns the parent process ID of the process `pid`, or `None` when the
+/// process doesn't exist.
+pub(super) fn parent_pid(pid: libc::pid_t) -> Option<libc::pid_t> {
+    let info = kern_proc(libc::KERN_PROC_PID, pid).ok()?;
+    (info.len() >= mem::size_of::<libc::kinfo_proc>()).then(|| {
+        // Safety: The buffer holds a `kinfo_proc` struct, that may not be
+        // aligned.
+        let info = unsafe { ptr::read_unaligned(info.as_ptr() as *const libc::kinfo_proc) };
+        info.ki_ppid
+    })
+}
+
+/// Returns the IDs of the running processes.
+pub(super) fn list_pids() -> io::Result<Vec<libc::pid_t>> {
+    let procs = kern_proc(libc::KERN_PROC_PROC, 0)?;
+    Ok(procs
+        .chunks_exact(mem::size_of::<libc::kinfo_proc>())
+        .map(|info| {
+            // Safety: Each chunk holds a `kinfo_proc` struct, that may not
+            // be aligned.
+            let info = unsafe { ptr::read_unaligned(info.as_ptr() as *const libc::kinfo_proc) };
+            info.ki_pid
+        })
+        .collect())
+}
+
+/// Reads the `kern.proc` sysctl node with the given `query` and `arg`.
+fn kern_proc(query: libc::c_int, arg: libc::c_int) -> io::Result<Vec<u8>> {
+    let name = [libc::CTL_KERN, libc::KERN_PROC, query, arg];
+    loop {
+        let mut size = 0;
+        // Safety: Calling the ffi function with a null buffer is safe, as it
+        // only returns the size of the node then.
+        if unsafe {
+            libc::sysctl(
+                name.as_ptr(),
+                name.len() as libc::c_uint,
+                ptr::null_mut(),
+                &mut size,
+                ptr::null(),
+                0,
+            )
+        } == -1
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        // The node can grow between calls, like when new processes are
+        // started, so leave some room.
+        size += size / 8;
+        let mut buffer = vec![0u8; size];
+        // Safety: Calling the ffi function with a pointer to buffer is safe
+        // as it has the given size.
+        match unsafe {
+            libc::sysctl(
+                name.as_ptr(),
+                name.len() as libc::c_uint,
+                buffer.as_mut_ptr() as *mut _,
+                &mut size,
+                ptr::null(),
+                0,
+            )
+        } {
+            -1 => match io::Error::last_os_error() {
+                // It grew more than expected, try again.
+                error if error.raw_os_error() == Some(libc::ENOMEM) => continue,
+                error => return Err(error),
+            },
+            _ => {
+                buffer.truncate(size);
+                return Ok(buffer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn process_path_and_cmdline_of_the_current_process() {
+        let pid = PID::from_raw(std::process::id() as i32);
+        assert_eq!(
+            env::current_exe().unwrap().canonicalize().ok(),
+            process_path(pid).map(|path| path.canonicalize().unwrap())
+        );
+        assert_eq!(
+            &env::args_os().collect::<Vec<_>>(),
+            process_cmdline(pid).unwrap().as_ref()
+        );
+    }
+
+    #[test]
+    fn list_pids_lists_the_current_process() {
+        let pid = std::process::id() as libc::pid_t;
+        assert!(list_pids().unwrap().contains(&pid));
+        assert_eq!(
+            Some(std::os::unix::process::parent_id() as libc::pid_t),
+            parent_pid(pid)
+        );
+    }
+}
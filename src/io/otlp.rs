@@ -0,0 +1,453 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Export of the process spans to an OpenTelemetry collector.
+//!
+//! Spans are sent in batches with the OTLP/HTTP protocol, JSON encoded, from
+//! a worker thread. Only plain `http` endpoints are supported, as collectors
+//! usually run on the same host or network.
+
+use std::{
+    fmt::{self, Write as _},
+    fs::File,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        OnceLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    io::sink::{json_string, SCHEMA_VERSION},
+    report::Span,
+};
+
+/// Default port of the OTLP/HTTP receivers.
+const DEFAULT_PORT: u16 = 4318;
+
+/// Path of the traces in the OTLP/HTTP receivers.
+const TRACES_PATH: &str = "/v1/traces";
+
+/// Maximum time the spans are held before exporting them.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of spans that are exported right away.
+const MAX_BATCH_SIZE: usize = 512;
+
+/// Timeout of the connection and the requests to the collector.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `SpanKind` of the spans: `SPAN_KIND_INTERNAL`.
+const SPAN_KIND: u8 = 1;
+
+/// OTLP/HTTP traces endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtlpEndpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl FromStr for OtlpEndpoint {
+    type Err = String;
+
+    /// Parses an `http://host[:port][/path]` URL. The port defaults to 4318
+    /// and `/v1/traces` is appended to the path, unless it already ends with
+    /// it.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("'{}' is not an http:// URL", s))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => {
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| format!("'{}' is not a valid port", port))?;
+                (host, port)
+            }
+            _ => (authority, DEFAULT_PORT),
+        };
+        if host.is_empty() {
+            return Err(format!("'{}' has no host", s));
+        }
+
+        let path = path.trim_end_matches('/');
+        let path = if path.ends_with(TRACES_PATH) {
+            path.to_string()
+        } else {
+            format!("{}{}", path, TRACES_PATH)
+        };
+        Ok(OtlpEndpoint {
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+}
+
+impl fmt::Display for OtlpEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+/// Exports spans to an OpenTelemetry collector from a worker thread.
+///
+/// The spans not yet exported are sent when the exporter is dropped.
+#[derive(Debug)]
+pub struct OtlpExporter {
+    spans: Option<Sender<Span>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl OtlpExporter {
+    /// Attempts to start the worker thread of an `OtlpExporter` that sends
+    /// the spans to `endpoint`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the worker thread can't be started.
+    pub fn spawn(endpoint: OtlpEndpoint) -> io::Result<Self> {
+        let (spans, receiver) = mpsc::channel::<Span>();
+        let worker = thread::Builder::new()
+            .name("otlp-exporter".to_string())
+            .spawn(move || {
+                let mut batch = Vec::new();
+                let mut deadline = Instant::now() + EXPORT_INTERVAL;
+                loop {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    let stop = match receiver.recv_timeout(timeout) {
+                        Ok(span) => {
+                            batch.push(span);
+                            false
+                        }
+                        Err(RecvTimeoutError::Timeout) => false,
+                        Err(RecvTimeoutError::Disconnected) => true,
+                    };
+
+                    if stop || batch.len() >= MAX_BATCH_SIZE || Instant::now() >= deadline {
+                        if !batch.is_empty() {
+                            if let Err(e) = export(&endpoint, &batch) {
+                                log::warn!(
+                                    "Couldn't export {} spans to {}: {}",
+                                    batch.len(),
+                                    endpoint,
+                                    e
+                                );
+                            }
+                            batch.clear();
+                        }
+                        deadline = Instant::now() + EXPORT_INTERVAL;
+                    }
+                    if stop {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(OtlpExporter {
+            spans: Some(spans),
+            worker: Some(worker),
+        })
+    }
+
+    /// Queues an ended `span` to be exported.
+    pub fn export(&self, span: Span) {
+        if let Some(spans) = &self.spans {
+            if spans.send(span).is_err() {
+                log::warn!("OTLP exporter thread stopped");
+            }
+        }
+    }
+}
+
+impl Drop for OtlpExporter {
+    fn drop(&mut self) {
+        drop(self.spans.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Fills `bytes` with random data from the kernel, read from `/dev/urandom`
+/// as it's available on every Unix system.
+pub fn random_bytes(bytes: &mut [u8]) {
+    static URANDOM: OnceLock<Option<File>> = OnceLock::new();
+    let urandom = URANDOM.get_or_init(|| File::open("/dev/urandom").ok());
+    if !urandom
+        .as_ref()
+        .is_some_and(|mut urandom| urandom.read_exact(bytes).is_ok())
+    {
+        // The identifiers only need to be unique, so fall back to the clock
+        // when the random source isn't available.
+        unique_bytes(bytes);
+    }
+}
+
+/// Fills `bytes` with data that is different on each call, mixing the clock
+/// with a counter.
+fn unique_bytes(bytes: &mut [u8]) {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut state = (unix_nanos(SystemTime::now()) as u64)
+        ^ COUNTER
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    for chunk in bytes.chunks_mut(8) {
+        // SplitMix64 step
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^= value >> 31;
+        chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+    }
+}
+
+/// Sends the `spans` to the collector at `endpoint`.
+fn export(endpoint: &OtlpEndpoint, spans: &[Span]) -> io::Result<()> {
+    let body = traces_json(spans);
+    let address = (endpoint.host.as_str(), endpoint.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Host not found"))?;
+    let mut stream = TcpStream::connect_timeout(&address, REQUEST_TIMEOUT)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        endpoint.path,
+        endpoint.host,
+        endpoint.port,
+        body.len(),
+        body
+    )?;
+
+    let mut response = Vec::new();
+    stream.take(4096).read_to_end(&mut response)?;
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "Unexpected response '{}'",
+            status
+        ))),
+    }
+}
+
+/// Returns the OTLP/JSON `ExportTraceServiceRequest` of the `spans`.
+fn traces_json(spans: &[Span]) -> String {
+    let mut json = String::new();
+    let _ = write!(
+        json,
+        "{{\"resourceSpans\":[{{\"resource\":{{\"attributes\":[\
+         {{\"key\":\"service.name\",\"value\":{{\"stringValue\":\"copes\"}}}},\
+         {{\"key\":\"copes.schema_version\",\"value\":{{\"intValue\":\"{}\"}}}}]}},\
+         \"scopeSpans\":[{{\"scope\":{{\"name\":\"copes\",\"version\":",
+        SCHEMA_VERSION
+    );
+    json.push_str(&json_string(env!("CARGO_PKG_VERSION")));
+    json.push_str("},\"spans\":[");
+    for (index, span) in spans.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        span_json(&mut json, span);
+    }
+    json.push_str("]}]}]}");
+    json
+}
+
+fn span_json(json: &mut String, span: &Span) {
+    let _ = write!(
+        json,
+        "{{\"traceId\":\"{}\",\"spanId\":\"{}\",",
+        hex(&span.trace_id),
+        hex(&span.span_id)
+    );
+    if let Some(parent) = &span.parent_span_id {
+        let _ = write!(json, "\"parentSpanId\":\"{}\",", hex(parent));
+    }
+    let _ = write!(
+        json,
+        "\"name\":{},\"kind\":{},\"startTimeUnixNano\":\"{}\",\"endTimeUnixNano\":\"{}\",\
+         \"attributes\":[",
+        json_string(&span.name),
+        SPAN_KIND,
+        unix_nanos(span.start),
+        unix_nanos(span.end)
+    );
+
+    let _ = write!(
+        json,
+        "{{\"key\":\"process.pid\",\"value\":{{\"intValue\":\"{}\"}}}}",
+        span.pid
+    );
+    if let Some(ppid) = span.ppid {
+        let _ = write!(
+            json,
+            ",{{\"key\":\"process.parent_pid\",\"value\":{{\"intValue\":\"{}\"}}}}",
+            ppid
+        );
+    }
+    let _ = write!(
+        json,
+        ",{{\"key\":\"process.executable.name\",\"value\":{{\"stringValue\":{}}}}}",
+        json_string(&span.name)
+    );
+    if let Some(path) = &span.path {
+        let _ = write!(
+            json,
+            ",{{\"key\":\"process.executable.path\",\"value\":{{\"stringValue\":{}}}}}",
+            json_string(path)
+        );
+    }
+    if let Some(uid) = span.uid {
+        let _ = write!(
+            json,
+            ",{{\"key\":\"process.user.id\",\"value\":{{\"intValue\":\"{}\"}}}}",
+            uid
+        );
+    }
+    json.push_str("]}");
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use crate::solver::{PUid, PID};
+
+    use super::*;
+
+    fn span() -> Span {
+        Span {
+            trace_id: [0xab; 16],
+            span_id: [0x01; 8],
+            parent_span_id: Some([0x02; 8]),
+            name: "game\"".to_string(),
+            path: Some("/opt/game".to_string()),
+            pid: PID::from(42),
+            ppid: Some(PID::from(1)),
+            uid: Some(PUid::from(1000)),
+            start: SystemTime::UNIX_EPOCH + Duration::from_secs(1),
+            end: SystemTime::UNIX_EPOCH + Duration::from_millis(2500),
+        }
+    }
+
+    #[test]
+    fn endpoints_default_to_the_traces_path() {
+        let endpoint = "http://localhost".parse::<OtlpEndpoint>().unwrap();
+        assert_eq!("http://localhost:4318/v1/traces", endpoint.to_string());
+
+        let endpoint = "http://collector:4000/otlp/"
+            .parse::<OtlpEndpoint>()
+            .unwrap();
+        assert_eq!("http://collector:4000/otlp/v1/traces", endpoint.to_string());
+
+        let endpoint = "http://[::1]:4318/v1/traces"
+            .parse::<OtlpEndpoint>()
+            .unwrap();
+        assert_eq!("http://[::1]:4318/v1/traces", endpoint.to_string());
+
+        assert!("https://localhost".parse::<OtlpEndpoint>().is_err());
+        assert!("http://localhost:port".parse::<OtlpEndpoint>().is_err());
+        assert!("http://:4318".parse::<OtlpEndpoint>().is_err());
+    }
+
+    #[test]
+    fn spans_are_otlp_json() {
+        let json = traces_json(&[span()]);
+        assert!(json.starts_with(
+            "{\"resourceSpans\":[{\"resource\":{\"attributes\":[{\"key\":\"service.name\""
+        ));
+        assert!(json.contains(&format!(
+            "{{\"traceId\":\"{}\",\"spanId\":\"0101010101010101\",\
+             \"parentSpanId\":\"0202020202020202\",\"name\":\"game\\\"\",\"kind\":1,\
+             \"startTimeUnixNano\":\"1000000000\",\"endTimeUnixNano\":\"2500000000\"",
+            "ab".repeat(16)
+        )));
+        assert!(
+            json.contains("{\"key\":\"copes.schema_version\",\"value\":{\"intValue\":\"1\"}}]}")
+        );
+        assert!(json.contains("{\"key\":\"process.pid\",\"value\":{\"intValue\":\"42\"}}"));
+        assert!(json.contains("{\"key\":\"process.parent_pid\",\"value\":{\"intValue\":\"1\"}}"));
+        assert!(json.contains(
+            "{\"key\":\"process.executable.path\",\"value\":{\"stringValue\":\"/opt/game\"}}"
+        ));
+        assert!(json.contains("{\"key\":\"process.user.id\",\"value\":{\"intValue\":\"1000\"}}"));
+        assert!(json.ends_with("]}]}]}]}"));
+    }
+
+    #[test]
+    fn exporter_posts_the_spans_when_dropped() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let collector = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            while !String::from_utf8_lossy(&request).ends_with("]}]}]}") {
+                let size = stream.read(&mut buffer).unwrap();
+                assert_ne!(0, size);
+                request.extend_from_slice(&buffer[..size]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let endpoint = format!("http://127.0.0.1:{}", port).parse().unwrap();
+        let exporter = OtlpExporter::spawn(endpoint).unwrap();
+        exporter.export(span());
+        drop(exporter);
+
+        let request = collector.join().unwrap();
+        assert!(request.starts_with("POST /v1/traces HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Type: application/json\r\n"));
+        assert!(request.contains("\"spanId\":\"0101010101010101\""));
+    }
+
+    #[test]
+    fn random_bytes_fills_the_buffer() {
+        let mut bytes = [0u8; 16];
+        random_bytes(&mut bytes);
+        assert_ne!([0u8; 16], bytes);
+    }
+
+    #[test]
+    fn unique_bytes_differ_on_each_call() {
+        let mut first = [0u8; 12];
+        let mut second = [0u8; 12];
+        unique_bytes(&mut first);
+        unique_bytes(&mut second);
+        assert_ne!(first, second);
+    }
+}
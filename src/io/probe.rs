@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Probes of the system features needed to monitor process events.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Capability needed to subscribe to the process events connector.
+pub const CAP_NET_ADMIN: u32 = 12;
+
+/// Attempts to check whether the process has the capability with the given
+/// number on its effective capabilities set.
+///
+/// # Errors
+///
+/// If this function encounters any form of I/O error or the capabilities
+/// cannot be parsed, an error variant will be returned.
+pub fn has_effective_capability(capability: u32) -> io::Result<bool> {
+    let status = fs::read_to_string("/proc/self/status")?;
+    let capabilities = parse_effective_capabilities(&status)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No effective capabilities"))?;
+
+    Ok(capabilities & (1 << capability) != 0)
+}
+
+/// Returns whether the running kernel was built with the configuration
+/// `option` (like `CONFIG_PROC_EVENTS`) enabled.
+///
+/// Returns `None` when the kernel configuration is not available.
+pub fn kernel_config_option(option: &str) -> Option<bool> {
+    let release = kernel_release()?;
+
+    [
+        PathBuf::from(format!("/boot/config-{}", release)),
+        PathBuf::from(format!("/lib/modules/{}/config", release)),
+    ]
+    .iter()
+    .find_map(|path| fs::read_to_string(path).ok())
+    .map(|config| is_config_option_enabled(&config, option))
+}
+
+/// Returns the release of the running kernel (like `6.8.0-40-generic`), or
+/// `None` when it can't be read.
+pub fn kernel_release() -> Option<String> {
+    fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .map(|release| release.trim().to_string())
+}
+
+/// Attempts to get the `hidepid` option of the proc file system mounted on
+/// `mount_point`, which hides the processes of other users.
+///
+/// Returns `None` when the option is not set or the processes are visible.
+///
+/// # Errors
+///
+/// If this function encounters any form of I/O error reading the mounts of
+/// the process, an error variant will be returned.
+pub fn proc_hidepid(mount_point: &Path) -> io::Result<Option<String>> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo")?;
+    Ok(parse_hidepid(&mountinfo, mount_point))
+}
+
+fn parse_effective_capabilities(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+}
+
+fn is_config_option_enabled(config: &str, option: &str) -> bool {
+    config.lines().any(|line| {
+        line.strip_prefix(option)
+            .and_then(|value| value.strip_prefix('='))
+            .is_some_and(|value| value == "y" || value == "m")
+    })
+}
+
+fn parse_hidepid(mountinfo: &str, mount_point: &Path) -> Option<String> {
+    // Mount ID, parent ID, device, root, mount point and options, optional
+    // fields ended by a separator, then file system type, source and super
+    // block options.
+    let options = mountinfo.lines().rev().find_map(|line| {
+        let (mount, fs) = line.split_once(" - ")?;
+        let mut fs = fs.split(' ');
+        let is_proc = fs.next() == Some("proc");
+        let on_mount_point = mount.split(' ').nth(4).map(Path::new) == Some(mount_point);
+        (is_proc && on_mount_point).then(|| fs.nth(1).unwrap_or_default())
+    })?;
+
+    options
+        .split(',')
+        .find_map(|option| option.strip_prefix("hidepid="))
+        .filter(|hidepid| !matches!(*hidepid, "0" | "off"))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_effective_capabilities_returns_capabilities_mask() {
+        let status = "Name:\tcopes\nCapPrm:\t0000000000000000\nCapEff:\t0000000000001000\n";
+        assert_eq!(
+            Some(1 << CAP_NET_ADMIN),
+            parse_effective_capabilities(status)
+        );
+        assert_eq!(
+            Some(0x1fffeffffff),
+            parse_effective_capabilities("CapEff:\t000001fffeffffff\n")
+        );
+    }
+
+    #[test]
+    fn parse_effective_capabilities_returns_none_without_capabilities() {
+        assert_eq!(None, parse_effective_capabilities("Name:\tcopes\n"));
+        assert_eq!(None, parse_effective_capabilities("CapEff:\tinvalid\n"));
+    }
+
+    #[test]
+    fn is_config_option_enabled_returns_option_state() {
+        let config =
+            "CONFIG_CONNECTOR=y\n# CONFIG_PROC_EVENTS is not set\nCONFIG_PROC_EVENTS_X=y\n";
+        assert!(is_config_option_enabled(config, "CONFIG_CONNECTOR"));
+        assert!(!is_config_option_enabled(config, "CONFIG_PROC_EVENTS"));
+        assert!(!is_config_option_enabled(config, "CONFIG_MISSING"));
+        assert!(is_config_option_enabled(
+            "CONFIG_CONNECTOR=m",
+            "CONFIG_CONNECTOR"
+        ));
+    }
+
+    #[test]
+    fn parse_hidepid_returns_option_of_proc_mount_point() {
+        let mountinfo = "22 1 0:21 / /proc rw,nosuid - proc proc rw,hidepid=invisible\n\
+                         30 22 0:25 / /proc/sys/fs/binfmt_misc rw - autofs systemd-1 rw\n\
+                         40 1 0:30 / /chroot/proc rw - proc proc rw\n";
+        assert_eq!(
+            Some("invisible".to_string()),
+            parse_hidepid(mountinfo, Path::new("/proc"))
+        );
+        assert_eq!(None, parse_hidepid(mountinfo, Path::new("/chroot/proc")));
+        assert_eq!(None, parse_hidepid(mountinfo, Path::new("/missing")));
+    }
+
+    #[test]
+    fn parse_hidepid_ignores_visible_processes_and_overmounted_proc() {
+        let mountinfo = "22 1 0:21 / /proc rw shared:13 - proc proc rw,hidepid=2\n\
+                         41 22 0:31 / /proc rw - proc proc rw,hidepid=off\n";
+        assert_eq!(None, parse_hidepid(mountinfo, Path::new("/proc")));
+        assert_eq!(
+            Some("2".to_string()),
+            parse_hidepid(mountinfo.lines().next().unwrap(), Path::new("/proc"))
+        );
+    }
+}
@@ -5,50 +5,1251 @@
 
 use std::{
     ffi::OsString,
-    fs::File,
+    fs::{self, File},
     io::{self, BufRead},
-    os::unix::prelude::OsStringExt,
-    path::PathBuf,
+    os::unix::{fs::MetadataExt, prelude::OsStringExt},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
 };
 
-use crate::solver::{PCmdLine, PExe, PID};
+use crate::{
+    solver::{
+        CgroupHierarchy, PCapabilities, PCgroup, PCgroups, PCmdLine, PComm, PCwd, PEnviron, PExe,
+        PExePath, PIo, PNsPIDs, PRoot, PSchedPolicy, PSecurityLabel, PSessionId, PSetId, PStat,
+        PStatus, PTty, PUid, PID,
+    },
+    Error, Result,
+};
 
-/// Attempts to get the process executable name for the given `pid`.
-///
-/// # Errors
-///
-/// If this function encounters any form of I/O error, an error variant will be
-/// returned.
-pub fn exe_reader(pid: PID) -> io::Result<PExe> {
-    match PathBuf::from(pid).join("exe").read_link()?.file_name() {
-        Some(exe) => Ok(exe.to_os_string().into()),
-        None => Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "No executable file name",
-        )),
+mod cache;
+#[cfg(any(test, feature = "test-util"))]
+pub mod fake;
+
+pub use cache::{CachedProcess, ProcCache};
+
+/// Default mount point of the proc file system.
+pub const DEFAULT_PROC_ROOT: &str = "/proc";
+
+/// Value of the login user ID and session ID of processes not started from a
+/// login session.
+const AUDIT_UNSET: u32 = u32::MAX;
+
+/// Number of attempts made by [`retry_read`] before giving up.
+pub const READ_ATTEMPTS: u32 = 3;
+
+/// Delay between the attempts made by [`retry_read`].
+pub const READ_RETRY_DELAY: Duration = Duration::from_millis(1);
+
+/// A proc file system mounted at a root path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcFs(PathBuf);
+
+impl ProcFs {
+    /// Creates a `ProcFs` for the proc file system mounted at `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ProcFs(root.into())
+    }
+
+    /// Returns the mount point of the proc file system.
+    pub fn root(&self) -> &Path {
+        &self.0
+    }
+
+    /// Returns the path of the directory of the process with the given `pid`.
+    pub fn pid_path(&self, pid: PID) -> PathBuf {
+        self.0.join(pid.to_string())
+    }
+
+    /// Attempts to get the process executable name for the given `pid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn exe_reader(&self, pid: PID) -> Result<PExe> {
+        let exe = self.exe_path_reader(pid)?;
+        match exe.as_ref().file_name() {
+            Some(exe) => Ok(exe.to_os_string().into()),
+            None => Err(parse_error(
+                pid,
+                self.pid_path(pid).join("exe"),
+                "no executable file name",
+            )),
+        }
+    }
+
+    /// Attempts to get the process executable path for the given `pid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process link can't be
+    /// read.
+    pub fn exe_path_reader(&self, pid: PID) -> Result<PExePath> {
+        let path = self.pid_path(pid).join("exe");
+        read_file(pid, &path, |path| path.read_link()).map(PExePath::from)
+    }
+
+    /// Attempts to get the identities granted by the set-user-ID and
+    /// set-group-ID permissions of the executable of the process with the
+    /// given `pid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process executable
+    /// can't be accessed.
+    pub fn setid_reader(&self, pid: PID) -> Result<PSetId> {
+        let path = self.pid_path(pid).join("exe");
+        let metadata = read_file(pid, &path, |path| fs::metadata(path))?;
+        let mode = metadata.mode();
+        // Without group execute permission, the set-group-ID permission
+        // enables mandatory file locking instead.
+        let setgid = libc::S_ISGID | libc::S_IXGRP;
+        Ok(PSetId {
+            uid: (mode & libc::S_ISUID != 0).then(|| PUid::from(metadata.uid())),
+            gid: (mode & setgid == setgid).then_some(metadata.gid()),
+        })
+    }
+
+    /// Attempts to open the executable file of the process with the given
+    /// `pid`. The file stays the executed one even if its path is replaced
+    /// or deleted afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process executable
+    /// can't be opened.
+    pub fn exe_file_reader(&self, pid: PID) -> Result<File> {
+        let path = self.pid_path(pid).join("exe");
+        read_file(pid, &path, |path| File::open(path))
+    }
+
+    /// Attempts to get the process command name for the given `pid`.
+    ///
+    /// Unlike the executable, the command name can be read from processes of
+    /// other users and from processes that exited but were not reaped yet.
+    /// Reading it is also cheaper than resolving the executable link.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read.
+    pub fn comm_reader(&self, pid: PID) -> Result<PComm> {
+        let path = self.pid_path(pid).join("comm");
+        let mut comm = read_file(pid, &path, |path| fs::read(path))?;
+        if comm.last() == Some(&b'\n') {
+            comm.pop();
+        }
+        Ok(OsString::from_vec(comm).into())
+    }
+
+    /// Attempts to get the current working directory of the process with the
+    /// given `pid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process link can't be
+    /// read.
+    pub fn cwd_reader(&self, pid: PID) -> Result<PCwd> {
+        let path = self.pid_path(pid).join("cwd");
+        read_file(pid, &path, |path| path.read_link()).map(PCwd::from)
+    }
+
+    /// Attempts to get the root directory of the process with the given
+    /// `pid`. Use [`PRoot::is_chrooted`] to detect chrooted processes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process link can't be
+    /// read.
+    pub fn root_reader(&self, pid: PID) -> Result<PRoot> {
+        let path = self.pid_path(pid).join("root");
+        read_file(pid, &path, |path| path.read_link()).map(PRoot::from)
+    }
+
+    /// Attempts to get the process command line for the given `pid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn cmdline_reader(&self, pid: PID) -> Result<PCmdLine> {
+        let path = self.pid_path(pid).join("cmdline");
+        let mut cmdline = read_file(pid, &path, |path| {
+            io::BufReader::new(File::open(path)?)
+                .split(b'\0')
+                .map(|arg| arg.map(OsString::from_vec))
+                .collect::<io::Result<Vec<_>>>()
+        })?;
+        // Processes that rewrite their command line pad it with null bytes,
+        // read as trailing empty arguments.
+        while cmdline.last().is_some_and(|arg| arg.is_empty()) {
+            cmdline.pop();
+        }
+
+        Ok(cmdline.into())
+    }
+
+    /// Attempts to get the process environment variables for the given `pid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read.
+    pub fn environ_reader(&self, pid: PID) -> Result<PEnviron> {
+        let environ = self.environ_iter(pid)?.collect::<Result<Vec<_>>>()?;
+        Ok(environ.into())
+    }
+
+    /// Attempts to get the process environment variables named `keys` for
+    /// the given `pid`. Variables that are not set are not returned.
+    ///
+    /// The environment is read until all the variables are found, without
+    /// keeping the other variables in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read.
+    pub fn environ_vars_reader(&self, pid: PID, keys: &[&str]) -> Result<PEnviron> {
+        let mut environ = Vec::new();
+        for var in self.environ_iter(pid)? {
+            let (key, value) = var?;
+            if keys.iter().any(|k| key == *k) && !environ.iter().any(|(k, _)| *k == key) {
+                environ.push((key, value));
+                if environ.len() == keys.len() {
+                    break;
+                }
+            }
+        }
+        Ok(environ.into())
+    }
+
+    /// Attempts to open the environment variables of the process with the
+    /// given `pid`, returning an iterator that reads them lazily.
+    ///
+    /// The environment of processes of other users can't be read without
+    /// privileges, failing with a permission denied error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// opened.
+    pub fn environ_iter(&self, pid: PID) -> Result<EnvironIter> {
+        let path = self.pid_path(pid).join("environ");
+        let file = read_file(pid, &path, |path| File::open(path))?;
+        Ok(EnvironIter {
+            pid,
+            path,
+            entries: io::BufReader::new(file).split(b'\0'),
+        })
+    }
+
+    /// Attempts to get the process IDs on each PID namespace of the process with
+    /// the given `pid`.
+    ///
+    /// On kernels without PID namespaces information, only `pid` is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn nspid_reader(&self, pid: PID) -> Result<PNsPIDs> {
+        let path = self.pid_path(pid).join("status");
+        let status = read_file(pid, &path, |path| fs::read_to_string(path))?;
+        Ok(parse_nspid(&status).unwrap_or_else(|| vec![pid]).into())
+    }
+
+    /// Attempts to translate `ns_pid`, the PID of a process on the PID
+    /// namespace of the process with the given `pid`, to its PID on the PID
+    /// namespace of the proc file system. Processes of containers can be
+    /// found from the PIDs they have inside them this way.
+    ///
+    /// Returns `None` when no process has `ns_pid` on that PID namespace.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the PID namespace of the
+    /// process with the given `pid` can't be read, and an [`Error::Io`]
+    /// error when the processes can't be listed.
+    pub fn host_pid_reader(&self, pid: PID, ns_pid: PID) -> Result<Option<PID>> {
+        let path = self.pid_path(pid).join("ns").join("pid");
+        let namespace = read_file(pid, &path, |path| path.read_link())?;
+
+        for entry in fs::read_dir(&self.0)? {
+            let Some(candidate) = entry?
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse().ok())
+                .map(PID::from_raw)
+            else {
+                continue;
+            };
+            // Processes that can't be read, like the ones that exited
+            // meanwhile, aren't the translated one.
+            let same_namespace = self
+                .pid_path(candidate)
+                .join("ns")
+                .join("pid")
+                .read_link()
+                .is_ok_and(|candidate_namespace| candidate_namespace == namespace);
+            if same_namespace
+                && self
+                    .nspid_reader(candidate)
+                    .is_ok_and(|nspids| nspids.innermost() == Some(ns_pid))
+            {
+                return Ok(Some(candidate));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Attempts to get the real user ID of the process with the given `pid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn uid_reader(&self, pid: PID) -> Result<PUid> {
+        let path = self.pid_path(pid).join("status");
+        let status = read_file(pid, &path, |path| fs::read_to_string(path))?;
+        match parse_uid(&status) {
+            Some(uid) => Ok(uid.into()),
+            None => Err(parse_error(pid, path, "no user ID")),
+        }
+    }
+
+    /// Attempts to get the information of the `stat` file of the process
+    /// with the given `pid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn stat_reader(&self, pid: PID) -> Result<PStat> {
+        let path = self.pid_path(pid).join("stat");
+        let stat = read_file(pid, &path, |path| fs::read(path))?;
+        match parse_stat(&stat, clock_ticks_per_second()) {
+            Some(stat) => Ok(stat),
+            None => Err(parse_error(pid, path, "missing or invalid stat fields")),
+        }
+    }
+
+    /// Attempts to get the information of the `status` file of the process
+    /// with the given `pid`.
+    ///
+    /// On kernels without PID namespaces information, only `pid` is returned
+    /// as the process IDs on each PID namespace.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn status_reader(&self, pid: PID) -> Result<PStatus> {
+        let path = self.pid_path(pid).join("status");
+        let status = read_file(pid, &path, |path| fs::read_to_string(path))?;
+        match parse_status(pid, &status) {
+            Some(status) => Ok(status),
+            None => Err(parse_error(pid, path, "missing or invalid status fields")),
+        }
+    }
+
+    /// Attempts to get the peak resident set size of the process with the
+    /// given `pid`, in bytes.
+    ///
+    /// Returns `None` for processes without memory, like kernel threads and
+    /// processes that exited, as their memory is released before they are
+    /// reaped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read.
+    pub fn peak_rss_reader(&self, pid: PID) -> Result<Option<u64>> {
+        let path = self.pid_path(pid).join("status");
+        let status = read_file(pid, &path, |path| fs::read_to_string(path))?;
+        Ok(parse_peak_rss(&status))
+    }
+
+    /// Attempts to get the input / output counters of the process with the
+    /// given `pid`. They can be read until the process is reaped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn io_reader(&self, pid: PID) -> Result<PIo> {
+        let path = self.pid_path(pid).join("io");
+        let io = read_file(pid, &path, |path| fs::read_to_string(path))?;
+        match parse_io(&io) {
+            Some(io) => Ok(io),
+            None => Err(parse_error(pid, path, "missing or invalid I/O counters")),
+        }
+    }
+
+    /// Attempts to get the login user ID of the process with the given `pid`,
+    /// that is, the user that logged in to start the login session of the
+    /// process. It's kept when the process changes its user IDs, as `sudo`
+    /// or `su` do.
+    ///
+    /// Returns `None` for processes not started from a login session, like
+    /// system services.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn loginuid_reader(&self, pid: PID) -> Result<Option<PUid>> {
+        let path = self.pid_path(pid).join("loginuid");
+        let loginuid = read_file(pid, &path, |path| fs::read_to_string(path))?;
+        match parse_audit_id(&loginuid) {
+            Some(loginuid) => Ok(loginuid.map(PUid::from)),
+            None => Err(parse_error(pid, path, "invalid login user ID")),
+        }
+    }
+
+    /// Attempts to get the audit session ID of the process with the given
+    /// `pid`.
+    ///
+    /// Returns `None` for processes not started from a login session, like
+    /// system services.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn sessionid_reader(&self, pid: PID) -> Result<Option<PSessionId>> {
+        let path = self.pid_path(pid).join("sessionid");
+        let sessionid = read_file(pid, &path, |path| fs::read_to_string(path))?;
+        match parse_audit_id(&sessionid) {
+            Some(sessionid) => Ok(sessionid.map(PSessionId::from)),
+            None => Err(parse_error(pid, path, "invalid session ID")),
+        }
+    }
+
+    /// Attempts to get the security label of the process with the given
+    /// `pid`, from the Linux security module that provides them, like
+    /// SELinux or AppArmor.
+    ///
+    /// Returns `None` when no active security module labels processes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read.
+    pub fn security_label_reader(&self, pid: PID) -> Result<Option<PSecurityLabel>> {
+        let path = self.pid_path(pid).join("attr").join("current");
+        let label = read_file(pid, &path, |path| match fs::read(path) {
+            Ok(label) => Ok(Some(label)),
+            // The file can't be read when no security module provides it.
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => Ok(None),
+            Err(e) => Err(e),
+        })?;
+        Ok(label.and_then(parse_security_label))
+    }
+
+    /// Attempts to get the control group of the process with the given `pid`.
+    ///
+    /// The control group on the unified hierarchy (cgroup v2) is preferred.
+    /// Otherwise, the control group on the first cgroup v1 hierarchy is
+    /// returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read, and an [`Error::ParseError`] error when it can't be parsed.
+    pub fn cgroup_reader(&self, pid: PID) -> Result<PCgroup> {
+        let cgroups = self.cgroups_reader(pid)?;
+        match cgroups.path() {
+            Some(cgroup) => Ok(cgroup.clone()),
+            None => Err(parse_error(
+                pid,
+                self.pid_path(pid).join("cgroup"),
+                "no control group",
+            )),
+        }
+    }
+
+    /// Attempts to get the control groups of the process with the given
+    /// `pid` on each cgroup hierarchy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::ProcRead`] error when the process file can't be
+    /// read.
+    pub fn cgroups_reader(&self, pid: PID) -> Result<PCgroups> {
+        let path = self.pid_path(pid).join("cgroup");
+        let cgroup = read_file(pid, &path, |path| fs::read(path))?;
+        Ok(parse_cgroups(&cgroup))
     }
 }
 
-/// Attempts to get the process command line for the given `pid`.
+/// Iterator over the environment variables of a process, read lazily from
+/// its `environ` file. Entries without `=` separator are skipped.
+#[derive(Debug)]
+pub struct EnvironIter {
+    pid: PID,
+    path: PathBuf,
+    entries: io::Split<io::BufReader<File>>,
+}
+
+impl Iterator for EnvironIter {
+    type Item = Result<(OsString, OsString)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut entry = match self.entries.next()? {
+                Ok(entry) => entry,
+                Err(source) => {
+                    return Some(Err(Error::ProcRead {
+                        pid: self.pid,
+                        path: self.path.clone(),
+                        source,
+                    }))
+                }
+            };
+
+            if let Some(pos) = entry.iter().position(|c| *c == b'=') {
+                let value = entry.split_off(pos + 1);
+                entry.truncate(pos);
+                return Some(Ok((OsString::from_vec(entry), OsString::from_vec(value))));
+            }
+        }
+    }
+}
+
+impl Default for ProcFs {
+    fn default() -> Self {
+        ProcFs::new(DEFAULT_PROC_ROOT)
+    }
+}
+
+/// Errors of the reads made through [`retry_read`].
+pub trait ReadError {
+    /// Returns `true` when the read may succeed if it's retried.
+    fn is_transient(&self) -> bool;
+}
+
+impl ReadError for io::Error {
+    /// Denied reads and reads of the files of processes that no longer exist
+    /// fail again on every retry.
+    fn is_transient(&self) -> bool {
+        self.kind() != io::ErrorKind::PermissionDenied && !is_process_gone(self)
+    }
+}
+
+impl ReadError for Error {
+    fn is_transient(&self) -> bool {
+        self.io_error().map_or(true, ReadError::is_transient)
+    }
+}
+
+/// Calls `read` until it succeeds or [`READ_ATTEMPTS`] attempts are made.
+///
+/// Files of processes that just started or are about to exit can be
+/// transiently unreadable, so failed reads are retried after a short delay.
+/// Reads failing with non-transient errors aren't retried.
 ///
 /// # Errors
 ///
-/// If this function encounters any form of I/O error, an error variant will be
-/// returned.
-pub fn cmdline_reader(pid: PID) -> io::Result<PCmdLine> {
-    let cmdline = io::BufReader::new(File::open(PathBuf::from(pid).join("cmdline"))?)
-        .split(b'\0')
-        .filter_map(|v| match v {
-            Err(e) => Some(Err(e)),
-            Ok(data) => {
-                if data.is_empty() {
-                    None
-                } else {
-                    Some(Ok(OsString::from_vec(data)))
-                }
+/// Returns the error of the last attempt when all the attempts fail, or the
+/// first non-transient error.
+pub fn retry_read<T, E: ReadError>(
+    mut read: impl FnMut() -> std::result::Result<T, E>,
+) -> std::result::Result<T, E> {
+    let mut attempt = 1;
+    loop {
+        match read() {
+            Err(e) if attempt < READ_ATTEMPTS && e.is_transient() => {
+                attempt += 1;
+                thread::sleep(READ_RETRY_DELAY);
             }
+            result => return result,
+        }
+    }
+}
+
+/// Calls `read` with the `path` of a file of the process with the given
+/// `pid`, adding them to the returned error.
+fn read_file<T>(pid: PID, path: &Path, read: impl FnOnce(&Path) -> io::Result<T>) -> Result<T> {
+    read(path).map_err(|source| Error::ProcRead {
+        pid,
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn parse_error(pid: PID, path: PathBuf, reason: &'static str) -> Error {
+    Error::ParseError { pid, path, reason }
+}
+
+/// Returns `true` when `error` was caused by reading the files of a process
+/// that no longer exists.
+pub fn is_process_gone(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::NotFound || error.raw_os_error() == Some(libc::ESRCH)
+}
+
+/// Returns the number of clock ticks per second, the unit of the times on
+/// the `stat` file.
+fn clock_ticks_per_second() -> u64 {
+    // Safety: sysconf has no preconditions.
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as u64,
+        // Fallback to USER_HZ value on most architectures.
+        _ => 100,
+    }
+}
+
+/// Process flag of the kernel threads, from `include/linux/sched.h`.
+const PF_KTHREAD: u32 = 0x0020_0000;
+
+fn parse_stat(stat: &[u8], ticks_per_second: u64) -> Option<PStat> {
+    let comm = parse_stat_comm(stat)?;
+
+    // The fields after the command name start with the state (3rd field).
+    let end = stat.iter().rposition(|c| *c == b')')?;
+    let fields = std::str::from_utf8(&stat[end + 1..]).ok()?;
+    let mut fields = fields.split_whitespace();
+    let state = fields.next()?.chars().next()?;
+    let ppid = fields.next()?.parse::<i32>().ok()?;
+    let session = fields.nth(1)?.parse::<i32>().ok()?;
+    let tty_nr = fields.next()?.parse::<i32>().ok()?;
+    // flags is the 9th field
+    let flags = fields.nth(9 - 8)?.parse::<u32>().ok()?;
+    // nice is the 19th field
+    let nice = fields.nth(19 - 10)?.parse::<i32>().ok()?;
+    // starttime is the 22nd field
+    let start_ticks = fields.nth(22 - 20)?.parse::<u64>().ok()?;
+    // rt_priority and policy are the 40th and 41st fields
+    let rt_priority = fields
+        .nth(40 - 23)
+        .and_then(|field| field.parse::<u32>().ok());
+    let policy = fields.next().and_then(|field| field.parse::<u32>().ok());
+
+    Some(PStat {
+        comm: PComm::from(OsString::from_vec(comm.to_vec())),
+        state,
+        ppid: PID::from(ppid),
+        session: PID::from(session),
+        tty: (tty_nr != 0).then_some(PTty::from(tty_nr as u32)),
+        nice,
+        policy: policy.map(PSchedPolicy::from),
+        rt_priority: rt_priority.unwrap_or_default(),
+        start_time: Duration::from_secs(start_ticks / ticks_per_second)
+            + Duration::from_secs(start_ticks % ticks_per_second) / ticks_per_second as u32,
+        kernel_thread: flags & PF_KTHREAD != 0,
+    })
+}
+
+fn parse_stat_comm(stat: &[u8]) -> Option<&[u8]> {
+    // The command name is enclosed in parentheses and can contain any
+    // character, including parentheses, so it ends at the last ')'.
+    let start = stat.iter().position(|c| *c == b'(')? + 1;
+    let end = stat.iter().rposition(|c| *c == b')')?;
+    stat.get(start..end)
+}
+
+/// Returns the value of the `name` field of a `status` file.
+fn status_field<'a>(status: &'a str, name: &str) -> Option<&'a str> {
+    status.lines().find_map(|line| {
+        line.strip_prefix(name)
+            .and_then(|line| line.strip_prefix(':'))
+            .map(str::trim)
+    })
+}
+
+fn parse_status(pid: PID, status: &str) -> Option<PStatus> {
+    let first_number = |name| {
+        status_field(status, name)?
+            .split_whitespace()
+            .next()?
+            .parse::<u32>()
+            .ok()
+    };
+    let capabilities = |name| {
+        u64::from_str_radix(status_field(status, name)?, 16)
+            .ok()
+            .map(PCapabilities::from)
+    };
+
+    Some(PStatus {
+        name: PComm::from(OsString::from(status_field(status, "Name")?)),
+        state: status_field(status, "State")?.chars().next()?,
+        ppid: PID::from(status_field(status, "PPid")?.parse::<i32>().ok()?),
+        uid: PUid::from(first_number("Uid")?),
+        gid: first_number("Gid")?,
+        nspids: parse_nspid(status).unwrap_or_else(|| vec![pid]).into(),
+        tracer_pid: match status_field(status, "TracerPid")?.parse::<i32>().ok()? {
+            0 => None,
+            tracer_pid => Some(PID::from(tracer_pid)),
+        },
+        cap_effective: capabilities("CapEff")?,
+        cap_permitted: capabilities("CapPrm")?,
+    })
+}
+
+fn parse_peak_rss(status: &str) -> Option<u64> {
+    let kb = status_field(status, "VmHWM")?
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+fn parse_io(io: &str) -> Option<PIo> {
+    let counter = |name| status_field(io, name)?.parse::<u64>().ok();
+
+    Some(PIo {
+        rchar: counter("rchar")?,
+        wchar: counter("wchar")?,
+        read_bytes: counter("read_bytes")?,
+        write_bytes: counter("write_bytes")?,
+    })
+}
+
+fn parse_nspid(status: &str) -> Option<Vec<PID>> {
+    status_field(status, "NSpid").map(|pids| {
+        pids.split_whitespace()
+            .filter_map(|pid| pid.parse::<i32>().ok())
+            .map(PID::from)
+            .collect()
+    })
+}
+
+fn parse_uid(status: &str) -> Option<u32> {
+    status_field(status, "Uid")
+        .and_then(|uids| uids.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok())
+}
+
+/// Parses the contents of a `loginuid` or `sessionid` file. Returns
+/// `Some(None)` when the ID is not set.
+fn parse_audit_id(id: &str) -> Option<Option<u32>> {
+    match id.trim().parse::<u32>().ok()? {
+        AUDIT_UNSET => Some(None),
+        id => Some(Some(id)),
+    }
+}
+
+fn parse_security_label(mut label: Vec<u8>) -> Option<PSecurityLabel> {
+    // SELinux terminates the label with a null character and AppArmor with
+    // a newline.
+    while matches!(label.last(), Some(b'\0' | b'\n')) {
+        label.pop();
+    }
+    (!label.is_empty()).then(|| PSecurityLabel::from(OsString::from_vec(label)))
+}
+
+fn parse_cgroups(cgroup: &[u8]) -> PCgroups {
+    // Each line has the form `hierarchy-ID:controllers:path`, and the path
+    // can contain `:` characters.
+    cgroup
+        .split(|c| *c == b'\n')
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, |c| *c == b':');
+            let id = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+            let controllers = String::from_utf8_lossy(fields.next()?);
+            let path = OsString::from_vec(fields.next()?.to_vec());
+            Some(CgroupHierarchy {
+                id,
+                controllers: controllers
+                    .split(',')
+                    .filter(|controller| !controller.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                path: path.into(),
+            })
         })
-        .collect::<io::Result<Vec<_>>>()?;
+        .collect::<Vec<_>>()
+        .into()
+}
 
-    Ok(cmdline.into())
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::{fake::FakeProc, *};
+
+    /// Creates a fake proc file system with the files of a process with the
+    /// given `pid`.
+    fn fake_proc(test: &str, pid: PID) -> FakeProc {
+        let fake = FakeProc::new(test);
+        fake.process(pid)
+            .exe("/usr/bin/game")
+            .cwd("/home/user/games")
+            .root("/srv/chroot")
+            .file("loginuid", "1000")
+            .file("sessionid", "4294967295")
+            .cmdline(&["/usr/bin/game", "--fullscreen"])
+            .environ(&[("HOME", "/home/user"), ("EMPTY", "")])
+            .stat("game", PID::from(1), 5 * clock_ticks_per_second())
+            .comm("game")
+            .status(
+                "Name:\tgame\nState:\tS (sleeping)\nTgid:\t1234\nPid:\t1234\nPPid:\t1\n\
+                 TracerPid:\t0\nUid:\t1000\t1000\t1000\t1000\nGid:\t100\t100\t100\t100\n\
+                 NSpid:\t1234\t1\nCapInh:\t0000000000000000\nCapPrm:\t0000000000002000\n\
+                 CapEff:\t0000000000000000\n",
+            )
+            .file("cgroup", "0::/user.slice/game.scope\n")
+            .file("attr/current", "unconfined\n");
+        fake
+    }
+
+    #[test]
+    fn readers_read_process_files_from_proc_root() {
+        let pid = PID::from(1234);
+        let fake = fake_proc("readers", pid);
+        let proc = fake.proc();
+
+        assert_eq!(
+            PExe::from(OsString::from("game")),
+            proc.exe_reader(pid).unwrap()
+        );
+        assert_eq!(
+            PExePath::from(PathBuf::from("/usr/bin/game")),
+            proc.exe_path_reader(pid).unwrap()
+        );
+        assert_eq!(
+            PCmdLine::from(vec![
+                OsString::from("/usr/bin/game"),
+                OsString::from("--fullscreen")
+            ]),
+            proc.cmdline_reader(pid).unwrap()
+        );
+        assert_eq!(
+            PEnviron::from(vec![
+                (OsString::from("HOME"), OsString::from("/home/user")),
+                (OsString::from("EMPTY"), OsString::new())
+            ]),
+            proc.environ_reader(pid).unwrap()
+        );
+        assert_eq!(
+            PNsPIDs::from(vec![pid, PID::from(1)]),
+            proc.nspid_reader(pid).unwrap()
+        );
+        assert_eq!(
+            PComm::from(OsString::from("game")),
+            proc.comm_reader(pid).unwrap()
+        );
+        assert_eq!(PUid::from(1000), proc.uid_reader(pid).unwrap());
+        assert_eq!(
+            PCwd::from(PathBuf::from("/home/user/games")),
+            proc.cwd_reader(pid).unwrap()
+        );
+        assert!(proc.root_reader(pid).unwrap().is_chrooted());
+        assert_eq!(Some(PUid::from(1000)), proc.loginuid_reader(pid).unwrap());
+        assert_eq!(None, proc.sessionid_reader(pid).unwrap());
+        assert_eq!(
+            Some(PSecurityLabel::from(OsString::from("unconfined"))),
+            proc.security_label_reader(pid).unwrap()
+        );
+        assert_eq!(
+            PStatus {
+                name: PComm::from(OsString::from("game")),
+                state: 'S',
+                ppid: PID::from(1),
+                uid: PUid::from(1000),
+                gid: 100,
+                nspids: PNsPIDs::from(vec![pid, PID::from(1)]),
+                tracer_pid: None,
+                cap_effective: PCapabilities::default(),
+                cap_permitted: PCapabilities::from(0x2000),
+            },
+            proc.status_reader(pid).unwrap()
+        );
+        assert_eq!(
+            PCgroup::from(OsString::from("/user.slice/game.scope")),
+            proc.cgroup_reader(pid).unwrap()
+        );
+        assert_eq!(
+            PStat {
+                comm: PComm::from(OsString::from("game")),
+                state: 'S',
+                ppid: PID::from(1),
+                session: pid,
+                tty: None,
+                nice: 0,
+                policy: None,
+                rt_priority: 0,
+                start_time: Duration::from_secs(5),
+                kernel_thread: false,
+            },
+            proc.stat_reader(pid).unwrap()
+        );
+    }
+
+    #[test]
+    fn cmdline_reader_keeps_empty_arguments_but_the_padding() {
+        let fake = FakeProc::new("cmdline");
+        fake.process(PID::from(1))
+            .file("cmdline", "sh\0-c\0\0it's\0\0\0\0");
+        fake.process(PID::from(2)).file("cmdline", "");
+        let proc = fake.proc();
+
+        assert_eq!(
+            PCmdLine::from(["sh", "-c", "", "it's"].map(OsString::from).to_vec()),
+            proc.cmdline_reader(PID::from(1)).unwrap()
+        );
+        assert!(proc.cmdline_reader(PID::from(2)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn setid_reader_returns_the_granted_identities() {
+        let fake = FakeProc::new("setid");
+        let executable = |pid: PID, mode: u32| {
+            let process = fake.process(pid).file("game", "");
+            let path = process.path().join("game");
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+            process.exe(&path);
+        };
+        executable(PID::from(1), 0o755);
+        executable(PID::from(2), 0o4755);
+        executable(PID::from(3), 0o2755);
+        executable(PID::from(4), 0o2745);
+        let proc = fake.proc();
+
+        let metadata = fs::metadata(proc.pid_path(PID::from(1)).join("game")).unwrap();
+        assert!(!proc.setid_reader(PID::from(1)).unwrap().is_set());
+        assert_eq!(
+            PSetId {
+                uid: Some(PUid::from(metadata.uid())),
+                gid: None
+            },
+            proc.setid_reader(PID::from(2)).unwrap()
+        );
+        assert_eq!(
+            PSetId {
+                uid: None,
+                gid: Some(metadata.gid())
+            },
+            proc.setid_reader(PID::from(3)).unwrap()
+        );
+        assert!(!proc.setid_reader(PID::from(4)).unwrap().is_set());
+    }
+
+    #[test]
+    fn environ_vars_reader_returns_only_the_given_variables() {
+        let pid = PID::from(1234);
+        let fake = fake_proc("environ", pid);
+        let proc = fake.proc();
+
+        assert_eq!(
+            PEnviron::from(vec![(OsString::from("EMPTY"), OsString::new())]),
+            proc.environ_vars_reader(pid, &["EMPTY", "MISSING"])
+                .unwrap()
+        );
+        assert_eq!(
+            PEnviron::from(Vec::new()),
+            proc.environ_vars_reader(pid, &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn readers_return_not_found_error_for_missing_processes() {
+        let fake = fake_proc("missing", PID::from(1234));
+        let proc = fake.proc();
+
+        let error = proc.cmdline_reader(PID::from(4321)).unwrap_err();
+        assert_eq!(io::ErrorKind::NotFound, error.kind());
+    }
+
+    #[test]
+    fn host_pid_reader_translates_pids_of_the_namespace() {
+        let fake = FakeProc::new("host-pid");
+        let container = "pid:[4026532200]";
+        fake.process(PID::from(1000))
+            .pid_namespace("pid:[4026531836]")
+            .status("Name:\tsystemd\nNSpid:\t1000\n");
+        fake.process(PID::from(2000))
+            .pid_namespace(container)
+            .status("Name:\tinit\nNSpid:\t2000\t1\n");
+        fake.process(PID::from(2001))
+            .pid_namespace(container)
+            .status("Name:\tgame\nNSpid:\t2001\t7\n");
+        fake.process(PID::from(3000))
+            .pid_namespace("pid:[4026532300]")
+            .status("Name:\tother\nNSpid:\t3000\t7\n");
+        let proc = fake.proc();
+
+        assert_eq!(
+            Some(PID::from(2001)),
+            proc.host_pid_reader(PID::from(2000), PID::from(7)).unwrap()
+        );
+        assert_eq!(
+            Some(PID::from(2000)),
+            proc.host_pid_reader(PID::from(2001), PID::from(1)).unwrap()
+        );
+        assert_eq!(
+            None,
+            proc.host_pid_reader(PID::from(2000), PID::from(8)).unwrap()
+        );
+        assert!(proc
+            .host_pid_reader(PID::from(4000), PID::from(1))
+            .unwrap_err()
+            .is_process_gone());
+    }
+
+    #[test]
+    fn retry_read_retries_failed_reads() {
+        let mut attempts = 0;
+        let result = retry_read(|| {
+            attempts += 1;
+            match attempts {
+                1 => Err(io::Error::from(io::ErrorKind::Interrupted)),
+                _ => Ok(attempts),
+            }
+        });
+        assert_eq!(2, result.unwrap());
+    }
+
+    #[test]
+    fn retry_read_gives_up_after_max_attempts() {
+        let mut attempts = 0;
+        let result: io::Result<()> = retry_read(|| {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::Interrupted))
+        });
+        assert!(result.is_err());
+        assert_eq!(READ_ATTEMPTS, attempts);
+    }
+
+    #[test]
+    fn retry_read_does_not_retry_non_transient_errors() {
+        for error in [
+            || io::Error::from(io::ErrorKind::PermissionDenied),
+            || io::Error::from(io::ErrorKind::NotFound),
+            || io::Error::from_raw_os_error(libc::ESRCH),
+        ] {
+            let mut attempts = 0;
+            let result: io::Result<()> = retry_read(|| {
+                attempts += 1;
+                Err(error())
+            });
+            assert!(result.is_err());
+            assert_eq!(1, attempts);
+        }
+    }
+
+    #[test]
+    fn retry_read_does_not_retry_reads_of_gone_processes() {
+        let fake = fake_proc("retry-gone", PID::from(1234));
+        let proc = fake.proc();
+
+        let mut attempts = 0;
+        let result = retry_read(|| {
+            attempts += 1;
+            proc.cmdline_reader(PID::from(4321))
+        });
+        assert!(result.unwrap_err().is_process_gone());
+        assert_eq!(1, attempts);
+    }
+
+    #[test]
+    fn is_process_gone_returns_true_for_missing_process_errors() {
+        assert!(is_process_gone(&io::Error::from(io::ErrorKind::NotFound)));
+        assert!(is_process_gone(&io::Error::from_raw_os_error(libc::ESRCH)));
+        assert!(!is_process_gone(&io::Error::from(
+            io::ErrorKind::PermissionDenied
+        )));
+    }
+
+    #[test]
+    fn parse_stat_comm_returns_command_name() {
+        assert_eq!(
+            Some(&b"game"[..]),
+            parse_stat_comm(b"1234 (game) S 1 1234 1234 0 -1")
+        );
+        assert_eq!(
+            Some(&b"a) (b"[..]),
+            parse_stat_comm(b"1234 (a) (b) S 1 1234 1234 0 -1")
+        );
+        assert_eq!(Some(&b""[..]), parse_stat_comm(b"1234 () S 1"));
+    }
+
+    #[test]
+    fn parse_stat_comm_returns_none_from_malformed_stat() {
+        assert_eq!(None, parse_stat_comm(b""));
+        assert_eq!(None, parse_stat_comm(b"1234 game S 1"));
+    }
+
+    #[test]
+    fn parse_stat_returns_state_parent_session_and_start_time() {
+        let stat = b"1234 (a (b) c) R 12 1234 1200 34819 1234 4194560 100 0 0 0 \
+                     5 2 0 0 20 0 1 0 12345 1000000 100";
+        assert_eq!(
+            Some(PStat {
+                comm: PComm::from(OsString::from("a (b) c")),
+                state: 'R',
+                ppid: PID::from(12),
+                session: PID::from(1200),
+                tty: Some(PTty::from(34819)),
+                nice: 0,
+                policy: None,
+                rt_priority: 0,
+                start_time: Duration::from_millis(123450),
+                kernel_thread: false,
+            }),
+            parse_stat(stat, 100)
+        );
+    }
+
+    #[test]
+    fn parse_stat_detects_kernel_threads() {
+        let stat = b"42 (kworker/3:1-events) I 2 0 0 0 -1 69238880 0 0 0 0 \
+                     0 12 0 0 20 0 1 0 250 0 0";
+        let stat = parse_stat(stat, 100).unwrap();
+        assert!(stat.kernel_thread);
+        assert_eq!(PID::from(2), stat.ppid);
+        assert_eq!(None, stat.tty);
+        assert_eq!(0, stat.nice);
+        assert_eq!(None, stat.policy);
+        assert_eq!(Duration::from_millis(2500), stat.start_time);
+    }
+
+    #[test]
+    fn parse_stat_returns_scheduling_information() {
+        let stat = b"1234 (game) S 1 1234 1234 0 -1 4194560 100 0 0 0 5 2 0 0 39 19 1 0 \
+                     12345 1000000 100 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 0 17 \
+                     3 50 1 0 0 0";
+        let stat = parse_stat(stat, 100).unwrap();
+        assert_eq!(19, stat.nice);
+        assert_eq!(Some(PSchedPolicy::Fifo), stat.policy);
+        assert_eq!(50, stat.rt_priority);
+        assert_eq!(Duration::from_millis(123450), stat.start_time);
+    }
+
+    #[test]
+    fn parse_stat_returns_none_from_truncated_stat() {
+        assert_eq!(None, parse_stat(b"1234 (game) S 1 1234 1234 0 -1", 100));
+    }
+
+    #[test]
+    fn parse_nspid_returns_pids_on_each_namespace() {
+        let status = "Name:\tbash\nTgid:\t4321\nNSpid:\t4321\t12\t1\nPPid:\t1\n";
+        assert_eq!(
+            Some(vec![PID::from(4321), PID::from(12), PID::from(1)]),
+            parse_nspid(status)
+        );
+    }
+
+    #[test]
+    fn parse_nspid_returns_none_without_namespace_information() {
+        let status = "Name:\tbash\nTgid:\t4321\nPPid:\t1\n";
+        assert_eq!(None, parse_nspid(status));
+    }
+
+    #[test]
+    fn parse_uid_returns_real_user_id() {
+        let status = "Name:\tbash\nUid:\t1000\t0\t0\t0\nGid:\t100\t100\t100\t100\n";
+        assert_eq!(Some(1000), parse_uid(status));
+        assert_eq!(None, parse_uid("Name:\tbash\n"));
+    }
+
+    #[test]
+    fn parse_status_returns_traced_process_information() {
+        let pid = PID::from(4321);
+        let status = "Name:\tbash\nState:\tt (tracing stop)\nPPid:\t12\nTracerPid:\t99\n\
+                      Uid:\t0\t0\t0\t0\nGid:\t0\t0\t0\t0\nCapPrm:\t000001ffffffffff\n\
+                      CapEff:\t000001ffffffffff\n";
+        let status = parse_status(pid, status).unwrap();
+        assert_eq!('t', status.state);
+        assert_eq!(PID::from(12), status.ppid);
+        assert_eq!(Some(PID::from(99)), status.tracer_pid);
+        assert_eq!(PNsPIDs::from(vec![pid]), status.nspids);
+        assert!(status.cap_effective.is_full());
+        assert!(status.cap_permitted.is_full());
+    }
+
+    #[test]
+    fn parse_status_returns_none_when_fields_are_missing() {
+        let status = "Name:\tbash\nState:\tS (sleeping)\nPPid:\t1\nUid:\t0\t0\t0\t0\n";
+        assert_eq!(None, parse_status(PID::from(4321), status));
+    }
+
+    #[test]
+    fn parse_peak_rss_returns_bytes() {
+        let status = "Name:\tgame\nVmPeak:\t  20480 kB\nVmHWM:\t    1024 kB\n";
+        assert_eq!(Some(1024 * 1024), parse_peak_rss(status));
+        assert_eq!(None, parse_peak_rss("Name:\tkthreadd\n"));
+    }
+
+    #[test]
+    fn parse_io_returns_counters() {
+        let io = "rchar: 100\nwchar: 200\nsyscr: 3\nsyscw: 4\nread_bytes: 4096\n\
+                  write_bytes: 8192\ncancelled_write_bytes: 0\n";
+        assert_eq!(
+            Some(PIo {
+                rchar: 100,
+                wchar: 200,
+                read_bytes: 4096,
+                write_bytes: 8192
+            }),
+            parse_io(io)
+        );
+        assert_eq!(None, parse_io("rchar: 100\n"));
+    }
+
+    #[test]
+    fn parse_audit_id_returns_none_for_unset_ids() {
+        assert_eq!(Some(Some(3)), parse_audit_id("3"));
+        assert_eq!(Some(None), parse_audit_id("4294967295"));
+        assert_eq!(None, parse_audit_id("unset"));
+    }
+
+    #[test]
+    fn parse_security_label_trims_terminators() {
+        assert_eq!(
+            Some(PSecurityLabel::from(OsString::from(
+                "system_u:system_r:init_t:s0"
+            ))),
+            parse_security_label(b"system_u:system_r:init_t:s0\0".to_vec())
+        );
+        assert_eq!(
+            Some(PSecurityLabel::from(OsString::from(
+                "/usr/bin/game (enforce)"
+            ))),
+            parse_security_label(b"/usr/bin/game (enforce)\n".to_vec())
+        );
+        assert_eq!(None, parse_security_label(b"\0".to_vec()));
+    }
+
+    #[test]
+    fn parse_cgroups_prefers_unified_hierarchy() {
+        let path = |cgroup: &[u8]| {
+            parse_cgroups(cgroup)
+                .path()
+                .map(|path| path.as_ref().to_os_string())
+        };
+        let cgroup = b"12:cpu,cpuacct:/user.slice\n0::/user.slice/app:1.scope\n";
+        assert_eq!(
+            Some(OsString::from("/user.slice/app:1.scope")),
+            path(cgroup)
+        );
+        assert_eq!(
+            Some(OsString::from("/system.slice")),
+            path(b"12:cpu,cpuacct:/system.slice\n1:name=systemd:/\n")
+        );
+        assert_eq!(None, path(b""));
+    }
+
+    #[test]
+    fn parse_cgroups_returns_each_hierarchy() {
+        let cgroups = parse_cgroups(b"12:cpu,cpuacct:/system.slice\n1:name=systemd:/init.scope\n");
+        assert_eq!(None, cgroups.unified());
+        assert_eq!(
+            Some(&PCgroup::from(OsString::from("/system.slice"))),
+            cgroups.controller("cpuacct")
+        );
+        assert_eq!(
+            Some(&PCgroup::from(OsString::from("/init.scope"))),
+            cgroups.controller("name=systemd")
+        );
+        assert_eq!(
+            vec![String::from("cpu"), String::from("cpuacct")],
+            cgroups.as_ref()[0].controllers
+        );
+        assert!(parse_cgroups(b"0::/\n").as_ref()[0].is_unified());
+    }
 }
@@ -0,0 +1,267 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Cache of the `/proc` files of processes.
+
+use std::collections::{hash_map, HashMap};
+
+use super::ProcFs;
+use crate::{
+    solver::{PCgroups, PCmdLine, PEnviron, PExe, PExePath, PStat, PStatus, PID},
+    Result,
+};
+
+/// Caches the information read from the `/proc` files of processes, so it's
+/// read only once while the process is being inspected.
+///
+/// Processes are identified by their `pid` and start time, so the
+/// information of a process is never returned for another one reusing its
+/// `pid`. The information of a process must be invalidated when it executes
+/// a new file or exits.
+#[derive(Debug, Default)]
+pub struct ProcCache {
+    entries: HashMap<PID, Entry>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    stat: PStat,
+    exe: Option<PExe>,
+    exe_path: Option<PExePath>,
+    cmdline: Option<PCmdLine>,
+    environ: Option<PEnviron>,
+    status: Option<PStatus>,
+    cgroups: Option<PCgroups>,
+}
+
+impl Entry {
+    fn new(stat: PStat) -> Self {
+        Entry {
+            stat,
+            exe: None,
+            exe_path: None,
+            cmdline: None,
+            environ: None,
+            status: None,
+            cgroups: None,
+        }
+    }
+}
+
+impl ProcCache {
+    /// Creates an empty `ProcCache`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attempts to get the cached information of the process with the given
+    /// `pid`, read from `proc`.
+    ///
+    /// The cached information is discarded when the process start time
+    /// differs from the cached one, as its `pid` was reused by another
+    /// process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the `stat` file of the process can't be read.
+    pub fn process<'a>(&'a mut self, proc: &'a ProcFs, pid: PID) -> Result<CachedProcess<'a>> {
+        let stat = proc.stat_reader(pid)?;
+        let entry = match self.entries.entry(pid) {
+            hash_map::Entry::Occupied(entry) => {
+                let entry = entry.into_mut();
+                if entry.stat.start_time != stat.start_time {
+                    *entry = Entry::new(stat);
+                } else {
+                    entry.stat = stat;
+                }
+                entry
+            }
+            hash_map::Entry::Vacant(entry) => entry.insert(Entry::new(stat)),
+        };
+
+        Ok(CachedProcess { proc, pid, entry })
+    }
+
+    /// Discards the cached information of the process with the given `pid`.
+    pub fn invalidate(&mut self, pid: PID) {
+        self.entries.remove(&pid);
+    }
+
+    /// Returns the number of cached processes.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` when no process is cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Cached information of a process. Each file of the process is read the
+/// first time its information is requested.
+#[derive(Debug)]
+pub struct CachedProcess<'a> {
+    proc: &'a ProcFs,
+    pid: PID,
+    entry: &'a mut Entry,
+}
+
+impl CachedProcess<'_> {
+    /// Returns the `pid` of the process.
+    pub fn pid(&self) -> PID {
+        self.pid
+    }
+
+    /// Returns the information of the `stat` file of the process, read
+    /// when the process was requested from the cache.
+    pub fn stat(&self) -> &PStat {
+        &self.entry.stat
+    }
+
+    /// Attempts to get the executable of the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`ProcFs::exe_reader`] errors.
+    pub fn exe(&mut self) -> Result<PExe> {
+        cached(&mut self.entry.exe, || self.proc.exe_reader(self.pid))
+    }
+
+    /// Attempts to get the executable path of the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`ProcFs::exe_path_reader`] errors.
+    pub fn exe_path(&mut self) -> Result<PExePath> {
+        cached(&mut self.entry.exe_path, || {
+            self.proc.exe_path_reader(self.pid)
+        })
+    }
+
+    /// Attempts to get the command line of the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`ProcFs::cmdline_reader`] errors.
+    pub fn cmdline(&mut self) -> Result<PCmdLine> {
+        cached(&mut self.entry.cmdline, || {
+            self.proc.cmdline_reader(self.pid)
+        })
+    }
+
+    /// Attempts to get the environment of the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`ProcFs::environ_reader`] errors.
+    pub fn environ(&mut self) -> Result<PEnviron> {
+        cached(&mut self.entry.environ, || {
+            self.proc.environ_reader(self.pid)
+        })
+    }
+
+    /// Attempts to get the information of the `status` file of the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`ProcFs::status_reader`] errors.
+    pub fn status(&mut self) -> Result<PStatus> {
+        cached(&mut self.entry.status, || self.proc.status_reader(self.pid))
+    }
+
+    /// Attempts to get the control groups of the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`ProcFs::cgroups_reader`] errors.
+    pub fn cgroups(&mut self) -> Result<PCgroups> {
+        cached(&mut self.entry.cgroups, || {
+            self.proc.cgroups_reader(self.pid)
+        })
+    }
+}
+
+/// Returns the value of `slot`, calling `read` to fill it when empty. Failed
+/// reads are not cached.
+fn cached<T: Clone>(slot: &mut Option<T>, read: impl FnOnce() -> Result<T>) -> Result<T> {
+    if let Some(value) = slot {
+        return Ok(value.clone());
+    }
+
+    let value = read()?;
+    *slot = Some(value.clone());
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::*;
+    use crate::io::proc::fake::FakeProc;
+
+    fn fake_proc(test: &str, pid: PID) -> FakeProc {
+        let fake = FakeProc::new(test);
+        fake.process(pid)
+            .stat("game", PID::from(1), 100)
+            .exe("/usr/bin/game");
+        fake
+    }
+
+    #[test]
+    fn process_information_is_read_once() {
+        let pid = PID::from(1234);
+        let fake = fake_proc("cache-read-once", pid);
+        let proc = fake.proc();
+        let mut cache = ProcCache::new();
+
+        let game = PExe::from(OsString::from("game"));
+        assert_eq!(game, cache.process(proc, pid).unwrap().exe().unwrap());
+
+        fake.process(pid).remove("exe");
+        let mut process = cache.process(proc, pid).unwrap();
+        assert_eq!(pid, process.pid());
+        assert_eq!(game, process.exe().unwrap());
+        assert!(process.cmdline().is_err());
+        assert_eq!(1, cache.len());
+
+        cache.invalidate(pid);
+        assert!(cache.is_empty());
+        assert!(cache.process(proc, pid).unwrap().exe().is_err());
+    }
+
+    #[test]
+    fn process_information_is_discarded_when_the_pid_is_reused() {
+        let pid = PID::from(1234);
+        let fake = fake_proc("cache-pid-reuse", pid);
+        let proc = fake.proc();
+        let mut cache = ProcCache::new();
+        assert!(cache.process(proc, pid).unwrap().exe().is_ok());
+
+        fake.process(pid)
+            .remove("exe")
+            .stat("game", PID::from(1), 200);
+        assert!(cache.process(proc, pid).unwrap().exe().is_err());
+    }
+
+    #[test]
+    fn process_stat_is_read_on_every_request() {
+        let pid = PID::from(1234);
+        let fake = fake_proc("cache-stat", pid);
+        let proc = fake.proc();
+        let mut cache = ProcCache::new();
+        let mut process = cache.process(proc, pid).unwrap();
+        assert_eq!(PID::from(1), process.stat().ppid);
+        assert!(process.exe().is_ok());
+
+        // Reparented, so it's still the same process
+        fake.process(pid)
+            .remove("exe")
+            .stat("game", PID::from(2), 100);
+        let mut process = cache.process(proc, pid).unwrap();
+        assert_eq!(PID::from(2), process.stat().ppid);
+        assert!(!process.stat().kernel_thread);
+        assert!(process.exe().is_ok());
+    }
+}
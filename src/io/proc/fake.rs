@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Fake proc file systems to test the `/proc` readers.
+//!
+//! This module is only available on tests, or with the `test-util` feature.
+
+use std::{
+    env, fs,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+    process,
+};
+
+use super::ProcFs;
+use crate::solver::PID;
+
+/// A fake proc file system on a temporary directory. The directory is
+/// removed when the `FakeProc` is dropped.
+///
+/// The functions of this module panic on I/O errors, as they are meant to be
+/// used on tests.
+#[derive(Debug)]
+pub struct FakeProc(ProcFs);
+
+impl FakeProc {
+    /// Creates an empty fake proc file system named after the `test` using
+    /// it, so concurrent tests don't share it.
+    pub fn new(test: &str) -> Self {
+        let root = env::temp_dir().join(format!("copes-{}-{}", test, process::id()));
+        if root.exists() {
+            fs::remove_dir_all(&root).unwrap();
+        }
+        fs::create_dir_all(&root).unwrap();
+        FakeProc(ProcFs::new(root))
+    }
+
+    /// Returns the fake proc file system.
+    pub fn proc(&self) -> &ProcFs {
+        &self.0
+    }
+
+    /// Adds the directory of the process with the given `pid`, returning a
+    /// builder to add its files.
+    pub fn process(&self, pid: PID) -> FakeProcess {
+        let path = self.0.pid_path(pid);
+        fs::create_dir_all(&path).unwrap();
+        FakeProcess(path)
+    }
+}
+
+impl Drop for FakeProc {
+    fn drop(&mut self) {
+        // Failing to clean up the temporary directory is harmless.
+        let _ = fs::remove_dir_all(self.0.root());
+    }
+}
+
+/// Builder of the files of a process on a [`FakeProc`].
+#[derive(Debug)]
+pub struct FakeProcess(PathBuf);
+
+impl FakeProcess {
+    /// Returns the path of the process directory.
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Adds the `exe` symlink pointing to `target`.
+    pub fn exe(self, target: impl AsRef<Path>) -> Self {
+        self.link("exe", target)
+    }
+
+    /// Adds the `cwd` symlink pointing to `target`.
+    pub fn cwd(self, target: impl AsRef<Path>) -> Self {
+        self.link("cwd", target)
+    }
+
+    /// Adds the `root` symlink pointing to `target`.
+    pub fn root(self, target: impl AsRef<Path>) -> Self {
+        self.link("root", target)
+    }
+
+    /// Adds the `ns/pid` symlink, pointing to the PID namespace `target`,
+    /// like `pid:[4026531836]`.
+    pub fn pid_namespace(self, target: impl AsRef<Path>) -> Self {
+        self.link("ns/pid", target)
+    }
+
+    /// Adds the `cmdline` file with the given arguments.
+    pub fn cmdline(self, args: &[&str]) -> Self {
+        let cmdline = args.iter().flat_map(|arg| [arg.as_bytes(), b"\0"]);
+        self.file("cmdline", cmdline.collect::<Vec<_>>().concat())
+    }
+
+    /// Adds the `environ` file with the given variables.
+    pub fn environ(self, vars: &[(&str, &str)]) -> Self {
+        let environ = vars
+            .iter()
+            .map(|(key, value)| format!("{}={}\0", key, value))
+            .collect::<String>();
+        self.file("environ", environ)
+    }
+
+    /// Adds the `comm` file with the given command name.
+    pub fn comm(self, comm: &str) -> Self {
+        self.file("comm", format!("{}\n", comm))
+    }
+
+    /// Adds a `stat` file with the given command name, parent process ID and
+    /// start time in clock ticks since boot.
+    pub fn stat(self, comm: &str, ppid: PID, start_time: u64) -> Self {
+        let pid = self.pid();
+        self.file(
+            "stat",
+            format!(
+                "{} ({}) S {} {} {} 0 -1 4194560 0 0 0 0 0 0 0 0 20 0 1 0 {} 0",
+                pid, comm, ppid, pid, pid, start_time
+            ),
+        )
+    }
+
+    /// Adds the `status` file with the given contents.
+    pub fn status(self, status: &str) -> Self {
+        self.file("status", status)
+    }
+
+    /// Adds a file with the given `name` and `contents`, replacing it when it
+    /// exists.
+    pub fn file(self, name: &str, contents: impl AsRef<[u8]>) -> Self {
+        let path = self.0.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        self
+    }
+
+    /// Removes the file or symlink with the given `name`.
+    pub fn remove(self, name: &str) -> Self {
+        fs::remove_file(self.0.join(name)).unwrap();
+        self
+    }
+
+    fn link(self, name: &str, target: impl AsRef<Path>) -> Self {
+        let path = self.0.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        if path.symlink_metadata().is_ok() {
+            fs::remove_file(&path).unwrap();
+        }
+        symlink(target, path).unwrap();
+        self
+    }
+
+    fn pid(&self) -> String {
+        self.0
+            .file_name()
+            .map(|pid| pid.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fake_proc_is_removed_on_drop() {
+        let fake = FakeProc::new("fake-drop");
+        let root = fake.proc().root().to_path_buf();
+        fake.process(PID::from(1234)).comm("game");
+        assert!(root.join("1234").join("comm").exists());
+
+        drop(fake);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn fake_process_files_can_be_replaced_and_removed() {
+        let fake = FakeProc::new("fake-replace");
+        let process = fake
+            .process(PID::from(1234))
+            .exe("/usr/bin/game")
+            .exe("/usr/bin/other")
+            .comm("game")
+            .remove("comm");
+
+        assert_eq!(
+            Path::new("/usr/bin/other"),
+            fs::read_link(process.path().join("exe")).unwrap()
+        );
+        assert!(!process.path().join("comm").exists());
+    }
+}
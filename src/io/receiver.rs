@@ -0,0 +1,632 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Background receiver of process events.
+//!
+//! Reading the information of the processes takes time, and the kernel drops
+//! the process events that don't fit on the socket receive buffer. The
+//! receiver drains the socket on its own thread into a bounded queue, so
+//! bursts of events are not lost while the queued events are processed.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{io::source::ProcessEventSource, solver::PEventRecord, Error, Result};
+
+/// Maximum time the receiver thread waits for new events or free space on
+/// the queue before checking whether it must stop.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of errors received within [`ERROR_STORM_WINDOW`] after which the
+/// source is set up again, as it's unlikely to recover by itself.
+const ERROR_STORM_THRESHOLD: u32 = 64;
+const ERROR_STORM_WINDOW: Duration = Duration::from_secs(1);
+
+/// Time waited after the first failed attempt to set up the source again, or
+/// to pause or resume it. It's doubled on each failed attempt, up to
+/// [`MAX_RECONNECT_DELAY`].
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// What to do with the received events when the queue is full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Wait for free space on the queue. Events might be dropped by the
+    /// kernel meanwhile.
+    #[default]
+    Block,
+    /// Drop the oldest queued event.
+    DropOldest,
+    /// Drop the received event.
+    DropNewest,
+}
+
+/// A bounded queue of items, shared between threads.
+#[derive(Debug)]
+pub struct Queue<T> {
+    state: Mutex<QueueState<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: BackpressurePolicy,
+}
+
+#[derive(Debug)]
+struct QueueState<T> {
+    items: VecDeque<T>,
+    dropped: u64,
+    closed: bool,
+}
+
+impl<T> Queue<T> {
+    /// Creates a `Queue` holding up to `capacity` items (at least one), that
+    /// follows `policy` when it's full.
+    pub fn new(capacity: usize, policy: BackpressurePolicy) -> Self {
+        let capacity = capacity.max(1);
+        Queue {
+            state: Mutex::new(QueueState {
+                items: VecDeque::with_capacity(capacity),
+                dropped: 0,
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity,
+            policy,
+        }
+    }
+
+    /// Adds `item` to the queue, following the queue policy when it's full.
+    ///
+    /// With [`BackpressurePolicy::Block`], waits up to `timeout` for free
+    /// space and returns the item back when there is still no space.
+    pub fn push(&self, item: T, timeout: Duration) -> std::result::Result<(), T> {
+        let mut state = self.lock();
+
+        if state.items.len() >= self.capacity {
+            match self.policy {
+                BackpressurePolicy::Block => {
+                    let deadline = Instant::now() + timeout;
+                    while state.items.len() >= self.capacity {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            return Err(item);
+                        }
+                        state = self
+                            .not_full
+                            .wait_timeout(state, deadline - now)
+                            .unwrap_or_else(|e| e.into_inner())
+                            .0;
+                    }
+                }
+                BackpressurePolicy::DropOldest => {
+                    state.items.pop_front();
+                    state.dropped += 1;
+                }
+                BackpressurePolicy::DropNewest => {
+                    state.dropped += 1;
+                    return Ok(());
+                }
+            }
+        }
+
+        state.items.push_back(item);
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    /// Removes the oldest item from the queue, waiting up to `timeout` for
+    /// it. Returns `None` when the queue is still empty, or at once when it's
+    /// empty and closed.
+    pub fn pop(&self, timeout: Duration) -> Option<T> {
+        let state = self.lock();
+        let (mut state, _) = self
+            .not_empty
+            .wait_timeout_while(state, timeout, |state| {
+                state.items.is_empty() && !state.closed
+            })
+            .unwrap_or_else(|e| e.into_inner());
+
+        let item = state.items.pop_front();
+        if item.is_some() {
+            self.not_full.notify_one();
+        }
+        item
+    }
+
+    /// Returns the number of items dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    /// Closes the queue, so waiting for items on an empty queue returns at
+    /// once. The queued items can still be removed.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.not_empty.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Receives the process events of a [`ProcessEventSource`], like the
+/// process events connector, on a background thread. The thread is stopped
+/// when the `EventsReceiver` is dropped.
+///
+/// When the source fails, it's set up again and an [`Error::Reconnected`]
+/// error is received, so the process events keep being received.
+///
+/// When the waker of the source is woken up, the thread stops at once and
+/// the remaining queued events are returned without waiting.
+#[derive(Debug)]
+pub struct EventsReceiver {
+    queue: Arc<Queue<Result<PEventRecord>>>,
+    stop: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EventsReceiver {
+    /// Attempts to start receiving the events of `source` on a background
+    /// thread. Up to `capacity` events are queued, following `policy` when
+    /// the queue is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the source, like an [`Error::NetlinkSetup`]
+    /// error, when its timeout can't be set, and an [`Error::Io`] error when
+    /// the thread can't be started.
+    pub fn spawn<S: ProcessEventSource + Send + 'static>(
+        source: S,
+        capacity: usize,
+        policy: BackpressurePolicy,
+    ) -> Result<Self> {
+        source.set_timeout(STOP_CHECK_INTERVAL)?;
+
+        let queue = Arc::new(Queue::new(capacity, policy));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_queue = queue.clone();
+        let thread_stop = stop.clone();
+        let paused = Arc::new(AtomicBool::new(false));
+        let thread_paused = paused.clone();
+        let thread = thread::Builder::new()
+            .name("events receiver".to_string())
+            .spawn(move || {
+                receive(source, &thread_queue, &thread_stop, &thread_paused);
+                thread_queue.close();
+            })
+            .map_err(Error::Io)?;
+
+        Ok(EventsReceiver {
+            queue,
+            stop,
+            paused,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the next received event record, waiting up to `timeout` for
+    /// it. Returns `None` when no events are received within `timeout`.
+    ///
+    /// The record timestamp tells when the kernel reported the event, so the
+    /// time that the event spent queued can be measured.
+    pub fn next_timeout(&self, timeout: Duration) -> Option<Result<PEventRecord>> {
+        self.queue.pop(timeout)
+    }
+
+    /// Returns the number of events dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+
+    /// Pauses the source, so no process events are received until the
+    /// receiver is resumed. The events already queued can still be read.
+    ///
+    /// The source is paused by the receiver thread shortly after. When it
+    /// can't be paused, the error is received as an event once, and pausing
+    /// it is retried later.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumes the source after pausing it. The process events sent while
+    /// it was paused are lost.
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns `true` when the receiver was paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+}
+
+/// Receives the events of `source` into `queue` until `stop` is set or
+/// the source waker is woken up. The source is paused while `paused`
+/// is set, dropping the events received meanwhile.
+///
+/// The source is set up again when it fails, or when it reports
+/// too many errors in a short time. Then, an [`Error::Reconnected`] error is
+/// queued.
+fn receive<S: ProcessEventSource>(
+    mut source: S,
+    queue: &Queue<Result<PEventRecord>>,
+    stop: &AtomicBool,
+    paused: &AtomicBool,
+) {
+    let mut storm = ErrorStorm::new(Instant::now());
+    let mut pausing = Pausing::default();
+    loop {
+        let mut events = source.records();
+        let broken = loop {
+            if stop.load(Ordering::Relaxed) || source.is_woken() {
+                return;
+            }
+            let paused = paused.load(Ordering::Relaxed);
+            if let Some(e) = pausing.set_paused(&source, paused, Instant::now()) {
+                if !push(queue, stop, Err(e)) {
+                    return;
+                }
+            }
+            let Some(event) = events.next() else {
+                continue;
+            };
+            if source.is_paused() {
+                continue;
+            }
+            let broken = event
+                .as_ref()
+                .err()
+                .is_some_and(|e| e.is_connection_broken() || storm.record(Instant::now()));
+            if !push(queue, stop, event) {
+                return;
+            }
+            if broken {
+                break Instant::now();
+            }
+        };
+
+        drop(events);
+        let Some(attempts) = reconnect(&mut source, queue, stop) else {
+            return;
+        };
+        storm = ErrorStorm::new(Instant::now());
+        let reconnected = Err(Error::Reconnected {
+            attempts,
+            downtime: broken.elapsed(),
+        });
+        if !push(queue, stop, reconnected) {
+            return;
+        }
+    }
+}
+
+/// Pauses and resumes a source, retrying the failed attempts with increasing
+/// delays.
+#[derive(Debug, Default)]
+struct Pausing {
+    failed: Option<FailedPausing>,
+}
+
+/// A failed attempt to pause or resume a source.
+#[derive(Debug)]
+struct FailedPausing {
+    paused: bool,
+    retry: Instant,
+    delay: Duration,
+}
+
+impl Pausing {
+    /// Pauses or resumes the `source` when it's not yet on the `paused`
+    /// state.
+    ///
+    /// Returns the error of the first failed attempt to reach the state.
+    /// Later attempts are made after increasing delays, and their errors
+    /// aren't returned, so a failing source doesn't flood the queue.
+    fn set_paused<S: ProcessEventSource>(
+        &mut self,
+        source: &S,
+        paused: bool,
+        now: Instant,
+    ) -> Option<Error> {
+        if paused == source.is_paused() {
+            self.failed = None;
+            return None;
+        }
+        let retrying = self
+            .failed
+            .as_ref()
+            .filter(|failed| failed.paused == paused);
+        if retrying.is_some_and(|failed| now < failed.retry) {
+            return None;
+        }
+
+        let result = match paused {
+            true => source.pause(),
+            false => source.resume(),
+        };
+        let Err(e) = result else {
+            self.failed = None;
+            return None;
+        };
+        match retrying {
+            Some(failed) => {
+                let delay = (failed.delay * 2).min(MAX_RECONNECT_DELAY);
+                self.failed = Some(FailedPausing {
+                    paused,
+                    retry: now + delay,
+                    delay,
+                });
+                None
+            }
+            None => {
+                self.failed = Some(FailedPausing {
+                    paused,
+                    retry: now + RECONNECT_DELAY,
+                    delay: RECONNECT_DELAY,
+                });
+                Some(e)
+            }
+        }
+    }
+}
+
+/// Sets up the `source` again, retrying with increasing delays until it
+/// succeeds. The errors of the failed attempts are queued.
+///
+/// Returns the number of attempts, or `None` when `stop` is set meanwhile.
+fn reconnect<S: ProcessEventSource>(
+    source: &mut S,
+    queue: &Queue<Result<PEventRecord>>,
+    stop: &AtomicBool,
+) -> Option<u32> {
+    let mut delay = RECONNECT_DELAY;
+    for attempts in 1.. {
+        match source.reconnect() {
+            Ok(()) => return Some(attempts),
+            Err(e) => {
+                if !push(queue, stop, Err(e)) {
+                    return None;
+                }
+            }
+        }
+
+        let deadline = Instant::now() + delay;
+        while Instant::now() < deadline {
+            if stop.load(Ordering::Relaxed) || source.is_woken() {
+                return None;
+            }
+            thread::sleep(STOP_CHECK_INTERVAL.min(deadline - Instant::now()));
+        }
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+
+    None
+}
+
+/// Adds `item` to the `queue`, waiting for free space until `stop` is set.
+/// Returns `false` when stopped.
+fn push<T>(queue: &Queue<T>, stop: &AtomicBool, mut item: T) -> bool {
+    while let Err(rejected) = queue.push(item, STOP_CHECK_INTERVAL) {
+        if stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        item = rejected;
+    }
+
+    true
+}
+
+/// Counts the errors received within a time window, to detect error storms.
+#[derive(Debug)]
+struct ErrorStorm {
+    window_start: Instant,
+    errors: u32,
+}
+
+impl ErrorStorm {
+    fn new(now: Instant) -> Self {
+        ErrorStorm {
+            window_start: now,
+            errors: 0,
+        }
+    }
+
+    /// Records an error received at `now`, and returns `true` when it
+    /// reaches the storm threshold.
+    fn record(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.window_start) >= ERROR_STORM_WINDOW {
+            *self = ErrorStorm::new(now);
+        }
+
+        self.errors += 1;
+        self.errors >= ERROR_STORM_THRESHOLD
+    }
+}
+
+impl Drop for EventsReceiver {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("The events receiver thread panicked");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_WAIT: Duration = Duration::ZERO;
+
+    fn items(queue: &Queue<u32>) -> Vec<u32> {
+        std::iter::from_fn(|| queue.pop(NO_WAIT)).collect()
+    }
+
+    #[test]
+    fn queue_block_policy_rejects_items_when_full() {
+        let queue = Queue::new(2, BackpressurePolicy::Block);
+        assert_eq!(Ok(()), queue.push(1, NO_WAIT));
+        assert_eq!(Ok(()), queue.push(2, NO_WAIT));
+        assert_eq!(Err(3), queue.push(3, Duration::from_millis(1)));
+
+        assert_eq!(vec![1, 2], items(&queue));
+        assert_eq!(0, queue.dropped());
+    }
+
+    #[test]
+    fn queue_block_policy_waits_for_free_space() {
+        let queue = Arc::new(Queue::new(1, BackpressurePolicy::Block));
+        queue.push(1, NO_WAIT).unwrap();
+
+        let consumer_queue = queue.clone();
+        let consumer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            consumer_queue.pop(NO_WAIT)
+        });
+
+        assert_eq!(Ok(()), queue.push(2, Duration::from_secs(5)));
+        assert_eq!(Some(1), consumer.join().unwrap());
+        assert_eq!(vec![2], items(&queue));
+    }
+
+    #[test]
+    fn queue_drop_policies_count_dropped_items() {
+        let queue = Queue::new(2, BackpressurePolicy::DropOldest);
+        for item in 1..=5 {
+            assert_eq!(Ok(()), queue.push(item, NO_WAIT));
+        }
+        assert_eq!(vec![4, 5], items(&queue));
+        assert_eq!(3, queue.dropped());
+
+        let queue = Queue::new(2, BackpressurePolicy::DropNewest);
+        for item in 1..=5 {
+            assert_eq!(Ok(()), queue.push(item, NO_WAIT));
+        }
+        assert_eq!(vec![1, 2], items(&queue));
+        assert_eq!(3, queue.dropped());
+    }
+
+    #[test]
+    fn error_storm_is_detected_within_the_window() {
+        let start = Instant::now();
+        let mut storm = ErrorStorm::new(start);
+        for _ in 1..ERROR_STORM_THRESHOLD {
+            assert!(!storm.record(start));
+        }
+        assert!(storm.record(start + ERROR_STORM_WINDOW / 2));
+
+        let mut storm = ErrorStorm::new(start);
+        for _ in 1..ERROR_STORM_THRESHOLD {
+            assert!(!storm.record(start));
+        }
+        assert!(!storm.record(start + ERROR_STORM_WINDOW));
+    }
+
+    /// Source that can't be paused nor resumed.
+    #[derive(Default)]
+    struct StuckSource {
+        attempts: std::cell::Cell<u32>,
+    }
+
+    impl ProcessEventSource for StuckSource {
+        fn records(&self) -> Box<dyn Iterator<Item = Result<PEventRecord>> + '_> {
+            Box::new(std::iter::empty())
+        }
+
+        fn set_timeout(&self, _duration: Duration) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_waker(&mut self, _waker: crate::io::waker::Waker) {}
+
+        fn is_woken(&self) -> bool {
+            false
+        }
+
+        fn pause(&self) -> Result<()> {
+            self.attempts.set(self.attempts.get() + 1);
+            Err(Error::Io(std::io::Error::from(
+                std::io::ErrorKind::PermissionDenied,
+            )))
+        }
+
+        fn resume(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_paused(&self) -> bool {
+            false
+        }
+
+        fn reconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pausing_reports_failures_once_and_retries_later() {
+        let source = StuckSource::default();
+        let mut pausing = Pausing::default();
+        let start = Instant::now();
+
+        assert!(pausing.set_paused(&source, true, start).is_some());
+        assert!(pausing.set_paused(&source, true, start).is_none());
+        assert_eq!(1, source.attempts.get());
+
+        assert!(pausing
+            .set_paused(&source, true, start + RECONNECT_DELAY)
+            .is_none());
+        assert_eq!(2, source.attempts.get());
+        assert!(pausing
+            .set_paused(&source, true, start + RECONNECT_DELAY * 2)
+            .is_none());
+        assert_eq!(2, source.attempts.get());
+
+        // Resumed meanwhile, so a new failure is reported
+        assert!(pausing.set_paused(&source, false, start).is_none());
+        assert!(pausing.set_paused(&source, true, start).is_some());
+        assert_eq!(3, source.attempts.get());
+    }
+
+    #[test]
+    fn push_gives_up_when_stopped() {
+        let queue = Queue::new(1, BackpressurePolicy::Block);
+        let stop = AtomicBool::new(false);
+        assert!(push(&queue, &stop, 1));
+
+        stop.store(true, Ordering::Relaxed);
+        assert!(!push(&queue, &stop, 2));
+        assert_eq!(vec![1], items(&queue));
+    }
+
+    #[test]
+    fn queue_pop_returns_none_when_empty() {
+        let queue = Queue::<u32>::new(1, BackpressurePolicy::Block);
+        assert_eq!(None, queue.pop(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn queue_pop_returns_at_once_when_closed() {
+        let queue = Queue::new(2, BackpressurePolicy::Block);
+        queue.push(1, NO_WAIT).unwrap();
+        queue.close();
+
+        let start = Instant::now();
+        assert_eq!(Some(1), queue.pop(Duration::from_secs(10)));
+        assert_eq!(None, queue.pop(Duration::from_secs(10)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
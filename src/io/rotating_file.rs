@@ -0,0 +1,364 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Output files with rotation.
+//!
+//! Files are rotated when they grow over a size or after some time. Rotated
+//! files are renamed to `<file>.1`, `<file>.2`... from the newest to the
+//! oldest one, and optionally compressed with `gzip` (`<file>.1.gz`...).
+//!
+//! `gzip` runs in the background, so writes aren't delayed while a big file
+//! is compressed. It's waited for before the next rotation shifts the
+//! compressed file, and when the [`RotatingFile`] is dropped.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    time::{Duration, Instant},
+};
+
+/// When and how a [`RotatingFile`] is rotated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// Rotate the file when its size reaches this number of bytes.
+    pub max_size: Option<u64>,
+    /// Rotate the file after this time. The file is only rotated when a line
+    /// is written, so it may be rotated later on quiet periods.
+    pub interval: Option<Duration>,
+    /// Number of rotated files to keep.
+    pub keep: usize,
+    /// Compress the rotated files.
+    pub compress: bool,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        RotationPolicy {
+            max_size: None,
+            interval: None,
+            keep: 5,
+            compress: false,
+        }
+    }
+}
+
+/// A file that is rotated following a [`RotationPolicy`].
+///
+/// Files are only rotated between lines, so lines are never split across
+/// files.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    policy: RotationPolicy,
+    file: File,
+    size: u64,
+    opened: Instant,
+    at_line_start: bool,
+    /// Compression of the last rotated file, running in the background.
+    compression: Option<Compression>,
+}
+
+impl RotatingFile {
+    /// Attempts to open the file at `path` for appending, creating it when
+    /// it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn open(path: impl Into<PathBuf>, policy: RotationPolicy) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(RotatingFile {
+            path,
+            policy,
+            file,
+            size,
+            opened: Instant::now(),
+            at_line_start: true,
+            compression: None,
+        })
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn needs_rotation(&self) -> bool {
+        self.at_line_start
+            && (self.policy.max_size.is_some_and(|size| self.size >= size)
+                || self
+                    .policy
+                    .interval
+                    .is_some_and(|interval| self.opened.elapsed() >= interval))
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        // The last rotated file is shifted once it's compressed
+        if let Some(compression) = self.compression.take() {
+            compression.wait();
+        }
+
+        if self.policy.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            self.shift(Self::rotated_path)?;
+            if self.policy.compress {
+                // Rotated files that couldn't be compressed are shifted too
+                self.shift(Self::rotated_file_path)?;
+            }
+
+            let rotated = self.rotated_file_path(1);
+            fs::rename(&self.path, &rotated)?;
+            if self.policy.compress {
+                self.compression = Compression::spawn(rotated);
+            }
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.opened = Instant::now();
+
+        Ok(())
+    }
+
+    /// Shifts the rotated files named by `path` to the next index, removing
+    /// the oldest one.
+    fn shift(&self, path: fn(&Self, usize) -> PathBuf) -> io::Result<()> {
+        remove_if_exists(&path(self, self.policy.keep))?;
+        for index in (1..self.policy.keep).rev() {
+            rename_if_exists(&path(self, index), &path(self, index + 1))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the path of the rotated file with the given `index`, as it is
+    /// right after the rotation (uncompressed).
+    fn rotated_file_path(&self, index: usize) -> PathBuf {
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    /// Returns the path of the rotated file with the given `index`.
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let path = self.rotated_file_path(index);
+        match self.policy.compress {
+            true => {
+                let mut path = path.into_os_string();
+                path.push(".gz");
+                PathBuf::from(path)
+            }
+            false => path,
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.needs_rotation() {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        if written > 0 {
+            self.at_line_start = buf[written - 1] == b'\n';
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+impl Drop for RotatingFile {
+    fn drop(&mut self) {
+        if let Some(compression) = self.compression.take() {
+            compression.wait();
+        }
+    }
+}
+
+/// Compression of a file with `gzip`, running in the background.
+#[derive(Debug)]
+struct Compression {
+    gzip: Child,
+    path: PathBuf,
+}
+
+impl Compression {
+    /// Starts compressing the file at `path`. Failures are logged, leaving
+    /// the file uncompressed.
+    fn spawn(path: PathBuf) -> Option<Self> {
+        match Command::new("gzip").arg("-f").arg(&path).spawn() {
+            Ok(gzip) => Some(Compression { gzip, path }),
+            Err(e) => {
+                log::error!("Couldn't compress {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Waits for the compression to finish, logging its failures.
+    fn wait(mut self) {
+        match self.gzip.wait() {
+            Ok(status) if status.success() => {}
+            Ok(status) => log::error!("Couldn't compress {}: gzip {}", self.path.display(), status),
+            Err(e) => log::error!("Couldn't compress {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn test_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("copes-{}-{}", test, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn rotating_file_rotates_when_size_is_reached() {
+        let dir = test_dir("rotate-size");
+        let path = dir.join("events.log");
+        let policy = RotationPolicy {
+            max_size: Some(10),
+            keep: 2,
+            ..Default::default()
+        };
+
+        let mut file = RotatingFile::open(&path, policy).unwrap();
+        for line in ["line 1 ...", "line 2 ...", "line 3 ...", "line 4 ..."] {
+            writeln!(file, "{}", line).unwrap();
+        }
+        file.flush().unwrap();
+
+        assert_eq!("line 4 ...\n", fs::read_to_string(&path).unwrap());
+        assert_eq!(
+            "line 3 ...\n",
+            fs::read_to_string(dir.join("events.log.1")).unwrap()
+        );
+        assert_eq!(
+            "line 2 ...\n",
+            fs::read_to_string(dir.join("events.log.2")).unwrap()
+        );
+        assert!(!dir.join("events.log.3").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotating_file_does_not_split_lines() {
+        let dir = test_dir("rotate-lines");
+        let path = dir.join("events.log");
+        let policy = RotationPolicy {
+            max_size: Some(4),
+            keep: 1,
+            ..Default::default()
+        };
+
+        let mut file = RotatingFile::open(&path, policy).unwrap();
+        write!(file, "Exec(1)").unwrap();
+        write!(file, " game").unwrap();
+        writeln!(file).unwrap();
+        file.write_all(b"Exit(1) game\n").unwrap();
+
+        assert_eq!("Exit(1) game\n", fs::read_to_string(&path).unwrap());
+        assert_eq!(
+            "Exec(1) game\n",
+            fs::read_to_string(dir.join("events.log.1")).unwrap()
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotating_file_appends_to_existing_files() {
+        let dir = test_dir("rotate-append");
+        let path = dir.join("events.log");
+        fs::write(&path, "0123456789\n").unwrap();
+        let policy = RotationPolicy {
+            max_size: Some(10),
+            keep: 0,
+            ..Default::default()
+        };
+
+        let mut file = RotatingFile::open(&path, policy).unwrap();
+        writeln!(file, "line").unwrap();
+
+        assert_eq!("line\n", fs::read_to_string(&path).unwrap());
+        assert!(!dir.join("events.log.1").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotating_file_compresses_rotated_files() {
+        let dir = test_dir("rotate-compress");
+        let path = dir.join("events.log");
+        let policy = RotationPolicy {
+            max_size: Some(10),
+            keep: 2,
+            compress: true,
+            ..Default::default()
+        };
+
+        let mut file = RotatingFile::open(&path, policy).unwrap();
+        for line in ["line 1 ...", "line 2 ...", "line 3 ...", "line 4 ..."] {
+            writeln!(file, "{}", line).unwrap();
+        }
+        drop(file);
+
+        let gunzip = |name| {
+            let output = Command::new("gzip")
+                .arg("-dc")
+                .arg(dir.join(name))
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            String::from_utf8(output.stdout).unwrap()
+        };
+        assert_eq!("line 4 ...\n", fs::read_to_string(&path).unwrap());
+        assert_eq!("line 3 ...\n", gunzip("events.log.1.gz"));
+        assert_eq!("line 2 ...\n", gunzip("events.log.2.gz"));
+        assert!(!dir.join("events.log.1").exists());
+        assert!(!dir.join("events.log.3.gz").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
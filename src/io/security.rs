@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Checks of the locations of the executed files, to spot the programs run
+//! from places where attackers usually drop them.
+
+use std::{fmt, fs, os::unix::fs::MetadataExt, path::Path};
+
+use crate::solver::PExePath;
+
+/// Directories for temporary files, writable by every user.
+const TEMP_DIRS: [&str; 3] = ["/tmp", "/var/tmp", "/dev/shm"];
+
+/// Reason why the location of an executable is suspicious.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SuspiciousLocation {
+    /// The executable was deleted after the process started, or while it was
+    /// being executed.
+    Deleted,
+    /// The executable is inside a directory for temporary files, like `/tmp`
+    /// or `/dev/shm`.
+    TempDir,
+    /// The executable is inside a directory writable by every user, or by a
+    /// user other than root.
+    WritableDir,
+}
+
+impl fmt::Display for SuspiciousLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SuspiciousLocation::Deleted => write!(f, "deleted"),
+            SuspiciousLocation::TempDir => write!(f, "temp-dir"),
+            SuspiciousLocation::WritableDir => write!(f, "writable-dir"),
+        }
+    }
+}
+
+/// Returns the reasons why the location of the executable `path` is
+/// suspicious, if any.
+///
+/// The directory of the executable is read to find out whether it's user
+/// writable, unless it's a directory for temporary files.
+pub fn suspicious_locations(path: &PExePath) -> Vec<SuspiciousLocation> {
+    let mut locations = Vec::new();
+    if path.is_deleted() {
+        locations.push(SuspiciousLocation::Deleted);
+    }
+
+    if TEMP_DIRS.iter().any(|dir| path.is_under(Path::new(dir))) {
+        locations.push(SuspiciousLocation::TempDir);
+    } else if let Some(dir) = path
+        .as_ref()
+        .parent()
+        .and_then(|dir| fs::metadata(dir).ok())
+    {
+        if is_user_writable(dir.mode(), dir.uid()) {
+            locations.push(SuspiciousLocation::WritableDir);
+        }
+    }
+
+    locations
+}
+
+/// Returns `true` when a directory with the given `mode` and owner `uid` is
+/// writable by every user, or by its owner when it's not root.
+fn is_user_writable(mode: u32, uid: u32) -> bool {
+    // The mode constants are 16 bits wide on BSD systems.
+    #[allow(clippy::useless_conversion)]
+    let (other_write, user_write) = (u32::from(libc::S_IWOTH), u32::from(libc::S_IWUSR));
+    mode & other_write != 0 || (uid != 0 && mode & user_write != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn suspicious_locations_detects_temp_dirs_and_deleted_executables() {
+        let path = |path: &str| PExePath::from(PathBuf::from(path));
+        assert_eq!(
+            vec![SuspiciousLocation::Deleted, SuspiciousLocation::TempDir],
+            suspicious_locations(&path("/dev/shm/payload (deleted)"))
+        );
+        assert_eq!(
+            vec![SuspiciousLocation::TempDir],
+            suspicious_locations(&path("/tmp/build/payload"))
+        );
+        assert_eq!(
+            Vec::<SuspiciousLocation>::new(),
+            suspicious_locations(&path("/tmpfiles"))
+        );
+    }
+
+    #[test]
+    fn is_user_writable_detects_writable_directories() {
+        assert!(is_user_writable(0o1777, 0));
+        assert!(is_user_writable(0o755, 1000));
+        assert!(!is_user_writable(0o755, 0));
+        assert!(!is_user_writable(0o555, 1000));
+    }
+}
@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Flags raised by signals, to handle them outside of the signal handlers.
+//!
+//! Signal handlers can only do a few things safely, so the handler installed
+//! by [`SignalFlag::register`] just raises a flag, which is checked later
+//! with [`SignalFlag::take`].
+
+use std::{
+    io, mem,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Raised flags, one bit per signal number.
+static RAISED: AtomicU64 = AtomicU64::new(0);
+
+/// A flag raised when a signal is received.
+#[derive(Debug)]
+pub struct SignalFlag(libc::c_int);
+
+impl SignalFlag {
+    /// Attempts to install a handler for `signal` that raises its flag,
+    /// replacing the signal current handler.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::InvalidInput`] error for signals that
+    /// can't be caught, or any other I/O error installing the handler.
+    pub fn register(signal: libc::c_int) -> io::Result<Self> {
+        if !(1..=64).contains(&signal) || signal == libc::SIGKILL || signal == libc::SIGSTOP {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        // Safety: `libc::sigaction` is a C structure, so it's safe to
+        // initialize it to zero. The handler is async signal safe, as it
+        // only updates an atomic integer.
+        unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = raise as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(SignalFlag(signal))
+    }
+
+    /// Returns `true` when the signal was received since the last call,
+    /// lowering its flag.
+    pub fn take(&self) -> bool {
+        let bit = signal_bit(self.0);
+        RAISED.fetch_and(!bit, Ordering::Relaxed) & bit != 0
+    }
+}
+
+extern "C" fn raise(signal: libc::c_int) {
+    RAISED.fetch_or(signal_bit(signal), Ordering::Relaxed);
+}
+
+fn signal_bit(signal: libc::c_int) -> u64 {
+    1 << (signal - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_flag_is_raised_once_per_signal() {
+        let flag = SignalFlag::register(libc::SIGURG).unwrap();
+        assert!(!flag.take());
+
+        // Safety: The process handles the signal, so raising it is safe.
+        assert_eq!(0, unsafe { libc::raise(libc::SIGURG) });
+        assert!(flag.take());
+        assert!(!flag.take());
+    }
+
+    #[test]
+    fn signal_flag_rejects_uncatchable_signals() {
+        for signal in [0, libc::SIGKILL, libc::SIGSTOP, 65] {
+            assert_eq!(
+                io::ErrorKind::InvalidInput,
+                SignalFlag::register(signal).unwrap_err().kind()
+            );
+        }
+    }
+}
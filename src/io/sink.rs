@@ -0,0 +1,825 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Output sinks of the reported process events.
+//!
+//! Each [`Sink`] writes the reported events in its own [`Format`], and
+//! [`Sinks`] combines several of them, so the same events can be written to
+//! the standard output, a file, a socket and the journal at once.
+
+use std::{
+    fmt, io,
+    os::unix::net::UnixDatagram,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use termcolor::WriteColor;
+
+use crate::{
+    filter::EventKind,
+    solver::{Encoding, ExecutedFileName, PEvent, PExitStatus, PID},
+};
+
+mod colors;
+#[cfg(feature = "systemd")]
+mod journal;
+#[cfg(feature = "siem")]
+mod siem;
+
+pub use colors::{parse_style, ColorScheme, ExitColors, ExitOutcome};
+#[cfg(feature = "systemd")]
+pub use journal::JournalSink;
+
+/// Version of the records written by the machine-readable formats. It's
+/// increased on incompatible changes of the records.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// JSON Schema of the records written by the JSON format.
+#[cfg(feature = "json")]
+pub const JSON_SCHEMA: &str = include_str!("sink/events.schema.json");
+
+/// Format of the events written by a sink.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A line of text per event, like `Exec(1234) game [...]`.
+    #[default]
+    Text,
+    /// A JSON object per event, like
+    /// `{"schema_version":1,"event":"exec","severity":"info","pid":1234,"exes":["game"],"elements":[...]}`.
+    /// Other messages are written as `{"schema_version":1,"message":"..."}`
+    /// objects. The records are described by [`JSON_SCHEMA`].
+    #[cfg(feature = "json")]
+    Json,
+    /// An ArcSight Common Event Format (CEF) line per event, like
+    /// `CEF:0|copes|copes|1.0.0|exec|Process executed|3|dpid=1234 dproc=game`.
+    #[cfg(feature = "siem")]
+    Cef,
+    /// An IBM Log Event Extended Format (LEEF) line per event, like
+    /// `LEEF:1.0|copes|copes|1.0.0|exec|cat=exec\tsev=3\tpid=1234\tproc=game`.
+    #[cfg(feature = "siem")]
+    Leef,
+}
+
+/// Severity of a reported event.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// A regular event.
+    #[default]
+    Info,
+    /// An event that deserves attention, like the execution of a setuid
+    /// program.
+    Warning,
+}
+
+/// When the buffered events are written to the sinks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Flush the sinks after every event or message.
+    EveryEvent,
+    /// Flush the sinks after every line. The sinks writers are expected to
+    /// be line buffered.
+    #[default]
+    LineBuffered,
+    /// Flush the sinks periodically, with [`Sinks::flush_if_due`]. The sinks
+    /// writers are expected to be fully buffered.
+    Interval(Duration),
+}
+
+/// A reported process event.
+#[derive(Debug, Clone, Copy)]
+pub struct EventLine<'a> {
+    pub kind: EventKind,
+    pub severity: Severity,
+    pub pid: PID,
+    /// Executed files of the process. Coalesced executions have more than
+    /// one, ending with the last executed file.
+    pub exes: &'a [ExecutedFileName],
+    /// Additional information of the event, like `[cwd:/home/user]`.
+    pub elements: &'a [String],
+    /// Encoding of the executed files names.
+    pub encoding: Encoding,
+    /// Exit status of exit events, when known.
+    pub status: Option<PExitStatus>,
+    /// Abbreviations of long elements, like command lines, as pairs of the
+    /// element and its abbreviation. They replace the elements on the text
+    /// format only, so the other formats keep the whole information.
+    pub abbreviations: &'a [(String, String)],
+}
+
+impl EventLine<'_> {
+    /// Returns the event formatted in the given `format`.
+    pub fn format(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.to_string(),
+            #[cfg(feature = "json")]
+            Format::Json => {
+                let exes = self
+                    .exes
+                    .iter()
+                    .map(|exe| json_string(&exe.encode(self.encoding)));
+                let elements = self.elements.iter().map(|element| json_string(element));
+                let mut status = String::new();
+                if let Some(code) = self.status.and_then(|status| status.code()) {
+                    status.push_str(&format!(",\"exit_code\":{}", code));
+                }
+                if let Some(signal) = self.status.and_then(|status| status.signal()) {
+                    status.push_str(&format!(",\"signal\":{}", signal));
+                }
+                if let Some(name) = self.status.and_then(|status| status.signal_name()) {
+                    status.push_str(&format!(",\"signal_name\":\"{}\"", name));
+                }
+                format!(
+                    "{{\"schema_version\":{},\"event\":\"{}\",\"severity\":\"{}\",\"pid\":{}{},\"exes\":[{}],\"elements\":[{}]}}",
+                    SCHEMA_VERSION,
+                    event_name(self.kind),
+                    severity_name(self.severity),
+                    self.pid,
+                    status,
+                    exes.collect::<Vec<_>>().join(","),
+                    elements.collect::<Vec<_>>().join(",")
+                )
+            }
+            #[cfg(feature = "siem")]
+            Format::Cef => siem::cef_event(self),
+            #[cfg(feature = "siem")]
+            Format::Leef => siem::leef_event(self),
+        }
+    }
+}
+
+impl EventLine<'_> {
+    /// Returns the text format of the event split in the event kind and PID,
+    /// the executed files, and the exit status and elements.
+    fn text_parts(&self) -> [String; 3] {
+        let head = match self.kind {
+            EventKind::Exec => format!("{} ", PEvent::exec(self.pid)),
+            EventKind::Exit => format!("Exit({}) ", self.pid),
+        };
+        let exes = self.exes.iter().map(|exe| exe.encode(self.encoding));
+        let tail = self
+            .status
+            .and_then(status_element)
+            .iter()
+            .chain(self.elements)
+            .map(|element| {
+                let abbreviation = self
+                    .abbreviations
+                    .iter()
+                    .find_map(|(whole, abbreviation)| (whole == element).then_some(abbreviation));
+                format!(" {}", abbreviation.unwrap_or(element))
+            })
+            .collect();
+        [head, exes.collect::<Vec<_>>().join(" -> "), tail]
+    }
+}
+
+impl fmt::Display for EventLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.text_parts()
+            .iter()
+            .try_for_each(|part| f.write_str(part))
+    }
+}
+
+/// Destination of the reported process events.
+pub trait Sink {
+    /// Attempts to write an event `line`.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    fn write_event(&mut self, line: &EventLine) -> io::Result<()>;
+
+    /// Attempts to write a `message` not related to a single event, like the
+    /// summary of the reported events. Each non-empty line of the message is
+    /// written as a separate message on line based formats.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    fn write_message(&mut self, message: &str) -> io::Result<()>;
+
+    /// Attempts to write the buffered data, if any.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Writes the events to a stream, like the standard output or a file, one
+/// per line.
+pub struct StreamSink<W> {
+    writer: W,
+    format: Format,
+    color: bool,
+    colors: ColorScheme,
+}
+
+impl<W: WriteColor> StreamSink<W> {
+    /// Creates a `StreamSink` writing the events to `writer` in the given
+    /// `format`. With `color`, the events are written with the default
+    /// [`ColorScheme`] on text format.
+    pub fn new(writer: W, format: Format, color: bool) -> Self {
+        StreamSink {
+            writer,
+            format,
+            color: color && format == Format::Text,
+            colors: ColorScheme::default(),
+        }
+    }
+
+    /// Sets the colors of the events.
+    pub fn with_colors(mut self, colors: ColorScheme) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    fn set_color(&mut self, line: Option<&EventLine>) {
+        if !self.color {
+            return;
+        }
+
+        if let Err(e) = match line.map(|line| (line.kind, line.severity, line.status)) {
+            Some((_, Severity::Warning, _)) => self.writer.set_color(&self.colors.warning),
+            Some((EventKind::Exec, _, _)) => self.writer.set_color(&self.colors.exec),
+            Some((EventKind::Exit, _, status)) => {
+                let outcome = ExitOutcome::from(status.unwrap_or_default());
+                self.writer.set_color(self.colors.exit.get(outcome))
+            }
+            None => self.writer.reset(),
+        } {
+            log::error!("Couldn't setup output color: {}", e);
+        }
+    }
+
+    /// Applies the highlight style on top of the current one.
+    fn set_highlight(&mut self) {
+        let mut highlight = self.colors.highlight.clone();
+        highlight.set_reset(false);
+        if let Err(e) = self.writer.set_color(&highlight) {
+            log::error!("Couldn't setup output color: {}", e);
+        }
+    }
+}
+
+impl<W: WriteColor> Sink for StreamSink<W> {
+    fn write_event(&mut self, line: &EventLine) -> io::Result<()> {
+        self.set_color(Some(line));
+        if !self.color || self.colors.highlight.is_none() {
+            return writeln!(self.writer, "{}", line.format(self.format));
+        }
+
+        let [head, exes, tail] = line.text_parts();
+        write!(self.writer, "{}", head)?;
+        self.set_highlight();
+        write!(self.writer, "{}", exes)?;
+        self.set_color(Some(line));
+        writeln!(self.writer, "{}", tail)
+    }
+
+    fn write_message(&mut self, message: &str) -> io::Result<()> {
+        self.set_color(None);
+        if self.format == Format::Text {
+            return writeln!(self.writer, "{}", message);
+        }
+
+        message
+            .lines()
+            .filter(|line| !line.is_empty())
+            .try_for_each(|line| writeln!(self.writer, "{}", format_message(self.format, line)))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W> fmt::Debug for StreamSink<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamSink")
+            .field("format", &self.format)
+            .field("color", &self.color)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sends the events to a Unix datagram socket, one per datagram.
+#[derive(Debug)]
+pub struct SocketSink {
+    socket: UnixDatagram,
+    format: Format,
+}
+
+impl SocketSink {
+    /// Attempts to create a `SocketSink` sending the events to the socket at
+    /// `path` in the given `format`.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    pub fn connect(path: &Path, format: Format) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(SocketSink { socket, format })
+    }
+}
+
+impl Sink for SocketSink {
+    fn write_event(&mut self, line: &EventLine) -> io::Result<()> {
+        self.socket
+            .send(line.format(self.format).as_bytes())
+            .map(|_| ())
+    }
+
+    fn write_message(&mut self, message: &str) -> io::Result<()> {
+        message
+            .lines()
+            .filter(|line| !line.is_empty())
+            .try_for_each(|line| {
+                let line = format_message(self.format, line);
+                self.socket.send(line.as_bytes()).map(|_| ())
+            })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the events to several sinks, flushing them following a
+/// [`FlushPolicy`].
+pub struct Sinks {
+    sinks: Vec<Box<dyn Sink>>,
+    flush_policy: FlushPolicy,
+    last_flush: Instant,
+}
+
+impl Sinks {
+    /// Creates an empty `Sinks` with the default [`FlushPolicy`].
+    pub fn new() -> Self {
+        Self::with_flush_policy(FlushPolicy::default())
+    }
+
+    /// Creates an empty `Sinks` flushed following `flush_policy`.
+    pub fn with_flush_policy(flush_policy: FlushPolicy) -> Self {
+        Sinks {
+            sinks: Vec::new(),
+            flush_policy,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Adds a `sink`.
+    pub fn push(&mut self, sink: Box<dyn Sink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Returns `true` when there are no sinks.
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Attempts to flush the sinks when the [`FlushPolicy::Interval`]
+    /// elapsed since the last flush at `now`. Does nothing with other
+    /// policies.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    pub fn flush_if_due(&mut self, now: Instant) -> io::Result<()> {
+        match self.flush_policy {
+            FlushPolicy::Interval(interval)
+                if now.saturating_duration_since(self.last_flush) >= interval =>
+            {
+                self.last_flush = now;
+                self.flush()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Flushes the sinks after a write with [`FlushPolicy::EveryEvent`].
+    fn flush_written(&mut self, written: io::Result<()>) -> io::Result<()> {
+        match self.flush_policy {
+            FlushPolicy::EveryEvent => written.and(self.flush()),
+            _ => written,
+        }
+    }
+
+    /// Calls `write` on every sink, even when it fails on some of them.
+    /// Returns the first error.
+    fn for_each(
+        &mut self,
+        mut write: impl FnMut(&mut dyn Sink) -> io::Result<()>,
+    ) -> io::Result<()> {
+        self.sinks
+            .iter_mut()
+            .map(|sink| write(sink.as_mut()))
+            .fold(Ok(()), Result::and)
+    }
+}
+
+impl Sink for Sinks {
+    fn write_event(&mut self, line: &EventLine) -> io::Result<()> {
+        let written = self.for_each(|sink| sink.write_event(line));
+        self.flush_written(written)
+    }
+
+    fn write_message(&mut self, message: &str) -> io::Result<()> {
+        let written = self.for_each(|sink| sink.write_message(message));
+        self.flush_written(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.for_each(|sink| sink.flush())
+    }
+}
+
+impl Default for Sinks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Sinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sinks")
+            .field("sinks", &self.sinks.len())
+            .field("flush_policy", &self.flush_policy)
+            .finish_non_exhaustive()
+    }
+}
+
+fn event_name(kind: EventKind) -> &'static str {
+    match kind {
+        EventKind::Exec => "exec",
+        EventKind::Exit => "exit",
+    }
+}
+
+/// Returns the element of the text format describing an exit `status`, like
+/// `[signal:SIGSEGV]`, or `None` for successful exits.
+fn status_element(status: PExitStatus) -> Option<String> {
+    match (status.code(), status.signal_name(), status.signal()) {
+        (Some(0), _, _) => None,
+        (Some(code), _, _) => Some(format!("[exit:{}]", code)),
+        (_, Some(name), _) => Some(format!("[signal:{}]", name)),
+        (_, _, Some(signal)) => Some(format!("[signal:{}]", signal)),
+        _ => None,
+    }
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+    }
+}
+
+/// Returns a line of a message, not related to a single event, in the given
+/// `format`.
+fn format_message(format: Format, line: &str) -> String {
+    match format {
+        Format::Text => line.to_string(),
+        #[cfg(feature = "json")]
+        Format::Json => json_message(line),
+        #[cfg(feature = "siem")]
+        Format::Cef => siem::cef_message(line),
+        #[cfg(feature = "siem")]
+        Format::Leef => siem::leef_message(line),
+    }
+}
+
+#[cfg(feature = "json")]
+fn json_message(message: &str) -> String {
+    format!(
+        "{{\"schema_version\":{},\"message\":{}}}",
+        SCHEMA_VERSION,
+        json_string(message)
+    )
+}
+
+/// Returns `value` as a quoted JSON string.
+#[cfg(feature = "json")]
+pub(crate) fn json_string(value: &str) -> String {
+    use std::fmt::Write as _;
+
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, ffi::OsString, fs, process};
+
+    use termcolor::NoColor;
+
+    use super::*;
+    use crate::solver::PExe;
+
+    fn exes(names: &[&str]) -> Vec<ExecutedFileName> {
+        names
+            .iter()
+            .map(|name| ExecutedFileName::from(PExe::from(OsString::from(name))))
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn event_line_is_formatted_as_text_and_json() {
+        let exes = exes(&["launcher", "game \"1\""]);
+        let elements = ["[cwd:/home/user]".to_string()];
+        let line = EventLine {
+            kind: EventKind::Exec,
+            severity: Severity::Warning,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &elements,
+            encoding: Encoding::Lossy,
+            status: None,
+            abbreviations: &[],
+        };
+
+        assert_eq!(
+            "Exec(1234) launcher -> game \"1\" [cwd:/home/user]",
+            line.format(Format::Text)
+        );
+        assert_eq!(
+            "{\"schema_version\":1,\"event\":\"exec\",\"severity\":\"warning\",\"pid\":1234,\"exes\":[\"launcher\",\"game \\\"1\\\"\"],\
+             \"elements\":[\"[cwd:/home/user]\"]}",
+            line.format(Format::Json)
+        );
+
+        let line = EventLine {
+            kind: EventKind::Exit,
+            exes: &exes[1..],
+            elements: &[],
+            ..line
+        };
+        assert_eq!("Exit(1234) game \"1\"", line.format(Format::Text));
+
+        let line = EventLine {
+            status: Some(PExitStatus::from(3 << 8)),
+            ..line
+        };
+        assert_eq!("Exit(1234) game \"1\" [exit:3]", line.format(Format::Text));
+        assert_eq!(
+            "{\"schema_version\":1,\"event\":\"exit\",\"severity\":\"warning\",\"pid\":1234,\"exit_code\":3,\
+             \"exes\":[\"game \\\"1\\\"\"],\"elements\":[]}",
+            line.format(Format::Json)
+        );
+
+        let abbreviations = [("[cwd:/home/user]".to_string(), "[cwd:…]".to_string())];
+        let line = EventLine {
+            kind: EventKind::Exec,
+            elements: &elements,
+            status: None,
+            abbreviations: &abbreviations,
+            ..line
+        };
+        assert_eq!("Exec(1234) game \"1\" [cwd:…]", line.format(Format::Text));
+        assert_eq!(
+            "{\"schema_version\":1,\"event\":\"exec\",\"severity\":\"warning\",\"pid\":1234,\
+             \"exes\":[\"game \\\"1\\\"\"],\"elements\":[\"[cwd:/home/user]\"]}",
+            line.format(Format::Json)
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "json", target_os = "linux"))]
+    fn signal_deaths_are_formatted_with_the_signal_name() {
+        let exes = exes(&["game"]);
+        let line = EventLine {
+            kind: EventKind::Exit,
+            severity: Severity::Info,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &[],
+            encoding: Encoding::Lossy,
+            status: Some(PExitStatus::from(libc::SIGSEGV as u32)),
+            abbreviations: &[],
+        };
+        assert_eq!(
+            "Exit(1234) game [signal:SIGSEGV]",
+            line.format(Format::Text)
+        );
+        assert_eq!(
+            "{\"schema_version\":1,\"event\":\"exit\",\"severity\":\"info\",\"pid\":1234,\"signal\":11,\
+             \"signal_name\":\"SIGSEGV\",\"exes\":[\"game\"],\"elements\":[]}",
+            line.format(Format::Json)
+        );
+
+        let line = EventLine {
+            status: Some(PExitStatus::from(40)),
+            ..line
+        };
+        assert_eq!("Exit(1234) game [signal:40]", line.format(Format::Text));
+        assert_eq!(
+            "{\"schema_version\":1,\"event\":\"exit\",\"severity\":\"info\",\"pid\":1234,\"signal\":40,\
+             \"exes\":[\"game\"],\"elements\":[]}",
+            line.format(Format::Json)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn json_string_escapes_special_characters() {
+        assert_eq!("\"a\\\\b\\n\\u0001\"", json_string("a\\b\n\u{1}"));
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn json_schema_describes_the_schema_version() {
+        assert!(JSON_SCHEMA.contains(&format!("\"const\": {}", SCHEMA_VERSION)));
+        for field in [
+            "schema_version",
+            "event",
+            "severity",
+            "pid",
+            "exit_code",
+            "signal",
+            "signal_name",
+            "exes",
+            "elements",
+        ] {
+            assert!(JSON_SCHEMA.contains(&format!("\"{}\":", field)));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn sinks_write_to_every_sink_in_its_format() {
+        let (socket, peer) = UnixDatagram::pair().unwrap();
+        let mut sinks = Sinks::new();
+        sinks.push(Box::new(SocketSink {
+            socket,
+            format: Format::Json,
+        }));
+        sinks.push(Box::new(StreamSink::new(
+            NoColor::new(Vec::new()),
+            Format::Text,
+            false,
+        )));
+
+        let exes = exes(&["game"]);
+        let line = EventLine {
+            kind: EventKind::Exit,
+            severity: Severity::Info,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &[],
+            encoding: Encoding::Lossy,
+            status: None,
+            abbreviations: &[],
+        };
+        sinks.write_event(&line).unwrap();
+        sinks.write_message("\nTotal: 1 execs\nDone").unwrap();
+
+        let mut buffer = [0u8; 256];
+        let mut received = || {
+            let size = peer.recv(&mut buffer).unwrap();
+            String::from_utf8_lossy(&buffer[..size]).into_owned()
+        };
+        assert_eq!(
+            "{\"schema_version\":1,\"event\":\"exit\",\"severity\":\"info\",\"pid\":1234,\"exes\":[\"game\"],\"elements\":[]}",
+            received()
+        );
+        assert_eq!(
+            "{\"schema_version\":1,\"message\":\"Total: 1 execs\"}",
+            received()
+        );
+        assert_eq!("{\"schema_version\":1,\"message\":\"Done\"}", received());
+    }
+
+    #[test]
+    fn sinks_are_flushed_following_the_flush_policy() {
+        let exes = exes(&["game"]);
+        let line = EventLine {
+            kind: EventKind::Exec,
+            severity: Severity::Info,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &[],
+            encoding: Encoding::Lossy,
+            status: None,
+            abbreviations: &[],
+        };
+        let file = env::temp_dir().join(format!("copes-sinks-{}", process::id()));
+        let written = || fs::read_to_string(&file).unwrap();
+        let buffered_sinks = |policy| {
+            let writer = io::BufWriter::new(fs::File::create(&file).unwrap());
+            let mut sinks = Sinks::with_flush_policy(policy);
+            sinks.push(Box::new(StreamSink::new(
+                NoColor::new(writer),
+                Format::Text,
+                false,
+            )));
+            sinks
+        };
+
+        let mut sinks = buffered_sinks(FlushPolicy::EveryEvent);
+        sinks.write_event(&line).unwrap();
+        assert_eq!("Exec(1234) game\n", written());
+
+        let interval = Duration::from_secs(60);
+        let mut sinks = buffered_sinks(FlushPolicy::Interval(interval));
+        sinks.write_event(&line).unwrap();
+        sinks.flush_if_due(Instant::now()).unwrap();
+        assert_eq!("", written());
+        sinks.flush_if_due(Instant::now() + interval).unwrap();
+        assert_eq!("Exec(1234) game\n", written());
+
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn stream_sink_writes_lines() {
+        let mut sink = StreamSink::new(NoColor::new(Vec::new()), Format::Json, true);
+        sink.write_message("Total: 1 execs\nDone").unwrap();
+        assert_eq!(
+            "{\"schema_version\":1,\"message\":\"Total: 1 execs\"}\n{\"schema_version\":1,\"message\":\"Done\"}\n",
+            String::from_utf8(sink.writer.into_inner()).unwrap()
+        );
+    }
+
+    #[test]
+    fn exit_events_are_colored_by_outcome() {
+        let exes = exes(&["game"]);
+        let line = |status| EventLine {
+            kind: EventKind::Exit,
+            severity: Severity::Info,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &[],
+            encoding: Encoding::Lossy,
+            status: Some(PExitStatus::from(status)),
+            abbreviations: &[],
+        };
+        let mut colors = ColorScheme::default();
+        colors
+            .exit
+            .set(ExitOutcome::Failure, parse_style("208").unwrap());
+        colors
+            .exit
+            .set(ExitOutcome::Signal, parse_style("none").unwrap());
+        let mut sink =
+            StreamSink::new(termcolor::Buffer::ansi(), Format::Text, true).with_colors(colors);
+
+        sink.write_event(&line(0)).unwrap();
+        sink.write_event(&line(1 << 8)).unwrap();
+        sink.write_event(&line(9)).unwrap();
+        assert_eq!(
+            "\x1b[0m\x1b[32mExit(1234) game\n\
+             \x1b[0m\x1b[38;5;208mExit(1234) game [exit:1]\n\
+             \x1b[0mExit(1234) game [signal:SIGKILL]\n",
+            String::from_utf8(sink.writer.into_inner()).unwrap()
+        );
+    }
+
+    #[test]
+    fn executed_files_are_highlighted_on_top_of_the_event_color() {
+        let exes = exes(&["launcher", "game"]);
+        let line = EventLine {
+            kind: EventKind::Exec,
+            severity: Severity::Info,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &["[tty:pts/1]".to_string()],
+            encoding: Encoding::Lossy,
+            status: None,
+            abbreviations: &[],
+        };
+        let colors = "exec=cyan:highlight=bold".parse().unwrap();
+        let mut sink =
+            StreamSink::new(termcolor::Buffer::ansi(), Format::Text, true).with_colors(colors);
+
+        sink.write_event(&line).unwrap();
+        assert_eq!(
+            "\x1b[0m\x1b[36mExec(1234) \x1b[1mlauncher -> game\x1b[0m\x1b[36m [tty:pts/1]\n",
+            String::from_utf8(sink.writer.into_inner()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Colors of the events written on text format.
+//!
+//! A [`ColorScheme`] is parsed from a list of `KEY=STYLE` entries separated
+//! by colons, like `exec=cyan:exit-signal=bold,#ff0000:highlight=underline`.
+//! The keys are:
+//!
+//! | Key | Colored events |
+//! |-----|----------------|
+//! | `exec` | Exec events |
+//! | `exit` | Exit events of any outcome |
+//! | `exit-success` | Exit events of processes that exited successfully |
+//! | `exit-failure` | Exit events of processes that exited with a non-zero exit code |
+//! | `exit-signal` | Exit events of processes killed by a signal |
+//! | `warning` | Warnings |
+//! | `highlight` | Executed files names, on top of the event color |
+//!
+//! Styles are comma separated lists of colors and attributes. Colors are
+//! named (`black`, `blue`, `green`, `red`, `cyan`, `magenta`, `yellow` and
+//! `white`), 256-color numbers, like `208`, or truecolor RGB values, like
+//! `#ff8000`. Colors prefixed with `on-` are background colors. The
+//! attributes are `bold`, `dimmed`, `italic` and `underline`, and `none`
+//! is the default terminal style.
+
+use std::str::FromStr;
+
+use termcolor::{Color, ColorSpec};
+
+use crate::solver::PExitStatus;
+
+/// Outcome of an exited process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitOutcome {
+    /// The process exited with a zero exit code.
+    Success,
+    /// The process exited with a non-zero exit code.
+    Failure,
+    /// The process was killed by a signal.
+    Signal,
+}
+
+impl From<PExitStatus> for ExitOutcome {
+    fn from(status: PExitStatus) -> Self {
+        match (status.code(), status.signal()) {
+            (Some(0), _) => ExitOutcome::Success,
+            (_, Some(_)) => ExitOutcome::Signal,
+            _ => ExitOutcome::Failure,
+        }
+    }
+}
+
+impl FromStr for ExitOutcome {
+    type Err = String;
+
+    /// Parses the `success`, `failure` and `signal` outcome names.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "success" => Ok(ExitOutcome::Success),
+            "failure" => Ok(ExitOutcome::Failure),
+            "signal" => Ok(ExitOutcome::Signal),
+            _ => Err(format!(
+                "Unknown exit outcome '{}', expected success, failure or signal",
+                s
+            )),
+        }
+    }
+}
+
+/// Colors of the exit events, by the [`ExitOutcome`] of the process.
+/// Successful exits are green, failures yellow and signal deaths red by
+/// default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitColors {
+    success: ColorSpec,
+    failure: ColorSpec,
+    signal: ColorSpec,
+}
+
+impl ExitColors {
+    /// Sets the `style` of the exits with the given `outcome`.
+    pub fn set(&mut self, outcome: ExitOutcome, style: ColorSpec) {
+        match outcome {
+            ExitOutcome::Success => self.success = style,
+            ExitOutcome::Failure => self.failure = style,
+            ExitOutcome::Signal => self.signal = style,
+        }
+    }
+
+    /// Returns the style of the exits with the given `outcome`.
+    pub fn get(&self, outcome: ExitOutcome) -> &ColorSpec {
+        match outcome {
+            ExitOutcome::Success => &self.success,
+            ExitOutcome::Failure => &self.failure,
+            ExitOutcome::Signal => &self.signal,
+        }
+    }
+}
+
+impl Default for ExitColors {
+    fn default() -> Self {
+        let style = |color| {
+            let mut style = ColorSpec::new();
+            style.set_fg(Some(color));
+            style
+        };
+        ExitColors {
+            success: style(Color::Green),
+            failure: style(Color::Yellow),
+            signal: style(Color::Red),
+        }
+    }
+}
+
+/// Colors of the events on text format. Exec events have the default
+/// terminal style, exit events the default [`ExitColors`], warnings are bold
+/// yellow, and the executed files names aren't highlighted by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorScheme {
+    /// Style of the exec events.
+    pub exec: ColorSpec,
+    /// Styles of the exit events.
+    pub exit: ExitColors,
+    /// Style of the warnings, of any event kind.
+    pub warning: ColorSpec,
+    /// Style of the executed files names, applied on top of the style of the
+    /// event.
+    pub highlight: ColorSpec,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        let mut warning = ColorSpec::new();
+        warning.set_fg(Some(Color::Yellow)).set_bold(true);
+        ColorScheme {
+            exec: ColorSpec::new(),
+            exit: ExitColors::default(),
+            warning,
+            highlight: ColorSpec::new(),
+        }
+    }
+}
+
+impl FromStr for ColorScheme {
+    type Err = String;
+
+    /// Parses a list of `KEY=STYLE` entries separated by colons, overriding
+    /// the styles of the default scheme. Empty entries are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut scheme = ColorScheme::default();
+        for entry in s.split(':').filter(|entry| !entry.is_empty()) {
+            let (key, style) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{}' is not a KEY=STYLE entry", entry))?;
+            let style = parse_style(style)?;
+            match key {
+                "exec" => scheme.exec = style,
+                "exit" => {
+                    for outcome in [
+                        ExitOutcome::Success,
+                        ExitOutcome::Failure,
+                        ExitOutcome::Signal,
+                    ] {
+                        scheme.exit.set(outcome, style.clone());
+                    }
+                }
+                "exit-success" => scheme.exit.set(ExitOutcome::Success, style),
+                "exit-failure" => scheme.exit.set(ExitOutcome::Failure, style),
+                "exit-signal" => scheme.exit.set(ExitOutcome::Signal, style),
+                "warning" => scheme.warning = style,
+                "highlight" => scheme.highlight = style,
+                _ => {
+                    return Err(format!(
+                        "Unknown color key '{}', expected exec, exit, exit-success, \
+                         exit-failure, exit-signal, warning or highlight",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(scheme)
+    }
+}
+
+/// Parses a comma separated list of colors and attributes, like
+/// `bold,#ff8000` or `208,on-black`.
+pub fn parse_style(s: &str) -> Result<ColorSpec, String> {
+    let mut style = ColorSpec::new();
+    for word in s.split(',').map(str::trim) {
+        match word {
+            "none" | "" => {}
+            "bold" => {
+                style.set_bold(true);
+            }
+            "dimmed" => {
+                style.set_dimmed(true);
+            }
+            "italic" => {
+                style.set_italic(true);
+            }
+            "underline" => {
+                style.set_underline(true);
+            }
+            word => match word.strip_prefix("on-") {
+                Some(color) => {
+                    style.set_bg(Some(parse_color(color)?));
+                }
+                None => {
+                    style.set_fg(Some(parse_color(word)?));
+                }
+            },
+        }
+    }
+    Ok(style)
+}
+
+/// Parses a color name, a 256-color number or a `#rrggbb` RGB value.
+fn parse_color(s: &str) -> Result<Color, String> {
+    match s.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+            let component = |i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+            Ok(Color::Rgb(component(0), component(2), component(4)))
+        }
+        Some(_) => Err(format!("'{}' is not a #rrggbb color", s)),
+        None => s
+            .parse()
+            .map_err(|e: termcolor::ParseColorError| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fg(color: Color) -> ColorSpec {
+        let mut style = ColorSpec::new();
+        style.set_fg(Some(color));
+        style
+    }
+
+    #[test]
+    fn exit_outcomes_are_parsed_from_their_names() {
+        assert_eq!(Ok(ExitOutcome::Success), "success".parse());
+        assert_eq!(Ok(ExitOutcome::Failure), "failure".parse());
+        assert_eq!(Ok(ExitOutcome::Signal), "signal".parse());
+        assert!("crash".parse::<ExitOutcome>().is_err());
+    }
+
+    #[test]
+    fn styles_combine_colors_and_attributes() {
+        let mut style = fg(Color::Rgb(0xff, 0x80, 0x00));
+        style.set_bold(true).set_bg(Some(Color::Ansi256(236)));
+        assert_eq!(Ok(style), parse_style("bold,#ff8000,on-236"));
+        assert_eq!(Ok(fg(Color::Cyan)), parse_style("cyan"));
+        assert_eq!(Ok(ColorSpec::new()), parse_style("none"));
+        assert!(parse_style("#ff80").is_err());
+        assert!(parse_style("pink").is_err());
+        assert!(parse_style("bold,256").is_err());
+    }
+
+    #[test]
+    fn color_schemes_override_the_default_styles() {
+        let scheme: ColorScheme = "exec=cyan::exit=none:exit-signal=magenta:highlight=bold"
+            .parse()
+            .unwrap();
+        assert_eq!(fg(Color::Cyan), scheme.exec);
+        assert_eq!(&ColorSpec::new(), scheme.exit.get(ExitOutcome::Success));
+        assert_eq!(&ColorSpec::new(), scheme.exit.get(ExitOutcome::Failure));
+        assert_eq!(&fg(Color::Magenta), scheme.exit.get(ExitOutcome::Signal));
+        assert_eq!(ColorScheme::default().warning, scheme.warning);
+        assert!(scheme.highlight.bold());
+
+        assert_eq!(Ok(ColorScheme::default()), "".parse());
+        assert!("exec".parse::<ColorScheme>().is_err());
+        assert!("exe=red".parse::<ColorScheme>().is_err());
+    }
+}
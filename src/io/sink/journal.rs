@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Sink writing the events to the systemd journal, using its [native
+//! protocol].
+//!
+//! [native protocol]: https://systemd.io/JOURNAL_NATIVE_PROTOCOL/
+
+use std::{io, os::unix::net::UnixDatagram, path::Path};
+
+use super::{
+    event_name, format_message, severity_name, EventLine, Format, Severity, Sink, SCHEMA_VERSION,
+};
+
+/// Socket of the journal native protocol.
+const JOURNAL_SOCKET_PATH: &str = "/run/systemd/journal/socket";
+
+/// Priority of the informational journal entries.
+const INFO_PRIORITY: &str = "6";
+
+/// Priority of the warning journal entries.
+const WARNING_PRIORITY: &str = "4";
+
+/// Sends the events to the systemd journal, as entries with the formatted
+/// event as message, and the `COPES_SCHEMA_VERSION`, `COPES_EVENT`,
+/// `COPES_SEVERITY`, `COPES_PID` and `COPES_EXE` fields. Exit events also
+/// get the `COPES_EXIT_CODE`, or the `COPES_SIGNAL` and `COPES_SIGNAL_NAME`
+/// fields. Warning events get the warning priority.
+#[derive(Debug)]
+pub struct JournalSink {
+    socket: UnixDatagram,
+    format: Format,
+}
+
+impl JournalSink {
+    /// Attempts to create a `JournalSink` writing the event messages in the
+    /// given `format`.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    pub fn connect(format: Format) -> io::Result<Self> {
+        JournalSink::connect_to(Path::new(JOURNAL_SOCKET_PATH), format)
+    }
+
+    fn connect_to(path: &Path, format: Format) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(JournalSink { socket, format })
+    }
+
+    fn send(&self, severity: Severity, fields: &[(&str, &str)]) -> io::Result<()> {
+        let priority = match severity {
+            Severity::Info => INFO_PRIORITY,
+            Severity::Warning => WARNING_PRIORITY,
+        };
+        let mut entry = Vec::new();
+        for (name, value) in [
+            ("SYSLOG_IDENTIFIER", env!("CARGO_CRATE_NAME")),
+            ("PRIORITY", priority),
+        ]
+        .iter()
+        .chain(fields)
+        {
+            append_field(&mut entry, name, value);
+        }
+
+        self.socket.send(&entry).map(|_| ())
+    }
+}
+
+impl Sink for JournalSink {
+    fn write_event(&mut self, line: &EventLine) -> io::Result<()> {
+        let exe = line
+            .exes
+            .last()
+            .map(|exe| exe.encode(line.encoding).into_owned())
+            .unwrap_or_default();
+        let message = line.format(self.format);
+        let schema_version = SCHEMA_VERSION.to_string();
+        let pid = line.pid.to_string();
+        let mut fields = vec![
+            ("MESSAGE", message.as_str()),
+            ("COPES_SCHEMA_VERSION", &schema_version),
+            ("COPES_EVENT", event_name(line.kind)),
+            ("COPES_SEVERITY", severity_name(line.severity)),
+            ("COPES_PID", &pid),
+            ("COPES_EXE", &exe),
+        ];
+        let code = line
+            .status
+            .and_then(|status| status.code())
+            .map(|code| code.to_string());
+        if let Some(code) = &code {
+            fields.push(("COPES_EXIT_CODE", code));
+        }
+        let signal = line
+            .status
+            .and_then(|status| status.signal())
+            .map(|signal| signal.to_string());
+        if let Some(signal) = &signal {
+            fields.push(("COPES_SIGNAL", signal));
+        }
+        if let Some(name) = line.status.and_then(|status| status.signal_name()) {
+            fields.push(("COPES_SIGNAL_NAME", name));
+        }
+        self.send(line.severity, &fields)
+    }
+
+    fn write_message(&mut self, message: &str) -> io::Result<()> {
+        let message = if self.format == Format::Text {
+            message.to_string()
+        } else {
+            message
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(|line| format_message(self.format, line))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        self.send(Severity::Info, &[("MESSAGE", &message)])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends the field `name` with the given `value` to the journal `entry`.
+/// Values with line breaks are appended in binary form.
+fn append_field(entry: &mut Vec<u8>, name: &str, value: &str) {
+    entry.extend(name.as_bytes());
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend((value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend(value.as_bytes());
+    entry.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, ffi::OsString, fs, process};
+
+    use super::*;
+    use crate::{
+        filter::EventKind,
+        solver::{Encoding, ExecutedFileName, PExe, PExitStatus, PID},
+    };
+
+    #[test]
+    fn append_field_uses_binary_form_for_multiline_values() {
+        let mut entry = Vec::new();
+        append_field(&mut entry, "MESSAGE", "Exec(1234) game");
+        append_field(&mut entry, "MESSAGE", "a\nb");
+        assert_eq!(
+            b"MESSAGE=Exec(1234) game\nMESSAGE\n\x03\0\0\0\0\0\0\0a\nb\n".as_slice(),
+            entry
+        );
+    }
+
+    #[test]
+    fn journal_sink_sends_event_fields() {
+        let path = env::temp_dir().join(format!("copes-journal-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        let journal = UnixDatagram::bind(&path).unwrap();
+        let mut sink = JournalSink::connect_to(&path, Format::Text).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let exes = [ExecutedFileName::from(PExe::from(OsString::from("game")))];
+        sink.write_event(&EventLine {
+            kind: EventKind::Exec,
+            severity: Severity::Warning,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &[],
+            encoding: Encoding::Lossy,
+            status: None,
+            abbreviations: &[],
+        })
+        .unwrap();
+
+        let mut buffer = [0u8; 256];
+        let size = journal.recv(&mut buffer).unwrap();
+        assert_eq!(
+            "SYSLOG_IDENTIFIER=copes\nPRIORITY=4\nMESSAGE=Exec(1234) game\nCOPES_SCHEMA_VERSION=1\n\
+             COPES_EVENT=exec\nCOPES_SEVERITY=warning\nCOPES_PID=1234\nCOPES_EXE=game\n",
+            String::from_utf8_lossy(&buffer[..size])
+        );
+
+        sink.write_event(&EventLine {
+            kind: EventKind::Exit,
+            severity: Severity::Info,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &[],
+            encoding: Encoding::Lossy,
+            status: Some(PExitStatus::from(libc::SIGKILL as u32)),
+            abbreviations: &[],
+        })
+        .unwrap();
+
+        let size = journal.recv(&mut buffer).unwrap();
+        assert_eq!(
+            "SYSLOG_IDENTIFIER=copes\nPRIORITY=6\nMESSAGE=Exit(1234) game [signal:SIGKILL]\n\
+             COPES_SCHEMA_VERSION=1\nCOPES_EVENT=exit\nCOPES_SEVERITY=info\nCOPES_PID=1234\n\
+             COPES_EXE=game\nCOPES_SIGNAL=9\nCOPES_SIGNAL_NAME=SIGKILL\n",
+            String::from_utf8_lossy(&buffer[..size])
+        );
+    }
+}
@@ -0,0 +1,283 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Formats of the security information and event management (SIEM) systems:
+//! the ArcSight Common Event Format (CEF) and the IBM Log Event Extended
+//! Format (LEEF).
+
+use crate::filter::EventKind;
+
+use super::{event_name, EventLine, Severity};
+
+/// Vendor and product of the events.
+const PRODUCT: &str = env!("CARGO_PKG_NAME");
+
+/// Version of the product of the events.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Returns the event `line` formatted as a CEF line, like
+/// `CEF:0|copes|copes|1.0.0|exec|Process executed|3|dpid=1234 dproc=game`.
+pub(super) fn cef_event(line: &EventLine) -> String {
+    let name = match line.kind {
+        EventKind::Exec => "Process executed",
+        EventKind::Exit => "Process exited",
+    };
+    let mut extension = vec![format!("dpid={}", line.pid)];
+    let exes = line
+        .exes
+        .iter()
+        .map(|exe| exe.encode(line.encoding))
+        .collect::<Vec<_>>();
+    if let Some(exe) = exes.last() {
+        extension.push(format!("dproc={}", cef_value(exe)));
+    }
+    if exes.len() > 1 {
+        extension.push("cs1Label=chain".to_string());
+        extension.push(format!("cs1={}", cef_value(&exes.join(" -> "))));
+    }
+    if let Some(code) = line.status.and_then(|status| status.code()) {
+        extension.push("cn1Label=exitCode".to_string());
+        extension.push(format!("cn1={}", code));
+    }
+    if let Some(signal) = line.status.and_then(|status| status.signal()) {
+        extension.push("cn2Label=signal".to_string());
+        extension.push(format!("cn2={}", signal));
+    }
+    if let Some(name) = line.status.and_then(|status| status.signal_name()) {
+        extension.push("cs3Label=signalName".to_string());
+        extension.push(format!("cs3={}", name));
+    }
+    if !line.elements.is_empty() {
+        extension.push("cs2Label=details".to_string());
+        extension.push(format!("cs2={}", cef_value(&line.elements.join(" "))));
+    }
+
+    cef_line(
+        event_name(line.kind),
+        name,
+        cef_severity(line.severity),
+        &extension.join(" "),
+    )
+}
+
+/// Returns the `message` formatted as a CEF line.
+pub(super) fn cef_message(message: &str) -> String {
+    cef_line(
+        "message",
+        "Message",
+        1,
+        &format!("msg={}", cef_value(message)),
+    )
+}
+
+/// Returns the event `line` formatted as a LEEF line, like
+/// `LEEF:1.0|copes|copes|1.0.0|exec|cat=exec\tsev=3\tpid=1234\tproc=game`.
+pub(super) fn leef_event(line: &EventLine) -> String {
+    let mut attributes = vec![
+        format!("cat={}", event_name(line.kind)),
+        format!("sev={}", cef_severity(line.severity)),
+        format!("pid={}", line.pid),
+    ];
+    let exes = line
+        .exes
+        .iter()
+        .map(|exe| exe.encode(line.encoding))
+        .collect::<Vec<_>>();
+    if let Some(exe) = exes.last() {
+        attributes.push(format!("proc={}", leef_value(exe)));
+    }
+    if exes.len() > 1 {
+        attributes.push(format!("chain={}", leef_value(&exes.join(" -> "))));
+    }
+    if let Some(code) = line.status.and_then(|status| status.code()) {
+        attributes.push(format!("exitCode={}", code));
+    }
+    if let Some(signal) = line.status.and_then(|status| status.signal()) {
+        attributes.push(format!("signal={}", signal));
+    }
+    if let Some(name) = line.status.and_then(|status| status.signal_name()) {
+        attributes.push(format!("signalName={}", name));
+    }
+    if !line.elements.is_empty() {
+        attributes.push(format!("details={}", leef_value(&line.elements.join(" "))));
+    }
+
+    leef_line(event_name(line.kind), &attributes.join("\t"))
+}
+
+/// Returns the `message` formatted as a LEEF line.
+pub(super) fn leef_message(message: &str) -> String {
+    leef_line("message", &format!("msg={}", leef_value(message)))
+}
+
+fn cef_line(class: &str, name: &str, severity: u8, extension: &str) -> String {
+    format!(
+        "CEF:0|{}|{}|{}|{}|{}|{}|{}",
+        PRODUCT,
+        PRODUCT,
+        cef_header(VERSION),
+        class,
+        name,
+        severity,
+        extension
+    )
+}
+
+fn leef_line(event_id: &str, attributes: &str) -> String {
+    format!(
+        "LEEF:1.0|{}|{}|{}|{}|{}",
+        PRODUCT, PRODUCT, VERSION, event_id, attributes
+    )
+}
+
+/// Returns the CEF severity, from 0 (lowest) to 10 (highest), of events
+/// with the given `severity`.
+fn cef_severity(severity: Severity) -> u8 {
+    match severity {
+        Severity::Info => 3,
+        Severity::Warning => 7,
+    }
+}
+
+/// Escapes a CEF header `value`.
+fn cef_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Escapes a CEF extension `value`.
+fn cef_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '=' => escaped.push_str("\\="),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes a LEEF attribute `value`, so it has no attribute separators.
+fn leef_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::*;
+    use crate::solver::{Encoding, ExecutedFileName, PExe, PExitStatus, PID};
+
+    #[test]
+    fn events_are_formatted_as_cef_and_leef() {
+        let exes = ["launcher", "game=1"]
+            .map(|name| ExecutedFileName::from(PExe::from(OsString::from(name))));
+        let elements = ["[cwd:/home/user]".to_string(), "[setuid:root]".to_string()];
+        let line = EventLine {
+            kind: EventKind::Exec,
+            severity: Severity::Warning,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &elements,
+            encoding: Encoding::Lossy,
+            status: None,
+            abbreviations: &[],
+        };
+
+        assert_eq!(
+            format!(
+                "CEF:0|copes|copes|{}|exec|Process executed|7|dpid=1234 dproc=game\\=1 \
+                 cs1Label=chain cs1=launcher -> game\\=1 \
+                 cs2Label=details cs2=[cwd:/home/user] [setuid:root]",
+                VERSION
+            ),
+            cef_event(&line)
+        );
+        assert_eq!(
+            format!(
+                "LEEF:1.0|copes|copes|{}|exec|cat=exec\tsev=7\tpid=1234\tproc=game=1\t\
+                 chain=launcher -> game=1\tdetails=[cwd:/home/user] [setuid:root]",
+                VERSION
+            ),
+            leef_event(&line)
+        );
+
+        let line = EventLine {
+            kind: EventKind::Exit,
+            severity: Severity::Info,
+            exes: &exes[1..],
+            elements: &[],
+            status: Some(PExitStatus::from(0)),
+            ..line
+        };
+        assert_eq!(
+            format!(
+                "CEF:0|copes|copes|{}|exit|Process exited|3|dpid=1234 dproc=game\\=1 \
+                 cn1Label=exitCode cn1=0",
+                VERSION
+            ),
+            cef_event(&line)
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn signal_deaths_have_numeric_and_symbolic_fields() {
+        let exes = [ExecutedFileName::from(PExe::from(OsString::from("game")))];
+        let line = EventLine {
+            kind: EventKind::Exit,
+            severity: Severity::Info,
+            pid: PID::from(1234),
+            exes: &exes,
+            elements: &[],
+            encoding: Encoding::Lossy,
+            status: Some(PExitStatus::from(libc::SIGKILL as u32)),
+            abbreviations: &[],
+        };
+
+        assert_eq!(
+            format!(
+                "CEF:0|copes|copes|{}|exit|Process exited|3|dpid=1234 dproc=game \
+                 cn2Label=signal cn2=9 cs3Label=signalName cs3=SIGKILL",
+                VERSION
+            ),
+            cef_event(&line)
+        );
+        assert_eq!(
+            format!(
+                "LEEF:1.0|copes|copes|{}|exit|cat=exit\tsev=3\tpid=1234\tproc=game\t\
+                 signal=9\tsignalName=SIGKILL",
+                VERSION
+            ),
+            leef_event(&line)
+        );
+    }
+
+    #[test]
+    fn messages_are_escaped() {
+        assert_eq!(
+            format!(
+                "CEF:0|copes|copes|{}|message|Message|1|msg=a\\=b\\\\c",
+                VERSION
+            ),
+            cef_message("a=b\\c")
+        );
+        assert_eq!(
+            format!("LEEF:1.0|copes|copes|{}|message|msg=a\\tb", VERSION),
+            leef_message("a\tb")
+        );
+    }
+}
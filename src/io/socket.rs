@@ -2,10 +2,16 @@
 // Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
 
 //! This module contains a simple `Socket` implementation built around
-//! [`libc::socket`]. `Socket` methods are just direct calls to their ffi
-//! counterparts.
+//! [`libc::socket`]. `Socket` methods are direct calls to their ffi
+//! counterparts, with typed safe methods for the socket configuration.
+//!
+//! With the `rustix` feature, the socket creation, timeout and data transfer
+//! are implemented with [`rustix`] instead. Netlink bindings and socket
+//! filters are not supported by `rustix`, so they are always set up through
+//! `libc`.
 //!
 //! [`libc::socket`]: https://docs.rs/libc/latest/libc/fn.socket.html
+//! [`rustix`]: https://docs.rs/rustix
 
 /// Helper macro to call a libc ffi function.
 /// The resulting value is returned into a [std::io::Result].
@@ -24,12 +30,25 @@ macro_rules! ffi_call {
     };
 }
 
-pub struct Socket(libc::c_int);
+use std::{
+    mem,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd},
+    time::Duration,
+};
+
+/// A socket owning its file descriptor. The file descriptor is closed when
+/// the `Socket` is dropped.
+#[derive(Debug)]
+pub struct Socket(OwnedFd);
 
 impl Socket {
     /// Attempts to create a new `Socket` in a `domain`, with type `ty` using a
     /// specific `protocol`.
     ///
+    /// The socket is closed on `exec`, so it doesn't leak into child
+    /// processes. When `nonblocking` is `true`, the socket operations fail
+    /// with [`std::io::ErrorKind::WouldBlock`] errors instead of blocking.
+    ///
     /// # Errors
     ///
     /// If this function encounters any form of I/O error, an error variant will be
@@ -38,56 +57,148 @@ impl Socket {
         domain: libc::c_int,
         ty: libc::c_int,
         protocol: libc::c_int,
+        nonblocking: bool,
     ) -> std::io::Result<Socket> {
-        // Safety: It's safe to call the ffi function in this context as it
-        // won't produce undefined behaviour on the Rust side upon a failure.
-        unsafe { ffi_call!(socket(domain, ty, protocol)).map(Socket) }
+        #[cfg(not(feature = "rustix"))]
+        {
+            let mut ty = ty | libc::SOCK_CLOEXEC;
+            if nonblocking {
+                ty |= libc::SOCK_NONBLOCK;
+            }
+
+            // Safety: It's safe to call the ffi function in this context as it
+            // won't produce undefined behaviour on the Rust side upon a failure.
+            // The returned file descriptor is open and owned by nobody else.
+            unsafe { ffi_call!(socket(domain, ty, protocol)).map(|fd| Socket::from_raw_fd(fd)) }
+        }
+
+        #[cfg(feature = "rustix")]
+        {
+            use rustix::net::{AddressFamily, Protocol, SocketFlags, SocketType};
+
+            let mut flags = SocketFlags::CLOEXEC;
+            if nonblocking {
+                flags |= SocketFlags::NONBLOCK;
+            }
+            let protocol = u32::try_from(protocol)
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+
+            rustix::net::socket_with(
+                AddressFamily::from_raw(domain as _),
+                SocketType::from_raw(ty as _),
+                flags,
+                std::num::NonZeroU32::new(protocol).map(Protocol::from_raw),
+            )
+            .map(Socket::from)
+            .map_err(std::io::Error::from)
+        }
     }
 
-    /// Assign an `address` with of a specific `length` to the `Socket`.
-    ///
-    /// Calls ffi `bind` on the `Socket` with `address` and `length` as
-    /// arguments.
+    /// Assigns the netlink `address` to the `Socket`.
     ///
-    /// # Safety
+    /// # Errors
     ///
-    /// The caller must ensure that the memory pointed by `address` has been
-    /// allocated and properly initialized and its size is passed as `length`.
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn bind_netlink(&self, address: &libc::sockaddr_nl) -> std::io::Result<()> {
+        // Safety: The pointer refers to an initialized `sockaddr_nl` and its
+        // size is passed along with it.
+        unsafe {
+            ffi_call!(bind(
+                self.as_raw_fd(),
+                address as *const _ as *const libc::sockaddr,
+                mem::size_of_val(address) as libc::socklen_t
+            ))
+            .map(|_| ())
+        }
+    }
+
+    /// Joins the netlink multicast `group`, or leaves it when `member` is
+    /// `false`.
     ///
     /// # Errors
     ///
     /// If this function encounters any form of I/O error, an error variant will be
     /// returned.
-    pub unsafe fn bind(
-        &self,
-        address: *const libc::sockaddr,
-        length: libc::socklen_t,
-    ) -> std::io::Result<()> {
-        ffi_call!(bind(self.0, address, length)).map(|_| ())
+    pub fn set_netlink_membership(&self, group: u32, member: bool) -> std::io::Result<()> {
+        let name = match member {
+            true => libc::NETLINK_ADD_MEMBERSHIP,
+            false => libc::NETLINK_DROP_MEMBERSHIP,
+        };
+        self.set_option(libc::SOL_NETLINK, name, &group)
     }
 
-    /// Manipulates the options of the `Socket`.
-    ///
-    /// Calls ffi `setsockopt` on the `Socket` with `level`, `name`, `value` and
-    /// `length` as arguments.
+    /// Sets the maximum time that receiving data from the `Socket` blocks.
+    /// When no data is received within this time, receiving fails with a
+    /// [`std::io::ErrorKind::WouldBlock`] error.
     ///
-    /// # Safety
+    /// # Errors
     ///
-    /// The caller must ensure that the memory pointed by `value` has been
-    /// allocated and properly initialized and its size is passed as `length`.
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn set_timeout(&self, duration: Duration) -> std::io::Result<()> {
+        #[cfg(not(feature = "rustix"))]
+        {
+            // The fields of `libc::timeval` differ between C libraries and
+            // time bits, like the padding fields of musl on 32 bit targets,
+            // so it's zeroed and only its time fields are set.
+            //
+            // Safety: `libc::timeval` is a C structure, so it's safe to
+            // initialize it with zeros.
+            let mut timeval = unsafe { mem::zeroed::<libc::timeval>() };
+            timeval.tv_sec = duration.as_secs().min(libc::time_t::MAX as u64) as _;
+            timeval.tv_usec = duration.subsec_micros() as _;
+            self.set_option(libc::SOL_SOCKET, libc::SO_RCVTIMEO, &timeval)
+        }
+
+        #[cfg(feature = "rustix")]
+        {
+            use rustix::net::sockopt::{self, Timeout};
+
+            sockopt::set_socket_timeout(self, Timeout::Recv, Some(duration))
+                .map_err(std::io::Error::from)
+        }
+    }
+
+    /// Attaches the classic BPF `filter` program to the `Socket`, so only the
+    /// data accepted by the filter is received.
     ///
     /// # Errors
     ///
     /// If this function encounters any form of I/O error, an error variant will be
     /// returned.
-    pub unsafe fn set_option(
+    pub fn attach_filter(&self, filter: &[libc::sock_filter]) -> std::io::Result<()> {
+        let program = libc::sock_fprog {
+            len: filter
+                .len()
+                .try_into()
+                .map_err(|_| std::io::Error::from(std::io::ErrorKind::InvalidInput))?,
+            // The kernel copies the filter, it's never written through this
+            // pointer.
+            filter: filter.as_ptr() as *mut _,
+        };
+        self.set_option(libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, &program)
+    }
+
+    /// Sets the option `name` at `level` to `value`.
+    fn set_option<T>(
         &self,
         level: libc::c_int,
         name: libc::c_int,
-        value: *const libc::c_void,
-        length: libc::socklen_t,
+        value: &T,
     ) -> std::io::Result<()> {
-        ffi_call!(setsockopt(self.0, level, name, value, length)).map(|_| ())
+        // Safety: The pointer refers to an initialized value and its size is
+        // passed along with it.
+        unsafe {
+            ffi_call!(setsockopt(
+                self.as_raw_fd(),
+                level,
+                name,
+                value as *const T as *const libc::c_void,
+                mem::size_of::<T>() as libc::socklen_t
+            ))
+            .map(|_| ())
+        }
     }
 
     /// Transmit a message through the `Socket`.
@@ -110,10 +221,23 @@ impl Socket {
         length: libc::size_t,
         flags: libc::c_int,
     ) -> std::io::Result<()> {
-        ffi_call!(send(self.0, data, length, flags)).map(|_| ())
+        #[cfg(not(feature = "rustix"))]
+        {
+            ffi_call!(send(self.as_raw_fd(), data, length, flags)).map(|_| ())
+        }
+
+        #[cfg(feature = "rustix")]
+        {
+            use rustix::net::SendFlags;
+
+            let data = std::slice::from_raw_parts(data as *const u8, length);
+            rustix::net::send(self, data, SendFlags::from_bits_retain(flags as u32))
+                .map(|_| ())
+                .map_err(std::io::Error::from)
+        }
     }
 
-    /// Receive a message from the `Socket`.
+    /// Receive a message from the `Socket`, returning its size.
     ///
     /// Calls ffi `recv` on the `Socket` with `buffer`, `length` and `flags` as
     /// arguments.
@@ -132,16 +256,144 @@ impl Socket {
         buffer: *mut libc::c_void,
         length: libc::size_t,
         flags: libc::c_int,
-    ) -> std::io::Result<()> {
-        ffi_call!(recv(self.0, buffer, length, flags)).map(|_| ())
+    ) -> std::io::Result<usize> {
+        #[cfg(not(feature = "rustix"))]
+        {
+            ffi_call!(recv(self.as_raw_fd(), buffer, length, flags)).map(|size| size as usize)
+        }
+
+        #[cfg(feature = "rustix")]
+        {
+            use rustix::net::RecvFlags;
+
+            let buffer = std::slice::from_raw_parts_mut(buffer as *mut u8, length);
+            rustix::net::recv(self, buffer, RecvFlags::from_bits_retain(flags as u32))
+                .map_err(std::io::Error::from)
+        }
     }
 }
 
-impl Drop for Socket {
-    fn drop(&mut self) {
-        // Safety: A Socket instance always have a valid open file descriptor.
-        if let Err(e) = unsafe { ffi_call!(close(self.0)) } {
-            log::error!("An error occur while closing the socket: {}", e);
-        }
+/// Returns a netlink socket address with the given port ID (`pid`) and
+/// multicast `groups`.
+pub fn netlink_address(pid: u32, groups: u32) -> libc::sockaddr_nl {
+    // Safety: `libc::sockaddr_nl` is a C structure, so it's safe to
+    // initialize it with zeros.
+    let mut address = unsafe { mem::zeroed::<libc::sockaddr_nl>() };
+    address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    address.nl_pid = pid;
+    address.nl_groups = groups;
+    address
+}
+
+impl AsFd for Socket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for Socket {
+    fn into_raw_fd(self) -> RawFd {
+        self.0.into_raw_fd()
+    }
+}
+
+impl FromRawFd for Socket {
+    /// Creates a `Socket` from the socket file descriptor `fd`.
+    ///
+    /// # Safety
+    ///
+    /// The file descriptor must be an open socket, and ownership of it is
+    /// transferred to the `Socket`.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Socket(OwnedFd::from_raw_fd(fd))
+    }
+}
+
+impl From<OwnedFd> for Socket {
+    fn from(fd: OwnedFd) -> Self {
+        Socket(fd)
+    }
+}
+
+impl From<Socket> for OwnedFd {
+    fn from(socket: Socket) -> Self {
+        socket.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_file_descriptor_ownership_can_be_transferred() {
+        let socket = Socket::try_new(libc::AF_UNIX, libc::SOCK_DGRAM, 0, false).unwrap();
+        let fd = socket.as_raw_fd();
+        assert_eq!(fd, socket.as_fd().as_raw_fd());
+
+        let raw_fd = socket.into_raw_fd();
+        assert_eq!(fd, raw_fd);
+
+        // Safety: The file descriptor is an open socket released above.
+        let socket = unsafe { Socket::from_raw_fd(raw_fd) };
+        let owned_fd = OwnedFd::from(socket);
+        assert_eq!(fd, owned_fd.as_raw_fd());
+
+        let socket = Socket::from(owned_fd);
+        socket.set_timeout(Duration::from_millis(10)).unwrap();
+        assert_eq!(fd, socket.as_raw_fd());
+    }
+
+    #[test]
+    fn socket_timeout_is_set() {
+        let socket = Socket::try_new(libc::AF_UNIX, libc::SOCK_DGRAM, 0, false).unwrap();
+        socket.set_timeout(Duration::from_millis(1500)).unwrap();
+
+        // Safety: `getsockopt` is called with an open file descriptor, and a
+        // pointer to a value of the given size.
+        let timeval = unsafe {
+            let mut timeval = mem::zeroed::<libc::timeval>();
+            let mut size = mem::size_of::<libc::timeval>() as libc::socklen_t;
+            let result = libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &mut timeval as *mut _ as *mut libc::c_void,
+                &mut size,
+            );
+            assert_eq!(0, result);
+            timeval
+        };
+        assert_eq!(
+            (1, 500_000),
+            (timeval.tv_sec as i64, timeval.tv_usec as i64)
+        );
+    }
+
+    #[test]
+    fn socket_is_closed_on_exec_and_optionally_nonblocking() {
+        // Safety: `fcntl` is called with open file descriptors.
+        let flags = |socket: &Socket| unsafe {
+            (
+                libc::fcntl(socket.as_raw_fd(), libc::F_GETFD),
+                libc::fcntl(socket.as_raw_fd(), libc::F_GETFL),
+            )
+        };
+
+        let socket = Socket::try_new(libc::AF_UNIX, libc::SOCK_DGRAM, 0, false).unwrap();
+        let (fd_flags, status_flags) = flags(&socket);
+        assert_ne!(0, fd_flags & libc::FD_CLOEXEC);
+        assert_eq!(0, status_flags & libc::O_NONBLOCK);
+
+        let socket = Socket::try_new(libc::AF_UNIX, libc::SOCK_DGRAM, 0, true).unwrap();
+        let (fd_flags, status_flags) = flags(&socket);
+        assert_ne!(0, fd_flags & libc::FD_CLOEXEC);
+        assert_ne!(0, status_flags & libc::O_NONBLOCK);
     }
 }
@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Platform independent sources of process events.
+//!
+//! Each platform reports the process events through its own interface, like
+//! the process events connector on Linux or `kqueue` on macOS. A
+//! [`ProcessEventSource`] yields them as the same [`PEventRecord`] events, so
+//! they can be received with an [`EventsReceiver`] on any platform.
+//!
+//! [`EventsReceiver`]: crate::io::receiver::EventsReceiver
+
+use std::time::Duration;
+
+use crate::{io::waker::Waker, solver::PEventRecord, Result};
+
+/// A source of process events.
+pub trait ProcessEventSource {
+    /// Returns an iterator over the received event records.
+    ///
+    /// The iterator yields `None` when no events are received within the
+    /// source timeout, or once its waker is woken up.
+    fn records(&self) -> Box<dyn Iterator<Item = Result<PEventRecord>> + '_>;
+
+    /// Sets the maximum time that the records iterator waits for new events.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the timeout can't be set.
+    fn set_timeout(&self, duration: Duration) -> Result<()>;
+
+    /// Sets the `waker` that interrupts the waits for new events.
+    fn set_waker(&mut self, waker: Waker);
+
+    /// Returns `true` when the waker of the source was woken up.
+    fn is_woken(&self) -> bool;
+
+    /// Stops receiving process events until the source is resumed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the source can't be paused.
+    fn pause(&self) -> Result<()>;
+
+    /// Starts receiving process events again after pausing the source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the source can't be resumed.
+    fn resume(&self) -> Result<()>;
+
+    /// Returns `true` when the source is paused.
+    fn is_paused(&self) -> bool;
+
+    /// Attempts to set up the source again, after a [broken connection]
+    /// error. The source keeps its timeout, waker and paused state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the source can't be set up. The source is left
+    /// unchanged then.
+    ///
+    /// [broken connection]: crate::Error::is_connection_broken
+    fn reconnect(&mut self) -> Result<()>;
+}
@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Utilities to read Steam client files.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::solver::SteamAppId;
+
+/// Attempts to get the name of the Steam application `app_id` from the
+/// application manifests of the Steam libraries of the user with the given
+/// `home` directory.
+///
+/// # Errors
+///
+/// If this function encounters any form of I/O error, an error variant will be
+/// returned.
+pub fn app_name_reader(home: &Path, app_id: SteamAppId) -> io::Result<String> {
+    let manifest = format!("appmanifest_{}.acf", app_id);
+
+    for library in libraries(home) {
+        match fs::read_to_string(library.join("steamapps").join(&manifest)) {
+            Ok(data) => {
+                if let Some(name) = vdf_values(&data, "name").next() {
+                    return Ok(name);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "No Steam application manifest",
+    ))
+}
+
+/// Returns the paths of the Steam libraries of the user with the given `home`
+/// directory.
+fn libraries(home: &Path) -> Vec<PathBuf> {
+    let mut libraries = Vec::new();
+
+    let roots = [
+        home.join(".local/share/Steam"),
+        home.join(".steam/steam"),
+        home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+    ];
+    for root in roots.iter().filter_map(|root| root.canonicalize().ok()) {
+        let folders = fs::read_to_string(root.join("steamapps/libraryfolders.vdf"));
+        let folders = folders
+            .iter()
+            .flat_map(|folders| vdf_values(folders, "path"));
+
+        for library in [root.clone()].into_iter().chain(folders.map(PathBuf::from)) {
+            if !libraries.contains(&library) {
+                libraries.push(library);
+            }
+        }
+    }
+
+    libraries
+}
+
+/// Returns the values of `key` in the Valve data file `data`.
+fn vdf_values<'a>(data: &'a str, key: &'a str) -> impl Iterator<Item = String> + 'a {
+    data.lines().filter_map(move |line| {
+        // Key value lines have the form: "key"    "value"
+        let mut tokens = line.trim().split('"').skip(1).step_by(2);
+        match (tokens.next(), tokens.next()) {
+            (Some(k), Some(value)) if k.eq_ignore_ascii_case(key) => {
+                Some(value.replace("\\\\", "\\"))
+            }
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vdf_values_returns_values_of_key() {
+        let data = r#"
+"AppState"
+{
+	"appid"		"870780"
+	"Universe"		"1"
+	"name"		"Control Ultimate Edition"
+	"StateFlags"		"4"
+}
+"#;
+        assert_eq!(
+            vec!["Control Ultimate Edition".to_string()],
+            vdf_values(data, "name").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn vdf_values_returns_all_values_of_repeated_keys() {
+        let data = r#"
+"libraryfolders"
+{
+	"0"
+	{
+		"path"		"/home/user/.local/share/Steam"
+		"label"		""
+	}
+	"1"
+	{
+		"path"		"/mnt/games/Steam\\Library"
+	}
+}
+"#;
+        assert_eq!(
+            vec![
+                "/home/user/.local/share/Steam".to_string(),
+                "/mnt/games/Steam\\Library".to_string()
+            ],
+            vdf_values(data, "path").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn vdf_values_returns_nothing_for_missing_keys() {
+        let data = r#""AppState" { "appid" "870780" }"#;
+        assert_eq!(0, vdf_values(data, "name").count());
+    }
+}
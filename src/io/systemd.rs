@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Utilities to notify the service manager about the state of the program,
+//! using the systemd [notification protocol].
+//!
+//! [notification protocol]: https://www.freedesktop.org/software/systemd/man/sd_notify.html
+
+use std::{
+    env,
+    ffi::OsStr,
+    io,
+    os::{
+        linux::net::SocketAddrExt,
+        unix::{
+            ffi::OsStrExt,
+            net::{SocketAddr, UnixDatagram},
+        },
+    },
+    process,
+    time::{Duration, Instant},
+};
+
+/// Notifies the service manager about the state of the program.
+#[derive(Debug)]
+pub struct Notifier {
+    socket: UnixDatagram,
+    address: SocketAddr,
+    watchdog: Option<Duration>,
+    last_ping: Option<Instant>,
+}
+
+impl Notifier {
+    /// Attempts to create a `Notifier` from the environment set by the
+    /// service manager. Returns `None` when the program was not started by a
+    /// service manager expecting notifications.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn from_env() -> io::Result<Option<Self>> {
+        let Some(socket_path) = env::var_os("NOTIFY_SOCKET") else {
+            return Ok(None);
+        };
+
+        let watchdog = watchdog_interval(
+            env::var("WATCHDOG_USEC").ok().as_deref(),
+            env::var("WATCHDOG_PID").ok().as_deref(),
+            process::id(),
+        );
+        Notifier::new(&socket_path, watchdog).map(Some)
+    }
+
+    /// Attempts to create a `Notifier` that sends the notifications to the
+    /// socket at `socket_path` and pings the watchdog every `watchdog`
+    /// interval.
+    ///
+    /// Paths starting with `@` are abstract socket addresses.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn new(socket_path: &OsStr, watchdog: Option<Duration>) -> io::Result<Self> {
+        let address = match socket_path.as_bytes() {
+            [b'@', name @ ..] => SocketAddr::from_abstract_name(name)?,
+            _ => SocketAddr::from_pathname(socket_path)?,
+        };
+
+        Ok(Notifier {
+            socket: UnixDatagram::unbound()?,
+            address,
+            watchdog,
+            last_ping: None,
+        })
+    }
+
+    /// Attempts to send the `state` notification, as one or more
+    /// `VARIABLE=value` lines.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        self.socket
+            .send_to_addr(state.as_bytes(), &self.address)
+            .map(|_| ())
+    }
+
+    /// Attempts to notify that the program finished its startup.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn ready(&self) -> io::Result<()> {
+        self.notify(&format!("READY=1\nMAINPID={}", process::id()))
+    }
+
+    /// Attempts to notify that the program is stopping.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn stopping(&self) -> io::Result<()> {
+        self.notify("STOPPING=1")
+    }
+
+    /// Attempts to ping the service manager watchdog, when it's enabled.
+    ///
+    /// The watchdog is pinged at half of its interval, so calling this
+    /// function more often is fine.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant will be
+    /// returned.
+    pub fn watchdog(&mut self) -> io::Result<()> {
+        let Some(interval) = self.watchdog else {
+            return Ok(());
+        };
+
+        if self
+            .last_ping
+            .map_or(true, |ping| ping.elapsed() >= interval / 2)
+        {
+            self.notify("WATCHDOG=1")?;
+            self.last_ping = Some(Instant::now());
+        }
+
+        Ok(())
+    }
+}
+
+fn watchdog_interval(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    // The watchdog is meant for another process
+    if pid.is_some_and(|pid| pid.parse::<u32>().ok() != Some(own_pid)) {
+        return None;
+    }
+
+    usec?
+        .parse::<u64>()
+        .ok()
+        .filter(|usec| *usec > 0)
+        .map(Duration::from_micros)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+
+    fn socket_path(test: &str) -> PathBuf {
+        env::temp_dir().join(format!("copes-{}-{}.sock", test, process::id()))
+    }
+
+    #[test]
+    fn notifier_sends_notifications_to_the_socket() {
+        let path = socket_path("notify");
+        let _ = fs::remove_file(&path);
+        let receiver = UnixDatagram::bind(&path).unwrap();
+
+        let mut notifier = Notifier::new(path.as_os_str(), Some(Duration::from_secs(10))).unwrap();
+        notifier.ready().unwrap();
+        notifier.watchdog().unwrap();
+        notifier.watchdog().unwrap();
+        notifier.stopping().unwrap();
+
+        let mut buffer = [0u8; 64];
+        let mut receive = || {
+            let size = receiver.recv(&mut buffer).unwrap();
+            String::from_utf8_lossy(&buffer[..size]).to_string()
+        };
+        assert_eq!(format!("READY=1\nMAINPID={}", process::id()), receive());
+        // The second watchdog ping is skipped as the interval didn't elapse
+        assert_eq!("WATCHDOG=1", receive());
+        assert_eq!("STOPPING=1", receive());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn watchdog_interval_returns_interval_for_this_process() {
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            watchdog_interval(Some("30000000"), None, 100)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            watchdog_interval(Some("30000000"), Some("100"), 100)
+        );
+    }
+
+    #[test]
+    fn watchdog_interval_returns_none_when_disabled_or_for_other_processes() {
+        assert_eq!(None, watchdog_interval(None, None, 100));
+        assert_eq!(None, watchdog_interval(Some("0"), None, 100));
+        assert_eq!(None, watchdog_interval(Some("30000000"), Some("200"), 100));
+    }
+}
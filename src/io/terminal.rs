@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Interactive commands typed on the terminal.
+//!
+//! While the standard input is a terminal, it's switched to non-canonical
+//! mode without echo, so single keystrokes are read at once by a worker
+//! thread and turned into control [`Command`]s:
+//!
+//! | Key | Command |
+//! |-----|---------|
+//! | `p` | Pause or resume monitoring |
+//! | `f` | Edit the filter expression |
+//! | `s` | Print the statistics |
+//! | `c` | Toggle the display of the command lines |
+//! | `?` | Print the keys help |
+//!
+//! Keystrokes are only read while the process is on the foreground process
+//! group of the terminal, as changing the terminal settings from the
+//! background stops it, like on `copes &`.
+//!
+//! The terminal settings are restored when the [`TerminalInput`] is dropped,
+//! or when the process exits without dropping it, like on
+//! [`std::process::exit`]. Signals are still generated by the terminal, so
+//! `Ctrl+C` still works.
+
+use std::{
+    fmt,
+    io::{self, BufRead, Read, Write},
+    mem,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex, MutexGuard, Once,
+    },
+    thread,
+};
+
+use super::control::Command;
+
+/// Help of the interactive keys.
+pub const KEYS_HELP: &str = "Keys: p pause/resume, f edit filter, s statistics, \
+                             c toggle command lines, ? help";
+
+/// Prompt of the filter expression edition.
+const FILTER_PROMPT: &str = "Filter expression (empty to remove it): ";
+
+/// Terminal settings to restore when the process exits, while a
+/// [`TerminalInput`] changes them.
+static SAVED_SETTINGS: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+/// Commands typed on the terminal. The terminal settings are restored when
+/// it's dropped.
+pub struct TerminalInput {
+    settings: libc::termios,
+    commands: Receiver<Command>,
+}
+
+impl TerminalInput {
+    /// Starts reading the keystrokes typed on the standard input when it's a
+    /// terminal and the process is on its foreground process group, or
+    /// returns `None` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    pub fn spawn() -> io::Result<Option<Self>> {
+        // Safety: It's safe to call the ffi functions with any file
+        // descriptor, and getpgrp always succeeds.
+        let foreground = unsafe {
+            libc::isatty(libc::STDIN_FILENO) == 1
+                && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+        };
+        if !foreground {
+            return Ok(None);
+        }
+
+        let settings = terminal_settings()?;
+        save_settings(settings);
+        if let Err(e) = set_terminal_settings(&keystrokes_settings(settings)) {
+            restore_saved_settings();
+            return Err(e);
+        }
+        let (sender, commands) = mpsc::channel();
+        if let Err(e) = thread::Builder::new()
+            .name("terminal".to_string())
+            .spawn(move || read_keys(settings, sender))
+        {
+            restore_saved_settings();
+            return Err(e);
+        }
+
+        Ok(Some(TerminalInput { settings, commands }))
+    }
+
+    /// Returns the commands typed since the last call, without waiting.
+    pub fn try_commands(&self) -> Vec<Command> {
+        self.commands.try_iter().collect()
+    }
+}
+
+// The terminal settings only implement `Debug` with the `extra_traits`
+// feature of `libc`.
+impl fmt::Debug for TerminalInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TerminalInput").finish_non_exhaustive()
+    }
+}
+
+impl Drop for TerminalInput {
+    fn drop(&mut self) {
+        lock_saved_settings().take();
+        if let Err(e) = set_terminal_settings(&self.settings) {
+            log::error!("Couldn't restore the terminal settings: {}", e);
+        }
+    }
+}
+
+/// Saves the terminal `settings` to restore them when the process exits.
+fn save_settings(settings: libc::termios) {
+    static AT_EXIT: Once = Once::new();
+    // Safety: The registered function is safe to call at any time.
+    AT_EXIT.call_once(|| unsafe {
+        libc::atexit(restore_saved_settings_at_exit);
+    });
+    *lock_saved_settings() = Some(settings);
+}
+
+/// Restores the saved terminal settings, if any.
+fn restore_saved_settings() {
+    if let Some(settings) = lock_saved_settings().take() {
+        if let Err(e) = set_terminal_settings(&settings) {
+            log::error!("Couldn't restore the terminal settings: {}", e);
+        }
+    }
+}
+
+extern "C" fn restore_saved_settings_at_exit() {
+    restore_saved_settings();
+}
+
+fn lock_saved_settings() -> MutexGuard<'static, Option<libc::termios>> {
+    // The settings are still valid when a thread panicked holding the lock
+    SAVED_SETTINGS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Returns the command of a `key`, or `None` for the keys without commands.
+/// The filter expression edition is handled separately.
+fn key_command(key: u8) -> Option<Command> {
+    match key {
+        b'p' => Some(Command::TogglePause),
+        b's' => Some(Command::Stats),
+        b'c' => Some(Command::ToggleCmdline),
+        _ => None,
+    }
+}
+
+/// Returns the command that sets the filter expression typed on `line`,
+/// removing it when the line is empty.
+fn filter_command(line: &str) -> Command {
+    match line.trim() {
+        "" => Command::ClearFilters,
+        expression => Command::SetFilter(expression.to_string()),
+    }
+}
+
+/// Reads the keystrokes typed on the standard input, and sends their commands
+/// until the input ends or the commands are no longer taken. The terminal
+/// `settings` are restored while a filter expression is edited.
+fn read_keys(settings: libc::termios, commands: Sender<Command>) {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let mut key = [0];
+    loop {
+        match stdin.read(&mut key) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                log::error!("Couldn't read the terminal input: {}", e);
+                break;
+            }
+        }
+
+        let command = match key[0] {
+            b'f' => match read_filter(&mut stdin, &settings) {
+                Ok(command) => command,
+                Err(e) => {
+                    log::error!("Couldn't read the filter expression: {}", e);
+                    continue;
+                }
+            },
+            b'?' => {
+                eprintln!("{}", KEYS_HELP);
+                continue;
+            }
+            key => match key_command(key) {
+                Some(command) => command,
+                None => continue,
+            },
+        };
+        if commands.send(command).is_err() {
+            break;
+        }
+    }
+}
+
+/// Prompts for a filter expression, read on canonical mode with echo.
+fn read_filter(stdin: &mut impl BufRead, settings: &libc::termios) -> io::Result<Command> {
+    set_terminal_settings(settings)?;
+    let mut stderr = io::stderr();
+    let line = write!(stderr, "{}", FILTER_PROMPT)
+        .and_then(|_| stderr.flush())
+        .and_then(|_| {
+            let mut line = String::new();
+            stdin.read_line(&mut line).map(|_| line)
+        });
+    set_terminal_settings(&keystrokes_settings(*settings))?;
+
+    line.map(|line| filter_command(&line))
+}
+
+/// Returns the terminal `settings` to read single keystrokes, without echo.
+fn keystrokes_settings(settings: libc::termios) -> libc::termios {
+    let mut keystrokes = settings;
+    keystrokes.c_lflag &= !(libc::ICANON | libc::ECHO);
+    keystrokes.c_cc[libc::VMIN] = 1;
+    keystrokes.c_cc[libc::VTIME] = 0;
+    keystrokes
+}
+
+fn terminal_settings() -> io::Result<libc::termios> {
+    // Safety: `libc::termios` is a C structure, so it's safe to initialize it
+    // to zero. Calling the ffi function with a pointer to it is safe.
+    unsafe {
+        let mut settings = mem::zeroed();
+        match libc::tcgetattr(libc::STDIN_FILENO, &mut settings) {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(settings),
+        }
+    }
+}
+
+fn set_terminal_settings(settings: &libc::termios) -> io::Result<()> {
+    // Safety: Calling the ffi function with a pointer to initialized settings
+    // is safe.
+    match unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, settings) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_turned_into_commands() {
+        assert_eq!(Some(Command::TogglePause), key_command(b'p'));
+        assert_eq!(Some(Command::Stats), key_command(b's'));
+        assert_eq!(Some(Command::ToggleCmdline), key_command(b'c'));
+        assert_eq!(None, key_command(b'x'));
+        assert_eq!(None, key_command(b'\n'));
+    }
+
+    #[test]
+    fn typed_filter_expressions_replace_the_filters() {
+        assert_eq!(
+            Command::SetFilter("exe == \"steam\"".to_string()),
+            filter_command(" exe == \"steam\"\n")
+        );
+        assert_eq!(Command::ClearFilters, filter_command("\n"));
+        assert_eq!(Command::ClearFilters, filter_command(""));
+    }
+
+    #[test]
+    fn keystrokes_settings_disable_canonical_mode_and_echo() {
+        // Safety: `libc::termios` is a C structure, so it's safe to
+        // initialize it to zero.
+        let mut settings: libc::termios = unsafe { mem::zeroed() };
+        settings.c_lflag = libc::ICANON | libc::ECHO | libc::ISIG;
+
+        let keystrokes = keystrokes_settings(settings);
+        assert_eq!(libc::ISIG, keystrokes.c_lflag);
+        assert_eq!(1, keystrokes.c_cc[libc::VMIN]);
+        assert_eq!(0, keystrokes.c_cc[libc::VTIME]);
+    }
+}
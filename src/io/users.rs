@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Utilities to read the user accounts information.
+
+use std::{ffi::CStr, mem, ptr};
+
+use crate::solver::PUid;
+
+/// Initial size of the buffer for the strings of a user account entry.
+const ENTRY_BUFFER_SIZE: usize = 1024;
+
+/// Maximum size of the buffer for the strings of a user account entry.
+const MAX_ENTRY_BUFFER_SIZE: usize = 1 << 20;
+
+/// Returns the name of the user with the given `uid`, if it has an account.
+///
+/// Accounts are looked up through the system name service, so users from
+/// network directories are also found.
+pub fn user_name(uid: PUid) -> Option<String> {
+    let mut buffer = vec![0u8; ENTRY_BUFFER_SIZE];
+    loop {
+        // Safety: `libc::passwd` is a C structure, so it's safe to initialize
+        // it to zero.
+        let mut entry: libc::passwd = unsafe { mem::zeroed() };
+        let mut result = ptr::null_mut();
+        // Safety: Calling the ffi function is safe as every pointer points to
+        // initialized data, and the buffer length is the given one. The
+        // returned strings point to the buffer, which outlives them.
+        let error = unsafe {
+            libc::getpwuid_r(
+                *uid.as_ref(),
+                &mut entry,
+                buffer.as_mut_ptr() as *mut libc::c_char,
+                buffer.len(),
+                &mut result,
+            )
+        };
+
+        match error {
+            libc::ERANGE if buffer.len() < MAX_ENTRY_BUFFER_SIZE => {
+                buffer.resize(buffer.len() * 2, 0);
+            }
+            0 if !result.is_null() && !entry.pw_name.is_null() => {
+                // Safety: The name is a null terminated string on the buffer.
+                let name = unsafe { CStr::from_ptr(entry.pw_name) };
+                return Some(name.to_string_lossy().into_owned());
+            }
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_name_returns_the_account_names() {
+        assert_eq!(Some("root".to_string()), user_name(PUid::from(0)));
+        assert_eq!(None, user_name(PUid::from(u32::MAX - 1)));
+    }
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Wakers to interrupt the waits for process events at once.
+//!
+//! A [`Waker`] is an `eventfd` polled along with the connector socket, so
+//! waking it up, for example from a termination signal handler, interrupts
+//! the wait without waiting for the connector timeout. Other platforms
+//! don't have `eventfd`, so a pipe is used there instead. On Windows, the
+//! sources check whether the waker was woken up while they wait.
+//!
+//! `eventfd` and `poll` are not socket system calls, so they are always
+//! called through `libc`, even with the `rustix` feature.
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+#[cfg(unix)]
+use std::{
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    time::Duration,
+};
+
+/// Wakes up the waits for process events. Once woken up, it stays woken up.
+///
+/// Clones share the same `eventfd`, so they can be moved to other threads.
+#[derive(Debug, Clone)]
+pub struct Waker(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    /// File descriptor that becomes readable once woken up.
+    #[cfg(unix)]
+    fd: OwnedFd,
+    /// Write end of the pipe, on platforms without `eventfd`.
+    #[cfg(all(unix, not(target_os = "linux")))]
+    write_fd: OwnedFd,
+    woken: AtomicBool,
+}
+
+/// Why [`wait_readable`] returned.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+    /// The file descriptor has data to read.
+    Readable,
+    /// The waker was woken up.
+    Woken,
+    /// The timeout elapsed.
+    TimedOut,
+}
+
+impl Waker {
+    /// Attempts to create a new `Waker`.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    #[cfg(target_os = "linux")]
+    pub fn new() -> io::Result<Self> {
+        // Safety: It's safe to call the ffi function in this context as it
+        // won't produce undefined behaviour on the Rust side upon a failure.
+        // The returned file descriptor is open and owned by nobody else.
+        let fd = unsafe {
+            match libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) {
+                -1 => return Err(io::Error::last_os_error()),
+                fd => OwnedFd::from_raw_fd(fd),
+            }
+        };
+
+        Ok(Waker(Arc::new(Inner {
+            fd,
+            woken: AtomicBool::new(false),
+        })))
+    }
+
+    /// Attempts to create a new `Waker`.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    #[cfg(all(unix, not(target_os = "linux")))]
+    pub fn new() -> io::Result<Self> {
+        let mut fds = [0; 2];
+        // Safety: Calling the ffi function with a pointer to fds is safe as it
+        // points to an array of two file descriptors. The returned file
+        // descriptors are open and owned by nobody else.
+        let (fd, write_fd) = unsafe {
+            if libc::pipe(fds.as_mut_ptr()) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1]))
+        };
+        for fd in [&fd, &write_fd] {
+            set_cloexec_nonblock(fd.as_raw_fd())?;
+        }
+
+        Ok(Waker(Arc::new(Inner {
+            fd,
+            write_fd,
+            woken: AtomicBool::new(false),
+        })))
+    }
+
+    /// Attempts to create a new `Waker`.
+    ///
+    /// # Errors
+    ///
+    /// Never fails on Windows, as it's only a flag there.
+    #[cfg(windows)]
+    pub fn new() -> io::Result<Self> {
+        Ok(Waker(Arc::new(Inner {
+            woken: AtomicBool::new(false),
+        })))
+    }
+
+    /// Wakes up the waits for process events, now and from now on.
+    ///
+    /// # Errors
+    ///
+    /// If this function encounters any form of I/O error, an error variant
+    /// will be returned.
+    pub fn wake(&self) -> io::Result<()> {
+        self.0.woken.store(true, Ordering::Relaxed);
+
+        #[cfg(unix)]
+        self.notify()?;
+
+        Ok(())
+    }
+
+    /// Makes the waker file descriptor readable.
+    #[cfg(unix)]
+    fn notify(&self) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        let (fd, value) = (&self.0.fd, 1u64.to_ne_bytes());
+        #[cfg(all(unix, not(target_os = "linux")))]
+        let (fd, value) = (&self.0.write_fd, [1u8]);
+        // Safety: Calling the ffi function with a pointer to value is safe as
+        // it points to an initialized buffer of the given size.
+        let result =
+            unsafe { libc::write(fd.as_raw_fd(), value.as_ptr() as *const _, value.len()) };
+        match result {
+            -1 => match io::Error::last_os_error() {
+                // The counter or the pipe is full, so it's already readable.
+                error if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+                error => Err(error),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns `true` when the waker was woken up.
+    pub fn is_woken(&self) -> bool {
+        self.0.woken.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(unix)]
+impl AsFd for Waker {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.fd.as_fd()
+    }
+}
+
+/// Sets the close-on-exec and non-blocking flags of `fd`.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn set_cloexec_nonblock(fd: libc::c_int) -> io::Result<()> {
+    // Safety: It's safe to call the ffi functions in this context as they
+    // won't produce undefined behaviour on the Rust side upon a failure.
+    unsafe {
+        if libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Waits up to `timeout` until `fd` has data to read or the `waker` is woken
+/// up. A zero `timeout` waits forever, like socket timeouts do.
+///
+/// # Errors
+///
+/// If this function encounters any form of I/O error, an error variant will
+/// be returned.
+#[cfg(unix)]
+pub fn wait_readable(fd: BorrowedFd, waker: &Waker, timeout: Duration) -> io::Result<Readiness> {
+    if waker.is_woken() {
+        return Ok(Readiness::Woken);
+    }
+
+    let mut fds = [fd.as_raw_fd(), waker.as_fd().as_raw_fd()].map(|fd| libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    });
+    let timeout = match timeout {
+        Duration::ZERO => -1,
+        // Round up, so short timeouts don't become busy waits.
+        timeout => timeout
+            .as_nanos()
+            .div_ceil(1_000_000)
+            .min(libc::c_int::MAX as u128) as libc::c_int,
+    };
+
+    // Safety: Calling the ffi function with a pointer to fds is safe as it
+    // points to an initialized array of the given length.
+    match unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ if fds[1].revents != 0 => Ok(Readiness::Woken),
+        // Errors are readable, so they are returned when reading.
+        _ if fds[0].revents != 0 => Ok(Readiness::Readable),
+        _ => Ok(Readiness::TimedOut),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{os::unix::net::UnixDatagram, thread, time::Instant};
+
+    use super::*;
+
+    #[test]
+    fn wait_readable_reports_readable_file_descriptors() {
+        let (sender, receiver) = UnixDatagram::pair().unwrap();
+        let waker = Waker::new().unwrap();
+        let timeout = Duration::from_millis(1);
+        assert_eq!(
+            Readiness::TimedOut,
+            wait_readable(receiver.as_fd(), &waker, timeout).unwrap()
+        );
+
+        sender.send(b"event").unwrap();
+        assert_eq!(
+            Readiness::Readable,
+            wait_readable(receiver.as_fd(), &waker, timeout).unwrap()
+        );
+    }
+
+    #[test]
+    fn waker_interrupts_waits_at_once() {
+        let (_sender, receiver) = UnixDatagram::pair().unwrap();
+        let waker = Waker::new().unwrap();
+        assert!(!waker.is_woken());
+
+        let thread_waker = waker.clone();
+        let waker_thread = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            thread_waker.wake().unwrap();
+        });
+
+        let start = Instant::now();
+        assert_eq!(
+            Readiness::Woken,
+            wait_readable(receiver.as_fd(), &waker, Duration::from_secs(10)).unwrap()
+        );
+        assert!(start.elapsed() < Duration::from_secs(5));
+        waker_thread.join().unwrap();
+
+        // It stays woken up
+        assert!(waker.is_woken());
+        waker.wake().unwrap();
+        assert_eq!(
+            Readiness::Woken,
+            wait_readable(receiver.as_fd(), &waker, Duration::from_secs(10)).unwrap()
+        );
+    }
+}
@@ -1,6 +1,11 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
 
+pub mod error;
+pub mod filter;
 #[allow(dead_code)]
 pub mod io;
+pub mod report;
 pub mod solver;
+
+pub use error::{Error, Result};
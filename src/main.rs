@@ -1,167 +1,3378 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
 
-use anyhow::{Context, Result};
+// The program monitors the process events through the process events
+// connector and reads the process information from `/proc`. Elsewhere, only
+// the library is available.
+#[cfg(not(target_os = "linux"))]
+compile_error!("The copes program only runs on Linux. Build the library with `cargo build --lib`.");
+
+use anyhow::{anyhow, Context, Result};
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use copes::{
-    io::{self, connector::ProcessEventsConnector},
-    solver::{self, ExecutedFileName, PEvent, PID},
+    filter::{self, EventKind, Field, Filter},
+    io::{
+        self,
+        clock::{Clock, ClockInstants, TimeFormat, DEFAULT_UTC_TIME_FORMAT},
+        connector::{MonitoredEvents, ProcessEventsConnector},
+        control::{self, Command as ControlCommand, ControlSocket, Reply},
+        daemon::PidFile,
+        exe_list::ExeList,
+        hash::{Digest, ExeHasher, Hash, JobId},
+        proc::{CachedProcess, ProcCache, ProcFs},
+        receiver::{BackpressurePolicy, EventsReceiver},
+        rotating_file::{RotatingFile, RotationPolicy},
+        signal::SignalFlag,
+        sink::{
+            self as sink, ColorScheme, EventLine, ExitOutcome, FlushPolicy, Format, Severity, Sink,
+            Sinks, SocketSink, StreamSink,
+        },
+        terminal::TerminalInput,
+        waker::Waker,
+    },
+    report::{
+        histogram::LIFETIME_BOUNDS, ExecChain, ExecChains, PeakRssSampler, PendingLines,
+        RateLimiter, Summary,
+    },
+    solver::{
+        self, CmdLineDisplay, Encoding, ExecutedFileName, PCmdLine, PEvent, PEventRecord, PExe,
+        PExePath, PExitStatus, PStat, PUid, Quoting, Resolvers, WineExecutables, PID,
+    },
 };
-use core::fmt;
+use core::fmt::{self, Write as _};
+use log::LevelFilter;
 use std::{
     collections::HashMap,
-    io::Write,
-    sync::{self, atomic},
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+use termcolor::{BufferedStandardStream, ColorChoice, ColorSpec, NoColor, StandardStream};
+
+#[cfg(feature = "systemd")]
+use copes::io::sink::JournalSink;
+#[cfg(feature = "json")]
+use copes::io::sink::JSON_SCHEMA;
+#[cfg(feature = "otlp")]
+use copes::{
+    io::otlp::{self, OtlpEndpoint, OtlpExporter},
+    report::{SpanExec, SpanTracker},
 };
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+#[cfg(feature = "otlp")]
+use std::time::SystemTime;
 
 const ARG_CMDLINE_NAME: &str = "cmdline";
+const ARG_CMDLINEMAXLEN_NAME: &str = "cmdlinemaxlen";
+const ARG_CMDLINEMAXARGS_NAME: &str = "cmdlinemaxargs";
+const ARG_LOSSLESS_NAME: &str = "lossless";
 const ARG_NOCOLOR_NAME: &str = "nocolor";
+const ARG_COLORS_NAME: &str = "colors";
+const ARG_EXITCOLOR_NAME: &str = "exitcolor";
+const ARG_KERNELTHREADS_NAME: &str = "kernelthreads";
+const ARG_OWNPROCESSES_NAME: &str = "ownprocesses";
+const ARG_HELPERS_NAME: &str = "helpers";
+const ARG_STEAM_NAME: &str = "steam";
+const ARG_WINE_NAME: &str = "wine";
+const ARG_ENV_NAME: &str = "env";
+const ARG_NODEFAULTWINE_NAME: &str = "nodefaultwine";
+const ARG_HIDEWINESERVICES_NAME: &str = "hidewineservices";
+const ARG_SHOWWINESERVICES_NAME: &str = "showwineservices";
+const ARG_NSPID_NAME: &str = "nspid";
+const ARG_LOGIN_NAME: &str = "login";
+const ARG_TTY_NAME: &str = "tty";
+const ARG_SCHED_NAME: &str = "sched";
+const ARG_SECURITY_LABEL_NAME: &str = "security-label";
+const ARG_CAPS_NAME: &str = "caps";
+const ARG_CAPS_NON_ROOT_NAME: &str = "caps-non-root";
+const ARG_SETID_NAME: &str = "setid";
+const ARG_SECURITY_NAME: &str = "security";
+const ARG_HASH_NAME: &str = "hash";
+const ARG_ALLOWLIST_NAME: &str = "allowlist";
+const ARG_BLOCKLIST_NAME: &str = "blocklist";
+const ARG_VIOLATIONSIGNAL_NAME: &str = "violation-signal";
+const ARG_VIOLATIONCOMMAND_NAME: &str = "violation-command";
+#[cfg(feature = "otlp")]
+const ARG_OTLP_NAME: &str = "otlp";
+const ARG_EXITSTATS_NAME: &str = "exitstats";
+const ARG_RAWNUMBERS_NAME: &str = "rawnumbers";
+const ARG_TIMESTAMPS_NAME: &str = "timestamps";
+const ARG_CLOCK_NAME: &str = "clock";
+const ARG_UTC_NAME: &str = "utc";
+const ARG_TIMEFORMAT_NAME: &str = "timeformat";
+const ARG_PROCROOT_NAME: &str = "procroot";
+const ARG_COALESCE_NAME: &str = "coalesce";
+const ARG_COALESCEWINDOW_NAME: &str = "coalescewindow";
+const ARG_SUMMARYUSERS_NAME: &str = "summaryusers";
+const ARG_SUMMARY_NAME: &str = "summary";
+const ARG_SUMMARYTOP_NAME: &str = "summarytop";
+const ARG_HISTOGRAM_NAME: &str = "histogram";
+const ARG_HISTOGRAMINTERVAL_NAME: &str = "histograminterval";
+const ARG_RATELIMIT_NAME: &str = "ratelimit";
+const ARG_NOEVENTS_NAME: &str = "noevents";
+const ARG_VERBOSE_NAME: &str = "verbose";
+const ARG_QUIET_NAME: &str = "quiet";
+const ARG_LOGFILE_NAME: &str = "logfile";
+const ARG_OUTPUT_NAME: &str = "output";
+const ARG_FLUSH_NAME: &str = "flush";
+const ARG_LOGROTATESIZE_NAME: &str = "logrotatesize";
+const ARG_LOGROTATEINTERVAL_NAME: &str = "logrotateinterval";
+const ARG_LOGKEEP_NAME: &str = "logkeep";
+const ARG_LOGCOMPRESS_NAME: &str = "logcompress";
+const ARG_DURATION_NAME: &str = "duration";
+const ARG_COUNT_NAME: &str = "count";
+const ARG_DAEMON_NAME: &str = "daemon";
+const ARG_PIDFILE_NAME: &str = "pidfile";
+const ARG_DAEMONLOG_NAME: &str = "daemonlog";
+const ARG_CONFIG_NAME: &str = "config";
+const ARG_FILTER_NAME: &str = "filter";
+const ARG_UNDER_NAME: &str = "under";
+const ARG_EVENTS_NAME: &str = "events";
+const ARG_RAW_NAME: &str = "raw";
+const ARG_QUEUESIZE_NAME: &str = "queuesize";
+const ARG_QUEUEPOLICY_NAME: &str = "queuepolicy";
+const ARG_CONTROL_NAME: &str = "control";
 
-fn main() -> Result<()> {
-    simple_logger::init_with_env().context("Couldn't setup logger")?;
+const CMD_WAIT_NAME: &str = "wait";
+const ARG_WAITEXEC_NAME: &str = "exec";
+const ARG_UNTILEXIT_NAME: &str = "untilexit";
+const ARG_TIMEOUT_NAME: &str = "timeout";
+const CMD_WAITEXIT_NAME: &str = "wait-exit";
+const ARG_PID_NAME: &str = "pid";
+const ARG_NSOF_NAME: &str = "nsof";
+const CMD_DOCTOR_NAME: &str = "doctor";
+const CMD_CTL_NAME: &str = "ctl";
+const ARG_CTLCOMMAND_NAME: &str = "command";
+#[cfg(feature = "json")]
+const CMD_SCHEMA_NAME: &str = "schema";
 
-    let args = cmdline_args();
-    let stop = sync::Arc::new(atomic::AtomicBool::new(false));
+const DEFAULT_CONFIG_PATH: &str = "/etc/copes.conf";
+const DEFAULT_PID_FILE_PATH: &str = "/run/copes.pid";
+const DEFAULT_CONTROL_PATH: &str = "/run/copes.ctl";
 
-    let stop_handle = stop.clone();
-    ctrlc::set_handler(move || {
-        stop_handle.store(true, atomic::Ordering::Relaxed);
-    })
-    .context("Couldn't set Ctrl-C handler")?;
+/// Environment variable with the color scheme, when not set with `--colors`.
+const COLORS_ENV_VAR: &str = "COPES_COLORS";
+
+/// Shown instead of the executed file name of processes that exit before
+/// their information can be read.
+const GONE_MARKER: &str = "<gone>";
+
+/// Event kind and executed file name of the lines suppressed by the output
+/// rate limit.
+type SuppressedLineKey = (&'static str, ExecutedFileName);
+
+/// Maximum time waiting for process events before checking whether a
+/// deadline (like the run duration) was reached.
+const DEADLINE_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Maximum time waiting for process events before doing periodic tasks, like
+/// pinging the service manager watchdog.
+const EVENTS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Maximum time waiting for process events while executables are being
+/// hashed, so the lines waiting for their digests are printed soon.
+const HASH_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Maximum time waiting for the pending executable digests when quitting.
+const HASH_STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Maximum size of the hashed executables, in bytes.
+const HASH_MAX_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Maximum time to wait for process events before checking the control
+/// socket requests and the keystrokes typed on the terminal.
+const CONTROL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Interval between the peak memory usage samples of the running processes.
+const PEAK_RSS_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
 
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
-    let mut line_color = ColorSpec::new();
+/// Width of the bars of the lifetime histograms.
+const HISTOGRAM_BAR_WIDTH: u64 = 40;
 
+fn main() -> Result<ExitCode> {
+    let args = cmdline_args()?;
+
+    simple_logger::SimpleLogger::new()
+        .with_level(log_level(&args))
+        .env()
+        .init()
+        .context("Couldn't setup logger")?;
+
+    match args.subcommand() {
+        Some((CMD_WAIT_NAME, wait_args)) => wait(wait_args),
+        Some((CMD_WAITEXIT_NAME, wait_exit_args)) => wait_exit(wait_exit_args),
+        Some((CMD_DOCTOR_NAME, doctor_args)) => doctor(doctor_args),
+        Some((CMD_CTL_NAME, ctl_args)) => ctl(ctl_args),
+        #[cfg(feature = "json")]
+        Some((CMD_SCHEMA_NAME, _)) => {
+            print!("{}", JSON_SCHEMA);
+            Ok(ExitCode::SUCCESS)
+        }
+        _ if args.get_flag(ARG_RAW_NAME) => dump_raw_messages(&args).map(|_| ExitCode::SUCCESS),
+        _ => monitor(&args).map(|_| ExitCode::SUCCESS),
+    }
+}
+
+fn monitor(args: &ArgMatches) -> Result<()> {
+    let mut args = args.clone();
+    let mut output = create_output(&args)?;
+
+    let proc = ProcFs::new(args.get_one::<PathBuf>(ARG_PROCROOT_NAME).unwrap());
+    let mut resolvers = Resolvers::with_builtin(wine_executables(&args));
     let mut process_registry = HashMap::new();
-    let data_source = create_events_source()?;
-    let mut event = data_source.into_iter();
+    let mut proc_cache = ProcCache::new();
+    let mut exec_chains = exec_chains(&args);
+    let histogram_interval = args
+        .get_one::<u64>(ARG_HISTOGRAMINTERVAL_NAME)
+        .map(|secs| Duration::from_secs(*secs));
+    let histograms = args.get_flag(ARG_HISTOGRAM_NAME) || histogram_interval.is_some();
+    // Also kept without the summary options, for the statistics dumps.
+    let mut summary = Summary::new();
+    let mut counters = EventCounters::default();
+    let clock = ClockInstants::new(*args.get_one::<Clock>(ARG_CLOCK_NAME).unwrap());
+    let start = clock.now();
+    let mut last_histograms = Instant::now();
+    let mut rate_limiter = args
+        .get_one::<u32>(ARG_RATELIMIT_NAME)
+        .map(|limit| RateLimiter::new(*limit, Duration::from_secs(1), Instant::now()));
+    let mut peak_rss_sampler = args
+        .get_flag(ARG_EXITSTATS_NAME)
+        .then(|| PeakRssSampler::new(PEAK_RSS_SAMPLE_INTERVAL));
+    let mut exe_lists = read_exe_lists(&args)?;
+    let mut pending_lines = PendingLines::new();
+    let mut data_source = create_events_source(monitored_events(&args))?;
+
+    // The daemon runs on the root directory, so the configuration file path
+    // must be absolute to reload it.
+    let reload_config = match args.get_flag(ARG_DAEMON_NAME) {
+        true => config_path(&std::env::args_os().collect::<Vec<_>>())
+            .map(|path| std::env::current_dir().map(|dir| dir.join(path)))
+            .transpose()
+            .context("Couldn't get the current directory")?,
+        false => None,
+    };
+
+    // The daemon runs on the root directory too, and the socket file is
+    // removed on quit.
+    let mut control = args
+        .get_one::<PathBuf>(ARG_CONTROL_NAME)
+        .map(|path| {
+            let path = std::env::current_dir()
+                .context("Couldn't get the current directory")?
+                .join(path);
+            ControlSocket::bind(&path)
+                .with_context(|| format!("Couldn't create control socket {}", path.display()))
+        })
+        .transpose()?;
+    let mut control_settings = ControlSettings::default();
+
+    // The daemon is started once everything is set up, so setup errors are
+    // still reported on the terminal.
+    let _pid_file = match args.get_flag(ARG_DAEMON_NAME) {
+        true => Some(start_daemon(&args)?),
+        false => None,
+    };
+    // Threads don't survive the daemon forks.
+    if let Some(control) = control.as_mut() {
+        control
+            .start()
+            .context("Couldn't start accepting control socket connections")?;
+    }
+    let mut hasher = (args.contains_id(ARG_HASH_NAME) || exe_lists.has_digests())
+        .then(|| ExeHasher::spawn(HASH_MAX_SIZE))
+        .transpose()
+        .context("Couldn't start hashing the executables")?;
+    #[cfg(feature = "otlp")]
+    let mut tracing = args
+        .get_one::<OtlpEndpoint>(ARG_OTLP_NAME)
+        .map(|endpoint| ProcessTracing::spawn(endpoint.clone()))
+        .transpose()
+        .context("Couldn't start exporting the process spans")?;
+    // The daemon has no terminal, so keystrokes are only read in the
+    // foreground.
+    let terminal = TerminalInput::spawn().unwrap_or_else(|e| {
+        log::warn!("Couldn't read the keystrokes typed on the terminal: {}", e);
+        None
+    });
+
+    let stop = stop_waker()?;
+    data_source.set_waker(stop.clone());
+
+    // Registered after the termination signals handler, which also handles
+    // SIGHUP.
+    let reload = reload_config
+        .as_ref()
+        .map(|_| SignalFlag::register(libc::SIGHUP))
+        .transpose()
+        .context("Couldn't set configuration reload signal handler")?;
+    let dump_stats = SignalFlag::register(libc::SIGUSR1)
+        .context("Couldn't set statistics dump signal handler")?;
+    let toggle_pause =
+        SignalFlag::register(libc::SIGUSR2).context("Couldn't set pause signal handler")?;
+
+    #[cfg(feature = "systemd")]
+    let mut notifier = io::systemd::Notifier::from_env().unwrap_or_else(|e| {
+        log::error!("Couldn't connect to the service manager: {}", e);
+        None
+    });
+    #[cfg(feature = "systemd")]
+    if let Some(Err(e)) = notifier.as_ref().map(|notifier| notifier.ready()) {
+        log::error!("Couldn't notify the service manager: {}", e);
+    }
+
+    let deadline = args
+        .get_one::<Duration>(ARG_DURATION_NAME)
+        .map(|duration| Instant::now() + *duration);
+    let events_timeout = match deadline {
+        Some(_) => DEADLINE_CHECK_INTERVAL,
+        None => EVENTS_TIMEOUT,
+    };
+    // Buffered events are flushed on time even when no more events arrive
+    let events_timeout = match flush_policy(&args) {
+        FlushPolicy::Interval(interval) => events_timeout.min(interval),
+        _ => events_timeout,
+    };
+    let events_timeout = match hasher {
+        Some(_) => events_timeout.min(HASH_CHECK_INTERVAL),
+        None => events_timeout,
+    };
+    let events_timeout = match (&control, &terminal) {
+        (None, None) => events_timeout,
+        _ => events_timeout.min(CONTROL_CHECK_INTERVAL),
+    };
+
+    let mut printed_lines = 0;
+    let receiver = EventsReceiver::spawn(
+        data_source,
+        *args.get_one::<usize>(ARG_QUEUESIZE_NAME).unwrap(),
+        backpressure_policy(&args),
+    )
+    .context("Couldn't start receiving process events")?;
     loop {
-        if let Some(event) = event.next() {
-            if let Err(e) = event
-                .and_then(|event| handle_event(event, &args, &mut process_registry))
-                .and_then(|line| print_output_line(line, &args, &mut stdout, &mut line_color))
-            {
+        if let Some(event) = receiver.next_timeout(events_timeout) {
+            counters.record(&event);
+            let timestamp = event
+                .as_ref()
+                .ok()
+                .map(|record| Duration::from_nanos(record.timestamp_ns));
+            match event
+                .and_then(|record| {
+                    handle_event(
+                        record.event,
+                        &args,
+                        &control_settings,
+                        &proc,
+                        &resolvers,
+                        &mut process_registry,
+                        &mut proc_cache,
+                        peak_rss_sampler.as_mut(),
+                    )
+                })
+                .inspect(|line| {
+                    if let Some(line) = line.as_ref() {
+                        record_output_line(&mut summary, line, clock.now());
+                        #[cfg(feature = "otlp")]
+                        if let Some(tracing) = tracing.as_mut() {
+                            tracing.record(line);
+                        }
+                    }
+                }) {
+                Ok(Some(mut line)) => {
+                    if let (true, Some(timestamp)) = (args.get_flag(ARG_TIMESTAMPS_NAME), timestamp)
+                    {
+                        line.elements_mut()
+                            .insert(0, time_element(clock.clock(), timestamp, &args));
+                    }
+                    let job = hasher
+                        .as_mut()
+                        .and_then(|hasher| hash_output_line(&mut line, &proc, hasher));
+                    pending_lines.push(line, job);
+                }
+                Ok(None) => {}
+                Err(e) => log_event_error(&e),
+            }
+        }
+
+        if let Some(hasher) = hasher.as_mut() {
+            for (job, digest) in hasher.try_completed() {
+                pending_lines.complete(&job, |line| set_line_digest(line, digest.as_ref().ok()));
+            }
+        }
+        let stop = stop.is_woken()
+            || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || is_count_reached(&args, printed_lines);
+        let ready_lines = match (stop, hasher.as_mut()) {
+            (true, Some(hasher)) => {
+                let wait_deadline = Instant::now() + HASH_STOP_TIMEOUT;
+                while let Some(timeout) = wait_deadline
+                    .checked_duration_since(Instant::now())
+                    .filter(|_| hasher.has_pending())
+                {
+                    if let Some((job, digest)) = hasher.wait_completed(timeout) {
+                        pending_lines
+                            .complete(&job, |line| set_line_digest(line, digest.as_ref().ok()));
+                    }
+                }
+                pending_lines.take_all(|line| set_line_digest(line, None))
+            }
+            _ => pending_lines.take_ready(),
+        };
+        for mut line in ready_lines {
+            complete_output_line(&mut line, &args, &exe_lists);
+            for line in coalesce_output_line(line, &args, exec_chains.as_mut()) {
+                if let Err(e) = print_output_line(
+                    line,
+                    &args,
+                    rate_limiter.as_mut(),
+                    &mut printed_lines,
+                    &mut output,
+                ) {
+                    log_event_error(&copes::Error::from(e));
+                }
+            }
+        }
+
+        if let Some(sampler) = peak_rss_sampler.as_mut() {
+            sample_peak_rss(sampler, &proc, &process_registry);
+        }
+
+        if let Some(exec_chains) = exec_chains.as_mut() {
+            let chains = match stop {
+                true => exec_chains.take_all(),
+                false => exec_chains.take_complete(Instant::now()),
+            };
+            for chain in chains {
+                let line = OutputLine::Exec(chain_exec_line(chain, &args));
+                if let Err(e) = print_output_line(
+                    line,
+                    &args,
+                    rate_limiter.as_mut(),
+                    &mut printed_lines,
+                    &mut output,
+                ) {
+                    log::error!("{}", e);
+                }
+            }
+        }
+
+        if let Some(rate_limiter) = rate_limiter.as_mut() {
+            let suppressed = match stop {
+                true => rate_limiter.take_all_suppressed(),
+                false => rate_limiter.take_suppressed(Instant::now()),
+            };
+            if let Err(e) = print_suppressed_lines(suppressed, &mut output) {
                 log::error!("{}", e);
             }
         }
 
-        if stop.load(atomic::Ordering::Relaxed) {
+        if let Some(interval) = histogram_interval {
+            if !stop && last_histograms.elapsed() >= interval {
+                if let Err(e) = print_histograms(&summary, &args, &mut output) {
+                    log::error!("Couldn't print the lifetime histograms: {}", e);
+                }
+                last_histograms = Instant::now();
+            }
+        }
+
+        if let Err(e) = output.flush_if_due(Instant::now()) {
+            log::error!("Couldn't flush the output: {}", e);
+        }
+
+        #[cfg(feature = "systemd")]
+        if let Some(Err(e)) = notifier.as_mut().map(|notifier| notifier.watchdog()) {
+            log::error!("Couldn't ping the service manager watchdog: {}", e);
+        }
+
+        if stop {
+            #[cfg(feature = "otlp")]
+            if let Some(tracing) = tracing.take() {
+                tracing.finish();
+            }
             break;
         }
+
+        let stats = || Stats {
+            uptime: clock.now().saturating_duration_since(start),
+            counters: &counters,
+            dropped: receiver.dropped(),
+            tracked_processes: process_registry.len(),
+            cached_processes: proc_cache.len(),
+            summary: &summary,
+        };
+        let top = *args.get_one::<usize>(ARG_SUMMARYTOP_NAME).unwrap();
+        let raw_numbers = args.get_flag(ARG_RAWNUMBERS_NAME);
+
+        // Written to the standard error, along with the diagnostic messages.
+        if dump_stats.take() {
+            if let Err(e) = print_stats(&stats(), top, raw_numbers, &mut std::io::stderr().lock()) {
+                log::error!("Couldn't print the statistics: {}", e);
+            }
+        }
+
+        let mut pause = toggle_pause.take().then(|| !receiver.is_paused());
+        let requests = control
+            .as_ref()
+            .map(ControlSocket::try_requests)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|request| (request.command().clone(), Some(request)));
+        let keystrokes = terminal
+            .as_ref()
+            .map(TerminalInput::try_commands)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|command| (command, None));
+        for (command, request) in requests.chain(keystrokes) {
+            let reply = match command {
+                ControlCommand::Stats => {
+                    let mut text = Vec::new();
+                    print_stats(&stats(), top, raw_numbers, &mut text)
+                        .map(|_| String::from_utf8_lossy(&text).into_owned())
+                        .map_err(|e| format!("Couldn't print the statistics: {}", e))
+                }
+                ControlCommand::Pause | ControlCommand::Resume | ControlCommand::TogglePause => {
+                    let paused = match command {
+                        ControlCommand::Pause => true,
+                        ControlCommand::Resume => false,
+                        _ => !pause.unwrap_or_else(|| receiver.is_paused()),
+                    };
+                    pause = Some(paused);
+                    Ok(match paused {
+                        true => "Process events monitoring paused".to_string(),
+                        false => "Process events monitoring resumed".to_string(),
+                    })
+                }
+                command => control_settings.apply(&command, &args),
+            };
+            match request {
+                Some(request) => {
+                    if let Err(e) = request.reply(reply) {
+                        log::warn!("Couldn't reply to a control socket client: {}", e);
+                    }
+                }
+                // Typed on the terminal, so written to the standard error,
+                // along with the diagnostic messages.
+                None => match reply {
+                    Ok(message) => eprintln!("{}", message),
+                    Err(message) => log::error!("{}", message),
+                },
+            }
+        }
+
+        match pause {
+            Some(false) if receiver.is_paused() => {
+                // The exit events of the processes were lost meanwhile
+                process_registry.retain(|pid, _| proc.pid_path(*pid).exists());
+                #[cfg(feature = "otlp")]
+                if let Some(tracing) = tracing.as_mut() {
+                    tracing.spans.retain(|pid| proc.pid_path(pid).exists());
+                }
+                receiver.resume();
+                log::info!("Process events monitoring resumed");
+            }
+            Some(true) if !receiver.is_paused() => {
+                receiver.pause();
+                log::info!("Process events monitoring paused");
+            }
+            _ => {}
+        }
+
+        if let (Some(config), Some(true)) = (&reload_config, reload.as_ref().map(SignalFlag::take))
+        {
+            match reload_args(config).and_then(|args| {
+                let output = create_output(&args)?;
+                let exe_lists = read_exe_lists(&args)?;
+                Ok((args, output, exe_lists))
+            }) {
+                Ok((reloaded_args, reloaded_output, reloaded_exe_lists)) => {
+                    if let Err(e) = output.flush() {
+                        log::error!("Couldn't flush the output: {}", e);
+                    }
+                    if reloaded_exe_lists.has_digests() && hasher.is_none() {
+                        log::warn!("The executables are not hashed, so the digests of the reloaded lists are not matched");
+                    }
+                    args = reloaded_args;
+                    output = reloaded_output;
+                    exe_lists = reloaded_exe_lists;
+                    resolvers = Resolvers::with_builtin(wine_executables(&args));
+                    log::info!("Configuration reloaded from {}", config.display());
+                }
+                Err(e) => log::error!("Couldn't reload the configuration: {:#}", e),
+            }
+        }
+    }
+
+    #[cfg(feature = "systemd")]
+    if let Some(Err(e)) = notifier.as_ref().map(|notifier| notifier.stopping()) {
+        log::error!("Couldn't notify the service manager: {}", e);
+    }
+
+    let dropped = receiver.dropped();
+    if dropped > 0 {
+        log::warn!(
+            "{} process events were dropped because they couldn't be processed in time",
+            dropped
+        );
+    }
+
+    if histograms {
+        print_histograms(&summary, &args, &mut output)
+            .context("Couldn't print the lifetime histograms")?;
+    }
+    if args.get_flag(ARG_SUMMARY_NAME) {
+        print_summary(&summary, dropped, &args, &mut output)
+            .context("Couldn't print the summary")?;
+    }
+
+    output.flush().context("Couldn't flush the output")
+}
+
+/// Prints the headers and a hex dump of every message received by the
+/// process events connector, without filtering them.
+fn dump_raw_messages(args: &ArgMatches) -> Result<()> {
+    let mut data_source = check_events_source(ProcessEventsConnector::try_new_raw())?;
+    let stop = stop_waker()?;
+    data_source.set_waker(stop.clone());
+
+    let deadline = args
+        .get_one::<Duration>(ARG_DURATION_NAME)
+        .map(|duration| Instant::now() + *duration);
+    if deadline.is_some() {
+        data_source
+            .set_timeout(DEADLINE_CHECK_INTERVAL)
+            .context("Couldn't set process events timeout")?;
+    }
+
+    let mut printed_messages = 0;
+    let mut messages = data_source.raw_messages();
+    while !stop.is_woken()
+        && !deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        && !is_count_reached(args, printed_messages)
+    {
+        match messages.next() {
+            Some(Ok(message)) => {
+                println!("{}\n", message);
+                printed_messages += 1;
+            }
+            Some(Err(e)) => log_event_error(&e),
+            None => {}
+        }
     }
 
     Ok(())
 }
 
-fn create_events_source() -> Result<ProcessEventsConnector> {
-    ProcessEventsConnector::try_new()
-        .map_err(|error| match &error.kind() {
-            std::io::ErrorKind::PermissionDenied => {
-                anyhow::Error::new(error).context("The program was started without root privileges")
+/// Waits until a process executes one of the files given on the `wait`
+/// subcommand `args`, and prints its PID.
+fn wait(args: &ArgMatches) -> Result<ExitCode> {
+    let proc = ProcFs::new(args.get_one::<PathBuf>(ARG_PROCROOT_NAME).unwrap());
+    let resolvers = Resolvers::with_builtin(wine_executables(args));
+    let exes = args
+        .get_many::<OsString>(ARG_WAITEXEC_NAME)
+        .unwrap()
+        .collect::<Vec<_>>();
+    let until_exit = args.get_flag(ARG_UNTILEXIT_NAME);
+
+    let mut data_source = create_events_source(match until_exit {
+        true => MonitoredEvents::All,
+        false => MonitoredEvents::Exec,
+    })?;
+    data_source
+        .set_timeout(DEADLINE_CHECK_INTERVAL)
+        .context("Couldn't set process events timeout")?;
+    let stop = stop_waker()?;
+    data_source.set_waker(stop.clone());
+    let deadline = args
+        .get_one::<Duration>(ARG_TIMEOUT_NAME)
+        .map(|timeout| Instant::now() + *timeout);
+
+    let mut waited_pid = None;
+    let mut proc_cache = ProcCache::new();
+    let mut event = data_source.into_iter();
+    while !stop.is_woken() {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            log::error!("Timed out waiting for the process");
+            break;
+        }
+
+        match event.next() {
+            Some(Ok(PEvent::Exec { pid, .. })) if waited_pid.is_none() => {
+                let process = proc_cache
+                    .process(&proc, pid)
+                    .and_then(|mut process| read_process(&mut process));
+                proc_cache.invalidate(pid);
+                let Ok((pexe, cmdline)) = process else {
+                    continue;
+                };
+                let exe = solver::get_process_executed_file(pexe, &cmdline, &resolvers);
+                if is_waited_exe(&exe, &exes) {
+                    if !until_exit {
+                        println!("{}", pid);
+                        return Ok(ExitCode::SUCCESS);
+                    }
+                    waited_pid = Some(pid);
+                }
             }
-            _ => anyhow::Error::new(error),
-        })
-        .context("Couldn't create process events source")
+            Some(Ok(PEvent::Exit { pid, .. })) if waited_pid == Some(pid) => {
+                println!("{}", pid);
+                return Ok(ExitCode::SUCCESS);
+            }
+            Some(Err(e)) => log_event_error(&e),
+            _ => {}
+        }
+    }
+
+    Ok(ExitCode::FAILURE)
 }
 
-fn cmdline_args() -> ArgMatches {
-    Command::new(env!("CARGO_CRATE_NAME"))
-        .version(env!("CARGO_PKG_VERSION"))
-        .arg(
-            Arg::new(ARG_CMDLINE_NAME)
-                .short('c')
-                .action(ArgAction::SetTrue)
-                .help("Print the process command line"),
-        )
-        .arg(
-            Arg::new(ARG_NOCOLOR_NAME)
-                .long("no-color")
-                .action(ArgAction::SetTrue)
-                .help("Do not colorize output"),
-        )
-        .get_matches()
+/// Waits until the process with the PID given on the `wait-exit` subcommand
+/// `args` exits, and prints its exit status.
+///
+/// Returns the exit code of the process, or 128 plus the signal number when
+/// it was killed by a signal, like shells do.
+fn wait_exit(args: &ArgMatches) -> Result<ExitCode> {
+    let proc = ProcFs::new(args.get_one::<PathBuf>(ARG_PROCROOT_NAME).unwrap());
+    let mut waited_pid = PID::from(*args.get_one::<i32>(ARG_PID_NAME).unwrap());
+
+    let mut data_source = create_events_source(MonitoredEvents::Exit)?;
+    data_source
+        .set_timeout(DEADLINE_CHECK_INTERVAL)
+        .context("Couldn't set process events timeout")?;
+
+    // Checked once subscribed to the process events, so the exit of the
+    // process can't be missed
+    if let Some(ns_of) = args.get_one::<i32>(ARG_NSOF_NAME).copied().map(PID::from) {
+        waited_pid = proc
+            .host_pid_reader(ns_of, waited_pid)
+            .with_context(|| format!("Couldn't read the PID namespace of process {}", ns_of))?
+            .with_context(|| {
+                format!(
+                    "There is no process with PID {} on the PID namespace of process {}",
+                    waited_pid, ns_of
+                )
+            })?;
+    }
+    if !proc.pid_path(waited_pid).exists() {
+        anyhow::bail!("There is no process with PID {}", waited_pid);
+    }
+
+    let stop = stop_waker()?;
+    data_source.set_waker(stop.clone());
+    let deadline = args
+        .get_one::<Duration>(ARG_TIMEOUT_NAME)
+        .map(|timeout| Instant::now() + *timeout);
+
+    let mut event = data_source.into_iter();
+    while !stop.is_woken() {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            log::error!("Timed out waiting for the process to exit");
+            break;
+        }
+
+        match event.next() {
+            Some(Ok(PEvent::Exit {
+                pid,
+                exit_code: status,
+                ..
+            })) if pid == waited_pid => {
+                println!("{}", status);
+                let code = match (status.code(), status.signal()) {
+                    (Some(code), _) => code,
+                    (_, Some(signal)) => 128 + signal,
+                    _ => 1,
+                };
+                return Ok(ExitCode::from(code as u8));
+            }
+            Some(Err(e)) => log_event_error(&e),
+            _ => {}
+        }
+    }
+
+    Ok(ExitCode::FAILURE)
 }
 
-enum OutputLine {
-    Exec(String),
-    Exit(String),
+/// Maximum time the `doctor` subcommand waits for the process events of a
+/// child process.
+const DOCTOR_EVENTS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Outcome of a check of the `doctor` subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Diagnosis {
+    Ok,
+    Warning,
+    Error,
 }
 
-impl fmt::Display for OutputLine {
+impl fmt::Display for Diagnosis {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            OutputLine::Exec(line) | OutputLine::Exit(line) => write!(f, "{}", line),
+            Diagnosis::Ok => write!(f, "ok"),
+            Diagnosis::Warning => write!(f, "warning"),
+            Diagnosis::Error => write!(f, "error"),
         }
     }
 }
 
-fn handle_event(
-    event: PEvent,
-    args: &ArgMatches,
-    process_registry: &mut HashMap<PID, ExecutedFileName>,
-) -> std::io::Result<Option<OutputLine>> {
-    let output_line = match event {
-        PEvent::Exec(pid) => handle_exec_event(pid, args, process_registry),
-        PEvent::Exit(pid) => handle_exit_event(pid, process_registry),
-    }?
-    .map(|event_line| {
-        let line = format!("{} {}", event, event_line);
-        match event {
-            PEvent::Exec(_) => OutputLine::Exec(line),
-            PEvent::Exit(_) => OutputLine::Exit(line),
+/// Checks whether the process events can be monitored on this system, and
+/// prints a diagnosis of each needed feature.
+///
+/// Fails when any of the checks finds an error.
+fn doctor(args: &ArgMatches) -> Result<ExitCode> {
+    let proc = ProcFs::new(args.get_one::<PathBuf>(ARG_PROCROOT_NAME).unwrap());
+    let mut worst = Diagnosis::Ok;
+    let mut report = |diagnosis: Diagnosis, message: String| {
+        println!("[{}] {}", diagnosis, message);
+        worst = worst.max(diagnosis);
+    };
+
+    match io::probe::kernel_release() {
+        Some(release) => report(Diagnosis::Ok, format!("Kernel release {}", release)),
+        None => report(Diagnosis::Warning, "Unknown kernel release".to_string()),
+    }
+    for (option, feature) in [
+        ("CONFIG_CONNECTOR", "Kernel connector"),
+        ("CONFIG_PROC_EVENTS", "Process events connector"),
+    ] {
+        match io::probe::kernel_config_option(option) {
+            Some(true) => report(Diagnosis::Ok, format!("{} enabled ({})", feature, option)),
+            Some(false) => report(
+                Diagnosis::Error,
+                format!("{} not built into the kernel ({})", feature, option),
+            ),
+            None => report(
+                Diagnosis::Warning,
+                format!(
+                    "{} support unknown, the kernel configuration is not available ({})",
+                    feature, option
+                ),
+            ),
         }
-    });
+    }
+
+    match io::probe::has_effective_capability(io::probe::CAP_NET_ADMIN) {
+        Ok(true) => report(
+            Diagnosis::Ok,
+            "CAP_NET_ADMIN capability granted".to_string(),
+        ),
+        Ok(false) => report(
+            Diagnosis::Warning,
+            "No CAP_NET_ADMIN capability, needed on Linux 6.5 and earlier versions".to_string(),
+        ),
+        Err(e) => report(
+            Diagnosis::Warning,
+            format!("Couldn't read the process capabilities: {}", e),
+        ),
+    }
+
+    match ProcessEventsConnector::try_new_with(MonitoredEvents::Exec) {
+        Ok(connector) => {
+            report(
+                Diagnosis::Ok,
+                "Subscribed to the process events".to_string(),
+            );
+            match doctor_exec_event(&connector) {
+                Ok(true) => report(Diagnosis::Ok, "Process events received".to_string()),
+                Ok(false) => report(
+                    Diagnosis::Error,
+                    "No process events received. The process events connector is only \
+                     available on the initial network namespace, outside of containers"
+                        .to_string(),
+                ),
+                Err(e) => report(
+                    Diagnosis::Error,
+                    format!("Couldn't receive process events: {:#}", e),
+                ),
+            }
+        }
+        Err(error) => {
+            let message = match events_source_diagnostic(&error) {
+                Some(diagnostic) => format!("{}: {}", diagnostic, error),
+                None => error.to_string(),
+            };
+            report(
+                Diagnosis::Error,
+                format!("Couldn't subscribe to the process events: {}", message),
+            );
+        }
+    }
 
-    Ok(output_line)
+    match io::probe::proc_hidepid(proc.root()) {
+        Ok(None) => report(
+            Diagnosis::Ok,
+            format!("Processes visible on {}", proc.root().display()),
+        ),
+        Ok(Some(hidepid)) => report(
+            Diagnosis::Warning,
+            format!(
+                "{} mounted with hidepid={}, the processes of other users can't be inspected",
+                proc.root().display(),
+                hidepid
+            ),
+        ),
+        Err(e) => report(
+            Diagnosis::Warning,
+            format!(
+                "Couldn't read the mount options of {}: {}",
+                proc.root().display(),
+                e
+            ),
+        ),
+    }
+    if let Err(e) = proc.exe_reader(PID::from(1)) {
+        report(
+            Diagnosis::Warning,
+            format!(
+                "Couldn't read the executable of PID 1, the processes of other users might not \
+                 be inspected: {}",
+                e
+            ),
+        );
+    }
+
+    Ok(match worst {
+        Diagnosis::Error => ExitCode::FAILURE,
+        _ => ExitCode::SUCCESS,
+    })
 }
 
-fn handle_exec_event(
-    pid: PID,
-    args: &ArgMatches,
-    process_registry: &mut HashMap<PID, ExecutedFileName>,
-) -> std::io::Result<Option<String>> {
-    let mut line_elements = Vec::new();
+/// Runs a child process and returns whether its exec event is received from
+/// the `connector`.
+fn doctor_exec_event(connector: &ProcessEventsConnector) -> Result<bool> {
+    connector
+        .set_timeout(DEADLINE_CHECK_INTERVAL)
+        .context("Couldn't set process events timeout")?;
+
+    let mut child = std::process::Command::new("true")
+        .spawn()
+        .context("Couldn't run a child process")?;
+    let child_pid = PID::from_raw(child.id() as i32);
+    child
+        .wait()
+        .context("Couldn't wait for the child process")?;
+
+    let deadline = Instant::now() + DOCTOR_EVENTS_TIMEOUT;
+    let mut records = connector.records();
+    while Instant::now() < deadline {
+        match records.next() {
+            Some(Ok(record)) if record.event.pid() == child_pid => return Ok(true),
+            Some(Err(e)) if !e.is_events_lost() => return Err(e.into()),
+            _ => {}
+        }
+    }
 
-    let cmdline = io::proc::cmdline_reader(pid)?;
-    let exe = solver::get_process_executed_file(io::proc::exe_reader(pid)?, &cmdline);
+    Ok(false)
+}
 
-    line_elements.push(exe.to_string());
-    process_registry.insert(pid, exe);
+/// Sends the command given on the `ctl` subcommand `args` to the control
+/// socket of a running instance, and prints its reply.
+fn ctl(args: &ArgMatches) -> Result<ExitCode> {
+    let path = args
+        .get_one::<PathBuf>(ARG_CONTROL_NAME)
+        .map_or(Path::new(DEFAULT_CONTROL_PATH), PathBuf::as_path);
+    let command = args
+        .get_many::<String>(ARG_CTLCOMMAND_NAME)
+        .unwrap()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ")
+        .parse::<ControlCommand>()
+        .map_err(anyhow::Error::msg)?;
 
-    if args.get_flag(ARG_CMDLINE_NAME) {
-        line_elements.push(cmdline.to_string());
+    let reply = control::send(path, &command)
+        .with_context(|| format!("Couldn't send the command to {}", path.display()))?;
+    match reply {
+        Ok(message) => {
+            if !message.is_empty() {
+                println!("{}", message);
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(message) => {
+            log::error!("{}", message);
+            Ok(ExitCode::FAILURE)
+        }
     }
+}
 
-    Ok(Some(line_elements.join(" ")))
+/// Returns `true` when `exe` is one of the waited executed files. Windows
+/// executable names are matched ignoring their case.
+fn is_waited_exe(exe: &ExecutedFileName, exes: &[&OsString]) -> bool {
+    let exe = exe.as_ref();
+    exes.iter().any(|waited| {
+        *waited == exe
+            || (waited
+                .to_string_lossy()
+                .to_ascii_lowercase()
+                .ends_with(".exe")
+                && waited.eq_ignore_ascii_case(exe))
+    })
 }
 
-fn handle_exit_event(
-    pid: PID,
-    process_registry: &mut HashMap<PID, ExecutedFileName>,
-) -> std::io::Result<Option<String>> {
-    Ok(process_registry.remove(&pid).map(|exe| exe.to_string()))
+/// Returns a waker that is woken up by the termination signals, so the
+/// waits for process events are interrupted at once.
+fn stop_waker() -> Result<Waker> {
+    let stop = Waker::new().context("Couldn't create termination signals waker")?;
+    let stop_handle = stop.clone();
+    ctrlc::set_handler(move || {
+        if let Err(e) = stop_handle.wake() {
+            log::error!("Couldn't interrupt the wait for process events: {}", e);
+        }
+    })
+    .context("Couldn't set termination signals handler")?;
+
+    Ok(stop)
 }
 
-fn print_output_line(
-    line: Option<OutputLine>,
-    args: &ArgMatches,
-    stdout: &mut StandardStream,
-    line_color: &mut ColorSpec,
-) -> std::io::Result<()> {
-    match line {
-        Some(line) => {
-            if !args.get_flag(ARG_NOCOLOR_NAME) {
-                if let Err(e) = match line {
-                    OutputLine::Exec(_) => stdout.reset(),
-                    OutputLine::Exit(_) => stdout.set_color(line_color.set_fg(Some(Color::Red))),
-                } {
-                    log::error!("Couldn't setup output color: {}", e);
+fn create_events_source(events: MonitoredEvents) -> Result<ProcessEventsConnector> {
+    check_events_source(ProcessEventsConnector::try_new_with(events))
+}
+
+fn check_events_source(
+    connector: copes::Result<ProcessEventsConnector>,
+) -> Result<ProcessEventsConnector> {
+    let connector = connector
+        .map_err(|error| match events_source_diagnostic(&error) {
+            Some(diagnostic) => anyhow::Error::new(error).context(diagnostic),
+            None => anyhow::Error::new(error),
+        })
+        .context("Couldn't create process events source")?;
+
+    // Without process events support, the connector works but no events are
+    // ever received.
+    if io::probe::kernel_config_option("CONFIG_PROC_EVENTS") == Some(false) {
+        log::warn!("The kernel was built without process events support (CONFIG_PROC_EVENTS)");
+    }
+
+    Ok(connector)
+}
+
+/// Returns a diagnostic message with the cause of an `error` creating the
+/// process events source and how to fix it, when it's known.
+fn events_source_diagnostic(error: &copes::Error) -> Option<String> {
+    if error.io_error().and_then(std::io::Error::raw_os_error) == Some(libc::EPROTONOSUPPORT)
+        || io::probe::kernel_config_option("CONFIG_CONNECTOR") == Some(false)
+    {
+        return Some(
+            "The kernel was built without the process events connector (CONFIG_CONNECTOR)"
+                .to_string(),
+        );
+    }
+
+    if error.kind() != std::io::ErrorKind::PermissionDenied {
+        return None;
+    }
+
+    match io::probe::has_effective_capability(io::probe::CAP_NET_ADMIN) {
+        Ok(false) => {
+            let exe = std::env::current_exe()
+                .map(|exe| exe.display().to_string())
+                .unwrap_or_else(|_| env!("CARGO_CRATE_NAME").to_string());
+            Some(format!(
+                "The program needs the CAP_NET_ADMIN capability. Run it as root or grant it \
+                 the capability with: setcap cap_net_admin+ep {}",
+                exe
+            ))
+        }
+        // Security modules or containers can deny the access even with the
+        // needed capability.
+        _ => Some(
+            "The access to process events was denied, even though the program has the \
+             CAP_NET_ADMIN capability"
+                .to_string(),
+        ),
+    }
+}
+
+fn start_daemon(args: &ArgMatches) -> Result<PidFile> {
+    if !args.contains_id(ARG_LOGFILE_NAME) && !args.contains_id(ARG_OUTPUT_NAME) {
+        log::warn!("No log file set, process events will be discarded");
+    }
+
+    // The daemon runs on the root directory, so paths must be absolute.
+    let current_dir = std::env::current_dir().context("Couldn't get the current directory")?;
+    let pid_file_path = current_dir.join(args.get_one::<PathBuf>(ARG_PIDFILE_NAME).unwrap());
+
+    io::daemon::daemonize(
+        args.get_one::<PathBuf>(ARG_DAEMONLOG_NAME)
+            .map(PathBuf::as_path),
+    )
+    .context("Couldn't start the daemon")?;
+
+    PidFile::create(&pid_file_path)
+        .with_context(|| format!("Couldn't create PID file {}", pid_file_path.display()))
+}
+
+/// Output sink set with the `--output` option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OutputSpec {
+    Stdout(Format),
+    File(PathBuf, Format),
+    Socket(PathBuf, Format),
+    #[cfg(feature = "systemd")]
+    Journal(Format),
+}
+
+fn create_output(args: &ArgMatches) -> Result<Sinks> {
+    let specs = args
+        .get_many::<OutputSpec>(ARG_OUTPUT_NAME)
+        .into_iter()
+        .flatten()
+        .cloned()
+        .chain(
+            args.get_one::<PathBuf>(ARG_LOGFILE_NAME)
+                .map(|path| OutputSpec::File(path.clone(), Format::Text)),
+        )
+        .collect::<Vec<_>>();
+
+    let mut sinks = Sinks::with_flush_policy(flush_policy(args));
+    for spec in specs {
+        sinks.push(create_sink(spec, args)?);
+    }
+    if sinks.is_empty() {
+        sinks.push(create_sink(OutputSpec::Stdout(Format::Text), args)?);
+    }
+
+    Ok(sinks)
+}
+
+/// Returns the colors of the events: the scheme of the `--colors` option, or
+/// else of the `COPES_COLORS` environment variable, with the exit colors set
+/// by the `--exit-color` options.
+fn color_scheme(args: &ArgMatches) -> Result<ColorScheme> {
+    let mut scheme = match args.get_one::<ColorScheme>(ARG_COLORS_NAME) {
+        Some(scheme) => scheme.clone(),
+        None => match std::env::var(COLORS_ENV_VAR) {
+            Ok(scheme) => scheme
+                .parse()
+                .map_err(|e| anyhow!("Invalid {} color scheme: {}", COLORS_ENV_VAR, e))?,
+            Err(_) => ColorScheme::default(),
+        },
+    };
+    for (outcome, style) in args
+        .get_many::<(ExitOutcome, ColorSpec)>(ARG_EXITCOLOR_NAME)
+        .into_iter()
+        .flatten()
+    {
+        scheme.exit.set(*outcome, style.clone());
+    }
+    Ok(scheme)
+}
+
+/// Creates the sink of `spec`. Stream sinks are buffered following the flush
+/// policy, while sockets and the journal get a message per event anyway.
+fn create_sink(spec: OutputSpec, args: &ArgMatches) -> Result<Box<dyn Sink>> {
+    let flush_policy = flush_policy(args);
+    let sink: Box<dyn Sink> = match spec {
+        OutputSpec::Stdout(format) => {
+            let color = !args.get_flag(ARG_NOCOLOR_NAME);
+            match flush_policy {
+                FlushPolicy::Interval(_) => Box::new(
+                    StreamSink::new(
+                        BufferedStandardStream::stdout(ColorChoice::Always),
+                        format,
+                        color,
+                    )
+                    .with_colors(color_scheme(args)?),
+                ),
+                // The standard output is always line buffered
+                _ => Box::new(
+                    StreamSink::new(StandardStream::stdout(ColorChoice::Always), format, color)
+                        .with_colors(color_scheme(args)?),
+                ),
+            }
+        }
+        OutputSpec::File(path, format) => {
+            let policy = RotationPolicy {
+                max_size: args.get_one::<u64>(ARG_LOGROTATESIZE_NAME).copied(),
+                interval: args
+                    .get_one::<Duration>(ARG_LOGROTATEINTERVAL_NAME)
+                    .copied(),
+                keep: *args.get_one::<usize>(ARG_LOGKEEP_NAME).unwrap(),
+                compress: args.get_flag(ARG_LOGCOMPRESS_NAME),
+            };
+            let file = RotatingFile::open(&path, policy)
+                .with_context(|| format!("Couldn't open log file {}", path.display()))?;
+            match flush_policy {
+                FlushPolicy::EveryEvent => {
+                    Box::new(StreamSink::new(NoColor::new(file), format, false))
                 }
+                FlushPolicy::LineBuffered => Box::new(StreamSink::new(
+                    NoColor::new(std::io::LineWriter::new(file)),
+                    format,
+                    false,
+                )),
+                FlushPolicy::Interval(_) => Box::new(StreamSink::new(
+                    NoColor::new(std::io::BufWriter::new(file)),
+                    format,
+                    false,
+                )),
             }
+        }
+        OutputSpec::Socket(path, format) => Box::new(
+            SocketSink::connect(&path, format)
+                .with_context(|| format!("Couldn't connect to socket {}", path.display()))?,
+        ),
+        #[cfg(feature = "systemd")]
+        OutputSpec::Journal(format) => {
+            Box::new(JournalSink::connect(format).context("Couldn't connect to the journal")?)
+        }
+    };
+
+    Ok(sink)
+}
+
+fn cmdline_args() -> Result<ArgMatches> {
+    let mut args = std::env::args_os().collect::<Vec<_>>();
+    if let Some(path) = config_path(&args) {
+        insert_config_args(&mut args, &path)?;
+    }
+
+    Ok(command().get_matches_from(args))
+}
+
+/// Reads the command line options again, along with the options of the
+/// configuration file at `config`.
+///
+/// Unlike on startup, invalid options are returned as errors instead of
+/// exiting.
+fn reload_args(config: &Path) -> Result<ArgMatches> {
+    let mut args = std::env::args_os().collect::<Vec<_>>();
+    insert_config_args(&mut args, config)?;
 
-            writeln!(stdout, "{}", line)
+    command()
+        .try_get_matches_from(args)
+        .context("Invalid options")
+}
+
+fn insert_config_args(args: &mut Vec<OsString>, path: &Path) -> Result<()> {
+    // Options from the configuration file are inserted before the command line
+    // ones, so the later take precedence.
+    let config_args = io::config::args_reader(path)
+        .with_context(|| format!("Couldn't read configuration file {}", path.display()))?;
+    let position = args.len().min(1);
+    args.splice(position..position, config_args);
+
+    Ok(())
+}
+
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        if arg == "--config" {
+            return iter.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return Some(PathBuf::from(path));
         }
-        None => Ok(()),
     }
+
+    Some(PathBuf::from(DEFAULT_CONFIG_PATH)).filter(|path| path.exists())
+}
+
+fn command() -> Command {
+    let command = Command::new(env!("CARGO_CRATE_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .args_override_self(true)
+        .arg(
+            Arg::new(ARG_CONFIG_NAME)
+                .long("config")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help(format!(
+                    "Configuration file with default option values [default: {}]",
+                    DEFAULT_CONFIG_PATH
+                )),
+        )
+        .arg(
+            Arg::new(ARG_CMDLINE_NAME)
+                .short('c')
+                .long("cmdline")
+                .action(ArgAction::SetTrue)
+                .help("Print the process command line"),
+        )
+        .arg(
+            Arg::new(ARG_CMDLINEMAXLEN_NAME)
+                .long("cmdline-max-len")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .help("Abbreviate the printed command lines longer than N characters"),
+        )
+        .arg(
+            Arg::new(ARG_CMDLINEMAXARGS_NAME)
+                .long("cmdline-max-args")
+                .value_name("N")
+                .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                .help("Abbreviate the printed command lines with more than N arguments"),
+        )
+        .arg(
+            Arg::new(ARG_LOSSLESS_NAME)
+                .long("lossless")
+                .action(ArgAction::SetTrue)
+                .help("Print invalid UTF-8 bytes of names and arguments as \\xNN, and backslashes as \\\\"),
+        )
+        .arg(
+            Arg::new(ARG_NOCOLOR_NAME)
+                .long("no-color")
+                .action(ArgAction::SetTrue)
+                .help("Do not colorize output"),
+        )
+        .arg(
+            Arg::new(ARG_COLORS_NAME)
+                .long("colors")
+                .value_name("SCHEME")
+                .value_parser(ColorScheme::from_str)
+                .help("Colors of the events, like exec=cyan:exit-signal=bold,#ff0000:highlight=underline (overrides COPES_COLORS)"),
+        )
+        .arg(
+            Arg::new(ARG_EXITCOLOR_NAME)
+                .long("exit-color")
+                .value_name("OUTCOME=STYLE")
+                .action(ArgAction::Append)
+                .value_parser(parse_exit_color)
+                .help("Color of the exits with an outcome (success, failure or signal), like failure=magenta, 208, bold,#ff8000 or none (can be repeated)"),
+        )
+        .arg(
+            Arg::new(ARG_HELPERS_NAME)
+                .long("helpers")
+                .action(ArgAction::SetTrue)
+                .help("Print helper processes of Chromium based applications"),
+        )
+        .arg(
+            Arg::new(ARG_KERNELTHREADS_NAME)
+                .long("kernel-threads")
+                .action(ArgAction::SetTrue)
+                .help("Print kernel threads"),
+        )
+        .arg(
+            Arg::new(ARG_OWNPROCESSES_NAME)
+                .long("own-processes")
+                .action(ArgAction::SetTrue)
+                .help("Print this program processes and the ones it starts, like log compressors"),
+        )
+        .arg(
+            Arg::new(ARG_STEAM_NAME)
+                .short('s')
+                .long("steam")
+                .action(ArgAction::SetTrue)
+                .help("Print the Steam application of processes started by Steam"),
+        )
+        .arg(
+            Arg::new(ARG_ENV_NAME)
+                .long("env")
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .help("Print the value of the environment variable NAME of processes (can be repeated)"),
+        )
+        .arg(
+            Arg::new(ARG_WINE_NAME)
+                .long("wine")
+                .global(true)
+                .value_name("EXE")
+                .value_parser(clap::value_parser!(OsString))
+                .action(ArgAction::Append)
+                .help("Additional wine executable name or path (can be repeated)"),
+        )
+        .arg(
+            Arg::new(ARG_NODEFAULTWINE_NAME)
+                .long("no-default-wine")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Do not use the default wine executable names"),
+        )
+        .arg(
+            Arg::new(ARG_RAW_NAME)
+                .long("raw")
+                .action(ArgAction::SetTrue)
+                .help("Print the headers and a hex dump of every received netlink message, for debugging"),
+        )
+        .arg(
+            Arg::new(ARG_EVENTS_NAME)
+                .long("events")
+                .value_name("EVENTS")
+                .value_parser(["all", "exec", "exit"])
+                .default_value("all")
+                .help("Process events to monitor"),
+        )
+        .arg(
+            Arg::new(ARG_FILTER_NAME)
+                .long("filter")
+                .value_name("EXPR")
+                .value_parser(Filter::parse)
+                .help("Only print the process events matching the filter expression"),
+        )
+        .arg(
+            Arg::new(ARG_UNDER_NAME)
+                .long("under")
+                .value_name("DIR")
+                .value_parser(parse_under)
+                .action(ArgAction::Append)
+                .help("Only print the process events of executables inside a directory (can be repeated)"),
+        )
+        .arg(
+            Arg::new(ARG_HIDEWINESERVICES_NAME)
+                .long("hide-wine-services")
+                .action(ArgAction::SetTrue)
+                .overrides_with(ARG_SHOWWINESERVICES_NAME)
+                .help("Do not print wine services (wineserver, services.exe...)"),
+        )
+        .arg(
+            Arg::new(ARG_SHOWWINESERVICES_NAME)
+                .long("show-wine-services")
+                .action(ArgAction::SetTrue)
+                .overrides_with(ARG_HIDEWINESERVICES_NAME)
+                .help("Print wine services (default)"),
+        )
+        .arg(
+            Arg::new(ARG_NSPID_NAME)
+                .long("ns-pid")
+                .action(ArgAction::SetTrue)
+                .help("Print the PIDs of processes running on nested PID namespaces (containers)"),
+        )
+        .arg(
+            Arg::new(ARG_LOGIN_NAME)
+                .long("login")
+                .action(ArgAction::SetTrue)
+                .help("Print the login user ID and session ID of processes started from a login session"),
+        )
+        .arg(
+            Arg::new(ARG_TTY_NAME)
+                .long("tty")
+                .action(ArgAction::SetTrue)
+                .help("Print the controlling terminal and session of processes"),
+        )
+        .arg(
+            Arg::new(ARG_SCHED_NAME)
+                .long("sched")
+                .action(ArgAction::SetTrue)
+                .help("Print the nice value and scheduling policy of processes"),
+        )
+        .arg(
+            Arg::new(ARG_SECURITY_LABEL_NAME)
+                .long("security-label")
+                .action(ArgAction::SetTrue)
+                .help("Print the SELinux or AppArmor security label of processes"),
+        )
+        .arg(
+            Arg::new(ARG_CAPS_NAME)
+                .long("caps")
+                .action(ArgAction::SetTrue)
+                .help("Print the capabilities of processes started with any"),
+        )
+        .arg(
+            Arg::new(ARG_CAPS_NON_ROOT_NAME)
+                .long("caps-non-root")
+                .action(ArgAction::SetTrue)
+                .help("Print the capabilities of non-root processes started with effective ones"),
+        )
+        .arg(
+            Arg::new(ARG_SETID_NAME)
+                .long("setid")
+                .action(ArgAction::SetTrue)
+                .help("Highlight the executions of setuid and setgid programs as warnings"),
+        )
+        .arg(
+            Arg::new(ARG_SECURITY_NAME)
+                .long("security")
+                .action(ArgAction::SetTrue)
+                .help("Highlight the executions from temporary or user writable directories, or of deleted files, as warnings"),
+        )
+        .arg(
+            Arg::new(ARG_HASH_NAME)
+                .long("hash")
+                .value_name("ALGORITHM")
+                .value_parser(["sha256"])
+                .help("Print the digest of the executed files"),
+        )
+        .arg(
+            Arg::new(ARG_ALLOWLIST_NAME)
+                .long("allowlist")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Report as violations the executions of files not on the list of paths, directories or SHA-256 digests"),
+        )
+        .arg(
+            Arg::new(ARG_BLOCKLIST_NAME)
+                .long("blocklist")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Report as violations the executions of files on the list of paths, directories or SHA-256 digests"),
+        )
+        .arg(
+            Arg::new(ARG_VIOLATIONSIGNAL_NAME)
+                .long("violation-signal")
+                .value_name("SIGNAL")
+                .value_parser(parse_signal)
+                .help("Send a signal, like KILL or STOP, to the processes violating the executable lists"),
+        )
+        .arg(
+            Arg::new(ARG_VIOLATIONCOMMAND_NAME)
+                .long("violation-command")
+                .value_name("COMMAND")
+                .help("Run a shell command on each executable lists violation, with the COPES_PID, COPES_EXE, COPES_PATH and COPES_VIOLATION variables"),
+        )
+        .arg(
+            Arg::new(ARG_EXITSTATS_NAME)
+                .long("exit-stats")
+                .action(ArgAction::SetTrue)
+                .help("Print the peak memory usage and the read and written bytes of exited processes"),
+        )
+        .arg(
+            Arg::new(ARG_RAWNUMBERS_NAME)
+                .long("raw-numbers")
+                .action(ArgAction::SetTrue)
+                .help("Print the sizes as bytes and the process lifetimes as seconds, instead of human readable"),
+        )
+        .arg(
+            Arg::new(ARG_TIMESTAMPS_NAME)
+                .long("timestamps")
+                .action(ArgAction::SetTrue)
+                .help("Print the time of the process events, on the --clock clock"),
+        )
+        .arg(
+            Arg::new(ARG_CLOCK_NAME)
+                .long("clock")
+                .value_name("CLOCK")
+                .value_parser(|clock: &str| clock.parse::<Clock>())
+                .default_value("mono")
+                .help("Clock for the event times and the process lifetimes: mono, real or boottime (counts the time the system is suspended)"),
+        )
+        .arg(
+            Arg::new(ARG_TIMEFORMAT_NAME)
+                .long("time-format")
+                .value_name("STRFTIME")
+                .requires(ARG_TIMESTAMPS_NAME)
+                .help("Print the event times as wall clock dates with a strftime format, like %Y-%m-%d %H:%M:%S.%f (%f for microseconds)"),
+        )
+        .arg(
+            Arg::new(ARG_UTC_NAME)
+                .long("utc")
+                .action(ArgAction::SetTrue)
+                .requires(ARG_TIMESTAMPS_NAME)
+                .help(format!(
+                    "Print the event times as wall clock dates in UTC instead of the local time zone [default format: {}]",
+                    DEFAULT_UTC_TIME_FORMAT
+                )),
+        )
+        .arg(
+            Arg::new(ARG_PROCROOT_NAME)
+                .long("proc-root")
+                .global(true)
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .default_value(io::proc::DEFAULT_PROC_ROOT)
+                .help("Mount point of the proc file system"),
+        )
+        .arg(
+            Arg::new(ARG_COALESCE_NAME)
+                .long("coalesce-execs")
+                .value_name("MODE")
+                .value_parser(["final", "chain"])
+                .num_args(0..=1)
+                .default_missing_value("final")
+                .help(
+                    "Report consecutive executions of the same process on a single line, \
+                     showing only the final executable or the whole chain [default: final]",
+                ),
+        )
+        .arg(
+            Arg::new(ARG_COALESCEWINDOW_NAME)
+                .long("coalesce-window")
+                .value_name("MS")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("100")
+                .help("Maximum time in milliseconds between coalesced executions"),
+        )
+        .arg(
+            Arg::new(ARG_SUMMARY_NAME)
+                .long("summary")
+                .action(ArgAction::SetTrue)
+                .help("Print a summary of the executed files on quit"),
+        )
+        .arg(
+            Arg::new(ARG_SUMMARYUSERS_NAME)
+                .long("summary-users")
+                .action(ArgAction::SetTrue)
+                .help("Print the executions and process lifetimes of each user on the summary"),
+        )
+        .arg(
+            Arg::new(ARG_SUMMARYTOP_NAME)
+                .long("summary-top")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10")
+                .help("Number of executed files shown on the summary and histograms"),
+        )
+        .arg(
+            Arg::new(ARG_HISTOGRAM_NAME)
+                .long("histogram")
+                .action(ArgAction::SetTrue)
+                .help("Print the lifetime histograms of the most executed files on quit"),
+        )
+        .arg(
+            Arg::new(ARG_HISTOGRAMINTERVAL_NAME)
+                .long("histogram-interval")
+                .value_name("SECS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Also print the lifetime histograms periodically"),
+        )
+        .arg(
+            Arg::new(ARG_NOEVENTS_NAME)
+                .long("no-events")
+                .action(ArgAction::SetTrue)
+                .help("Do not print process events (useful with --summary)"),
+        )
+        .arg(
+            Arg::new(ARG_VERBOSE_NAME)
+                .short('v')
+                .long("verbose")
+                .global(true)
+                .action(ArgAction::Count)
+                .overrides_with(ARG_QUIET_NAME)
+                .help("Print more diagnostic messages (can be repeated)"),
+        )
+        .arg(
+            Arg::new(ARG_QUIET_NAME)
+                .short('q')
+                .long("quiet")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .overrides_with(ARG_VERBOSE_NAME)
+                .help("Only print error diagnostic messages"),
+        )
+        .arg(
+            Arg::new(ARG_LOGFILE_NAME)
+                .long("log-file")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Write process events to a file instead of the standard output"),
+        )
+        .arg(
+            Arg::new(ARG_OUTPUT_NAME)
+                .long("output")
+                .value_name("SINK")
+                .value_parser(parse_output)
+                .action(ArgAction::Append)
+                .help("Write process events to stdout, file:PATH, socket:PATH or journal, optionally with ,format=json, ,format=cef or ,format=leef (can be repeated)"),
+        )
+        .arg(
+            Arg::new(ARG_FLUSH_NAME)
+                .long("flush")
+                .value_name("POLICY")
+                .value_parser(parse_flush)
+                .default_value("line-buffered")
+                .help("When to write the process events: every-event, line-buffered or interval=TIME (in milliseconds with ms suffix, or seconds)"),
+        )
+        .arg(
+            Arg::new(ARG_LOGROTATESIZE_NAME)
+                .long("log-rotate-size")
+                .value_name("SIZE")
+                .value_parser(parse_size)
+                .help("Rotate the log file when it reaches a size (in bytes, or with K, M or G suffixes)"),
+        )
+        .arg(
+            Arg::new(ARG_LOGROTATEINTERVAL_NAME)
+                .long("log-rotate-interval")
+                .value_name("TIME")
+                .value_parser(parse_interval)
+                .help("Rotate the log file periodically (in seconds, or with s, m, h or d suffixes)"),
+        )
+        .arg(
+            Arg::new(ARG_LOGKEEP_NAME)
+                .long("log-keep")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("5")
+                .help("Number of rotated log files to keep"),
+        )
+        .arg(
+            Arg::new(ARG_LOGCOMPRESS_NAME)
+                .long("log-compress")
+                .action(ArgAction::SetTrue)
+                .help("Compress rotated log files with gzip"),
+        )
+        .arg(
+            Arg::new(ARG_DURATION_NAME)
+                .long("duration")
+                .value_name("TIME")
+                .value_parser(parse_interval)
+                .help("Quit after some time (in seconds, or with s, m, h or d suffixes)"),
+        )
+        .arg(
+            Arg::new(ARG_COUNT_NAME)
+                .short('n')
+                .long("count")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Quit after printing some process events"),
+        )
+        .arg(
+            Arg::new(ARG_DAEMON_NAME)
+                .long("daemon")
+                .action(ArgAction::SetTrue)
+                .help("Run in the background (use with --log-file)"),
+        )
+        .arg(
+            Arg::new(ARG_PIDFILE_NAME)
+                .long("pid-file")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .default_value(DEFAULT_PID_FILE_PATH)
+                .help("File where the PID of the daemon is written"),
+        )
+        .arg(
+            Arg::new(ARG_DAEMONLOG_NAME)
+                .long("daemon-log")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("File where the daemon writes its diagnostic messages [default: none]"),
+        )
+        .arg(
+            Arg::new(ARG_RATELIMIT_NAME)
+                .long("rate-limit")
+                .value_name("N/s")
+                .value_parser(parse_rate)
+                .help("Maximum number of printed process events per second"),
+        )
+        .arg(
+            Arg::new(ARG_QUEUESIZE_NAME)
+                .long("queue-size")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("4096")
+                .help("Maximum number of received process events waiting to be processed"),
+        )
+        .arg(
+            Arg::new(ARG_QUEUEPOLICY_NAME)
+                .long("queue-policy")
+                .value_name("POLICY")
+                .value_parser(["block", "drop-oldest", "drop-newest"])
+                .default_value("block")
+                .help("What to do with the received process events when the queue is full"),
+        )
+        .arg(
+            Arg::new(ARG_CONTROL_NAME)
+                .long("control")
+                .global(true)
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help(format!(
+                    "Unix socket where control commands are accepted, sent with the ctl subcommand [ctl default: {}]",
+                    DEFAULT_CONTROL_PATH
+                )),
+        )
+        .subcommand(
+            Command::new(CMD_WAIT_NAME)
+                .about("Wait until a process executes a file, then print its PID")
+                .arg(
+                    Arg::new(ARG_WAITEXEC_NAME)
+                        .long("exec")
+                        .value_name("EXE")
+                        .value_parser(clap::value_parser!(OsString))
+                        .action(ArgAction::Append)
+                        .required(true)
+                        .help("Executed file name to wait for (can be repeated)"),
+                )
+                .arg(
+                    Arg::new(ARG_UNTILEXIT_NAME)
+                        .long("until-exit")
+                        .action(ArgAction::SetTrue)
+                        .help("Wait until the process exits"),
+                )
+                .arg(
+                    Arg::new(ARG_TIMEOUT_NAME)
+                        .long("timeout")
+                        .value_name("TIME")
+                        .value_parser(parse_interval)
+                        .help("Give up waiting after some time (in seconds, or with s, m, h or d suffixes)"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_WAITEXIT_NAME)
+                .about("Wait until a process exits, then print its exit status")
+                .arg(
+                    Arg::new(ARG_PID_NAME)
+                        .value_name("PID")
+                        .value_parser(clap::value_parser!(i32).range(1..))
+                        .required(true)
+                        .help("PID of the process to wait for"),
+                )
+                .arg(
+                    Arg::new(ARG_NSOF_NAME)
+                        .long("ns-of")
+                        .value_name("PID")
+                        .value_parser(clap::value_parser!(i32).range(1..))
+                        .help("The PID is on the PID namespace of this process, like a process of a container"),
+                )
+                .arg(
+                    Arg::new(ARG_TIMEOUT_NAME)
+                        .long("timeout")
+                        .value_name("TIME")
+                        .value_parser(parse_interval)
+                        .help("Give up waiting after some time (in seconds, or with s, m, h or d suffixes)"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_DOCTOR_NAME)
+                .about("Check whether the process events can be monitored, and print a diagnosis"),
+        )
+        .subcommand(
+            Command::new(CMD_CTL_NAME)
+                .about("Send a command to the control socket of a running instance")
+                .arg(
+                    Arg::new(ARG_CTLCOMMAND_NAME)
+                        .value_name("COMMAND")
+                        .num_args(1..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true)
+                        .required(true)
+                        .help("add-filter EXPR, remove-filter EXPR, set-filter EXPR, clear-filters, filters, cmdline, stats, pause, resume or toggle-pause"),
+                ),
+        );
+
+    #[cfg(feature = "otlp")]
+    let command = command.arg(
+        Arg::new(ARG_OTLP_NAME)
+            .long("otlp")
+            .value_name("URL")
+            .value_parser(|url: &str| url.parse::<OtlpEndpoint>())
+            .help("Export the lifetimes of the reported processes as spans to an OpenTelemetry collector, like http://localhost:4318"),
+    );
+    #[cfg(feature = "json")]
+    let command = command.subcommand(
+        Command::new(CMD_SCHEMA_NAME)
+            .about("Print the JSON Schema of the records written by the JSON output format"),
+    );
+    command
+}
+
+fn log_level(args: &ArgMatches) -> LevelFilter {
+    if args.get_flag(ARG_QUIET_NAME) {
+        return LevelFilter::Error;
+    }
+
+    match args.get_count(ARG_VERBOSE_NAME) {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+fn log_event_error(error: &copes::Error) {
+    let message = match std::error::Error::source(error) {
+        Some(source) => format!("{}: {}", error, source),
+        None => error.to_string(),
+    };
+    if error.is_events_lost() {
+        log::warn!("{}", message);
+        return;
+    }
+
+    match error.kind() {
+        // Processes can exit while their files are being read, and reads are
+        // interrupted when quitting. These errors are expected.
+        std::io::ErrorKind::NotFound | std::io::ErrorKind::Interrupted => {
+            log::debug!("{}", message)
+        }
+        std::io::ErrorKind::PermissionDenied => log::warn!("{}", message),
+        _ => log::error!("{}", message),
+    }
+}
+
+fn parse_rate(value: &str) -> Result<u32, String> {
+    value
+        .strip_suffix("/s")
+        .unwrap_or(value)
+        .parse::<u32>()
+        .ok()
+        .filter(|rate| *rate > 0)
+        .ok_or_else(|| format!("invalid rate '{}', expected a positive N/s value", value))
+}
+
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&value[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&value[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|size| *size > 0)
+        .ok_or_else(|| format!("invalid size '{}'", value))
+}
+
+/// Parses an output sink, like `stdout`, `file:PATH`, `socket:PATH` or
+/// `journal`, optionally followed by its format, like `,format=json`.
+fn parse_output(value: &str) -> Result<OutputSpec, String> {
+    let (sink, format) = match value.rsplit_once(",format=") {
+        Some((sink, "text")) => (sink, Format::Text),
+        #[cfg(feature = "json")]
+        Some((sink, "json")) => (sink, Format::Json),
+        #[cfg(feature = "siem")]
+        Some((sink, "cef")) => (sink, Format::Cef),
+        #[cfg(feature = "siem")]
+        Some((sink, "leef")) => (sink, Format::Leef),
+        Some((_, format)) => return Err(format!("unknown format {}", format)),
+        None => (value, Format::Text),
+    };
+
+    match sink.split_once(':') {
+        None if sink == "stdout" => Ok(OutputSpec::Stdout(format)),
+        #[cfg(feature = "systemd")]
+        None if sink == "journal" => Ok(OutputSpec::Journal(format)),
+        Some(("file", path)) if !path.is_empty() => Ok(OutputSpec::File(path.into(), format)),
+        Some(("socket", path)) if !path.is_empty() => Ok(OutputSpec::Socket(path.into(), format)),
+        _ => Err(format!(
+            "unknown output {}, expected stdout, file:PATH, socket:PATH or journal",
+            sink
+        )),
+    }
+}
+
+/// Parses an output flush policy, like `every-event`, `line-buffered` or
+/// `interval=100ms`.
+fn parse_flush(value: &str) -> Result<FlushPolicy, String> {
+    match value {
+        "every-event" => Ok(FlushPolicy::EveryEvent),
+        "line-buffered" => Ok(FlushPolicy::LineBuffered),
+        _ => match value.strip_prefix("interval=") {
+            Some(interval) => match interval.strip_suffix("ms") {
+                Some(millis) => millis
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|millis| *millis > 0)
+                    .map(Duration::from_millis)
+                    .ok_or_else(|| format!("invalid time '{}'", interval)),
+                None => parse_interval(interval),
+            }
+            .map(FlushPolicy::Interval),
+            None => Err(format!(
+                "unknown flush policy {}, expected every-event, line-buffered or interval=TIME",
+                value
+            )),
+        },
+    }
+}
+
+/// Parses a directory of monitored executables. Its symbolic links are
+/// resolved when it exists, as they are on the executables paths.
+fn parse_under(value: &str) -> Result<PathBuf, String> {
+    match Path::new(value) {
+        dir if dir.is_absolute() => Ok(std::fs::canonicalize(dir).unwrap_or_else(|_| dir.into())),
+        _ => Err(format!("'{}' is not an absolute path", value)),
+    }
+}
+
+/// Parses an `OUTCOME=STYLE` exit color pair.
+fn parse_exit_color(value: &str) -> Result<(ExitOutcome, ColorSpec), String> {
+    let (outcome, style) = value
+        .split_once('=')
+        .ok_or_else(|| format!("'{}' is not an OUTCOME=STYLE pair", value))?;
+    Ok((outcome.parse()?, sink::parse_style(style)?))
+}
+
+/// Parses a signal name, like `KILL` or `SIGKILL`, or number.
+fn parse_signal(value: &str) -> Result<libc::c_int, String> {
+    let name = value.strip_prefix("SIG").unwrap_or(value);
+    let signal = match name.to_ascii_uppercase().as_str() {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "ABRT" => libc::SIGABRT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        "TERM" => libc::SIGTERM,
+        "CONT" => libc::SIGCONT,
+        "STOP" => libc::SIGSTOP,
+        number => number.parse().unwrap_or(0),
+    };
+    match signal {
+        1..=64 => Ok(signal),
+        _ => Err(format!("unknown signal '{}'", value)),
+    }
+}
+
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 60 * 60),
+        Some((i, 'd')) => (&value[..i], 24 * 60 * 60),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid time '{}'", value))
+}
+
+fn read_exe_lists(args: &ArgMatches) -> Result<ExeLists> {
+    let read = |name| {
+        args.get_one::<PathBuf>(name)
+            .map(|path| {
+                ExeList::read(path).with_context(|| {
+                    format!("Couldn't read the executable list {}", path.display())
+                })
+            })
+            .transpose()
+    };
+    Ok(ExeLists {
+        allow: read(ARG_ALLOWLIST_NAME)?,
+        block: read(ARG_BLOCKLIST_NAME)?,
+    })
+}
+
+fn wine_executables(args: &ArgMatches) -> WineExecutables {
+    let mut wine_executables = if args.get_flag(ARG_NODEFAULTWINE_NAME) {
+        WineExecutables::from(Vec::new())
+    } else {
+        WineExecutables::default()
+    };
+
+    if let Some(exes) = args.get_many::<OsString>(ARG_WINE_NAME) {
+        wine_executables.extend(exes.cloned());
+    }
+
+    wine_executables
+}
+
+fn time_format(args: &ArgMatches) -> Option<TimeFormat> {
+    let utc = args.get_flag(ARG_UTC_NAME);
+    match args.get_one::<String>(ARG_TIMEFORMAT_NAME) {
+        Some(format) => Some(TimeFormat::new(format, utc)),
+        None => utc.then(|| TimeFormat::new(DEFAULT_UTC_TIME_FORMAT, true)),
+    }
+}
+
+fn flush_policy(args: &ArgMatches) -> FlushPolicy {
+    args.get_one::<FlushPolicy>(ARG_FLUSH_NAME)
+        .copied()
+        .unwrap_or_default()
+}
+
+fn encoding(args: &ArgMatches) -> Encoding {
+    match args.get_flag(ARG_LOSSLESS_NAME) {
+        true => Encoding::Escaped,
+        false => Encoding::Lossy,
+    }
+}
+
+fn backpressure_policy(args: &ArgMatches) -> BackpressurePolicy {
+    match args
+        .get_one::<String>(ARG_QUEUEPOLICY_NAME)
+        .map(String::as_str)
+    {
+        Some("drop-oldest") => BackpressurePolicy::DropOldest,
+        Some("drop-newest") => BackpressurePolicy::DropNewest,
+        _ => BackpressurePolicy::Block,
+    }
+}
+
+fn monitored_events(args: &ArgMatches) -> MonitoredEvents {
+    match args.get_one::<String>(ARG_EVENTS_NAME).map(String::as_str) {
+        Some("exec") => MonitoredEvents::Exec,
+        Some("exit") => MonitoredEvents::Exit,
+        _ => MonitoredEvents::All,
+    }
+}
+
+fn exec_chains(args: &ArgMatches) -> Option<ExecChains<ExecLine>> {
+    args.contains_id(ARG_COALESCE_NAME).then(|| {
+        let window = *args.get_one::<u64>(ARG_COALESCEWINDOW_NAME).unwrap();
+        ExecChains::new(Duration::from_millis(window))
+    })
+}
+
+/// Output line of an exec event.
+struct ExecLine {
+    pid: PID,
+    severity: Severity,
+    /// Real user ID of the process, for the users summary.
+    uid: Option<PUid>,
+    /// Parent process ID, for the process spans.
+    #[cfg(feature = "otlp")]
+    ppid: Option<PID>,
+    /// Path of the executed file, for the executable lists.
+    path: Option<PExePath>,
+    /// Digest of the executed file, when the executables are hashed.
+    digest: Option<ExeDigest>,
+    exes: Vec<ExecutedFileName>,
+    elements: Vec<String>,
+    /// Abbreviations of the long elements on the text format.
+    abbreviations: Vec<(String, String)>,
+}
+
+/// Digest of the executed file of an exec line.
+enum ExeDigest {
+    /// The file is being hashed.
+    Pending,
+    /// The digest of the file.
+    Known(Digest),
+    /// The file is bigger than the maximum hashed size.
+    TooLarge,
+    /// The file couldn't be hashed, or its hashing wasn't complete when
+    /// quitting.
+    Unavailable,
+}
+
+impl fmt::Display for ExeDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExeDigest::Pending => write!(f, "pending"),
+            ExeDigest::Known(digest) => write!(f, "{}", digest),
+            ExeDigest::TooLarge => write!(f, "too-large"),
+            ExeDigest::Unavailable => write!(f, "unavailable"),
+        }
+    }
+}
+
+/// Allowlist and blocklist of the executed files.
+#[derive(Debug, Default)]
+struct ExeLists {
+    allow: Option<ExeList>,
+    block: Option<ExeList>,
+}
+
+/// Violation of the executable lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Violation {
+    /// The executed file is not on the allowlist.
+    NotAllowed,
+    /// The executed file couldn't be read to check it against the allowlist,
+    /// like the ones of processes that exit at once.
+    Unverified,
+    /// The executed file is on the blocklist.
+    Blocked,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::NotAllowed => write!(f, "not-allowed"),
+            Violation::Unverified => write!(f, "unverified"),
+            Violation::Blocked => write!(f, "blocked"),
+        }
+    }
+}
+
+impl ExeLists {
+    fn has_digests(&self) -> bool {
+        [&self.allow, &self.block]
+            .into_iter()
+            .flatten()
+            .any(ExeList::has_digests)
+    }
+
+    /// Returns the violation of the lists by the executable with the given
+    /// `path` and `digest`, if any.
+    fn violation(&self, path: Option<&PExePath>, digest: Option<&Digest>) -> Option<Violation> {
+        let path = path.map(AsRef::as_ref);
+        if self
+            .block
+            .as_ref()
+            .is_some_and(|list| list.contains(path, digest))
+        {
+            return Some(Violation::Blocked);
+        }
+        match self.allow.as_ref() {
+            Some(list) if list.contains(path, digest) => None,
+            Some(_) if path.is_none() && digest.is_none() => Some(Violation::Unverified),
+            Some(_) => Some(Violation::NotAllowed),
+            None => None,
+        }
+    }
+}
+
+/// Output line of an exit event.
+struct ExitLine {
+    pid: PID,
+    exe: ExecutedFileName,
+    status: PExitStatus,
+    elements: Vec<String>,
+}
+
+enum OutputLine {
+    Exec(ExecLine),
+    Exit(ExitLine),
+}
+
+impl OutputLine {
+    fn elements_mut(&mut self) -> &mut Vec<String> {
+        match self {
+            OutputLine::Exec(line) => &mut line.elements,
+            OutputLine::Exit(line) => &mut line.elements,
+        }
+    }
+
+    fn event_line(&self, encoding: Encoding) -> EventLine<'_> {
+        match self {
+            OutputLine::Exec(line) => EventLine {
+                kind: EventKind::Exec,
+                severity: line.severity,
+                pid: line.pid,
+                exes: &line.exes,
+                elements: &line.elements,
+                encoding,
+                status: None,
+                abbreviations: &line.abbreviations,
+            },
+            OutputLine::Exit(line) => EventLine {
+                kind: EventKind::Exit,
+                severity: Severity::Info,
+                pid: line.pid,
+                exes: std::slice::from_ref(&line.exe),
+                elements: &line.elements,
+                encoding,
+                status: Some(line.status),
+                abbreviations: &[],
+            },
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_event(
+    event: PEvent,
+    args: &ArgMatches,
+    control_settings: &ControlSettings,
+    proc: &ProcFs,
+    resolvers: &Resolvers,
+    process_registry: &mut HashMap<PID, ExecutedFileName>,
+    proc_cache: &mut ProcCache,
+    peak_rss_sampler: Option<&mut PeakRssSampler>,
+) -> copes::Result<Option<OutputLine>> {
+    match event {
+        PEvent::Exec { pid, .. } => {
+            // The cached information belongs to the file executed before.
+            proc_cache.invalidate(pid);
+            let line = handle_exec_event(
+                pid,
+                args,
+                control_settings,
+                proc,
+                resolvers,
+                process_registry,
+                proc_cache,
+            );
+            if monitored_events(args) == MonitoredEvents::Exec {
+                // No exit event will discard the cached information.
+                proc_cache.invalidate(pid);
+            }
+            line
+        }
+        PEvent::Exit { pid, exit_code, .. } => {
+            let line = handle_exit_event(
+                pid,
+                exit_code,
+                args,
+                control_settings,
+                proc,
+                process_registry,
+                proc_cache,
+                peak_rss_sampler,
+            );
+            proc_cache.invalidate(pid);
+            line
+        }
+    }
+}
+
+fn handle_exec_event(
+    pid: PID,
+    args: &ArgMatches,
+    control_settings: &ControlSettings,
+    proc: &ProcFs,
+    resolvers: &Resolvers,
+    process_registry: &mut HashMap<PID, ExecutedFileName>,
+    proc_cache: &mut ProcCache,
+) -> copes::Result<Option<OutputLine>> {
+    let mut line_elements = Vec::new();
+
+    let process = proc_cache
+        .process(proc, pid)
+        .and_then(|mut process| read_process(&mut process).map(|info| (process, info)));
+    let (mut process, (pexe, cmdline)) = match process {
+        Ok(process) => process,
+        Err(e) if e.is_process_gone() => {
+            // Short-lived processes can exit before their information is read.
+            // They are still reported, so their exit event is reported too.
+            let exe = ExecutedFileName::from(PExe::from(OsString::from(GONE_MARKER)));
+            let (show_exec, show_exit) = filter_events(&exe, None, None, args, control_settings);
+            if show_exit {
+                process_registry.insert(pid, exe.clone());
+            }
+            return Ok(show_exec.then(|| {
+                OutputLine::Exec(ExecLine {
+                    pid,
+                    severity: Severity::Info,
+                    uid: None,
+                    #[cfg(feature = "otlp")]
+                    ppid: None,
+                    path: None,
+                    digest: None,
+                    exes: vec![exe],
+                    elements: Vec::new(),
+                    abbreviations: Vec::new(),
+                })
+            }));
+        }
+        Err(e) => return Err(e),
+    };
+
+    if !args.get_flag(ARG_HELPERS_NAME) && solver::is_helper_process(&cmdline) {
+        return Ok(None);
+    }
+
+    let exe = solver::get_process_executed_file(pexe, &cmdline, resolvers);
+
+    if args.get_flag(ARG_HIDEWINESERVICES_NAME) && solver::is_wine_service(&exe) {
+        return Ok(None);
+    }
+
+    let (show_exec, show_exit) = filter_events(
+        &exe,
+        Some(&cmdline),
+        Some(&mut process),
+        args,
+        control_settings,
+    );
+    if show_exit {
+        process_registry.insert(pid, exe.clone());
+    }
+    if !show_exec {
+        return Ok(None);
+    }
+
+    let mut severity = Severity::Info;
+    if args.get_flag(ARG_SETID_NAME) {
+        if let Some(setid) = setid_element(proc, pid) {
+            line_elements.push(setid);
+            severity = Severity::Warning;
+        }
+    }
+
+    if args.get_flag(ARG_SECURITY_NAME) {
+        if let Some(suspicious) = suspicious_element(&mut process, encoding(args)) {
+            line_elements.push(suspicious);
+            severity = Severity::Warning;
+        }
+    }
+
+    if args.get_flag(ARG_NSPID_NAME) {
+        if let Some(nspid) = nspid_element(proc, pid) {
+            line_elements.push(nspid);
+        }
+    }
+
+    if args.get_flag(ARG_LOGIN_NAME) {
+        if let Some(login) = login_element(proc, pid) {
+            line_elements.push(login);
+        }
+    }
+
+    if args.get_flag(ARG_TTY_NAME) {
+        line_elements.push(tty_element(process.stat()));
+    }
+
+    if args.get_flag(ARG_SCHED_NAME) {
+        line_elements.push(sched_element(process.stat()));
+    }
+
+    if args.get_flag(ARG_CAPS_NAME) || args.get_flag(ARG_CAPS_NON_ROOT_NAME) {
+        if let Some(caps) = caps_element(&mut process, args.get_flag(ARG_CAPS_NON_ROOT_NAME)) {
+            line_elements.push(caps);
+        }
+    }
+
+    if args.get_flag(ARG_SECURITY_LABEL_NAME) {
+        if let Some(label) = security_label_element(proc, pid, encoding(args)) {
+            line_elements.push(label);
+        }
+    }
+
+    if args.get_flag(ARG_STEAM_NAME) {
+        if let Some(steam_app) = steam_app_element(&mut process) {
+            line_elements.push(steam_app);
+        }
+    }
+
+    if let Some(keys) = args.get_many::<String>(ARG_ENV_NAME) {
+        let keys = keys.map(String::as_str).collect::<Vec<_>>();
+        line_elements.extend(env_elements(&mut process, &keys, encoding(args)));
+    }
+
+    let mut abbreviations = Vec::new();
+    if control_settings.cmdline(args) {
+        let display = cmdline.display(Quoting::Shell).encoding(encoding(args));
+        let element = format!("[{}]", display);
+        let abbreviation = format!("[{}]", cmdline_abbreviation(display, args));
+        if abbreviation != element {
+            abbreviations.push((element.clone(), abbreviation));
+        }
+        line_elements.push(element);
+    }
+
+    let uid = process.status().ok().map(|status| status.uid);
+    let path = process.exe_path().ok();
+    Ok(Some(OutputLine::Exec(ExecLine {
+        pid,
+        severity,
+        uid,
+        #[cfg(feature = "otlp")]
+        ppid: Some(process.stat().ppid),
+        path,
+        digest: None,
+        exes: vec![exe],
+        elements: line_elements,
+        abbreviations,
+    })))
+}
+
+/// Returns the command line `display` abbreviated following the
+/// `--cmdline-max-args` and `--cmdline-max-len` options.
+fn cmdline_abbreviation<'a>(
+    mut display: CmdLineDisplay<'a>,
+    args: &ArgMatches,
+) -> CmdLineDisplay<'a> {
+    if let Some(max_args) = args.get_one::<usize>(ARG_CMDLINEMAXARGS_NAME) {
+        display = display.max_args(*max_args);
+    }
+    if let Some(max_len) = args.get_one::<usize>(ARG_CMDLINEMAXLEN_NAME) {
+        display = display.max_len(*max_len);
+    }
+    display
+}
+
+/// Returns whether the exec and exit events of the process with the given
+/// `pid` are monitored and match the events filters and directories, if any.
+/// Kernel threads and this program processes are excluded unless requested.
+///
+/// Exit events are matched when the process is executed, as its information
+/// can't be read once it exits.
+fn filter_events(
+    exe: &ExecutedFileName,
+    cmdline: Option<&PCmdLine>,
+    mut process: Option<&mut CachedProcess>,
+    args: &ArgMatches,
+    control_settings: &ControlSettings,
+) -> (bool, bool) {
+    let events = monitored_events(args);
+    let (show_exec, show_exit) = (
+        events != MonitoredEvents::Exit,
+        events != MonitoredEvents::Exec,
+    );
+    if let Some(process) = process.as_ref() {
+        let stat = process.stat();
+        if stat.kernel_thread && !args.get_flag(ARG_KERNELTHREADS_NAME) {
+            return (false, false);
+        }
+        let own_pid = PID::from_raw(std::process::id() as i32);
+        if (process.pid() == own_pid || stat.ppid == own_pid)
+            && !args.get_flag(ARG_OWNPROCESSES_NAME)
+        {
+            return (false, false);
+        }
+    }
+
+    if let Some(mut dirs) = args.get_many::<PathBuf>(ARG_UNDER_NAME) {
+        // Processes whose executable path can't be read, like the ones that
+        // already exited, never match.
+        let exe_path = process
+            .as_mut()
+            .and_then(|process| io::proc::retry_read(|| process.exe_path()).ok());
+        if !exe_path.is_some_and(|exe_path| dirs.any(|dir| exe_path.is_under(dir))) {
+            return (false, false);
+        }
+    }
+
+    let filters = args
+        .get_one::<Filter>(ARG_FILTER_NAME)
+        .into_iter()
+        .chain(control_settings.filters.iter().map(|(_, filter)| filter))
+        .collect::<Vec<_>>();
+    if filters.is_empty() {
+        return (show_exec, show_exit);
+    }
+    let uses = |field| filters.iter().any(|filter| filter.uses(field));
+
+    let exe = exe.to_string();
+    let cmdline = cmdline
+        .map(|cmdline| cmdline.display(Quoting::None).to_string())
+        .unwrap_or_default();
+    let uid = match (uses(Field::Uid), process.as_mut()) {
+        (true, Some(process)) => process.status().ok().map(|status| *status.uid.as_ref()),
+        _ => None,
+    };
+    let path = match (uses(Field::Path), process.as_mut()) {
+        (true, Some(process)) => io::proc::retry_read(|| process.exe_path())
+            .ok()
+            .map(|path| path.as_ref().to_string_lossy().into_owned()),
+        _ => None,
+    };
+    let cgroup = match (uses(Field::Cgroup), process.as_mut()) {
+        (true, Some(process)) => process.cgroups().ok().and_then(|cgroups| {
+            cgroups
+                .path()
+                .map(|cgroup| cgroup.as_ref().to_string_lossy().into_owned())
+        }),
+        _ => None,
+    };
+
+    let exec = filter::Event {
+        kind: EventKind::Exec,
+        exe: &exe,
+        path: path.as_deref(),
+        cmdline: &cmdline,
+        uid,
+        cgroup: cgroup.as_deref(),
+    };
+    let exit = filter::Event {
+        kind: EventKind::Exit,
+        ..exec
+    };
+    (
+        show_exec && filters.iter().all(|filter| filter.matches(&exec)),
+        show_exit && filters.iter().all(|filter| filter.matches(&exit)),
+    )
+}
+
+fn read_process(process: &mut CachedProcess) -> copes::Result<(PExe, PCmdLine)> {
+    let cmdline = io::proc::retry_read(|| process.cmdline())?;
+    let pexe = io::proc::retry_read(|| process.exe()).or_else(|e| {
+        // The executable of processes from other users (without root
+        // privileges) or of exited processes cannot be read, but their
+        // command name still can.
+        process
+            .status()
+            .map(|status| PExe::from(status.name))
+            .map_err(|_| e)
+    })?;
+    Ok((pexe, cmdline))
+}
+
+fn login_element(proc: &ProcFs, pid: PID) -> Option<String> {
+    let loginuid = proc.loginuid_reader(pid).ok()??;
+    match proc.sessionid_reader(pid).ok().flatten() {
+        Some(sessionid) => Some(format!("[login:{} session:{}]", loginuid, sessionid)),
+        None => Some(format!("[login:{}]", loginuid)),
+    }
+}
+
+fn tty_element(stat: &PStat) -> String {
+    match stat.tty {
+        Some(tty) => format!("[tty:{} session:{}]", tty, stat.session),
+        None => format!("[tty:none session:{}]", stat.session),
+    }
+}
+
+fn sched_element(stat: &PStat) -> String {
+    match stat.policy {
+        Some(policy) if policy.is_real_time() && stat.rt_priority > 0 => {
+            format!("[nice:{} sched:{}/{}]", stat.nice, policy, stat.rt_priority)
+        }
+        Some(policy) => format!("[nice:{} sched:{}]", stat.nice, policy),
+        None => format!("[nice:{}]", stat.nice),
+    }
+}
+
+/// Returns the capabilities element of processes started with elevated
+/// capabilities. With `non_root`, only the processes of non-root users with
+/// effective capabilities get it, as every process of root has all of them.
+fn caps_element(process: &mut CachedProcess, non_root: bool) -> Option<String> {
+    let status = process.status().ok()?;
+    let elevated = if non_root {
+        *status.uid.as_ref() != 0 && !status.cap_effective.is_empty()
+    } else {
+        !status.cap_effective.is_empty() || !status.cap_permitted.is_empty()
+    };
+    elevated.then(|| {
+        format!(
+            "[caps:eff={} prm={}]",
+            status.cap_effective, status.cap_permitted
+        )
+    })
+}
+
+fn setid_element(proc: &ProcFs, pid: PID) -> Option<String> {
+    let setid = proc.setid_reader(pid).ok()?;
+    let user = setid.uid.map(|uid| match io::users::user_name(uid) {
+        Some(name) => format!("setuid:{}", name),
+        None => format!("setuid:{}", uid),
+    });
+    let group = setid.gid.map(|gid| format!("setgid:{}", gid));
+    let element = user.into_iter().chain(group).collect::<Vec<_>>();
+    (!element.is_empty()).then(|| format!("[{}]", element.join(" ")))
+}
+
+fn suspicious_element(process: &mut CachedProcess, encoding: Encoding) -> Option<String> {
+    let path = process.exe_path().ok()?;
+    let locations = io::security::suspicious_locations(&path);
+    if locations.is_empty() {
+        return None;
+    }
+
+    let locations = locations.iter().map(ToString::to_string);
+    Some(format!(
+        "[suspicious:{} path:{}]",
+        locations.collect::<Vec<_>>().join(","),
+        encoding.encode(path.as_ref().as_os_str())
+    ))
+}
+
+fn security_label_element(proc: &ProcFs, pid: PID, encoding: Encoding) -> Option<String> {
+    let label = proc.security_label_reader(pid).ok()??;
+    Some(format!("[label:{}]", encoding.encode(label.as_ref())))
+}
+
+fn nspid_element(proc: &ProcFs, pid: PID) -> Option<String> {
+    let nspids = proc.nspid_reader(pid).ok()?;
+    if !nspids.is_nested() {
+        return None;
+    }
+
+    let pids = nspids
+        .nested()
+        .iter()
+        .map(PID::to_string)
+        .collect::<Vec<_>>();
+    Some(format!("[ns-pid:{}]", pids.join("/")))
+}
+
+fn env_elements(process: &mut CachedProcess, keys: &[&str], encoding: Encoding) -> Vec<String> {
+    // The environment of processes from other users cannot be read without
+    // root privileges, so failing to read it is not an error.
+    let Ok(environ) = process.environ() else {
+        return Vec::new();
+    };
+
+    keys.iter()
+        .filter_map(|key| {
+            let value = environ.get(key)?;
+            Some(format!("[env:{}={}]", key, encoding.encode(value)))
+        })
+        .collect()
+}
+
+fn steam_app_element(process: &mut CachedProcess) -> Option<String> {
+    // The environment of processes from other users cannot be read without
+    // root privileges, so failing to read it is not an error.
+    let environ = process.environ().ok()?;
+    let app_id = solver::get_steam_app_id(&environ)?;
+
+    let name = environ
+        .get("HOME")
+        .and_then(|home| io::steam::app_name_reader(Path::new(home), app_id).ok());
+    match name {
+        Some(name) => Some(format!("[steam:{} {}]", app_id, name)),
+        None => Some(format!("[steam:{}]", app_id)),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_exit_event(
+    pid: PID,
+    status: PExitStatus,
+    args: &ArgMatches,
+    control_settings: &ControlSettings,
+    proc: &ProcFs,
+    process_registry: &mut HashMap<PID, ExecutedFileName>,
+    proc_cache: &mut ProcCache,
+    peak_rss_sampler: Option<&mut PeakRssSampler>,
+) -> copes::Result<Option<OutputLine>> {
+    let exe = if monitored_events(args) != MonitoredEvents::Exit {
+        match process_registry.remove(&pid) {
+            Some(exe) => exe,
+            None => return Ok(None),
+        }
+    } else {
+        // Without exec events, exited processes are only known by their
+        // command name, which can be read until they are reaped.
+        let exe = match proc.comm_reader(pid) {
+            Ok(comm) => ExecutedFileName::from(PExe::from(comm)),
+            Err(_) => ExecutedFileName::from(PExe::from(OsString::from(GONE_MARKER))),
+        };
+        let mut process = proc_cache.process(proc, pid).ok();
+        let (_, show_exit) = filter_events(&exe, None, process.as_mut(), args, control_settings);
+        if !show_exit {
+            return Ok(None);
+        }
+        exe
+    };
+
+    let mut elements = Vec::new();
+    if let Some(sampler) = peak_rss_sampler {
+        elements.extend(exit_stats_element(
+            proc,
+            pid,
+            sampler,
+            args.get_flag(ARG_RAWNUMBERS_NAME),
+        ));
+    }
+
+    Ok(Some(OutputLine::Exit(ExitLine {
+        pid,
+        exe,
+        status,
+        elements,
+    })))
+}
+
+/// Samples the peak memory usage of the processes on `process_registry`
+/// when a new sample is due.
+fn sample_peak_rss(
+    sampler: &mut PeakRssSampler,
+    proc: &ProcFs,
+    process_registry: &HashMap<PID, ExecutedFileName>,
+) {
+    if !sampler.start_sample(Instant::now()) {
+        return;
+    }
+
+    for pid in process_registry.keys() {
+        if let Ok(Some(peak_rss)) = proc.peak_rss_reader(*pid) {
+            sampler.record(*pid, peak_rss);
+        }
+    }
+}
+
+/// Returns the time of an event with the given monotonic `timestamp`, on the
+/// `clock`, or as a wall clock date when a time format is set.
+fn time_element(clock: Clock, timestamp: Duration, args: &ArgMatches) -> String {
+    match time_format(args) {
+        Some(format) => {
+            let time = Clock::Realtime.convert_monotonic(timestamp);
+            format!("[time:{}]", format.format(time))
+        }
+        None => {
+            let time = clock.convert_monotonic(timestamp);
+            format!("[time:{}.{:06}]", time.as_secs(), time.subsec_micros())
+        }
+    }
+}
+
+fn exit_stats_element(
+    proc: &ProcFs,
+    pid: PID,
+    sampler: &mut PeakRssSampler,
+    raw_numbers: bool,
+) -> Option<String> {
+    // The memory of exited processes is usually released by now, so the last
+    // sampled peak is used instead.
+    let sampled_peak_rss = sampler.take(pid);
+    let peak_rss = match proc.peak_rss_reader(pid) {
+        Ok(Some(peak_rss)) => Some(sampled_peak_rss.map_or(peak_rss, |peak| peak.max(peak_rss))),
+        _ => sampled_peak_rss,
+    };
+
+    // The I/O counters of processes from other users cannot be read without
+    // root privileges, so failing to read them is not an error.
+    let io = proc.io_reader(pid).ok();
+
+    let mut stats = Vec::new();
+    if let Some(peak_rss) = peak_rss {
+        stats.push(format!("peak-rss:{}", format_size(peak_rss, raw_numbers)));
+    }
+    if let Some(io) = io {
+        stats.push(format!("read:{}", format_size(io.rchar, raw_numbers)));
+        stats.push(format!("written:{}", format_size(io.wchar, raw_numbers)));
+    }
+    (!stats.is_empty()).then(|| format!("[{}]", stats.join(" ")))
+}
+
+/// Requests the digest of the executed file of an exec `line`. Returns the
+/// hashing job of the digest when it's not known yet, so the line must wait
+/// for it.
+fn hash_output_line(line: &mut OutputLine, proc: &ProcFs, hasher: &mut ExeHasher) -> Option<JobId> {
+    let OutputLine::Exec(line) = line else {
+        return None;
+    };
+
+    let hash = proc
+        .exe_file_reader(line.pid)
+        .map_err(|e| log::debug!("Couldn't open the executable of {}: {}", line.pid, e))
+        .ok()
+        .and_then(|file| {
+            hasher
+                .hash(file)
+                .map_err(|e| log::error!("Couldn't hash the executable of {}: {}", line.pid, e))
+                .ok()
+        });
+    let (digest, job) = match hash {
+        Some(Hash::Ready(digest)) => (ExeDigest::Known(digest), None),
+        Some(Hash::TooLarge) => (ExeDigest::TooLarge, None),
+        Some(Hash::Pending(job)) => (ExeDigest::Pending, Some(job)),
+        None => (ExeDigest::Unavailable, None),
+    };
+    line.digest = Some(digest);
+    job
+}
+
+/// Sets the `digest` of the executed file of an exec `line` that waited for
+/// it. It's `None` when the file couldn't be hashed.
+fn set_line_digest(line: &mut OutputLine, digest: Option<&Digest>) {
+    if let OutputLine::Exec(line) = line {
+        line.digest =
+            Some(digest.map_or(ExeDigest::Unavailable, |digest| ExeDigest::Known(*digest)));
+    }
+}
+
+/// Adds the elements that depend on the digest of the executed file of an
+/// exec `line`, once it's known: the digest itself and the executable lists
+/// violations. The violation actions are run too.
+fn complete_output_line(line: &mut OutputLine, args: &ArgMatches, exe_lists: &ExeLists) {
+    let OutputLine::Exec(line) = line else {
+        return;
+    };
+
+    if args.contains_id(ARG_HASH_NAME) {
+        if let Some(digest) = line.digest.as_ref() {
+            line.elements.push(format!("[sha256:{}]", digest));
+        }
+    }
+
+    let digest = match line.digest {
+        Some(ExeDigest::Known(digest)) => Some(digest),
+        _ => None,
+    };
+    if let Some(violation) = exe_lists.violation(line.path.as_ref(), digest.as_ref()) {
+        line.elements.push(format!("[violation:{}]", violation));
+        line.severity = Severity::Warning;
+        run_violation_actions(line, violation, args);
+    }
+}
+
+/// Sends the violation signal to the process of the exec `line`, and starts
+/// the violation command in the background, if any.
+fn run_violation_actions(line: &ExecLine, violation: Violation, args: &ArgMatches) {
+    if let Some(signal) = args.get_one::<libc::c_int>(ARG_VIOLATIONSIGNAL_NAME) {
+        // Safety: Sending a signal has no memory safety preconditions.
+        if unsafe { libc::kill(line.pid.as_raw(), *signal) } == -1 {
+            log::error!(
+                "Couldn't signal the process {}: {}",
+                line.pid,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    if let Some(command) = args.get_one::<String>(ARG_VIOLATIONCOMMAND_NAME) {
+        let command_line = command;
+        let mut command = std::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg(command_line)
+            .env("COPES_PID", line.pid.to_string())
+            .env("COPES_VIOLATION", violation.to_string());
+        if let Some(exe) = line.exes.last() {
+            command.env("COPES_EXE", exe.encode(Encoding::Lossy).as_ref());
+        }
+        if let Some(path) = line.path.as_ref() {
+            command.env("COPES_PATH", path.as_ref());
+        }
+        thread::spawn(move || match command.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => log::error!("Violation command {}", status),
+            Err(e) => log::error!("Couldn't run the violation command: {}", e),
+        });
+    }
+}
+
+/// Returns the lines to output for `line`.
+///
+/// When executions are coalesced, exec lines are held on `exec_chains` until
+/// their chain is complete. Exit lines complete the chain of their process.
+fn coalesce_output_line(
+    line: OutputLine,
+    args: &ArgMatches,
+    exec_chains: Option<&mut ExecChains<ExecLine>>,
+) -> Vec<OutputLine> {
+    let Some(exec_chains) = exec_chains else {
+        return vec![line];
+    };
+
+    match line {
+        OutputLine::Exec(exec_line) => {
+            exec_chains.push(exec_line.pid, exec_line, Instant::now());
+            Vec::new()
+        }
+        OutputLine::Exit(ExitLine { pid, .. }) => match exec_chains.take(pid) {
+            Some(chain) => vec![OutputLine::Exec(chain_exec_line(chain, args)), line],
+            None => vec![line],
+        },
+    }
+}
+
+fn chain_exec_line(chain: ExecChain<ExecLine>, args: &ArgMatches) -> ExecLine {
+    let show_chain = args
+        .get_one::<String>(ARG_COALESCE_NAME)
+        .is_some_and(|mode| mode == "chain");
+
+    chain
+        .into_execs()
+        .into_iter()
+        .reduce(|mut chain_line, mut exec_line| {
+            if show_chain {
+                chain_line.exes.append(&mut exec_line.exes);
+                exec_line.exes = chain_line.exes;
+            }
+            exec_line.severity = exec_line.severity.max(chain_line.severity);
+            exec_line
+        })
+        .expect("Execution chains are never empty")
+}
+
+/// Settings changed at runtime through the control socket. They are kept when
+/// the configuration is reloaded.
+#[derive(Debug, Default)]
+struct ControlSettings {
+    /// Filter expressions the process events must match too, with their
+    /// text.
+    filters: Vec<(String, Filter)>,
+    /// Whether the process command lines are printed, once toggled.
+    cmdline: Option<bool>,
+}
+
+impl ControlSettings {
+    /// Returns whether the process command lines are printed.
+    fn cmdline(&self, args: &ArgMatches) -> bool {
+        self.cmdline
+            .unwrap_or_else(|| args.get_flag(ARG_CMDLINE_NAME))
+    }
+
+    /// Applies the settings `command`, and returns its reply.
+    fn apply(&mut self, command: &ControlCommand, args: &ArgMatches) -> Reply {
+        match command {
+            ControlCommand::AddFilter(expression) => {
+                let filter = Filter::parse(expression)?;
+                self.filters.push((expression.clone(), filter));
+                Ok(format!("Filter added: {}", expression))
+            }
+            ControlCommand::RemoveFilter(expression) => {
+                let position = self
+                    .filters
+                    .iter()
+                    .position(|(added, _)| added == expression)
+                    .ok_or_else(|| format!("No filter was added with {}", expression))?;
+                self.filters.remove(position);
+                Ok(format!("Filter removed: {}", expression))
+            }
+            ControlCommand::SetFilter(expression) => {
+                let filter = Filter::parse(expression)?;
+                self.filters = vec![(expression.clone(), filter)];
+                Ok(format!("Filter set: {}", expression))
+            }
+            ControlCommand::ClearFilters => {
+                self.filters.clear();
+                Ok("Filters removed".to_string())
+            }
+            ControlCommand::Filters => Ok(args
+                .get_raw(ARG_FILTER_NAME)
+                .into_iter()
+                .flatten()
+                .map(|expression| format!("{} (option)", expression.to_string_lossy()))
+                .chain(
+                    self.filters
+                        .iter()
+                        .map(|(expression, _)| expression.clone()),
+                )
+                .collect::<Vec<_>>()
+                .join("\n")),
+            ControlCommand::ToggleCmdline => {
+                let cmdline = !self.cmdline(args);
+                self.cmdline = Some(cmdline);
+                Ok(match cmdline {
+                    true => "Command lines shown".to_string(),
+                    false => "Command lines hidden".to_string(),
+                })
+            }
+            ControlCommand::Stats
+            | ControlCommand::Pause
+            | ControlCommand::Resume
+            | ControlCommand::TogglePause => Err(format!(
+                "The {} command doesn't change the settings",
+                command
+            )),
+        }
+    }
+}
+
+/// Counters of the received process events, for the statistics dumps.
+#[derive(Debug, Default)]
+struct EventCounters {
+    received: u64,
+    lost: u64,
+    errors: u64,
+}
+
+impl EventCounters {
+    fn record(&mut self, event: &copes::Result<PEventRecord>) {
+        match event {
+            Ok(_) => self.received += 1,
+            Err(e) if e.is_events_lost() => self.lost += 1,
+            Err(_) => self.errors += 1,
+        }
+    }
+}
+
+/// Statistics of a running monitor, dumped on `SIGUSR1` or requested through
+/// the control socket.
+struct Stats<'a> {
+    uptime: Duration,
+    counters: &'a EventCounters,
+    dropped: u64,
+    tracked_processes: usize,
+    cached_processes: usize,
+    summary: &'a Summary,
+}
+
+/// Writes the `stats` to `output`, with the tallies of the `top` most
+/// executed files. Durations are written as seconds with `raw_numbers`.
+fn print_stats(
+    stats: &Stats,
+    top: usize,
+    raw_numbers: bool,
+    output: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+    writeln!(
+        output,
+        "Statistics after {:.0}s: {} received events, {} dropped events, {} lost events \
+         reports, {} receive errors, {} tracked processes, {} cached processes, {} reported \
+         execs, {} reported exits",
+        stats.uptime.as_secs_f64(),
+        stats.counters.received,
+        stats.dropped,
+        stats.counters.lost,
+        stats.counters.errors,
+        stats.tracked_processes,
+        stats.cached_processes,
+        stats.summary.execs(),
+        stats.summary.exits(),
+    )?;
+    for (exe, exe_stats) in stats.summary.top(top) {
+        writeln!(
+            output,
+            "  {}: {} execs, {} exits",
+            exe,
+            exe_stats.execs(),
+            exe_stats.exits()
+        )?;
+    }
+    for (uid, user_stats) in stats.summary.top_users(top) {
+        writeln!(
+            output,
+            "  user {}: {} execs, {} exits, {} total lifetime",
+            user_label(uid),
+            user_stats.execs(),
+            user_stats.exits(),
+            format_duration(user_stats.total_lifetime(), raw_numbers)
+        )?;
+    }
+
+    Ok(())
+}
+
+fn record_output_line(summary: &mut Summary, line: &OutputLine, now: Instant) {
+    match line {
+        OutputLine::Exec(line) => {
+            if let Some(exe) = line.exes.last() {
+                summary.exec_by_user(line.pid, exe.clone(), line.uid, now);
+            }
+        }
+        OutputLine::Exit(line) => summary.exit(line.pid, now),
+    }
+}
+
+/// Process spans exported to an OpenTelemetry collector.
+#[cfg(feature = "otlp")]
+struct ProcessTracing {
+    spans: SpanTracker,
+    exporter: OtlpExporter,
+}
+
+#[cfg(feature = "otlp")]
+impl ProcessTracing {
+    fn spawn(endpoint: OtlpEndpoint) -> std::io::Result<Self> {
+        Ok(ProcessTracing {
+            spans: SpanTracker::new(otlp::random_bytes),
+            exporter: OtlpExporter::spawn(endpoint)?,
+        })
+    }
+
+    /// Starts the span of an executed process, or exports the span of an
+    /// exited one.
+    fn record(&mut self, line: &OutputLine) {
+        let now = SystemTime::now();
+        match line {
+            OutputLine::Exec(line) => {
+                if let Some(exe) = line.exes.last() {
+                    let exec = SpanExec {
+                        pid: line.pid,
+                        ppid: line.ppid,
+                        name: exe.to_string(),
+                        path: line
+                            .path
+                            .as_ref()
+                            .map(|path| path.as_ref().to_string_lossy().into_owned()),
+                        uid: line.uid,
+                    };
+                    self.spans.exec(exec, now);
+                }
+            }
+            OutputLine::Exit(line) => {
+                if let Some(span) = self.spans.exit(line.pid, now) {
+                    self.exporter.export(span);
+                }
+            }
+        }
+    }
+
+    /// Exports the spans of the running processes, ending them now, and
+    /// waits until all the spans are sent.
+    fn finish(mut self) {
+        for span in self.spans.take_all(SystemTime::now()) {
+            self.exporter.export(span);
+        }
+    }
+}
+
+/// Writes a table with a left aligned first column, and right aligned
+/// others, to `text`.
+fn write_table(text: &mut String, header: &[String; 4], rows: &[[String; 4]]) {
+    let mut widths = header.clone().map(|title| title.chars().count());
+    for row in rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    for row in std::iter::once(header).chain(rows) {
+        writeln!(
+            text,
+            "{:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+        )
+        .unwrap();
+    }
+}
+
+/// Returns the name and ID of the user with the given `uid`, like
+/// `alice (1000)`.
+fn user_label(uid: Option<PUid>) -> String {
+    match uid.map(|uid| (uid, io::users::user_name(uid))) {
+        Some((uid, Some(name))) => format!("{} ({})", name, uid),
+        Some((uid, None)) => uid.to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+fn print_summary(
+    summary: &Summary,
+    dropped: u64,
+    args: &ArgMatches,
+    output: &mut dyn Sink,
+) -> std::io::Result<()> {
+    let count = *args.get_one::<usize>(ARG_SUMMARYTOP_NAME).unwrap();
+    let raw_numbers = args.get_flag(ARG_RAWNUMBERS_NAME);
+    let header = ["Executable", "Execs", "Exits", "Avg. lifetime"].map(String::from);
+    let rows = summary
+        .top(count)
+        .into_iter()
+        .map(|(exe, stats)| {
+            [
+                exe.encode(encoding(args)).into_owned(),
+                stats.execs().to_string(),
+                stats.exits().to_string(),
+                stats
+                    .average_lifetime()
+                    .map_or("-".to_string(), |lifetime| {
+                        format_duration(lifetime, raw_numbers)
+                    }),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut text = String::from("\n");
+    write_table(&mut text, &header, &rows);
+    if args.get_flag(ARG_SUMMARYUSERS_NAME) {
+        let header = ["User", "Execs", "Exits", "Total lifetime"].map(String::from);
+        let rows = summary
+            .top_users(count)
+            .into_iter()
+            .map(|(uid, stats)| {
+                [
+                    user_label(uid),
+                    stats.execs().to_string(),
+                    stats.exits().to_string(),
+                    format_duration(stats.total_lifetime(), raw_numbers),
+                ]
+            })
+            .collect::<Vec<_>>();
+        text.push('\n');
+        write_table(&mut text, &header, &rows);
+    }
+    write!(
+        text,
+        "\nTotal: {} execs, {} exits",
+        summary.execs(),
+        summary.exits()
+    )
+    .unwrap();
+    if dropped > 0 {
+        write!(text, ", {} dropped events", dropped).unwrap();
+    }
+    output.write_message(&text)
+}
+
+fn print_histograms(
+    summary: &Summary,
+    args: &ArgMatches,
+    output: &mut dyn Sink,
+) -> std::io::Result<()> {
+    let count = *args.get_one::<usize>(ARG_SUMMARYTOP_NAME).unwrap();
+    let mut text = String::new();
+    for (exe, stats) in summary.top(count) {
+        let histogram = stats.histogram();
+        let Some(max) = histogram
+            .buckets()
+            .map(|(_, count)| count)
+            .max()
+            .filter(|max| *max > 0)
+        else {
+            continue;
+        };
+
+        writeln!(
+            text,
+            "\n{} ({} exits)",
+            exe.encode(encoding(args)),
+            histogram.count()
+        )
+        .unwrap();
+        for (bound, count) in histogram.buckets() {
+            let label = match bound {
+                Some(bound) => format!("< {}", format_lifetime_bound(bound)),
+                None => format!(
+                    ">= {}",
+                    format_lifetime_bound(LIFETIME_BOUNDS[LIFETIME_BOUNDS.len() - 1])
+                ),
+            };
+            let bar = "#".repeat((count * HISTOGRAM_BAR_WIDTH / max) as usize);
+            writeln!(
+                text,
+                "  {:<7} |{:<width$}| {}",
+                label,
+                bar,
+                count,
+                width = HISTOGRAM_BAR_WIDTH as usize
+            )
+            .unwrap();
+        }
+    }
+
+    match text.is_empty() {
+        true => Ok(()),
+        false => output.write_message(text.trim_end()),
+    }
+}
+
+/// Formats a size human readably, like `512 B` or `1.4 GiB`, or as a number
+/// of bytes with `raw`.
+fn format_size(bytes: u64, raw: bool) -> String {
+    const UNITS: [(u64, &str); 4] = [
+        (1 << 40, "TiB"),
+        (1 << 30, "GiB"),
+        (1 << 20, "MiB"),
+        (1 << 10, "KiB"),
+    ];
+
+    match UNITS.iter().find(|(size, _)| bytes >= *size) {
+        _ if raw => bytes.to_string(),
+        Some((size, unit)) => format!("{:.1} {}", bytes as f64 / *size as f64, unit),
+        None => format!("{} B", bytes),
+    }
+}
+
+/// Formats a duration human readably, like `850ms`, `12.3s`, `2m13s` or
+/// `1h02m`, or as a number of seconds with `raw`.
+fn format_duration(duration: Duration, raw: bool) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        _ if raw => format!("{:.3}", duration.as_secs_f64()),
+        0 if duration.as_millis() == 0 => format!("{}µs", duration.as_micros()),
+        0 => format!("{}ms", duration.as_millis()),
+        1..=59 => format!("{:.1}s", duration.as_secs_f64()),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        3600..=86399 => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d{:02}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+fn format_lifetime_bound(bound: Duration) -> String {
+    if bound < Duration::from_secs(1) {
+        format!("{}ms", bound.as_millis())
+    } else {
+        format!("{}s", bound.as_secs())
+    }
+}
+
+fn print_output_line(
+    line: OutputLine,
+    args: &ArgMatches,
+    rate_limiter: Option<&mut RateLimiter<SuppressedLineKey>>,
+    printed_lines: &mut u64,
+    output: &mut dyn Sink,
+) -> std::io::Result<()> {
+    if args.get_flag(ARG_NOEVENTS_NAME) || is_count_reached(args, *printed_lines) {
+        return Ok(());
+    }
+
+    if let Some(rate_limiter) = rate_limiter {
+        let now = Instant::now();
+        print_suppressed_lines(rate_limiter.take_suppressed(now), output)?;
+
+        let key = match &line {
+            OutputLine::Exec(line) => ("execs", line.exes.last().cloned().unwrap_or_default()),
+            OutputLine::Exit(line) => ("exits", line.exe.clone()),
+        };
+        if !rate_limiter.allow(key, now) {
+            return Ok(());
+        }
+    }
+
+    output.write_event(&line.event_line(encoding(args)))?;
+    *printed_lines += 1;
+
+    Ok(())
+}
+
+fn is_count_reached(args: &ArgMatches, printed_lines: u64) -> bool {
+    args.get_one::<u64>(ARG_COUNT_NAME)
+        .is_some_and(|count| printed_lines >= *count)
+}
+
+fn print_suppressed_lines(
+    suppressed: Vec<(SuppressedLineKey, u64)>,
+    output: &mut dyn Sink,
+) -> std::io::Result<()> {
+    for ((kind, exe), count) in suppressed {
+        output.write_message(&format!("... and {} more {} {}", count, exe, kind))?;
+    }
+
+    Ok(())
 }
@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Process events reporting utilities.
+
+pub mod exec_chains;
+pub mod histogram;
+pub mod peak_rss;
+pub mod pending;
+pub mod rate_limit;
+#[cfg(feature = "otlp")]
+pub mod spans;
+pub mod summary;
+
+pub use exec_chains::{ExecChain, ExecChains};
+pub use histogram::LifetimeHistogram;
+pub use peak_rss::PeakRssSampler;
+pub use pending::PendingLines;
+pub use rate_limit::RateLimiter;
+#[cfg(feature = "otlp")]
+pub use spans::{Span, SpanExec, SpanTracker};
+pub use summary::{ExeStats, Summary};
@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Coalescing of consecutive executions of the same process.
+//!
+//! Processes can execute several files in a row, such as a shell script that
+//! runs `env`, which in turn executes the real program. Each execution
+//! produces an exec event for the same PID.
+
+use std::time::{Duration, Instant};
+
+use crate::solver::PID;
+
+/// Consecutive executions of a process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecChain<T> {
+    pid: PID,
+    execs: Vec<T>,
+    last_exec: Instant,
+}
+
+impl<T> ExecChain<T> {
+    /// Returns the PID of the process.
+    pub fn pid(&self) -> PID {
+        self.pid
+    }
+
+    /// Returns the executions of the process, from the first to the last one.
+    pub fn execs(&self) -> &[T] {
+        &self.execs
+    }
+
+    /// Returns the executions of the process, from the first to the last one.
+    pub fn into_execs(self) -> Vec<T> {
+        self.execs
+    }
+}
+
+/// Pending executions chains of processes.
+///
+/// Executions of the same process are chained while they happen within a
+/// time window from the previous one. Once the window elapses, the chain is
+/// complete.
+#[derive(Debug)]
+pub struct ExecChains<T> {
+    window: Duration,
+    chains: Vec<ExecChain<T>>,
+}
+
+impl<T> ExecChains<T> {
+    /// Creates an empty `ExecChains` that chains executions happening within
+    /// `window` from the previous one.
+    pub fn new(window: Duration) -> Self {
+        ExecChains {
+            window,
+            chains: Vec::new(),
+        }
+    }
+
+    /// Adds an execution of the process with the given `pid` that happened
+    /// at `now`.
+    pub fn push(&mut self, pid: PID, exec: T, now: Instant) {
+        match self.chains.iter_mut().find(|chain| chain.pid == pid) {
+            Some(chain) => {
+                chain.execs.push(exec);
+                chain.last_exec = now;
+            }
+            None => self.chains.push(ExecChain {
+                pid,
+                execs: vec![exec],
+                last_exec: now,
+            }),
+        }
+    }
+
+    /// Removes and returns the pending chain of the process with the given
+    /// `pid`, if any.
+    pub fn take(&mut self, pid: PID) -> Option<ExecChain<T>> {
+        let position = self.chains.iter().position(|chain| chain.pid == pid)?;
+        Some(self.chains.remove(position))
+    }
+
+    /// Removes and returns the chains whose time window elapsed at `now`, in
+    /// the order they were started.
+    pub fn take_complete(&mut self, now: Instant) -> Vec<ExecChain<T>> {
+        let (complete, pending) = self
+            .chains
+            .drain(..)
+            .partition(|chain| now.saturating_duration_since(chain.last_exec) >= self.window);
+        self.chains = pending;
+        complete
+    }
+
+    /// Removes and returns all the pending chains, in the order they were
+    /// started.
+    pub fn take_all(&mut self) -> Vec<ExecChain<T>> {
+        self.chains.drain(..).collect()
+    }
+
+    /// Returns `true` when there are no pending chains.
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn push_chains_executions_of_the_same_process() {
+        let now = Instant::now();
+        let mut chains = ExecChains::new(WINDOW);
+        chains.push(PID::from(1), "sh", now);
+        chains.push(PID::from(2), "cat", now);
+        chains.push(PID::from(1), "env", now + Duration::from_millis(10));
+        chains.push(PID::from(1), "game", now + Duration::from_millis(20));
+
+        let chain = chains.take(PID::from(1)).unwrap();
+        assert_eq!(PID::from(1), chain.pid());
+        assert_eq!(&["sh", "env", "game"], chain.execs());
+
+        let chain = chains.take(PID::from(2)).unwrap();
+        assert_eq!(&["cat"], chain.execs());
+
+        assert!(chains.is_empty());
+        assert_eq!(None, chains.take(PID::from(1)));
+    }
+
+    #[test]
+    fn take_complete_returns_chains_whose_window_elapsed() {
+        let now = Instant::now();
+        let mut chains = ExecChains::new(WINDOW);
+        chains.push(PID::from(1), "sh", now);
+        chains.push(PID::from(2), "cat", now + Duration::from_millis(10));
+        chains.push(PID::from(3), "sh", now + Duration::from_millis(20));
+        chains.push(PID::from(1), "game", now + Duration::from_millis(50));
+
+        let complete = chains.take_complete(now + Duration::from_millis(90));
+        assert!(complete.is_empty());
+
+        let complete = chains.take_complete(now + Duration::from_millis(120));
+        let pids = complete.iter().map(ExecChain::pid).collect::<Vec<_>>();
+        assert_eq!(vec![PID::from(2), PID::from(3)], pids);
+
+        let complete = chains.take_complete(now + Duration::from_millis(150));
+        assert_eq!(1, complete.len());
+        assert_eq!(&["sh", "game"], complete[0].execs());
+        assert!(chains.is_empty());
+    }
+
+    #[test]
+    fn take_all_returns_all_pending_chains_in_start_order() {
+        let now = Instant::now();
+        let mut chains = ExecChains::new(WINDOW);
+        chains.push(PID::from(2), "cat", now);
+        chains.push(PID::from(1), "sh", now);
+        chains.push(PID::from(2), "env", now);
+
+        let pids = chains
+            .take_all()
+            .iter()
+            .map(ExecChain::pid)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![PID::from(2), PID::from(1)], pids);
+        assert!(chains.is_empty());
+    }
+}
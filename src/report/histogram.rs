@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Histograms of process lifetimes.
+
+use std::time::Duration;
+
+/// Upper bounds of the lifetime histogram buckets. A last bucket holds the
+/// lifetimes greater than or equal to the last bound.
+pub const LIFETIME_BOUNDS: [Duration; 6] = [
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+];
+
+/// Histogram of process lifetimes, on logarithmic scale buckets.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LifetimeHistogram([u64; LIFETIME_BOUNDS.len() + 1]);
+
+impl LifetimeHistogram {
+    /// Records a process `lifetime`.
+    pub fn record(&mut self, lifetime: Duration) {
+        let bucket = LIFETIME_BOUNDS
+            .iter()
+            .position(|bound| lifetime < *bound)
+            .unwrap_or(LIFETIME_BOUNDS.len());
+        self.0[bucket] += 1;
+    }
+
+    /// Returns the buckets of the histogram as pairs of upper bound and
+    /// count, from the shortest to the longest lifetimes. The last bucket has
+    /// no upper bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        LIFETIME_BOUNDS
+            .iter()
+            .copied()
+            .map(Some)
+            .chain([None])
+            .zip(self.0.iter().copied())
+    }
+
+    /// Returns the number of recorded lifetimes.
+    pub fn count(&self) -> u64 {
+        self.0.iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_counts_lifetimes_on_their_buckets() {
+        let mut histogram = LifetimeHistogram::default();
+        histogram.record(Duration::from_micros(500));
+        histogram.record(Duration::from_millis(1));
+        histogram.record(Duration::from_millis(5));
+        histogram.record(Duration::from_secs(2));
+        histogram.record(Duration::from_secs(3600));
+
+        let buckets = histogram.buckets().collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (Some(Duration::from_millis(1)), 1),
+                (Some(Duration::from_millis(10)), 2),
+                (Some(Duration::from_millis(100)), 0),
+                (Some(Duration::from_secs(1)), 0),
+                (Some(Duration::from_secs(10)), 1),
+                (Some(Duration::from_secs(60)), 0),
+                (None, 1),
+            ],
+            buckets
+        );
+        assert_eq!(5, histogram.count());
+    }
+}
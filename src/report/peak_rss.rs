@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Peak memory usage sampling.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::solver::PID;
+
+/// Keeps the peak resident set size sampled from running processes.
+///
+/// The memory of a process is released before its exit is reported, so its
+/// peak resident set size must be sampled while it's still running. Processes
+/// exiting before being sampled have no peak resident set size.
+#[derive(Debug)]
+pub struct PeakRssSampler {
+    interval: Duration,
+    last_sample: Option<Instant>,
+    peaks: HashMap<PID, u64>,
+}
+
+impl PeakRssSampler {
+    /// Creates a `PeakRssSampler` sampling the processes every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        PeakRssSampler {
+            interval,
+            last_sample: None,
+            peaks: HashMap::new(),
+        }
+    }
+
+    /// Returns `true` when the processes must be sampled at `now`, and starts
+    /// a new sampling interval then.
+    pub fn start_sample(&mut self, now: Instant) -> bool {
+        let due = self.last_sample.map_or(true, |last_sample| {
+            now.saturating_duration_since(last_sample) >= self.interval
+        });
+        if due {
+            self.last_sample = Some(now);
+        }
+        due
+    }
+
+    /// Records the `peak_rss` sampled from the process with the given `pid`,
+    /// in bytes.
+    pub fn record(&mut self, pid: PID, peak_rss: u64) {
+        let peak = self.peaks.entry(pid).or_default();
+        *peak = (*peak).max(peak_rss);
+    }
+
+    /// Removes and returns the peak resident set size of the process with the
+    /// given `pid`, in bytes.
+    pub fn take(&mut self, pid: PID) -> Option<u64> {
+        self.peaks.remove(&pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn start_sample_is_due_once_per_interval() {
+        let now = Instant::now();
+        let mut sampler = PeakRssSampler::new(INTERVAL);
+        assert!(sampler.start_sample(now));
+        assert!(!sampler.start_sample(now + Duration::from_millis(500)));
+        assert!(sampler.start_sample(now + INTERVAL));
+        assert!(!sampler.start_sample(now + INTERVAL));
+    }
+
+    #[test]
+    fn take_returns_the_highest_recorded_peak() {
+        let mut sampler = PeakRssSampler::new(INTERVAL);
+        sampler.record(PID::from(1234), 2048);
+        sampler.record(PID::from(1234), 1024);
+        sampler.record(PID::from(5678), 4096);
+
+        assert_eq!(Some(2048), sampler.take(PID::from(1234)));
+        assert_eq!(None, sampler.take(PID::from(1234)));
+        assert_eq!(Some(4096), sampler.take(PID::from(5678)));
+    }
+}
@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Output lines waiting for some information that is obtained in the
+//! background, like the digest of an executable.
+//!
+//! Lines are released in the order they were pushed, so a line waiting for
+//! a job also holds back the following lines.
+
+use std::collections::VecDeque;
+
+/// Queue of output lines, some of them waiting for the completion of a job.
+#[derive(Debug)]
+pub struct PendingLines<T, J> {
+    lines: VecDeque<(T, Option<J>)>,
+}
+
+impl<T, J> Default for PendingLines<T, J> {
+    fn default() -> Self {
+        PendingLines {
+            lines: VecDeque::new(),
+        }
+    }
+}
+
+impl<T, J: PartialEq> PendingLines<T, J> {
+    /// Creates an empty `PendingLines`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a `line`, waiting for the given `job`, if any.
+    pub fn push(&mut self, line: T, job: Option<J>) {
+        self.lines.push_back((line, job));
+    }
+
+    /// Completes the `job`, updating the lines waiting for it with
+    /// `complete`.
+    pub fn complete(&mut self, job: &J, mut complete: impl FnMut(&mut T)) {
+        for (line, line_job) in self.lines.iter_mut() {
+            if line_job.as_ref() == Some(job) {
+                complete(line);
+                *line_job = None;
+            }
+        }
+    }
+
+    /// Removes and returns the lines that are not waiting for a job, up to
+    /// the first one that is.
+    pub fn take_ready(&mut self) -> Vec<T> {
+        let ready = self
+            .lines
+            .iter()
+            .position(|(_, job)| job.is_some())
+            .unwrap_or(self.lines.len());
+        self.lines.drain(..ready).map(|(line, _)| line).collect()
+    }
+
+    /// Removes and returns all the lines, updating the ones still waiting
+    /// for a job with `incomplete`.
+    pub fn take_all(&mut self, mut incomplete: impl FnMut(&mut T)) -> Vec<T> {
+        self.lines
+            .drain(..)
+            .map(|(mut line, job)| {
+                if job.is_some() {
+                    incomplete(&mut line);
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Returns `true` when there are no lines.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_lines_are_released_in_order() {
+        let mut lines = PendingLines::new();
+        lines.push("a".to_string(), None);
+        lines.push("b".to_string(), Some(1));
+        lines.push("c".to_string(), None);
+        lines.push("d".to_string(), Some(2));
+        assert_eq!(vec!["a"], lines.take_ready());
+
+        lines.complete(&2, |line| line.push('2'));
+        assert!(lines.take_ready().is_empty());
+        lines.complete(&1, |line| line.push('1'));
+        assert_eq!(vec!["b1", "c", "d2"], lines.take_ready());
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn take_all_releases_incomplete_lines() {
+        let mut lines = PendingLines::new();
+        lines.push("a".to_string(), Some(1));
+        lines.push("b".to_string(), None);
+        assert_eq!(vec!["a?", "b"], lines.take_all(|line| line.push('?')));
+    }
+}
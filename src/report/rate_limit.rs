@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Output rate limiting.
+
+use std::time::{Duration, Instant};
+
+/// Limits the number of items allowed on each time period.
+///
+/// Items exceeding the limit are suppressed and counted by their key, so
+/// they can be reported once the period ends.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    limit: u32,
+    period: Duration,
+    period_start: Instant,
+    allowed: u32,
+    suppressed: Vec<(K, u64)>,
+}
+
+impl<K: PartialEq> RateLimiter<K> {
+    /// Creates a `RateLimiter` allowing `limit` items on each `period`,
+    /// starting at `now`.
+    pub fn new(limit: u32, period: Duration, now: Instant) -> Self {
+        RateLimiter {
+            limit,
+            period,
+            period_start: now,
+            allowed: 0,
+            suppressed: Vec::new(),
+        }
+    }
+
+    /// Returns `true` when an item with the given `key` is allowed at `now`.
+    /// Otherwise, the item is counted as suppressed.
+    ///
+    /// Items suppressed on previous periods must be taken with
+    /// [`RateLimiter::take_suppressed`] before the limit is reset.
+    pub fn allow(&mut self, key: K, now: Instant) -> bool {
+        if self.suppressed.is_empty() && self.period_elapsed(now) {
+            self.period_start = now;
+            self.allowed = 0;
+        }
+
+        if self.allowed < self.limit {
+            self.allowed += 1;
+            return true;
+        }
+
+        match self.suppressed.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, count)) => *count += 1,
+            None => self.suppressed.push((key, 1)),
+        }
+        false
+    }
+
+    /// Removes and returns the counts of the suppressed items once the
+    /// current period elapsed at `now`, sorted by count.
+    pub fn take_suppressed(&mut self, now: Instant) -> Vec<(K, u64)> {
+        if !self.period_elapsed(now) {
+            return Vec::new();
+        }
+
+        self.period_start = now;
+        self.allowed = 0;
+        self.take_all_suppressed()
+    }
+
+    /// Removes and returns the counts of the suppressed items, sorted by
+    /// count.
+    pub fn take_all_suppressed(&mut self) -> Vec<(K, u64)> {
+        let mut suppressed = self.suppressed.drain(..).collect::<Vec<_>>();
+        suppressed.sort_by(|(_, a), (_, b)| b.cmp(a));
+        suppressed
+    }
+
+    fn period_elapsed(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.period_start) >= self.period
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn allow_allows_items_up_to_the_limit_on_each_period() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(2, PERIOD, now);
+        assert!(limiter.allow("cc1", now));
+        assert!(limiter.allow("cc1", now));
+        assert!(!limiter.allow("cc1", now));
+        assert!(!limiter.allow("ld", now + Duration::from_millis(500)));
+
+        let later = now + PERIOD;
+        assert_eq!(vec![("cc1", 1), ("ld", 1)], limiter.take_suppressed(later));
+        assert!(limiter.allow("cc1", later));
+        assert!(limiter.allow("cc1", later));
+        assert!(!limiter.allow("cc1", later));
+    }
+
+    #[test]
+    fn allow_resets_the_limit_when_nothing_was_suppressed() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(1, PERIOD, now);
+        assert!(limiter.allow("cc1", now));
+        assert!(limiter.allow("cc1", now + PERIOD));
+        assert!(!limiter.allow("cc1", now + PERIOD));
+    }
+
+    #[test]
+    fn take_suppressed_returns_nothing_until_the_period_elapses() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(1, PERIOD, now);
+        assert!(limiter.allow("cc1", now));
+        assert!(!limiter.allow("cc1", now));
+        assert!(limiter
+            .take_suppressed(now + Duration::from_millis(999))
+            .is_empty());
+        assert_eq!(vec![("cc1", 1)], limiter.take_suppressed(now + PERIOD));
+        assert!(limiter.take_suppressed(now + PERIOD * 2).is_empty());
+    }
+
+    #[test]
+    fn take_all_suppressed_returns_counts_sorted_by_count() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(0, PERIOD, now);
+        for key in ["ld", "cc1", "cc1", "as", "cc1", "as"] {
+            assert!(!limiter.allow(key, now));
+        }
+        assert_eq!(
+            vec![("cc1", 3), ("as", 2), ("ld", 1)],
+            limiter.take_all_suppressed()
+        );
+    }
+}
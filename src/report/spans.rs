@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Tracing spans of the process lifetimes.
+//!
+//! Each process is a span that starts when it's executed and ends when it
+//! exits. The span of a process is a child of the span of its parent
+//! process, when it's known, so the process trees are traces.
+
+use std::{collections::HashMap, fmt, time::SystemTime};
+
+use crate::solver::{PUid, PID};
+
+/// Identifier of a trace.
+pub type TraceId = [u8; 16];
+
+/// Identifier of a span.
+pub type SpanId = [u8; 8];
+
+/// Source of the random trace and span identifiers.
+type RandomSource = Box<dyn FnMut(&mut [u8])>;
+
+/// Lifetime of a process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    /// Span of the parent process, if it was traced.
+    pub parent_span_id: Option<SpanId>,
+    /// Name of the last executed file of the process.
+    pub name: String,
+    /// Path of the last executed file of the process, if known.
+    pub path: Option<String>,
+    pub pid: PID,
+    pub ppid: Option<PID>,
+    /// Real user ID of the process, if known.
+    pub uid: Option<PUid>,
+    pub start: SystemTime,
+    /// End of the span. Spans still open end when they are taken.
+    pub end: SystemTime,
+}
+
+/// Information of an executed process, to start or update its span.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanExec {
+    pub pid: PID,
+    pub ppid: Option<PID>,
+    pub name: String,
+    pub path: Option<String>,
+    pub uid: Option<PUid>,
+}
+
+/// Spans of the running processes.
+pub struct SpanTracker {
+    open: HashMap<PID, Span>,
+    random: RandomSource,
+}
+
+impl SpanTracker {
+    /// Creates a `SpanTracker` filling the trace and span identifiers with
+    /// the `random` function.
+    pub fn new(random: impl FnMut(&mut [u8]) + 'static) -> Self {
+        SpanTracker {
+            open: HashMap::new(),
+            random: Box::new(random),
+        }
+    }
+
+    /// Starts the span of a process executed at `now`. Processes executing
+    /// another file keep their span, with the new file name and path.
+    pub fn exec(&mut self, exec: SpanExec, now: SystemTime) {
+        if let Some(span) = self.open.get_mut(&exec.pid) {
+            span.name = exec.name;
+            span.path = exec.path;
+            span.uid = exec.uid.or(span.uid);
+            return;
+        }
+
+        let parent = exec
+            .ppid
+            .and_then(|ppid| self.open.get(&ppid))
+            .map(|parent| (parent.trace_id, parent.span_id));
+        let mut span_id = SpanId::default();
+        (self.random)(&mut span_id);
+        let trace_id = match parent {
+            Some((trace_id, _)) => trace_id,
+            None => {
+                let mut trace_id = TraceId::default();
+                (self.random)(&mut trace_id);
+                trace_id
+            }
+        };
+        self.open.insert(
+            exec.pid,
+            Span {
+                trace_id,
+                span_id,
+                parent_span_id: parent.map(|(_, span_id)| span_id),
+                name: exec.name,
+                path: exec.path,
+                pid: exec.pid,
+                ppid: exec.ppid,
+                uid: exec.uid,
+                start: now,
+                end: now,
+            },
+        );
+    }
+
+    /// Ends and returns the span of the process with the given `pid`, that
+    /// exited at `now`, if it was open.
+    pub fn exit(&mut self, pid: PID, now: SystemTime) -> Option<Span> {
+        let mut span = self.open.remove(&pid)?;
+        span.end = now;
+        Some(span)
+    }
+
+    /// Ends at `now` and returns all the open spans.
+    pub fn take_all(&mut self, now: SystemTime) -> Vec<Span> {
+        self.open
+            .drain()
+            .map(|(_, mut span)| {
+                span.end = now;
+                span
+            })
+            .collect()
+    }
+
+    /// Discards the open spans of the processes not matching `keep`, like
+    /// the ones whose exit was missed.
+    pub fn retain(&mut self, mut keep: impl FnMut(PID) -> bool) {
+        self.open.retain(|pid, _| keep(*pid));
+    }
+
+    /// Returns the number of open spans.
+    pub fn len(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Returns `true` when there are no open spans.
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty()
+    }
+}
+
+impl fmt::Debug for SpanTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpanTracker")
+            .field("open", &self.open.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn tracker() -> SpanTracker {
+        let mut next = 0u8;
+        SpanTracker::new(move |bytes| {
+            next += 1;
+            bytes.fill(next);
+        })
+    }
+
+    fn exec(pid: i32, ppid: i32, name: &str) -> SpanExec {
+        SpanExec {
+            pid: PID::from(pid),
+            ppid: Some(PID::from(ppid)),
+            name: name.to_string(),
+            path: None,
+            uid: Some(PUid::from(1000)),
+        }
+    }
+
+    #[test]
+    fn child_processes_spans_are_on_their_parent_trace() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let mut spans = tracker();
+        spans.exec(exec(10, 1, "launcher"), start);
+        spans.exec(exec(11, 10, "game"), start);
+        spans.exec(exec(12, 99, "other"), start);
+        assert_eq!(3, spans.len());
+
+        let game = spans
+            .exit(PID::from(11), start + Duration::from_secs(5))
+            .unwrap();
+        let launcher = spans
+            .exit(PID::from(10), start + Duration::from_secs(6))
+            .unwrap();
+        let other = spans.exit(PID::from(12), start).unwrap();
+        assert_eq!(launcher.trace_id, game.trace_id);
+        assert_eq!(Some(launcher.span_id), game.parent_span_id);
+        assert_eq!(None, launcher.parent_span_id);
+        assert_ne!(launcher.trace_id, other.trace_id);
+        assert_ne!(launcher.span_id, game.span_id);
+        assert_eq!(start + Duration::from_secs(5), game.end);
+        assert!(spans.is_empty());
+        assert_eq!(None, spans.exit(PID::from(11), start));
+    }
+
+    #[test]
+    fn executing_another_file_keeps_the_span() {
+        let start = SystemTime::UNIX_EPOCH;
+        let mut spans = tracker();
+        spans.exec(exec(10, 1, "sh"), start);
+        spans.exec(exec(10, 1, "game"), start + Duration::from_secs(1));
+
+        let now = start + Duration::from_secs(2);
+        let all = spans.take_all(now);
+        assert_eq!(1, all.len());
+        assert_eq!("game", all[0].name);
+        assert_eq!(start, all[0].start);
+        assert_eq!(now, all[0].end);
+    }
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Statistics of the executed files and of the users running them.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use super::LifetimeHistogram;
+use crate::solver::{ExecutedFileName, PUid, PID};
+
+/// Statistics of an executed file, or of the files executed by a user.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExeStats {
+    execs: u64,
+    exits: u64,
+    lifetime: Duration,
+    histogram: LifetimeHistogram,
+}
+
+impl ExeStats {
+    /// Returns the number of times the file was executed.
+    pub fn execs(&self) -> u64 {
+        self.execs
+    }
+
+    /// Returns the number of processes running the file that exited.
+    pub fn exits(&self) -> u64 {
+        self.exits
+    }
+
+    /// Returns the cumulative lifetime of the processes running the file
+    /// that exited.
+    pub fn total_lifetime(&self) -> Duration {
+        self.lifetime
+    }
+
+    /// Returns the average lifetime of the processes running the file that
+    /// exited.
+    pub fn average_lifetime(&self) -> Option<Duration> {
+        let exits = u32::try_from(self.exits).ok().filter(|exits| *exits > 0)?;
+        Some(self.lifetime / exits)
+    }
+
+    /// Returns the lifetimes histogram of the processes running the file
+    /// that exited.
+    pub fn histogram(&self) -> &LifetimeHistogram {
+        &self.histogram
+    }
+}
+
+/// Summary of the executed files of the monitored processes.
+#[derive(Debug, Default)]
+pub struct Summary {
+    execs: u64,
+    exits: u64,
+    exes: HashMap<ExecutedFileName, ExeStats>,
+    /// Statistics by real user ID, `None` for processes whose user is
+    /// unknown.
+    users: HashMap<Option<PUid>, ExeStats>,
+    running: HashMap<PID, Running>,
+}
+
+/// A process with a recorded execution.
+#[derive(Debug)]
+struct Running {
+    exe: ExecutedFileName,
+    uid: Option<PUid>,
+    exec_time: Instant,
+}
+
+impl Summary {
+    /// Creates an empty `Summary`.
+    pub fn new() -> Self {
+        Summary::default()
+    }
+
+    /// Records the execution of `exe` by the process with the given `pid` at
+    /// `time`.
+    ///
+    /// The lifetime of a process that executes another file is accounted to
+    /// the last file it executed, starting from that execution.
+    pub fn exec(&mut self, pid: PID, exe: ExecutedFileName, time: Instant) {
+        self.exec_by_user(pid, exe, None, time);
+    }
+
+    /// Records the execution of `exe` by the process with the given `pid`
+    /// and real user ID `uid` at `time`, like [`Summary::exec`] does.
+    pub fn exec_by_user(
+        &mut self,
+        pid: PID,
+        exe: ExecutedFileName,
+        uid: Option<PUid>,
+        time: Instant,
+    ) {
+        self.execs += 1;
+        self.exes.entry(exe.clone()).or_default().execs += 1;
+        self.users.entry(uid).or_default().execs += 1;
+        self.running.insert(
+            pid,
+            Running {
+                exe,
+                uid,
+                exec_time: time,
+            },
+        );
+    }
+
+    /// Records the exit of the process with the given `pid` at `time`.
+    ///
+    /// Exits of processes without a recorded execution are ignored.
+    pub fn exit(&mut self, pid: PID, time: Instant) {
+        if let Some(running) = self.running.remove(&pid) {
+            self.exits += 1;
+            let lifetime = time.saturating_duration_since(running.exec_time);
+            for stats in [
+                self.exes.entry(running.exe).or_default(),
+                self.users.entry(running.uid).or_default(),
+            ] {
+                stats.exits += 1;
+                stats.lifetime += lifetime;
+                stats.histogram.record(lifetime);
+            }
+        }
+    }
+
+    /// Returns the total number of recorded executions.
+    pub fn execs(&self) -> u64 {
+        self.execs
+    }
+
+    /// Returns the total number of recorded exits.
+    pub fn exits(&self) -> u64 {
+        self.exits
+    }
+
+    /// Returns the statistics of the `count` most executed files, sorted by
+    /// their number of executions.
+    pub fn top(&self, count: usize) -> Vec<(&ExecutedFileName, &ExeStats)> {
+        let mut exes = self.exes.iter().collect::<Vec<_>>();
+        exes.sort_by(|(exe_a, stats_a), (exe_b, stats_b)| {
+            (Reverse(stats_a.execs), exe_a.as_ref()).cmp(&(Reverse(stats_b.execs), exe_b.as_ref()))
+        });
+        exes.truncate(count);
+        exes
+    }
+
+    /// Returns the statistics of the `count` users that executed more files,
+    /// sorted by their number of executions. The user is `None` for the
+    /// processes whose user is unknown, which are sorted last on ties.
+    pub fn top_users(&self, count: usize) -> Vec<(Option<PUid>, &ExeStats)> {
+        let mut users = self
+            .users
+            .iter()
+            .map(|(uid, stats)| (*uid, stats))
+            .collect::<Vec<_>>();
+        // Unknown users go last
+        users.sort_by_key(|(uid, stats)| {
+            (
+                Reverse(stats.execs),
+                uid.is_none(),
+                uid.map(|uid| *uid.as_ref()),
+            )
+        });
+        users.truncate(count);
+        users
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::*;
+    use crate::solver::PExe;
+
+    fn exe(name: &str) -> ExecutedFileName {
+        ExecutedFileName::from(PExe::from(OsString::from(name)))
+    }
+
+    #[test]
+    fn summary_counts_execs_and_exits() {
+        let now = Instant::now();
+        let mut summary = Summary::new();
+        summary.exec(PID::from(1), exe("cc1"), now);
+        summary.exec(PID::from(2), exe("cc1"), now);
+        summary.exec(PID::from(3), exe("ld"), now);
+        summary.exit(PID::from(1), now);
+        summary.exit(PID::from(4), now);
+
+        assert_eq!(3, summary.execs());
+        assert_eq!(1, summary.exits());
+
+        let top = summary.top(10);
+        assert_eq!(2, top.len());
+        assert_eq!(&exe("cc1"), top[0].0);
+        assert_eq!(2, top[0].1.execs());
+        assert_eq!(1, top[0].1.exits());
+        assert_eq!(&exe("ld"), top[1].0);
+        assert_eq!(1, top[1].1.execs());
+        assert_eq!(0, top[1].1.exits());
+    }
+
+    #[test]
+    fn summary_computes_average_lifetimes() {
+        let now = Instant::now();
+        let mut summary = Summary::new();
+        summary.exec(PID::from(1), exe("cc1"), now);
+        summary.exec(PID::from(2), exe("cc1"), now);
+        summary.exec(PID::from(3), exe("ld"), now);
+        summary.exit(PID::from(1), now + Duration::from_millis(10));
+        summary.exit(PID::from(2), now + Duration::from_millis(30));
+
+        let top = summary.top(10);
+        assert_eq!(Some(Duration::from_millis(20)), top[0].1.average_lifetime());
+        assert_eq!(None, top[1].1.average_lifetime());
+        assert_eq!(2, top[0].1.histogram().count());
+        assert_eq!(0, top[1].1.histogram().count());
+    }
+
+    #[test]
+    fn summary_accounts_lifetimes_to_the_last_executed_file() {
+        let now = Instant::now();
+        let mut summary = Summary::new();
+        summary.exec(PID::from(1), exe("sh"), now);
+        summary.exec(PID::from(1), exe("game"), now + Duration::from_millis(10));
+        summary.exit(PID::from(1), now + Duration::from_millis(30));
+
+        let top = summary.top(10);
+        assert_eq!(&exe("game"), top[0].0);
+        assert_eq!(Some(Duration::from_millis(20)), top[0].1.average_lifetime());
+        assert_eq!(&exe("sh"), top[1].0);
+        assert_eq!(0, top[1].1.exits());
+    }
+
+    #[test]
+    fn summary_aggregates_users_activity() {
+        let now = Instant::now();
+        let (root, user) = (Some(PUid::from(0)), Some(PUid::from(1000)));
+        let mut summary = Summary::new();
+        summary.exec_by_user(PID::from(1), exe("game"), user, now);
+        summary.exec_by_user(PID::from(2), exe("cc1"), user, now);
+        summary.exec_by_user(PID::from(3), exe("cron"), root, now);
+        summary.exec(PID::from(4), exe("sh"), now);
+        summary.exit(PID::from(1), now + Duration::from_secs(60));
+        summary.exit(PID::from(2), now + Duration::from_secs(2));
+
+        let top = summary.top_users(10);
+        assert_eq!(3, top.len());
+        assert_eq!(user, top[0].0);
+        assert_eq!(2, top[0].1.execs());
+        assert_eq!(2, top[0].1.exits());
+        assert_eq!(Duration::from_secs(62), top[0].1.total_lifetime());
+        assert_eq!(root, top[1].0);
+        assert_eq!(None, top[2].0);
+        assert_eq!(Duration::ZERO, top[2].1.total_lifetime());
+        assert_eq!(1, summary.top_users(1).len());
+    }
+
+    #[test]
+    fn top_returns_most_executed_files() {
+        let now = Instant::now();
+        let mut summary = Summary::new();
+        for (pid, name) in ["b", "a", "c", "c", "b", "c"].iter().enumerate() {
+            summary.exec(PID::from(pid as i32), exe(name), now);
+        }
+
+        let top = summary
+            .top(2)
+            .into_iter()
+            .map(|(exe, stats)| (exe.clone(), stats.execs()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(exe("c"), 3), (exe("b"), 2)], top);
+    }
+}
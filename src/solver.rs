@@ -4,7 +4,16 @@
 //! Process executable solver bounded context.
 
 pub mod domain;
+pub mod resolver;
 pub mod workflow;
 
-pub use domain::{ExecutedFileName, PCmdLine, PEvent, PExe, PID};
-pub use workflow::get_process_executed_file;
+pub use domain::{
+    signal_name, CgroupHierarchy, CmdLineDisplay, Encoding, ExecutedFileName, PCapabilities,
+    PCgroup, PCgroups, PCmdLine, PComm, PCwd, PEnviron, PEvent, PEventRecord, PExe, PExePath,
+    PExitStatus, PIo, PNsPIDs, PRoot, PSchedPolicy, PSecurityLabel, PSessionId, PSetId, PStat,
+    PStatus, PTty, PUid, Quoting, RawPid, SteamAppId, WineExecutables, PID,
+};
+pub use resolver::{Resolver, Resolvers};
+pub use workflow::{
+    get_process_executed_file, get_steam_app_id, is_helper_process, is_wine_service,
+};
@@ -4,57 +4,393 @@
 //! Process executable solver domain model.
 
 use std::{
+    borrow::Cow,
     ffi::{OsStr, OsString},
-    fmt,
+    fmt::{self, Write as _},
     path::{Path, PathBuf},
-    sync::OnceLock,
+    time::Duration,
 };
 
+#[cfg(feature = "serde")]
+mod serialization;
+
+/// Signal sent to the parent process on exit, usually.
+#[cfg(unix)]
+const DEFAULT_EXIT_SIGNAL: u32 = libc::SIGCHLD as u32;
+/// Windows doesn't signal the parent processes on exit.
+#[cfg(not(unix))]
+const DEFAULT_EXIT_SIGNAL: u32 = 0;
+
+/// Marker of the abbreviated command lines.
+const ELLIPSIS: &str = "…";
+
+/// Process ID as represented by the platform: signed on Unix (`pid_t`), and
+/// unsigned on Windows (`DWORD`).
+#[cfg(unix)]
+pub type RawPid = i32;
+/// Process ID as represented by the platform: signed on Unix (`pid_t`), and
+/// unsigned on Windows (`DWORD`).
+#[cfg(windows)]
+pub type RawPid = u32;
+
 /// Process ID.
+///
+/// On Linux, the kernel identifies both processes and threads (tasks) with
+/// IDs of the same kind. The ID of a process is the ID of its thread group,
+/// which is the ID of its first thread.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct PID(i32);
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct PID(RawPid);
 
-/// Monitored process events.
+/// Monitored process events, with the process information reported by the
+/// kernel.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PEvent {
-    Exec(PID),
-    Exit(PID),
+    /// A process executed a file.
+    Exec {
+        /// ID of the thread that executed the file. On Linux, the thread
+        /// becomes the thread group leader when executing a file, so it's
+        /// usually the ID of the process.
+        pid: PID,
+        /// ID of the thread group of the process, the process ID. Only Linux
+        /// distinguishes it, elsewhere it's `pid`.
+        tgid: PID,
+        /// ID of the parent process. The kernel doesn't report it on exec
+        /// events, so it's only known when read from elsewhere.
+        parent_pid: Option<PID>,
+        /// Time of the event, in nanoseconds since boot (`CLOCK_MONOTONIC`).
+        timestamp: u64,
+    },
+    /// A process exited.
+    Exit {
+        /// ID of the process.
+        pid: PID,
+        /// Exit status of the process.
+        exit_code: PExitStatus,
+        /// Signal sent to the parent process on exit, usually `SIGCHLD`.
+        exit_signal: u32,
+    },
+}
+
+/// A monitored process event, along with the kernel provided information
+/// about where and when it happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PEventRecord {
+    /// The process event.
+    pub event: PEvent,
+    /// CPU that reported the event.
+    pub cpu: u32,
+    /// Time of the event, in nanoseconds since boot (`CLOCK_MONOTONIC`).
+    pub timestamp_ns: u64,
 }
 
+/// Process exit status, encoded like the status returned by `waitpid`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct PExitStatus(u32);
+
+/// Process IDs of a process on each of its PID namespaces, from the PID
+/// namespace of the observer to the innermost PID namespace of the process.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PNsPIDs(Vec<PID>);
+
 /// Process executable name.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PExe(OsString);
 
+/// Process executable path, with its symbolic links resolved by the kernel.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PExePath(PathBuf);
+
+/// Process command name, as reported by the kernel. It's usually the
+/// process executable name, truncated to 15 bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PComm(OsString);
+
+/// Process real user ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PUid(u32);
+
+/// Process information from its `/proc/<pid>/stat` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PStat {
+    /// Command name of the process.
+    pub comm: PComm,
+    /// State of the process, like `R` (running) or `S` (sleeping).
+    pub state: char,
+    /// ID of the parent process. It's zero for processes without parent.
+    pub ppid: PID,
+    /// ID of the session of the process, that is, the ID of its session
+    /// leader.
+    pub session: PID,
+    /// Controlling terminal of the process, if any.
+    pub tty: Option<PTty>,
+    /// Nice value of the process, from -20 (highest priority) to 19
+    /// (lowest priority).
+    pub nice: i32,
+    /// Scheduling policy of the process. It's only missing on ancient
+    /// kernels.
+    pub policy: Option<PSchedPolicy>,
+    /// Real-time priority of the process, from 1 to 99 for real-time
+    /// policies, or 0.
+    pub rt_priority: u32,
+    /// Time at which the process started, since boot (`CLOCK_BOOTTIME`).
+    pub start_time: Duration,
+    /// Whether the process is a kernel thread.
+    pub kernel_thread: bool,
+}
+
+/// Scheduling policy of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PSchedPolicy {
+    /// Default time-sharing policy (`SCHED_OTHER`).
+    Other,
+    /// First in, first out real-time policy (`SCHED_FIFO`).
+    Fifo,
+    /// Round-robin real-time policy (`SCHED_RR`).
+    RoundRobin,
+    /// Time-sharing policy for batch processes (`SCHED_BATCH`).
+    Batch,
+    /// Policy for very low priority background processes (`SCHED_IDLE`).
+    Idle,
+    /// Deadline real-time policy (`SCHED_DEADLINE`).
+    Deadline,
+    /// Policy unknown to this program.
+    Unknown(u32),
+}
+
+/// Terminal device number, encoded like the `tty_nr` field of the
+/// `/proc/<pid>/stat` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PTty(u32);
+
+/// Set of Linux capabilities of a process, encoded as a bit mask like on the
+/// `/proc/<pid>/status` file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PCapabilities(u64);
+
+/// Names of the capabilities, indexed by their number.
+const CAPABILITY_NAMES: [&str; 41] = [
+    "cap_chown",
+    "cap_dac_override",
+    "cap_dac_read_search",
+    "cap_fowner",
+    "cap_fsetid",
+    "cap_kill",
+    "cap_setgid",
+    "cap_setuid",
+    "cap_setpcap",
+    "cap_linux_immutable",
+    "cap_net_bind_service",
+    "cap_net_broadcast",
+    "cap_net_admin",
+    "cap_net_raw",
+    "cap_ipc_lock",
+    "cap_ipc_owner",
+    "cap_sys_module",
+    "cap_sys_rawio",
+    "cap_sys_chroot",
+    "cap_sys_ptrace",
+    "cap_sys_pacct",
+    "cap_sys_admin",
+    "cap_sys_boot",
+    "cap_sys_nice",
+    "cap_sys_resource",
+    "cap_sys_time",
+    "cap_sys_tty_config",
+    "cap_mknod",
+    "cap_lease",
+    "cap_audit_write",
+    "cap_audit_control",
+    "cap_setfcap",
+    "cap_mac_override",
+    "cap_mac_admin",
+    "cap_syslog",
+    "cap_wake_alarm",
+    "cap_block_suspend",
+    "cap_audit_read",
+    "cap_perfmon",
+    "cap_bpf",
+    "cap_checkpoint_restore",
+];
+
+/// Process information from its `/proc/<pid>/status` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PStatus {
+    /// Command name of the process.
+    pub name: PComm,
+    /// State of the process, like `R` (running) or `S` (sleeping).
+    pub state: char,
+    /// ID of the parent process. It's zero for processes without parent.
+    pub ppid: PID,
+    /// Real user ID of the process.
+    pub uid: PUid,
+    /// Real group ID of the process.
+    pub gid: u32,
+    /// Process IDs of the process on each of its PID namespaces.
+    pub nspids: PNsPIDs,
+    /// ID of the process tracing the process, if any.
+    pub tracer_pid: Option<PID>,
+    /// Capabilities used by the kernel for the permission checks of the
+    /// process.
+    pub cap_effective: PCapabilities,
+    /// Capabilities the process is allowed to make effective.
+    pub cap_permitted: PCapabilities,
+}
+
+/// Input / output counters of a process, from its `/proc/<pid>/io` file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PIo {
+    /// Bytes read by the process, including reads from pipes and cached
+    /// files.
+    pub rchar: u64,
+    /// Bytes written by the process, including writes to pipes.
+    pub wchar: u64,
+    /// Bytes read from storage by the process.
+    pub read_bytes: u64,
+    /// Bytes written to storage by the process.
+    pub write_bytes: u64,
+}
+
+/// Audit session ID of a process, assigned on login and inherited by all
+/// the processes started from the login session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PSessionId(u32);
+
+/// Path of the control group of a process, relative to the cgroup file
+/// system mount point.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PCgroup(OsString);
+
+/// Security label of a process, assigned by the active Linux security
+/// module. It's the security context on SELinux, like
+/// `system_u:system_r:init_t:s0`, and the profile on AppArmor, like
+/// `unconfined` or `/usr/bin/game (enforce)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PSecurityLabel(OsString);
+
+/// Control group of a process on a cgroup hierarchy.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CgroupHierarchy {
+    /// ID of the hierarchy. It's zero for the unified hierarchy (cgroup v2).
+    pub id: u32,
+    /// Controllers bound to the hierarchy, like `cpu` or `name=systemd`.
+    /// The unified hierarchy has none.
+    pub controllers: Vec<String>,
+    /// Path of the control group of the process on the hierarchy.
+    pub path: PCgroup,
+}
+
+/// Control groups of a process on each of the cgroup hierarchies it belongs
+/// to, covering cgroup v1, v2 and hybrid setups.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PCgroups(Vec<CgroupHierarchy>);
+
+/// Identities granted by the set-user-ID and set-group-ID permissions of a
+/// process executable.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PSetId {
+    /// Owner of a set-user-ID executable, whose privileges the process gets.
+    pub uid: Option<PUid>,
+    /// Group of a set-group-ID executable, whose privileges the process
+    /// gets.
+    pub gid: Option<u32>,
+}
+
+/// Current working directory of a process.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PCwd(PathBuf);
+
+/// Root directory of a process, as seen from the observer mount namespace.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PRoot(PathBuf);
+
 /// Process command line.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PCmdLine(Vec<OsString>);
 
+/// Quoting of the arguments of a displayed [`PCmdLine`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Quoting {
+    /// Arguments are displayed as they are.
+    #[default]
+    None,
+    /// Arguments that are empty or have whitespace or shell special
+    /// characters are single quoted, so the command line can be pasted on a
+    /// POSIX shell.
+    Shell,
+}
+
+/// Encoding of the displayed names and arguments that are not valid UTF-8.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD`.
+    #[default]
+    Lossy,
+    /// Invalid UTF-8 bytes are escaped as `\xNN`, and backslashes as `\\`,
+    /// so the original bytes can be recovered.
+    Escaped,
+}
+
+/// Helper struct to display a [`PCmdLine`] with a given [`Quoting`] and
+/// [`Encoding`], optionally abbreviated.
+#[derive(Debug, Clone, Copy)]
+pub struct CmdLineDisplay<'a> {
+    cmdline: &'a PCmdLine,
+    quoting: Quoting,
+    encoding: Encoding,
+    max_args: Option<usize>,
+    max_len: Option<usize>,
+}
+
+/// Process environment variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PEnviron(Vec<(OsString, OsString)>);
+
+/// Steam application ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SteamAppId(u32);
+
+/// Names of the executables used to run Windows programs through wine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WineExecutables(Vec<OsString>);
+
 /// Name of the file that was executed.
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct ExecutedFileName(OsString);
 
 // --- Implementations
 
-fn proc_path() -> &'static Path {
-    static PROC_PATH: OnceLock<&Path> = OnceLock::new();
-    PROC_PATH.get_or_init(|| Path::new("/proc"))
-}
+impl PID {
+    /// Creates a `PID` from the `raw` process ID of the platform.
+    pub const fn from_raw(raw: RawPid) -> Self {
+        PID(raw)
+    }
 
-impl From<i32> for PID {
-    fn from(value: i32) -> Self {
-        PID(value)
+    /// Returns the raw process ID of the platform.
+    pub const fn as_raw(self) -> RawPid {
+        self.0
     }
 }
 
-impl AsRef<i32> for PID {
-    fn as_ref(&self) -> &i32 {
-        &self.0
+impl From<RawPid> for PID {
+    fn from(value: RawPid) -> Self {
+        PID::from_raw(value)
     }
 }
 
-impl From<PID> for PathBuf {
-    fn from(pid: PID) -> Self {
-        proc_path().join(pid.0.to_string())
+impl AsRef<RawPid> for PID {
+    fn as_ref(&self) -> &RawPid {
+        &self.0
     }
 }
 
@@ -64,13 +400,185 @@ impl fmt::Display for PID {
     }
 }
 
+impl PEvent {
+    /// Creates an exec event of the process with the given `pid`, like the
+    /// former `PEvent::Exec(pid)` variant. The process is its own thread
+    /// group leader and its parent is unknown.
+    pub fn exec(pid: PID) -> Self {
+        PEvent::Exec {
+            pid,
+            tgid: pid,
+            parent_pid: None,
+            timestamp: 0,
+        }
+    }
+
+    /// Creates an exit event of the process with the given `pid` and
+    /// `status`, like the former `PEvent::Exit(pid, status)` variant.
+    pub fn exit(pid: PID, status: PExitStatus) -> Self {
+        PEvent::Exit {
+            pid,
+            exit_code: status,
+            exit_signal: DEFAULT_EXIT_SIGNAL,
+        }
+    }
+
+    /// Returns the ID of the process of the event, as reported. On Linux,
+    /// it's the ID of the thread (task) of the event.
+    pub fn pid(&self) -> PID {
+        match self {
+            PEvent::Exec { pid, .. } | PEvent::Exit { pid, .. } => *pid,
+        }
+    }
+
+    /// Returns the ID of the thread group of the event, shared by every
+    /// thread of the process. Only the exits of thread group leaders are
+    /// reported, so it's the `pid` of exit events.
+    pub fn tgid(&self) -> PID {
+        match self {
+            PEvent::Exec { tgid, .. } => *tgid,
+            PEvent::Exit { pid, .. } => *pid,
+        }
+    }
+}
+
 impl fmt::Display for PEvent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            PEvent::Exec(pid) => write!(f, "Exec({})", pid),
-            PEvent::Exit(pid) => write!(f, "Exit({})", pid),
+            PEvent::Exec { pid, .. } => write!(f, "Exec({})", pid),
+            PEvent::Exit { pid, .. } => write!(f, "Exit({})", pid),
+        }
+    }
+}
+
+impl PExitStatus {
+    /// Returns the exit code of the process, when it exited normally.
+    pub fn code(&self) -> Option<i32> {
+        match self.0 & 0x7f {
+            0 => Some(((self.0 >> 8) & 0xff) as i32),
+            _ => None,
         }
     }
+
+    /// Returns the number of the signal that terminated the process, if any.
+    pub fn signal(&self) -> Option<i32> {
+        match self.0 & 0x7f {
+            0 => None,
+            signal => Some(signal as i32),
+        }
+    }
+
+    /// Returns the symbolic name of the signal that terminated the process,
+    /// like `SIGSEGV`, when it's a known signal.
+    pub fn signal_name(&self) -> Option<&'static str> {
+        self.signal().and_then(signal_name)
+    }
+}
+
+/// Returns the symbolic name of the `signal` number, like `SIGKILL`, or
+/// `None` for unknown and real-time signals.
+pub fn signal_name(signal: i32) -> Option<&'static str> {
+    #[cfg(unix)]
+    {
+        let name = match signal {
+            libc::SIGHUP => "SIGHUP",
+            libc::SIGINT => "SIGINT",
+            libc::SIGQUIT => "SIGQUIT",
+            libc::SIGILL => "SIGILL",
+            libc::SIGTRAP => "SIGTRAP",
+            libc::SIGABRT => "SIGABRT",
+            libc::SIGBUS => "SIGBUS",
+            libc::SIGFPE => "SIGFPE",
+            libc::SIGKILL => "SIGKILL",
+            libc::SIGUSR1 => "SIGUSR1",
+            libc::SIGSEGV => "SIGSEGV",
+            libc::SIGUSR2 => "SIGUSR2",
+            libc::SIGPIPE => "SIGPIPE",
+            libc::SIGALRM => "SIGALRM",
+            libc::SIGTERM => "SIGTERM",
+            libc::SIGCHLD => "SIGCHLD",
+            libc::SIGCONT => "SIGCONT",
+            libc::SIGSTOP => "SIGSTOP",
+            libc::SIGTSTP => "SIGTSTP",
+            libc::SIGTTIN => "SIGTTIN",
+            libc::SIGTTOU => "SIGTTOU",
+            libc::SIGURG => "SIGURG",
+            libc::SIGXCPU => "SIGXCPU",
+            libc::SIGXFSZ => "SIGXFSZ",
+            libc::SIGVTALRM => "SIGVTALRM",
+            libc::SIGPROF => "SIGPROF",
+            libc::SIGWINCH => "SIGWINCH",
+            libc::SIGIO => "SIGIO",
+            libc::SIGSYS => "SIGSYS",
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            libc::SIGSTKFLT => "SIGSTKFLT",
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            libc::SIGPWR => "SIGPWR",
+            _ => return None,
+        };
+        Some(name)
+    }
+    // Processes aren't terminated by signals on other systems.
+    #[cfg(not(unix))]
+    {
+        let _ = signal;
+        None
+    }
+}
+
+impl From<u32> for PExitStatus {
+    fn from(value: u32) -> Self {
+        PExitStatus(value)
+    }
+}
+
+impl AsRef<u32> for PExitStatus {
+    fn as_ref(&self) -> &u32 {
+        &self.0
+    }
+}
+
+impl fmt::Display for PExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.code(), self.signal()) {
+            (Some(code), _) => write!(f, "exit code {}", code),
+            (_, Some(signal)) => match signal_name(signal) {
+                Some(name) => write!(f, "killed by {}", name),
+                None => write!(f, "killed by signal {}", signal),
+            },
+            _ => write!(f, "unknown exit status"),
+        }
+    }
+}
+
+impl PNsPIDs {
+    /// Returns `true` when the process runs on a nested PID namespace, like
+    /// the ones used by containers.
+    pub fn is_nested(&self) -> bool {
+        self.0.len() > 1
+    }
+
+    /// Returns the process ID on the innermost PID namespace of the process.
+    pub fn innermost(&self) -> Option<PID> {
+        self.0.last().copied()
+    }
+
+    /// Returns the process IDs on the nested PID namespaces of the process.
+    pub fn nested(&self) -> &[PID] {
+        self.0.get(1..).unwrap_or_default()
+    }
+}
+
+impl From<Vec<PID>> for PNsPIDs {
+    fn from(value: Vec<PID>) -> Self {
+        PNsPIDs(value)
+    }
+}
+
+impl AsRef<Vec<PID>> for PNsPIDs {
+    fn as_ref(&self) -> &Vec<PID> {
+        &self.0
+    }
 }
 
 impl From<OsString> for PExe {
@@ -85,6 +593,441 @@ impl AsRef<OsStr> for PExe {
     }
 }
 
+impl From<PComm> for PExe {
+    fn from(value: PComm) -> Self {
+        PExe(value.0)
+    }
+}
+
+impl From<OsString> for PComm {
+    fn from(value: OsString) -> Self {
+        PComm(value)
+    }
+}
+
+impl AsRef<OsStr> for PComm {
+    fn as_ref(&self) -> &OsStr {
+        &self.0
+    }
+}
+
+impl From<u32> for PUid {
+    fn from(value: u32) -> Self {
+        PUid(value)
+    }
+}
+
+impl AsRef<u32> for PUid {
+    fn as_ref(&self) -> &u32 {
+        &self.0
+    }
+}
+
+impl fmt::Display for PUid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<u32> for PSchedPolicy {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => PSchedPolicy::Other,
+            1 => PSchedPolicy::Fifo,
+            2 => PSchedPolicy::RoundRobin,
+            3 => PSchedPolicy::Batch,
+            5 => PSchedPolicy::Idle,
+            6 => PSchedPolicy::Deadline,
+            policy => PSchedPolicy::Unknown(policy),
+        }
+    }
+}
+
+impl PSchedPolicy {
+    /// Returns `true` for the real-time policies.
+    pub fn is_real_time(&self) -> bool {
+        matches!(
+            self,
+            PSchedPolicy::Fifo | PSchedPolicy::RoundRobin | PSchedPolicy::Deadline
+        )
+    }
+}
+
+impl fmt::Display for PSchedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PSchedPolicy::Other => write!(f, "other"),
+            PSchedPolicy::Fifo => write!(f, "fifo"),
+            PSchedPolicy::RoundRobin => write!(f, "rr"),
+            PSchedPolicy::Batch => write!(f, "batch"),
+            PSchedPolicy::Idle => write!(f, "idle"),
+            PSchedPolicy::Deadline => write!(f, "deadline"),
+            PSchedPolicy::Unknown(policy) => write!(f, "{}", policy),
+        }
+    }
+}
+
+impl From<u64> for PCapabilities {
+    fn from(value: u64) -> Self {
+        PCapabilities(value)
+    }
+}
+
+impl AsRef<u64> for PCapabilities {
+    fn as_ref(&self) -> &u64 {
+        &self.0
+    }
+}
+
+impl PCapabilities {
+    /// Returns `true` when the set has no capabilities.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` when the set has every capability known to this
+    /// program, as it happens with processes run by root.
+    pub fn is_full(&self) -> bool {
+        self.0 & KNOWN_CAPABILITIES == KNOWN_CAPABILITIES
+    }
+}
+
+/// Bit mask of the capabilities known to this program.
+const KNOWN_CAPABILITIES: u64 = (1 << CAPABILITY_NAMES.len()) - 1;
+
+impl fmt::Display for PCapabilities {
+    /// Formats the set as a comma separated list of capability names, or as
+    /// `none` for the empty set. Sets with most of the known capabilities
+    /// are formatted as `all` followed by the missing ones, like
+    /// `all,-cap_sys_module`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+
+        let name = |capability: u32| match CAPABILITY_NAMES.get(capability as usize) {
+            Some(name) => Cow::Borrowed(*name),
+            None => Cow::Owned(format!("cap_{}", capability)),
+        };
+        let known = self.0 & KNOWN_CAPABILITIES;
+        let mut separator = "";
+        let listed = if known.count_ones() > KNOWN_CAPABILITIES.count_ones() / 2 {
+            write!(f, "all")?;
+            for capability in (0..CAPABILITY_NAMES.len() as u32).filter(|c| known & (1 << c) == 0) {
+                write!(f, ",-{}", name(capability))?;
+            }
+            separator = ",";
+            self.0 & !KNOWN_CAPABILITIES
+        } else {
+            self.0
+        };
+        for capability in (0..u64::BITS).filter(|c| listed & (1 << c) != 0) {
+            write!(f, "{}{}", separator, name(capability))?;
+            separator = ",";
+        }
+        Ok(())
+    }
+}
+
+impl PTty {
+    /// Returns the major device number of the terminal.
+    pub fn major(&self) -> u32 {
+        (self.0 >> 8) & 0xfff
+    }
+
+    /// Returns the minor device number of the terminal.
+    pub fn minor(&self) -> u32 {
+        (self.0 & 0xff) | ((self.0 >> 12) & 0xfff00)
+    }
+}
+
+impl From<u32> for PTty {
+    fn from(value: u32) -> Self {
+        PTty(value)
+    }
+}
+
+impl AsRef<u32> for PTty {
+    fn as_ref(&self) -> &u32 {
+        &self.0
+    }
+}
+
+/// Displays the terminal name, like `pts/3` or `tty2`, or its device numbers
+/// for unknown devices.
+impl fmt::Display for PTty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Device numbers from the kernel `Documentation/admin-guide/devices.txt`
+        match (self.major(), self.minor()) {
+            (major @ 136..=143, minor) => write!(f, "pts/{}", (major - 136) * 256 + minor),
+            (4, minor @ 0..=63) => write!(f, "tty{}", minor),
+            (4, minor) => write!(f, "ttyS{}", minor - 64),
+            (major, minor) => write!(f, "{}:{}", major, minor),
+        }
+    }
+}
+
+impl From<u32> for PSessionId {
+    fn from(value: u32) -> Self {
+        PSessionId(value)
+    }
+}
+
+impl AsRef<u32> for PSessionId {
+    fn as_ref(&self) -> &u32 {
+        &self.0
+    }
+}
+
+impl fmt::Display for PSessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<OsString> for PCgroup {
+    fn from(value: OsString) -> Self {
+        PCgroup(value)
+    }
+}
+
+impl AsRef<OsStr> for PCgroup {
+    fn as_ref(&self) -> &OsStr {
+        &self.0
+    }
+}
+
+impl From<OsString> for PSecurityLabel {
+    fn from(value: OsString) -> Self {
+        PSecurityLabel(value)
+    }
+}
+
+impl AsRef<OsStr> for PSecurityLabel {
+    fn as_ref(&self) -> &OsStr {
+        &self.0
+    }
+}
+
+impl CgroupHierarchy {
+    /// Returns `true` when this is the unified hierarchy (cgroup v2).
+    pub fn is_unified(&self) -> bool {
+        self.id == 0 && self.controllers.is_empty()
+    }
+}
+
+impl PCgroups {
+    /// Returns the control group on the unified hierarchy (cgroup v2), if the
+    /// process belongs to it.
+    pub fn unified(&self) -> Option<&PCgroup> {
+        self.0
+            .iter()
+            .find(|hierarchy| hierarchy.is_unified())
+            .map(|hierarchy| &hierarchy.path)
+    }
+
+    /// Returns the control group on the cgroup v1 hierarchy bound to the
+    /// `controller`, like `cpu` or `name=systemd`.
+    pub fn controller(&self, controller: &str) -> Option<&PCgroup> {
+        self.0
+            .iter()
+            .find(|hierarchy| hierarchy.controllers.iter().any(|c| c == controller))
+            .map(|hierarchy| &hierarchy.path)
+    }
+
+    /// Returns the control group on the unified hierarchy when available, or
+    /// on the first cgroup v1 hierarchy otherwise.
+    pub fn path(&self) -> Option<&PCgroup> {
+        self.unified()
+            .or_else(|| self.0.first().map(|hierarchy| &hierarchy.path))
+    }
+}
+
+impl From<Vec<CgroupHierarchy>> for PCgroups {
+    fn from(value: Vec<CgroupHierarchy>) -> Self {
+        PCgroups(value)
+    }
+}
+
+impl AsRef<[CgroupHierarchy]> for PCgroups {
+    fn as_ref(&self) -> &[CgroupHierarchy] {
+        &self.0
+    }
+}
+
+impl PExePath {
+    /// Returns `true` when the executable is inside the directory `dir` or
+    /// any of its subdirectories. Only whole path components are matched.
+    pub fn is_under(&self, dir: &Path) -> bool {
+        self.0.starts_with(dir)
+    }
+
+    /// Returns `true` when the executable was deleted, as the kernel marks
+    /// its path with a ` (deleted)` suffix.
+    pub fn is_deleted(&self) -> bool {
+        self.0
+            .as_os_str()
+            .as_encoded_bytes()
+            .ends_with(b" (deleted)")
+    }
+}
+
+impl From<PathBuf> for PExePath {
+    fn from(value: PathBuf) -> Self {
+        PExePath(value)
+    }
+}
+
+impl AsRef<Path> for PExePath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl PSetId {
+    /// Returns `true` when the executable is set-user-ID or set-group-ID.
+    pub fn is_set(&self) -> bool {
+        self.uid.is_some() || self.gid.is_some()
+    }
+}
+
+impl From<PathBuf> for PCwd {
+    fn from(value: PathBuf) -> Self {
+        PCwd(value)
+    }
+}
+
+impl AsRef<Path> for PCwd {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl PRoot {
+    /// Returns `true` when the process root directory is not the root
+    /// directory of the observer, as it happens with processes run inside a
+    /// chroot.
+    ///
+    /// Processes on other mount namespaces, like most containers, have their
+    /// root directory outside of the observer mount namespace and it's
+    /// reported as `/`, so they are not detected.
+    pub fn is_chrooted(&self) -> bool {
+        self.0 != Path::new("/")
+    }
+}
+
+impl From<PathBuf> for PRoot {
+    fn from(value: PathBuf) -> Self {
+        PRoot(value)
+    }
+}
+
+impl AsRef<Path> for PRoot {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl PCmdLine {
+    /// Returns the first argument of the command line, usually the name of
+    /// the executed file.
+    pub fn argv0(&self) -> Option<&OsStr> {
+        self.0.first().map(OsString::as_os_str)
+    }
+
+    /// Returns the arguments of the command line, without the first one.
+    pub fn args(&self) -> &[OsString] {
+        self.0.get(1..).unwrap_or_default()
+    }
+
+    /// Returns an iterator over all the arguments of the command line.
+    pub fn iter(&self) -> std::slice::Iter<'_, OsString> {
+        self.0.iter()
+    }
+
+    /// Returns the number of arguments of the command line.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` when the command line has no arguments.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns a copy of the arguments of the command line.
+    pub fn to_vec(&self) -> Vec<OsString> {
+        self.0.clone()
+    }
+
+    /// Returns an object that displays the arguments of the command line
+    /// separated by spaces, quoting them with `quoting`.
+    pub fn display(&self, quoting: Quoting) -> CmdLineDisplay<'_> {
+        CmdLineDisplay {
+            cmdline: self,
+            quoting,
+            encoding: Encoding::default(),
+            max_args: None,
+            max_len: None,
+        }
+    }
+}
+
+impl CmdLineDisplay<'_> {
+    /// Displays the arguments with the given `encoding`.
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        CmdLineDisplay { encoding, ..self }
+    }
+
+    /// Displays at most `max_args` arguments, followed by an ellipsis when
+    /// the command line has more.
+    pub fn max_args(self, max_args: usize) -> Self {
+        CmdLineDisplay {
+            max_args: Some(max_args),
+            ..self
+        }
+    }
+
+    /// Displays at most `max_len` characters, ending with an ellipsis when
+    /// the displayed command line is longer.
+    pub fn max_len(self, max_len: usize) -> Self {
+        CmdLineDisplay {
+            max_len: Some(max_len),
+            ..self
+        }
+    }
+
+    fn write_args(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        let max_args = self.max_args.unwrap_or(usize::MAX);
+        for (index, arg) in self.cmdline.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            if index == max_args {
+                return f.write_str(ELLIPSIS);
+            }
+
+            let arg = self.encoding.encode(arg);
+            match self.quoting {
+                Quoting::Shell if needs_shell_quotes(&arg) => {
+                    write!(f, "'{}'", arg.replace('\'', r"'\''"))?
+                }
+                _ => write!(f, "{}", arg)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> IntoIterator for &'a PCmdLine {
+    type Item = &'a OsString;
+    type IntoIter = std::slice::Iter<'a, OsString>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
 impl From<Vec<OsString>> for PCmdLine {
     fn from(value: Vec<OsString>) -> Self {
         PCmdLine(value)
@@ -99,13 +1042,167 @@ impl AsRef<Vec<OsString>> for PCmdLine {
 
 impl fmt::Display for PCmdLine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let data = self
-            .0
+        write!(f, "[{}]", self.display(Quoting::None))
+    }
+}
+
+impl fmt::Display for CmdLineDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(max_len) = self.max_len else {
+            return self.write_args(f);
+        };
+
+        let mut text = String::new();
+        self.write_args(&mut text)?;
+        match text.char_indices().nth(max_len) {
+            Some(_) => {
+                let end = text
+                    .char_indices()
+                    .nth(max_len.saturating_sub(1))
+                    .map_or(0, |(end, _)| end);
+                write!(f, "{}{}", &text[..end], ELLIPSIS)
+            }
+            None => f.write_str(&text),
+        }
+    }
+}
+
+impl Encoding {
+    /// Returns `value` as a string with this encoding.
+    pub fn encode(self, value: &OsStr) -> Cow<'_, str> {
+        if self == Encoding::Lossy {
+            return value.to_string_lossy();
+        }
+
+        let mut bytes = value.as_encoded_bytes();
+        match std::str::from_utf8(bytes) {
+            Ok(valid) if !valid.contains('\\') => return Cow::Borrowed(valid),
+            _ => {}
+        }
+
+        let mut escaped = String::with_capacity(bytes.len());
+        while !bytes.is_empty() {
+            let (valid, invalid) = match std::str::from_utf8(bytes) {
+                Ok(valid) => (valid, &bytes[bytes.len()..]),
+                Err(e) => {
+                    let (valid, rest) = bytes.split_at(e.valid_up_to());
+                    let invalid = &rest[..e.error_len().unwrap_or(rest.len())];
+                    let valid = std::str::from_utf8(valid).expect("Checked UTF-8 bytes");
+                    (valid, invalid)
+                }
+            };
+            escaped.push_str(&valid.replace('\\', r"\\"));
+            for byte in invalid {
+                let _ = write!(escaped, r"\x{:02x}", byte);
+            }
+            bytes = &bytes[valid.len() + invalid.len()..];
+        }
+
+        Cow::Owned(escaped)
+    }
+}
+
+/// Returns `true` when `arg` must be quoted to be read as a single word by a
+/// POSIX shell.
+fn needs_shell_quotes(arg: &str) -> bool {
+    arg.is_empty()
+        || !arg.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '_' | '-' | '.' | '/' | ',' | ':' | '=' | '+' | '@' | '%')
+        })
+}
+
+impl PEnviron {
+    /// Returns the value of the environment variable `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&OsStr> {
+        self.0
             .iter()
-            .map(|s| s.to_string_lossy())
-            .collect::<Vec<_>>()
-            .join(" ");
-        write!(f, "[{}]", data)
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_os_str())
+    }
+}
+
+impl From<Vec<(OsString, OsString)>> for PEnviron {
+    fn from(value: Vec<(OsString, OsString)>) -> Self {
+        PEnviron(value)
+    }
+}
+
+impl AsRef<Vec<(OsString, OsString)>> for PEnviron {
+    fn as_ref(&self) -> &Vec<(OsString, OsString)> {
+        &self.0
+    }
+}
+
+impl From<u32> for SteamAppId {
+    fn from(value: u32) -> Self {
+        SteamAppId(value)
+    }
+}
+
+impl AsRef<u32> for SteamAppId {
+    fn as_ref(&self) -> &u32 {
+        &self.0
+    }
+}
+
+impl fmt::Display for SteamAppId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl WineExecutables {
+    /// Returns `true` if `name` is the name of a wine executable.
+    ///
+    /// Wine variants named after a wine executable with a suffix, like
+    /// `wine-staging` or `wine64-tkg`, are also recognized.
+    pub fn contains(&self, name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+        self.0.iter().any(|exe| {
+            name.strip_prefix(exe.to_string_lossy().as_ref())
+                .is_some_and(|suffix| {
+                    suffix.is_empty() || suffix.len() > 1 && suffix.starts_with('-')
+                })
+        })
+    }
+}
+
+impl Default for WineExecutables {
+    fn default() -> Self {
+        WineExecutables(vec![
+            OsString::from("wine-preloader"),
+            OsString::from("wine64-preloader"),
+            OsString::from("wine"),
+            OsString::from("wine64"),
+            OsString::from("wineloader"),
+            OsString::from("wineloader64"),
+        ])
+    }
+}
+
+impl From<Vec<OsString>> for WineExecutables {
+    fn from(value: Vec<OsString>) -> Self {
+        WineExecutables(value)
+    }
+}
+
+impl AsRef<Vec<OsString>> for WineExecutables {
+    fn as_ref(&self) -> &Vec<OsString> {
+        &self.0
+    }
+}
+
+/// Extends the executable names with the file names of the given paths.
+impl Extend<OsString> for WineExecutables {
+    fn extend<T: IntoIterator<Item = OsString>>(&mut self, iter: T) {
+        for path in iter {
+            if let Some(name) = Path::new(&path).file_name() {
+                if !self.contains(name) {
+                    self.0.push(name.to_os_string());
+                }
+            }
+        }
     }
 }
 
@@ -115,8 +1212,255 @@ impl From<PExe> for ExecutedFileName {
     }
 }
 
+impl AsRef<OsStr> for ExecutedFileName {
+    fn as_ref(&self) -> &OsStr {
+        &self.0
+    }
+}
+
+impl ExecutedFileName {
+    /// Returns the file name as a string with the given `encoding`.
+    pub fn encode(&self, encoding: Encoding) -> Cow<'_, str> {
+        encoding.encode(&self.0)
+    }
+}
+
 impl fmt::Display for ExecutedFileName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0.to_string_lossy())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::os::unix::ffi::OsStrExt;
+
+    use super::*;
+
+    #[test]
+    fn root_is_chrooted_when_not_the_observer_root() {
+        assert!(!PRoot::from(PathBuf::from("/")).is_chrooted());
+        assert!(PRoot::from(PathBuf::from("/srv/chroot")).is_chrooted());
+    }
+
+    #[test]
+    fn exit_status_returns_exit_code_of_normal_exits() {
+        let status = PExitStatus::from(3 << 8);
+        assert_eq!(Some(3), status.code());
+        assert_eq!(None, status.signal());
+        assert_eq!("exit code 3", status.to_string());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn exit_status_returns_signal_of_killed_processes() {
+        let status = PExitStatus::from(libc::SIGKILL as u32);
+        assert_eq!(None, status.code());
+        assert_eq!(Some(libc::SIGKILL), status.signal());
+        assert_eq!(Some("SIGKILL"), status.signal_name());
+        assert_eq!("killed by SIGKILL", status.to_string());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn unknown_signals_are_displayed_by_number() {
+        assert_eq!(Some("SIGSEGV"), signal_name(11));
+        assert_eq!(None, signal_name(libc::SIGRTMIN() + 1));
+
+        let status = PExitStatus::from(40);
+        assert_eq!(None, status.signal_name());
+        assert_eq!("killed by signal 40", status.to_string());
+        assert_eq!(None, PExitStatus::from(0).signal_name());
+    }
+
+    #[test]
+    fn event_compatibility_constructors_fill_defaults() {
+        let pid = PID::from(1234);
+        assert_eq!(
+            PEvent::Exec {
+                pid,
+                tgid: pid,
+                parent_pid: None,
+                timestamp: 0
+            },
+            PEvent::exec(pid)
+        );
+        assert_eq!(pid, PEvent::exec(pid).pid());
+        assert_eq!(pid, PEvent::exec(pid).tgid());
+
+        let status = PExitStatus::from(3 << 8);
+        assert_eq!(
+            PEvent::Exit {
+                pid,
+                exit_code: status,
+                exit_signal: DEFAULT_EXIT_SIGNAL
+            },
+            PEvent::exit(pid, status)
+        );
+        assert_eq!("Exit(1234)", PEvent::exit(pid, status).to_string());
+    }
+
+    #[test]
+    fn event_distinguishes_thread_and_thread_group_ids() {
+        let event = PEvent::Exec {
+            pid: PID::from_raw(1235),
+            tgid: PID::from_raw(1234),
+            parent_pid: None,
+            timestamp: 0,
+        };
+        assert_eq!(1235, event.pid().as_raw());
+        assert_eq!(1234, event.tgid().as_raw());
+        assert_eq!(PID::from(1234), PID::from_raw(1234));
+    }
+
+    #[test]
+    fn cmdline_accessors_split_argv0_and_args() {
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/game"),
+            OsString::from("--fullscreen"),
+        ]);
+        assert_eq!(Some(OsStr::new("/usr/bin/game")), cmdline.argv0());
+        assert_eq!(&[OsString::from("--fullscreen")], cmdline.args());
+        assert_eq!(2, cmdline.len());
+        assert_eq!(cmdline.as_ref(), &cmdline.to_vec());
+        assert_eq!(
+            cmdline.to_vec(),
+            cmdline.iter().cloned().collect::<Vec<_>>()
+        );
+
+        let empty = PCmdLine::from(Vec::new());
+        assert!(empty.is_empty());
+        assert_eq!(None, empty.argv0());
+        assert!(empty.args().is_empty());
+    }
+
+    #[test]
+    fn cmdline_display_quotes_shell_special_arguments() {
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("game"),
+            OsString::from("--name=My Game"),
+            OsString::from("it's"),
+            OsString::new(),
+            OsString::from("--level=2"),
+        ]);
+        assert_eq!(
+            "game --name=My Game it's  --level=2",
+            cmdline.display(Quoting::None).to_string()
+        );
+        assert_eq!(
+            r"game '--name=My Game' 'it'\''s' '' --level=2",
+            cmdline.display(Quoting::Shell).to_string()
+        );
+        assert_eq!("[game --name=My Game it's  --level=2]", cmdline.to_string());
+    }
+
+    #[test]
+    fn shell_quoting_round_trips_expansions_and_line_breaks() {
+        let cmdline = PCmdLine::from(
+            ["sh", "-c", "echo $HOME *.txt", "a\nb", "x;y", "~user"]
+                .map(OsString::from)
+                .to_vec(),
+        );
+        assert_eq!(
+            "sh -c 'echo $HOME *.txt' 'a\nb' 'x;y' '~user'",
+            cmdline.display(Quoting::Shell).to_string()
+        );
+    }
+
+    #[test]
+    fn cmdline_display_abbreviates_long_command_lines() {
+        let cmdline = PCmdLine::from(
+            ["chrome", "--type=renderer", "--lang=en-US", "--field-trial"]
+                .map(OsString::from)
+                .to_vec(),
+        );
+        let display = cmdline.display(Quoting::None);
+        assert_eq!("chrome --type=renderer …", display.max_args(2).to_string());
+        assert_eq!("…", display.max_args(0).to_string());
+        assert_eq!(
+            "chrome --type=renderer --lang=en-US --field-trial",
+            display.max_args(4).to_string()
+        );
+        assert_eq!("chrome --t…", display.max_len(11).to_string());
+        assert_eq!("…", display.max_len(1).to_string());
+        assert_eq!(
+            "chrome --type=renderer --lang=en-US --field-trial",
+            display.max_len(49).to_string()
+        );
+        assert_eq!("chrome --…", display.max_args(2).max_len(10).to_string());
+
+        let cmdline = PCmdLine::from(vec![OsString::from("ñandú"), OsString::from("ü")]);
+        assert_eq!(
+            "ñan…",
+            cmdline.display(Quoting::None).max_len(4).to_string()
+        );
+    }
+
+    #[test]
+    fn tty_is_displayed_by_name() {
+        assert_eq!("pts/3", PTty::from(34819).to_string());
+        assert_eq!("pts/300", PTty::from((137 << 8) | 44).to_string());
+        assert_eq!("tty2", PTty::from(1026).to_string());
+        assert_eq!("ttyS0", PTty::from(1088).to_string());
+        assert_eq!("5:1", PTty::from(1281).to_string());
+    }
+
+    #[test]
+    fn capabilities_are_displayed_by_name() {
+        assert_eq!("none", PCapabilities::from(0).to_string());
+        assert_eq!("all", PCapabilities::from(0x1ff_ffff_ffff).to_string());
+        assert_eq!(
+            "all,cap_41",
+            PCapabilities::from(0x3ff_ffff_ffff).to_string()
+        );
+        assert_eq!(
+            "all,-cap_sys_resource",
+            PCapabilities::from(0x1ff_feff_ffff).to_string()
+        );
+        assert_eq!(
+            "cap_net_bind_service,cap_net_raw",
+            PCapabilities::from(0x2400).to_string()
+        );
+        assert_eq!(
+            "cap_chown,cap_63",
+            PCapabilities::from(0x8000_0000_0000_0001).to_string()
+        );
+    }
+
+    #[test]
+    fn exe_path_is_under_its_parent_directories() {
+        let path = PExePath::from(PathBuf::from("/usr/games/bin/game"));
+        assert!(path.is_under(Path::new("/usr/games")));
+        assert!(path.is_under(Path::new("/usr/games/")));
+        assert!(path.is_under(Path::new("/")));
+        assert!(!path.is_under(Path::new("/usr/game")));
+        assert!(!path.is_deleted());
+        assert!(PExePath::from(PathBuf::from("/tmp/game (deleted)")).is_deleted());
+        assert!(!path.is_under(Path::new("/opt")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn escaped_encoding_preserves_invalid_utf8_bytes() {
+        let name = OsStr::from_bytes(b"g\\\xe4me\xff\xc3");
+        assert_eq!(
+            "g\\\u{fffd}me\u{fffd}\u{fffd}",
+            Encoding::Lossy.encode(name)
+        );
+        assert_eq!(r"g\\\xe4me\xff\xc3", Encoding::Escaped.encode(name));
+        assert!(matches!(
+            Encoding::Escaped.encode(OsStr::new("gäme")),
+            Cow::Borrowed("gäme")
+        ));
+
+        let cmdline = PCmdLine::from(vec![OsString::from("game"), name.to_os_string()]);
+        assert_eq!(
+            r"game 'g\\\xe4me\xff\xc3'",
+            cmdline
+                .display(Quoting::Shell)
+                .encoding(Encoding::Escaped)
+                .to_string()
+        );
+    }
+}
@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Serde support for the domain types.
+//!
+//! File names and command line arguments are serialized as strings, so they
+//! are readable on text formats like JSON. Invalid UTF-8 sequences are
+//! replaced by `U+FFFD REPLACEMENT CHARACTER`.
+
+use std::ffi::OsString;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{ExecutedFileName, PCmdLine, PExe};
+
+/// Implements `Serialize` and `Deserialize` for a newtype of `OsString`.
+macro_rules! impl_os_string_serde {
+    ($type: ident) => {
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&self.0.to_string_lossy())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer).map(|value| $type(OsString::from(value)))
+            }
+        }
+    };
+}
+
+impl_os_string_serde!(PExe);
+impl_os_string_serde!(ExecutedFileName);
+
+impl Serialize for PCmdLine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|arg| arg.to_string_lossy()))
+    }
+}
+
+impl<'de> Deserialize<'de> for PCmdLine {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<String>::deserialize(deserializer)
+            .map(|args| PCmdLine(args.into_iter().map(OsString::from).collect()))
+    }
+}
@@ -0,0 +1,286 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Process executed file resolvers.
+//!
+//! A [`Resolver`] resolves the file executed by processes started in a
+//! particular way, such as programs run through wine or scripts run by an
+//! interpreter. [`Resolvers`] are applied in priority order until one of them
+//! resolves the executed file.
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    path::Path,
+};
+
+use super::{ExecutedFileName, PCmdLine, PExe, WineExecutables};
+
+pub mod electron;
+pub mod emulator;
+pub mod flatpak;
+pub mod interpreter;
+pub mod wine;
+pub mod wrapper;
+
+pub use electron::ElectronResolver;
+pub use emulator::EmulatorResolver;
+pub use flatpak::FlatpakResolver;
+pub use interpreter::InterpreterResolver;
+pub use wine::WineResolver;
+pub use wrapper::WrapperResolver;
+
+/// Resolves the file executed by a process.
+pub trait Resolver: Send + Sync {
+    /// Returns the name of the file executed by the process started for the
+    /// executable `pexe` with the command line `cmdline`.
+    ///
+    /// Returns `None` when the resolver doesn't handle the process.
+    fn resolve(&self, pexe: &PExe, cmdline: &PCmdLine) -> Option<ExecutedFileName>;
+
+    /// Returns the resolver priority. Resolvers with higher priority are
+    /// applied first.
+    fn priority(&self) -> i32 {
+        0
+    }
+}
+
+/// A collection of resolvers sorted by priority.
+pub struct Resolvers(Vec<Box<dyn Resolver>>);
+
+impl Resolvers {
+    /// Creates an empty collection of resolvers.
+    pub fn empty() -> Self {
+        Resolvers(Vec::new())
+    }
+
+    /// Creates a collection with the built-in resolvers. `wine_executables`
+    /// are used to identify processes run through wine.
+    pub fn with_builtin(wine_executables: WineExecutables) -> Self {
+        let mut resolvers = Resolvers::empty();
+        resolvers.register(WrapperResolver::new(wine_executables.clone()));
+        resolvers.register(EmulatorResolver::new(wine_executables.clone()));
+        resolvers.register(WineResolver::new(wine_executables));
+        resolvers.register(FlatpakResolver);
+        resolvers.register(InterpreterResolver);
+        resolvers.register(ElectronResolver);
+        resolvers
+    }
+
+    /// Registers a `resolver`. Resolvers with the same priority are applied in
+    /// registration order.
+    pub fn register(&mut self, resolver: impl Resolver + 'static) {
+        let position = self
+            .0
+            .iter()
+            .position(|r| r.priority() < resolver.priority())
+            .unwrap_or(self.0.len());
+        self.0.insert(position, Box::new(resolver));
+    }
+
+    /// Returns an iterator over the resolvers in priority order.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Resolver> {
+        self.0.iter().map(|resolver| resolver.as_ref())
+    }
+}
+
+impl Default for Resolvers {
+    fn default() -> Self {
+        Resolvers::with_builtin(WineExecutables::default())
+    }
+}
+
+fn file_name(path: &OsStr) -> Option<OsString> {
+    Path::new(path).file_name().map(OsStr::to_os_string)
+}
+
+/// Strips the leading `env` command, its options and the environment variable
+/// assignments from `cmdline`.
+///
+/// The string of the `-S` option, used to pass several arguments on shebang
+/// lines, is split into words that are parsed as if they were arguments.
+fn strip_env(cmdline: &[OsString]) -> Cow<'_, [OsString]> {
+    if !cmdline
+        .first()
+        .is_some_and(|cmd| Path::new(cmd).file_name() == Some(OsStr::new("env")))
+    {
+        return Cow::Borrowed(cmdline);
+    }
+
+    let mut cmdline = Cow::Borrowed(cmdline);
+    let mut position = 1;
+    while let Some(arg) = cmdline.get(position) {
+        let arg = arg.to_string_lossy();
+        let split = match arg.as_ref() {
+            "-S" | "--split-string" => cmdline
+                .get(position + 1)
+                .map(|string| (string.to_string_lossy().into_owned(), 2)),
+            opt if opt.starts_with("--split-string=") => {
+                Some((opt["--split-string=".len()..].to_string(), 1))
+            }
+            opt if opt.starts_with("-S") => Some((opt[2..].to_string(), 1)),
+            _ => None,
+        };
+        if let Some((string, count)) = split {
+            let mut split_cmdline = cmdline[..position].to_vec();
+            split_cmdline.extend(split_env_string(&string).into_iter().map(OsString::from));
+            split_cmdline.extend_from_slice(&cmdline[position + count..]);
+            cmdline = Cow::Owned(split_cmdline);
+            continue;
+        }
+
+        match arg.as_ref() {
+            // Options taking an argument
+            "-u" | "-C" => position += 2,
+            "--" => {
+                position += 1;
+                break;
+            }
+            opt if opt.starts_with('-') => position += 1,
+            assignment if assignment.contains('=') => position += 1,
+            _ => break,
+        }
+    }
+
+    match cmdline {
+        Cow::Borrowed(cmdline) => Cow::Borrowed(cmdline.get(position..).unwrap_or_default()),
+        Cow::Owned(cmdline) => Cow::Owned(cmdline.get(position..).unwrap_or_default().to_vec()),
+    }
+}
+
+/// Splits the string of the `env -S` option into words, at unquoted
+/// whitespace. Quotes are removed, and backslashes escape the next character
+/// outside single quotes.
+fn split_env_string(string: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quote = None;
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None | Some('"'), '\\') => {
+                if let Some(c) = chars.next() {
+                    word.get_or_insert_with(String::new).push(c);
+                }
+            }
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Returns the file name of the program started by the `target` command line.
+///
+/// Targets run through wine are resolved to the Windows program they run.
+fn target_file_name(target: &[OsString], wine_executables: &WineExecutables) -> Option<OsString> {
+    let target = target
+        .iter()
+        .map(|cmd| Path::new(cmd).file_name().unwrap_or(cmd))
+        .find(|cmd| !wine_executables.contains(cmd))?;
+
+    wine::get_wine_exe_from_path(target).or_else(|| file_name(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NamedResolver(&'static str, i32);
+
+    impl Resolver for NamedResolver {
+        fn resolve(&self, _pexe: &PExe, _cmdline: &PCmdLine) -> Option<ExecutedFileName> {
+            Some(PExe::from(OsString::from(self.0)).into())
+        }
+
+        fn priority(&self) -> i32 {
+            self.1
+        }
+    }
+
+    #[test]
+    fn register_sorts_resolvers_by_priority() {
+        let mut resolvers = Resolvers::empty();
+        resolvers.register(NamedResolver("low", -10));
+        resolvers.register(NamedResolver("high", 10));
+        resolvers.register(NamedResolver("first", 0));
+        resolvers.register(NamedResolver("second", 0));
+
+        let exe = PExe::from(OsString::from("exe"));
+        let cmdline = PCmdLine::from(vec![OsString::from("exe")]);
+        let names = resolvers
+            .iter()
+            .filter_map(|resolver| resolver.resolve(&exe, &cmdline))
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["high", "first", "second", "low"], names);
+    }
+
+    #[test]
+    fn strip_env_returns_cmdline_without_env_prefix() {
+        let cmdline = vec![
+            OsString::from("/usr/bin/env"),
+            OsString::from("-i"),
+            OsString::from("-u"),
+            OsString::from("DISPLAY"),
+            OsString::from("VAR=value"),
+            OsString::from("game"),
+            OsString::from("OPTION=value"),
+        ];
+        assert_eq!(
+            &[OsString::from("game"), OsString::from("OPTION=value")],
+            strip_env(&cmdline).as_ref()
+        );
+    }
+
+    #[test]
+    fn strip_env_parses_the_words_of_split_strings() {
+        let cmdline = vec![
+            OsString::from("/usr/bin/env"),
+            OsString::from("-S"),
+            OsString::from("WINEDEBUG=-all wine start /unix 'My App.exe'"),
+            OsString::from("-windowed"),
+        ];
+        assert_eq!(
+            &[
+                OsString::from("wine"),
+                OsString::from("start"),
+                OsString::from("/unix"),
+                OsString::from("My App.exe"),
+                OsString::from("-windowed")
+            ],
+            strip_env(&cmdline).as_ref()
+        );
+
+        let cmdline = vec![
+            OsString::from("env"),
+            OsString::from("-S-i python3"),
+            OsString::from("script.py"),
+        ];
+        assert_eq!(
+            &[OsString::from("python3"), OsString::from("script.py")],
+            strip_env(&cmdline).as_ref()
+        );
+    }
+
+    #[test]
+    fn split_env_string_splits_at_unquoted_whitespace() {
+        assert_eq!(
+            vec!["a b", "c\"d", "", "e f", "g"],
+            split_env_string(r#"  "a b" c\"d '' e\ f g "#)
+        );
+        assert!(split_env_string("").is_empty());
+    }
+
+    #[test]
+    fn strip_env_returns_cmdline_without_env() {
+        let cmdline = vec![OsString::from("/usr/bin/game"), OsString::from("VAR=value")];
+        assert_eq!(cmdline.as_slice(), strip_env(&cmdline).as_ref());
+    }
+}
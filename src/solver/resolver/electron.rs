@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Resolver for Electron applications.
+
+use std::{
+    ffi::{OsStr, OsString},
+    path::Path,
+};
+
+use super::Resolver;
+use crate::solver::{ExecutedFileName, PCmdLine, PExe};
+
+/// Resolves Electron applications started through a shared `electron`
+/// executable to the application name.
+pub struct ElectronResolver;
+
+impl Resolver for ElectronResolver {
+    fn resolve(&self, pexe: &PExe, cmdline: &PCmdLine) -> Option<ExecutedFileName> {
+        if !is_electron_executable(pexe.as_ref()) {
+            return None;
+        }
+
+        electron_app_name(cmdline.as_ref()).map(|name| PExe::from(name).into())
+    }
+
+    fn priority(&self) -> i32 {
+        10
+    }
+}
+
+fn is_electron_executable(exe: &OsStr) -> bool {
+    // Distributions may ship versioned electron executables, like electron25
+    exe.to_string_lossy()
+        .strip_prefix("electron")
+        .is_some_and(|version| version.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn electron_app_name(cmdline: &[OsString]) -> Option<OsString> {
+    let mut args = cmdline.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let arg_str = arg.to_string_lossy();
+        if let Some(app) = arg_str.strip_prefix("--app=") {
+            return electron_app_name_from_path(OsStr::new(app));
+        }
+        if arg_str == "--app" {
+            return args.next().and_then(|app| electron_app_name_from_path(app));
+        }
+        if !arg_str.starts_with('-') {
+            return electron_app_name_from_path(arg);
+        }
+    }
+
+    // Applications bundling their own electron executable are installed on a
+    // directory named after them.
+    cmdline
+        .first()
+        .and_then(|exe| Path::new(exe).parent())
+        .and_then(Path::file_name)
+        .filter(|dir| *dir != "bin" && !is_electron_executable(dir))
+        .map(OsStr::to_os_string)
+}
+
+fn electron_app_name_from_path(path: &OsStr) -> Option<OsString> {
+    Path::new(path)
+        .components()
+        .rev()
+        .map(|component| Path::new(component.as_os_str()))
+        .find(|component| {
+            !["app", "app.asar", "resources"]
+                .iter()
+                .any(|name| component.as_os_str() == *name)
+        })
+        .and_then(|component| match component.extension() {
+            Some(ext) if ext == "asar" || ext == "js" => component.file_stem(),
+            _ => component.file_name(),
+        })
+        .map(OsStr::to_os_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_electron_executable_returns_true_for_electron_executables() {
+        assert!(is_electron_executable(OsStr::new("electron")));
+        assert!(is_electron_executable(OsStr::new("electron25")));
+    }
+
+    #[test]
+    fn is_electron_executable_returns_false_for_non_electron_executables() {
+        assert!(!is_electron_executable(OsStr::new("electron-app")));
+        assert!(!is_electron_executable(OsStr::new("slack")));
+    }
+
+    #[test]
+    fn electron_app_name_returns_app_name_from_app_argument() {
+        let cmdline = vec![
+            OsString::from("/usr/lib/electron25/electron"),
+            OsString::from("--app=/usr/lib/signal-desktop/resources/app.asar"),
+        ];
+        assert_eq!(Some("signal-desktop".into()), electron_app_name(&cmdline));
+
+        let cmdline = vec![
+            OsString::from("/usr/lib/electron25/electron"),
+            OsString::from("--app"),
+            OsString::from("/usr/share/app-name/app"),
+        ];
+        assert_eq!(Some("app-name".into()), electron_app_name(&cmdline));
+    }
+
+    #[test]
+    fn electron_app_name_returns_app_name_from_app_path() {
+        let cmdline = vec![
+            OsString::from("electron"),
+            OsString::from("--no-sandbox"),
+            OsString::from("/usr/lib/obsidian/obsidian.asar"),
+        ];
+        assert_eq!(Some("obsidian".into()), electron_app_name(&cmdline));
+
+        let cmdline = vec![
+            OsString::from("electron"),
+            OsString::from("/usr/lib/code/out/cli.js"),
+        ];
+        assert_eq!(Some("cli".into()), electron_app_name(&cmdline));
+    }
+
+    #[test]
+    fn electron_app_name_returns_app_name_from_executable_parent_directory() {
+        let cmdline = vec![OsString::from("/opt/App/electron")];
+        assert_eq!(Some("App".into()), electron_app_name(&cmdline));
+    }
+
+    #[test]
+    fn electron_app_name_returns_none_from_generic_executable_parent_directory() {
+        let cmdline = vec![OsString::from("/usr/bin/electron")];
+        assert_eq!(None, electron_app_name(&cmdline));
+
+        let cmdline = vec![OsString::from("/usr/lib/electron25/electron")];
+        assert_eq!(None, electron_app_name(&cmdline));
+
+        let cmdline = vec![OsString::from("electron")];
+        assert_eq!(None, electron_app_name(&cmdline));
+    }
+}
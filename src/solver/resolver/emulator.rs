@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Resolver for programs run through emulators and translation layers.
+
+use std::{
+    ffi::{OsStr, OsString},
+    sync::OnceLock,
+};
+
+use super::{target_file_name, Resolver};
+use crate::solver::{ExecutedFileName, PCmdLine, PExe, WineExecutables};
+
+/// Resolves programs run through user space emulators and translation layers
+/// (`box86`, `box64`, `FEX-Emu` and `qemu-user`) to the emulated program.
+///
+/// Emulated programs run through wine are resolved to the Windows program they
+/// run.
+pub struct EmulatorResolver(WineExecutables);
+
+impl EmulatorResolver {
+    /// Creates a resolver for emulator processes. `wine_executables` are used
+    /// to identify emulated programs run through wine.
+    pub fn new(wine_executables: WineExecutables) -> Self {
+        EmulatorResolver(wine_executables)
+    }
+}
+
+impl Resolver for EmulatorResolver {
+    fn resolve(&self, pexe: &PExe, cmdline: &PCmdLine) -> Option<ExecutedFileName> {
+        let guest = if translator_executables().contains(&pexe.as_ref()) {
+            translator_guest_cmdline(cmdline.as_ref())
+        } else if is_qemu_user_executable(pexe.as_ref()) {
+            qemu_guest_cmdline(cmdline.as_ref())
+        } else {
+            return None;
+        };
+
+        target_file_name(guest, &self.0).map(|name| PExe::from(name).into())
+    }
+
+    fn priority(&self) -> i32 {
+        40
+    }
+}
+
+fn translator_executables() -> &'static Vec<&'static OsStr> {
+    static TRANSLATOR_EXECUTABLES: OnceLock<Vec<&'static OsStr>> = OnceLock::new();
+    TRANSLATOR_EXECUTABLES.get_or_init(|| {
+        vec![
+            OsStr::new("box86"),
+            OsStr::new("box64"),
+            OsStr::new("FEXInterpreter"),
+            OsStr::new("FEXLoader"),
+        ]
+    })
+}
+
+fn translator_guest_cmdline(cmdline: &[OsString]) -> &[OsString] {
+    let options = cmdline
+        .iter()
+        .skip(1)
+        .take_while(|arg| arg.to_string_lossy().starts_with('-'))
+        .count();
+
+    cmdline.get(1 + options..).unwrap_or_default()
+}
+
+fn qemu_user_architectures() -> &'static Vec<&'static str> {
+    static QEMU_USER_ARCHITECTURES: OnceLock<Vec<&'static str>> = OnceLock::new();
+    QEMU_USER_ARCHITECTURES.get_or_init(|| {
+        vec![
+            "aarch64",
+            "aarch64_be",
+            "alpha",
+            "arm",
+            "armeb",
+            "hexagon",
+            "hppa",
+            "i386",
+            "loongarch64",
+            "m68k",
+            "microblaze",
+            "microblazeel",
+            "mips",
+            "mips64",
+            "mips64el",
+            "mipsel",
+            "mipsn32",
+            "mipsn32el",
+            "or1k",
+            "ppc",
+            "ppc64",
+            "ppc64le",
+            "riscv32",
+            "riscv64",
+            "s390x",
+            "sh4",
+            "sh4eb",
+            "sparc",
+            "sparc32plus",
+            "sparc64",
+            "x86_64",
+            "xtensa",
+            "xtensaeb",
+        ]
+    })
+}
+
+fn is_qemu_user_executable(exe: &OsStr) -> bool {
+    // qemu-user executables are named qemu-<arch>, with an optional -static
+    // suffix on statically linked builds
+    exe.to_string_lossy()
+        .strip_prefix("qemu-")
+        .map(|arch| arch.strip_suffix("-static").unwrap_or(arch))
+        .is_some_and(|arch| qemu_user_architectures().contains(&arch))
+}
+
+fn qemu_guest_cmdline(cmdline: &[OsString]) -> &[OsString] {
+    let mut position = 1;
+
+    while let Some(arg) = cmdline.get(position) {
+        let arg = arg.to_string_lossy();
+        match arg.strip_prefix('-').map(|opt| opt.trim_start_matches('-')) {
+            // Options taking an argument
+            Some(
+                "L" | "s" | "cpu" | "E" | "U" | "0" | "r" | "g" | "B" | "R" | "d" | "D" | "dfilter"
+                | "p" | "seed" | "trace" | "plugin",
+            ) => position += 2,
+            Some(_) => position += 1,
+            None => break,
+        }
+    }
+
+    cmdline.get(position..).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_qemu_user_executable_returns_true_for_qemu_user_executables() {
+        assert!(is_qemu_user_executable(OsStr::new("qemu-x86_64")));
+        assert!(is_qemu_user_executable(OsStr::new("qemu-i386-static")));
+    }
+
+    #[test]
+    fn is_qemu_user_executable_returns_false_for_non_qemu_user_executables() {
+        assert!(!is_qemu_user_executable(OsStr::new("qemu-system-x86_64")));
+        assert!(!is_qemu_user_executable(OsStr::new("qemu-img")));
+        assert!(!is_qemu_user_executable(OsStr::new("box64")));
+    }
+
+    #[test]
+    fn qemu_guest_cmdline_returns_guest_cmdline() {
+        let cmdline = vec![
+            OsString::from("/usr/bin/qemu-x86_64-static"),
+            OsString::from("-L"),
+            OsString::from("/usr/x86_64-linux-gnu"),
+            OsString::from("-strace"),
+            OsString::from("/opt/game/game.x86_64"),
+            OsString::from("-windowed"),
+        ];
+        assert_eq!(
+            &[
+                OsString::from("/opt/game/game.x86_64"),
+                OsString::from("-windowed")
+            ],
+            qemu_guest_cmdline(&cmdline)
+        );
+    }
+
+    #[test]
+    fn qemu_guest_cmdline_returns_empty_cmdline_without_guest() {
+        let cmdline = vec![OsString::from("qemu-x86_64"), OsString::from("-cpu")];
+        assert!(qemu_guest_cmdline(&cmdline).is_empty());
+    }
+
+    #[test]
+    fn translator_guest_cmdline_returns_empty_cmdline_without_guest() {
+        assert!(translator_guest_cmdline(&[]).is_empty());
+        let cmdline = vec![OsString::from("box64"), OsString::from("--version")];
+        assert!(translator_guest_cmdline(&cmdline).is_empty());
+    }
+
+    #[test]
+    fn resolve_returns_none_for_non_emulator_processes() {
+        let exe = PExe::from(OsString::from("cat"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/cat"),
+            OsString::from("/opt/game/game.x86_64"),
+        ]);
+        assert_eq!(
+            None,
+            EmulatorResolver::new(WineExecutables::default()).resolve(&exe, &cmdline)
+        );
+    }
+
+    #[test]
+    fn resolve_returns_guest_file_name_for_translator_processes() {
+        let exe = PExe::from(OsString::from("box64"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/local/bin/box64"),
+            OsString::from("/opt/game/game.x86_64"),
+        ]);
+        assert_eq!(
+            Some(ExecutedFileName::from(PExe::from(OsString::from(
+                "game.x86_64"
+            )))),
+            EmulatorResolver::new(WineExecutables::default()).resolve(&exe, &cmdline)
+        );
+    }
+
+    #[test]
+    fn resolve_returns_executed_windows_exe_for_emulated_wine_processes() {
+        let exe = PExe::from(OsString::from("FEXInterpreter"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/FEXInterpreter"),
+            OsString::from("/usr/bin/wine64"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ]);
+        assert_eq!(
+            Some(ExecutedFileName::from(PExe::from(OsString::from(
+                "App.exe"
+            )))),
+            EmulatorResolver::new(WineExecutables::default()).resolve(&exe, &cmdline)
+        );
+    }
+
+    #[test]
+    fn resolve_returns_guest_file_name_for_qemu_user_processes() {
+        let exe = PExe::from(OsString::from("qemu-aarch64"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/qemu-aarch64"),
+            OsString::from("-cpu"),
+            OsString::from("max"),
+            OsString::from("/opt/app/app"),
+        ]);
+        assert_eq!(
+            Some(ExecutedFileName::from(PExe::from(OsString::from("app")))),
+            EmulatorResolver::new(WineExecutables::default()).resolve(&exe, &cmdline)
+        );
+    }
+}
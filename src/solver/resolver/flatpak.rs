@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Resolver for Flatpak applications.
+
+use std::ffi::OsString;
+
+use super::Resolver;
+use crate::solver::{ExecutedFileName, PCmdLine, PExe};
+
+/// Resolves Flatpak applications started through `flatpak run` to the
+/// application ID.
+pub struct FlatpakResolver;
+
+impl Resolver for FlatpakResolver {
+    fn resolve(&self, pexe: &PExe, cmdline: &PCmdLine) -> Option<ExecutedFileName> {
+        if pexe.as_ref() != "flatpak" {
+            return None;
+        }
+
+        flatpak_app_id(cmdline.as_ref()).map(|app_id| PExe::from(app_id).into())
+    }
+
+    fn priority(&self) -> i32 {
+        20
+    }
+}
+
+fn flatpak_app_id(cmdline: &[OsString]) -> Option<OsString> {
+    let is_option = |arg: &&OsString| arg.to_string_lossy().starts_with('-');
+
+    let mut args = cmdline.iter().skip(1).skip_while(is_option);
+    if args.next()? != "run" {
+        return None;
+    }
+
+    // Run options must use the --option=value form
+    args.find(|arg| !is_option(arg)).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flatpak_app_id_returns_none_from_non_run_cmdline() {
+        let cmdline = vec![OsString::from("/usr/bin/flatpak")];
+        assert_eq!(None, flatpak_app_id(&cmdline));
+
+        let cmdline = vec![
+            OsString::from("/usr/bin/flatpak"),
+            OsString::from("update"),
+            OsString::from("org.app.Name"),
+        ];
+        assert_eq!(None, flatpak_app_id(&cmdline));
+    }
+
+    #[test]
+    fn flatpak_app_id_returns_app_id_from_run_cmdline() {
+        let cmdline = vec![
+            OsString::from("/usr/bin/flatpak"),
+            OsString::from("run"),
+            OsString::from("org.app.Name"),
+        ];
+        assert_eq!(Some("org.app.Name".into()), flatpak_app_id(&cmdline));
+
+        let cmdline = vec![
+            OsString::from("/usr/bin/flatpak"),
+            OsString::from("--verbose"),
+            OsString::from("run"),
+            OsString::from("--branch=stable"),
+            OsString::from("--arch=x86_64"),
+            OsString::from("--command=app"),
+            OsString::from("org.app.Name"),
+            OsString::from("file.txt"),
+        ];
+        assert_eq!(Some("org.app.Name".into()), flatpak_app_id(&cmdline));
+    }
+}
@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Resolver for scripts run by interpreters.
+
+use std::{
+    ffi::{OsStr, OsString},
+    sync::OnceLock,
+};
+
+use super::{file_name, Resolver};
+use crate::solver::{ExecutedFileName, PCmdLine, PExe};
+
+/// Resolves scripts run by shell and python interpreters to the script file
+/// name.
+pub struct InterpreterResolver;
+
+impl Resolver for InterpreterResolver {
+    fn resolve(&self, pexe: &PExe, cmdline: &PCmdLine) -> Option<ExecutedFileName> {
+        let name = if shell_executables().contains(&pexe.as_ref()) {
+            shell_script_file_name(cmdline.as_ref())
+        } else if is_python_executable(pexe.as_ref()) {
+            python_script_file_name(cmdline.as_ref())
+        } else {
+            None
+        };
+
+        name.map(|name| PExe::from(name).into())
+    }
+
+    fn priority(&self) -> i32 {
+        20
+    }
+}
+
+fn shell_executables() -> &'static Vec<&'static OsStr> {
+    static SHELL_EXECUTABLES: OnceLock<Vec<&'static OsStr>> = OnceLock::new();
+    SHELL_EXECUTABLES.get_or_init(|| {
+        vec![
+            OsStr::new("sh"),
+            OsStr::new("bash"),
+            OsStr::new("dash"),
+            OsStr::new("zsh"),
+            OsStr::new("ksh"),
+        ]
+    })
+}
+
+fn shell_script_file_name(cmdline: &[OsString]) -> Option<OsString> {
+    let mut args = cmdline.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        match arg.as_ref() {
+            // The next argument is the script, even if it looks like an option
+            "--" => return args.next().and_then(|script| file_name(script)),
+            // Options taking an argument
+            "-o" | "+o" | "-O" | "+O" | "--rcfile" | "--init-file" => {
+                args.next();
+            }
+            // Long options without arguments
+            opt if opt.starts_with("--") => {}
+            // Short options. Commands read from a string (-c) or from the
+            // standard input (-s) are not scripts.
+            opt if opt.starts_with('-') || opt.starts_with('+') => {
+                if opt.contains(['c', 's']) {
+                    return None;
+                }
+            }
+            script => return file_name(OsStr::new(script)),
+        }
+    }
+
+    None
+}
+
+fn is_python_executable(exe: &OsStr) -> bool {
+    // Python executables are usually versioned, like python3 or python3.12
+    exe.to_string_lossy()
+        .strip_prefix("python")
+        .is_some_and(|version| version.chars().all(|c| c.is_ascii_digit() || c == '.'))
+}
+
+fn python_script_file_name(cmdline: &[OsString]) -> Option<OsString> {
+    let mut args = cmdline.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        match arg.as_ref() {
+            // Commands read from the standard input are not scripts
+            "-" => return None,
+            // The next argument is the script, even if it looks like an option
+            "--" => return args.next().and_then(|script| file_name(script)),
+            // Library modules run as scripts
+            "-m" => return args.next().cloned(),
+            // Options taking an argument
+            "-W" | "-X" | "--check-hash-based-pycs" => {
+                args.next();
+            }
+            // Long options without arguments
+            opt if opt.starts_with("--") => {}
+            // Short options. Commands read from a string (-c) are not scripts.
+            opt if opt.starts_with('-') => {
+                if opt.contains('c') {
+                    return None;
+                }
+            }
+            script => return file_name(OsStr::new(script)),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_script_file_name_returns_none_from_non_script_cmdline() {
+        // interactive shell
+        let cmdline = vec![OsString::from("/bin/bash")];
+        assert_eq!(None, shell_script_file_name(&cmdline));
+
+        // command string
+        let cmdline = vec![
+            OsString::from("/bin/sh"),
+            OsString::from("-c"),
+            OsString::from("echo test.sh"),
+        ];
+        assert_eq!(None, shell_script_file_name(&cmdline));
+
+        // combined flags with command string
+        let cmdline = vec![
+            OsString::from("/bin/sh"),
+            OsString::from("-ec"),
+            OsString::from("echo test.sh"),
+        ];
+        assert_eq!(None, shell_script_file_name(&cmdline));
+
+        // commands from standard input
+        let cmdline = vec![
+            OsString::from("/bin/bash"),
+            OsString::from("-s"),
+            OsString::from("arg"),
+        ];
+        assert_eq!(None, shell_script_file_name(&cmdline));
+    }
+
+    #[test]
+    fn shell_script_file_name_returns_script_file_name_from_script_cmdline() {
+        // script path
+        let cmdline = vec![
+            OsString::from("/bin/bash"),
+            OsString::from("/home/user/bin/script.sh"),
+            OsString::from("arg"),
+        ];
+        assert_eq!(Some("script.sh".into()), shell_script_file_name(&cmdline));
+
+        // script without extension
+        let cmdline = vec![OsString::from("sh"), OsString::from("./start")];
+        assert_eq!(Some("start".into()), shell_script_file_name(&cmdline));
+
+        // shell options
+        let cmdline = vec![
+            OsString::from("/usr/bin/zsh"),
+            OsString::from("-e"),
+            OsString::from("-o"),
+            OsString::from("pipefail"),
+            OsString::from("--norc"),
+            OsString::from("script.zsh"),
+        ];
+        assert_eq!(Some("script.zsh".into()), shell_script_file_name(&cmdline));
+
+        // end of options
+        let cmdline = vec![
+            OsString::from("/bin/bash"),
+            OsString::from("--"),
+            OsString::from("-script.sh"),
+        ];
+        assert_eq!(Some("-script.sh".into()), shell_script_file_name(&cmdline));
+    }
+
+    #[test]
+    fn is_python_executable_returns_true_for_python_executables() {
+        assert!(is_python_executable(OsStr::new("python")));
+        assert!(is_python_executable(OsStr::new("python3")));
+        assert!(is_python_executable(OsStr::new("python3.12")));
+    }
+
+    #[test]
+    fn is_python_executable_returns_false_for_non_python_executables() {
+        assert!(!is_python_executable(OsStr::new("python-config")));
+        assert!(!is_python_executable(OsStr::new("cat")));
+    }
+
+    #[test]
+    fn python_script_file_name_returns_none_from_non_script_cmdline() {
+        // interactive interpreter
+        let cmdline = vec![OsString::from("/usr/bin/python3")];
+        assert_eq!(None, python_script_file_name(&cmdline));
+
+        // command string
+        let cmdline = vec![
+            OsString::from("/usr/bin/python3"),
+            OsString::from("-c"),
+            OsString::from("print('script.py')"),
+        ];
+        assert_eq!(None, python_script_file_name(&cmdline));
+
+        // commands from standard input
+        let cmdline = vec![OsString::from("python3"), OsString::from("-")];
+        assert_eq!(None, python_script_file_name(&cmdline));
+    }
+
+    #[test]
+    fn python_script_file_name_returns_script_file_name_from_script_cmdline() {
+        // script path
+        let cmdline = vec![
+            OsString::from("/usr/bin/python3"),
+            OsString::from("-u"),
+            OsString::from("-X"),
+            OsString::from("dev"),
+            OsString::from("/usr/bin/umu-run"),
+            OsString::from("game.exe"),
+        ];
+        assert_eq!(Some("umu-run".into()), python_script_file_name(&cmdline));
+
+        // module
+        let cmdline = vec![
+            OsString::from("python3"),
+            OsString::from("-m"),
+            OsString::from("http.server"),
+        ];
+        assert_eq!(
+            Some("http.server".into()),
+            python_script_file_name(&cmdline)
+        );
+    }
+
+    #[test]
+    fn resolve_returns_script_file_name_for_interpreted_scripts() {
+        let exe = PExe::from(OsString::from("python3.12"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/python3"),
+            OsString::from("/usr/bin/lutris"),
+        ]);
+        assert_eq!(
+            Some(ExecutedFileName::from(PExe::from(OsString::from("lutris")))),
+            InterpreterResolver.resolve(&exe, &cmdline)
+        );
+    }
+}
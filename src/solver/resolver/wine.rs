@@ -0,0 +1,527 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Resolver for processes run through wine.
+
+use std::{
+    ffi::{OsStr, OsString},
+    path::Path,
+};
+
+use super::{strip_env, Resolver};
+use crate::solver::{ExecutedFileName, PCmdLine, PExe, WineExecutables};
+
+/// Resolves processes run through wine to the Windows program they run.
+///
+/// Wine launcher scripts, such as the CrossOver `wine` script, are also
+/// resolved.
+pub struct WineResolver(WineExecutables);
+
+impl WineResolver {
+    /// Creates a resolver for processes started through any of the
+    /// `wine_executables`.
+    pub fn new(wine_executables: WineExecutables) -> Self {
+        WineResolver(wine_executables)
+    }
+}
+
+impl Resolver for WineResolver {
+    fn resolve(&self, pexe: &PExe, cmdline: &PCmdLine) -> Option<ExecutedFileName> {
+        let cmdline = cmdline.as_ref();
+        let env_cmdline = strip_env(cmdline);
+        let wine_cmdline = if self.0.contains(pexe.as_ref()) {
+            cmdline
+        } else if env_cmdline
+            .first()
+            .is_some_and(|cmd| is_wine_command(cmd, &self.0))
+        {
+            // Wine launched through env
+            &env_cmdline
+        } else if cmdline
+            .get(1)
+            .is_some_and(|cmd| is_wine_executable(cmd, &self.0))
+        {
+            // Wine launcher script run by an interpreter
+            &cmdline[1..]
+        } else {
+            return None;
+        };
+
+        wine_executed_file_name(wine_cmdline, &self.0).map(|name| PExe::from(name).into())
+    }
+
+    fn priority(&self) -> i32 {
+        30
+    }
+}
+
+fn wine_executed_file_name(
+    cmdline: &[OsString],
+    wine_executables: &WineExecutables,
+) -> Option<OsString> {
+    let cmdline = strip_env(cmdline);
+
+    // The first command is run from PATH when wine is launched through env
+    let mut args = cmdline
+        .iter()
+        .enumerate()
+        .skip_while(|(i, cmd)| {
+            is_wine_executable(cmd, wine_executables)
+                || *i == 0 && is_wine_command(cmd, wine_executables)
+        })
+        .map(|(_, cmd)| cmd);
+
+    let mut start = false;
+    while let Some(arg) = args.next() {
+        let arg_str = arg.to_string_lossy();
+        if !start && is_start_command(&arg_str) {
+            start = true;
+            continue;
+        }
+
+        match arg_str.to_lowercase().as_ref() {
+            // CrossOver options
+            "--cx-app" => return args.next().and_then(|app| get_wine_exe_from_path(app)),
+            "--bottle" | "--workdir" | "--debugmsg" | "--dll" | "--cx-log" => {
+                args.next();
+            }
+            "--no-wait" | "--wait" | "--wait-children" | "--new-console" | "--untrusted"
+            | "--no-update" => {}
+            // start options
+            "/d" | "/node" | "/affinity" | "/machine" | "/progidopen" if start => {
+                args.next();
+            }
+            "/unix" | "/exec" | "/wait" | "/w" | "/b" | "/min" | "/max" | "/l" | "/low"
+            | "/normal" | "/high" | "/realtime" | "/abovenormal" | "/belownormal"
+                if start => {}
+            _ => return get_wine_exe_from_path(arg),
+        }
+    }
+
+    None
+}
+
+/// Returns `true` when `cmd` runs the wine `start` program.
+fn is_start_command(cmd: &str) -> bool {
+    let program = cmd.rsplit(['\\', '/']).next().unwrap_or(cmd);
+    program.eq_ignore_ascii_case("start") || program.eq_ignore_ascii_case("start.exe")
+}
+
+/// Returns `true` when `cmd` is a wine executable, either as a path or as a
+/// command name.
+fn is_wine_command(cmd: &OsStr, wine_executables: &WineExecutables) -> bool {
+    Path::new(cmd)
+        .file_name()
+        .is_some_and(|name| wine_executables.contains(name))
+}
+
+fn is_wine_executable(cmd: &OsStr, wine_executables: &WineExecutables) -> bool {
+    let path = Path::new(cmd);
+    let file_name = path.file_name();
+
+    path.is_absolute() && file_name.is_some() && wine_executables.contains(file_name.unwrap())
+}
+
+pub(super) fn get_wine_exe_from_path(cmd: &OsStr) -> Option<OsString> {
+    // Remove leftover quotes from the command line
+    let path = cmd.to_string_lossy();
+    let path = path.trim().trim_matches(['"', '\'']);
+
+    // Try to get the last path component, which should be the app .exe
+    let app_name = strip_windows_path_prefix(path)?
+        .rsplit(['\\', '/'])
+        .next()?;
+
+    // Look for the .exe extension
+    match app_name.rsplit_once('.') {
+        Some((name, extension)) if !name.is_empty() && extension.eq_ignore_ascii_case("exe") => {
+            Some(app_name.into())
+        }
+        _ => None,
+    }
+}
+
+/// Strips the prefix of UNC (`\\server\share\`) and device (`\\?\`,
+/// `\\.\`) Windows paths.
+///
+/// Returns `None` when the path has no components after the prefix.
+fn strip_windows_path_prefix(path: &str) -> Option<&str> {
+    let unc_path = if let Some(path) = path.strip_prefix(r"\\?\UNC\") {
+        path
+    } else if let Some(path) = [r"\\?\", r"\\.\", r"\??\"]
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix))
+    {
+        return Some(path);
+    } else if let Some(path) = path.strip_prefix(r"\\") {
+        path
+    } else {
+        return Some(path);
+    };
+
+    // Skip the server and share names
+    unc_path.splitn(3, '\\').nth(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_wine_executable_returns_true_for_wine_executables_on_absolute_paths() {
+        assert!(is_wine_executable(
+            OsStr::new("/some/path/wine"),
+            &WineExecutables::default()
+        ));
+        assert!(is_wine_executable(
+            OsStr::new("/some/path/wine64"),
+            &WineExecutables::default()
+        ));
+        assert!(is_wine_executable(
+            OsStr::new("/some/path/wine-preloader"),
+            &WineExecutables::default()
+        ));
+        assert!(is_wine_executable(
+            OsStr::new("/some/path/wine64-preloader"),
+            &WineExecutables::default()
+        ));
+        assert!(is_wine_executable(
+            OsStr::new("/some/path/wineloader"),
+            &WineExecutables::default()
+        ));
+        assert!(is_wine_executable(
+            OsStr::new("/some/path/wineloader64"),
+            &WineExecutables::default()
+        ));
+    }
+
+    #[test]
+    fn is_wine_executable_returns_false_for_wine_executables_on_relative_paths() {
+        assert!(!is_wine_executable(
+            OsStr::new("wineloader64"),
+            &WineExecutables::default()
+        ));
+    }
+
+    #[test]
+    fn is_wine_executable_returns_false_for_non_wine_executables() {
+        assert!(!is_wine_executable(
+            OsStr::new("/some/path/executable"),
+            &WineExecutables::default()
+        ));
+        assert!(!is_wine_executable(
+            OsStr::new("executable"),
+            &WineExecutables::default()
+        ));
+    }
+
+    #[test]
+    fn get_wine_exe_from_path_returns_none_from_non_valid_wine_app_paths() {
+        let wine_cmd = OsStr::new("\\");
+        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+
+        let wine_cmd = OsStr::new("C:\\");
+        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+
+        let wine_cmd = OsStr::new("C:\\no_extension");
+        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+
+        let wine_cmd = OsStr::new("C:\\no_exe_extension.txt");
+        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+    }
+
+    #[test]
+    fn get_wine_exe_from_path_returns_exe_from_valid_wine_app_paths() {
+        // back slash
+        let wine_cmd = OsStr::new("C:\\Program Files (x86)\\App\\App.exe");
+        assert_eq!(Some("App.exe".into()), get_wine_exe_from_path(wine_cmd));
+
+        // slash
+        let wine_cmd = OsStr::new("C:/Program Files (x86)/App/App.exe");
+        assert_eq!(Some("App.exe".into()), get_wine_exe_from_path(wine_cmd));
+
+        // slash + back slash
+        let wine_cmd = OsStr::new("C:\\Program Files (x86)\\App/Binaries/App.exe");
+        assert_eq!(Some("App.exe".into()), get_wine_exe_from_path(wine_cmd));
+
+        // unix path
+        let wine_cmd = OsStr::new("/Program Files (x86)/App/Binaries/App.exe");
+        assert_eq!(Some("App.exe".into()), get_wine_exe_from_path(wine_cmd));
+    }
+
+    #[test]
+    fn wine_executed_file_name_returns_none_from_non_wine_launch_cmdline() {
+        let cmdline = vec![OsString::from("/usr/bin/cat")];
+        assert_eq!(
+            None,
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+    }
+
+    #[test]
+    fn wine_executed_file_name_returns_executed_windows_exe_from_wine_launch_cmdline() {
+        // traditional launch
+        let cmdline = vec![
+            OsString::from("/usr/bin/wine-preloader"),
+            OsString::from("/usr/bin/wine"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+
+        // without preloader
+        let cmdline = vec![
+            OsString::from("/usr/bin/wine"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+
+        // wine64 launch
+        let cmdline = vec![
+            OsString::from("/usr/bin/wine64-preloader"),
+            OsString::from("/usr/bin/wine64"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+
+        // unix path launch app argument
+        let cmdline = vec![
+            OsString::from("/usr/bin/wine-preloader"),
+            OsString::from("/usr/bin/wine"),
+            OsString::from("/Program Files (x86)/App/Binaries/App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+    }
+
+    #[test]
+    fn wine_executed_file_name_returns_executed_windows_exe_from_crossover_launch_cmdline() {
+        let cmdline = vec![
+            OsString::from("/opt/cxoffice/bin/wineloader64"),
+            OsString::from("--bottle"),
+            OsString::from("Steam"),
+            OsString::from("--no-wait"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+
+        let cmdline = vec![
+            OsString::from("/opt/cxoffice/bin/wine"),
+            OsString::from("--bottle"),
+            OsString::from("Steam"),
+            OsString::from("--cx-app"),
+            OsString::from("App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+    }
+
+    #[test]
+    fn resolve_returns_executed_windows_exe_for_wine_launcher_scripts() {
+        let exe = PExe::from(OsString::from("perl"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/perl"),
+            OsString::from("/opt/cxoffice/bin/wine"),
+            OsString::from("--bottle"),
+            OsString::from("Steam"),
+            OsString::from("--cx-app"),
+            OsString::from("App.exe"),
+        ]);
+        assert_eq!(
+            Some(ExecutedFileName::from(PExe::from(OsString::from(
+                "App.exe"
+            )))),
+            WineResolver::new(WineExecutables::default()).resolve(&exe, &cmdline)
+        );
+    }
+
+    #[test]
+    fn resolve_returns_none_for_scripts_not_run_through_wine() {
+        let exe = PExe::from(OsString::from("perl"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/perl"),
+            OsString::from("/usr/bin/script.pl"),
+            OsString::from("App.exe"),
+        ]);
+        assert_eq!(
+            None,
+            WineResolver::new(WineExecutables::default()).resolve(&exe, &cmdline)
+        );
+    }
+
+    #[test]
+    fn resolve_returns_executed_windows_exe_for_wine_variants() {
+        let exe = PExe::from(OsString::from("wine64-staging"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/opt/wine-staging/bin/wine64-staging"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ]);
+        assert_eq!(
+            Some(ExecutedFileName::from(PExe::from(OsString::from(
+                "App.exe"
+            )))),
+            WineResolver::new(WineExecutables::default()).resolve(&exe, &cmdline)
+        );
+    }
+
+    #[test]
+    fn wine_executed_file_name_returns_executed_windows_exe_from_start_launch_cmdline() {
+        let cmdline = vec![
+            OsString::from("/usr/bin/wine"),
+            OsString::from("start"),
+            OsString::from("/unix"),
+            OsString::from("/home/user/Games/App/App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+
+        let cmdline = vec![
+            OsString::from("/usr/bin/wine"),
+            OsString::from("C:\\windows\\command\\start.exe"),
+            OsString::from("/d"),
+            OsString::from("C:\\Games"),
+            OsString::from("/wait"),
+            OsString::from("/B"),
+            OsString::from("C:\\Games\\App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+    }
+
+    #[test]
+    fn wine_executed_file_name_returns_executed_windows_exe_from_env_launch_cmdline() {
+        let cmdline = vec![
+            OsString::from("/usr/bin/env"),
+            OsString::from("-u"),
+            OsString::from("DISPLAY"),
+            OsString::from("WINEPREFIX=/home/user/.wine-app"),
+            OsString::from("WINEDEBUG=-all"),
+            OsString::from("wine"),
+            OsString::from("App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wine_executed_file_name(&cmdline, &WineExecutables::default())
+        );
+    }
+
+    #[test]
+    fn resolve_returns_executed_windows_exe_for_env_processes_launching_wine() {
+        let exe = PExe::from(OsString::from("env"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("env"),
+            OsString::from("WINEPREFIX=/home/user/.wine-app"),
+            OsString::from("wine"),
+            OsString::from("start"),
+            OsString::from("/unix"),
+            OsString::from("/home/user/Games/App/App.exe"),
+        ]);
+        assert_eq!(
+            Some(ExecutedFileName::from(PExe::from(OsString::from(
+                "App.exe"
+            )))),
+            WineResolver::new(WineExecutables::default()).resolve(&exe, &cmdline)
+        );
+    }
+
+    #[test]
+    fn resolve_returns_executed_windows_exe_for_env_processes_splitting_strings() {
+        let exe = PExe::from(OsString::from("env"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/env"),
+            OsString::from("-S"),
+            OsString::from("wine start /unix app.exe"),
+        ]);
+        assert_eq!(
+            Some(ExecutedFileName::from(PExe::from(OsString::from(
+                "app.exe"
+            )))),
+            WineResolver::new(WineExecutables::default()).resolve(&exe, &cmdline)
+        );
+    }
+
+    #[test]
+    fn get_wine_exe_from_path_returns_none_from_unc_paths_without_components() {
+        let wine_cmd = OsStr::new(r"\\server.exe\share.exe");
+        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+
+        let wine_cmd = OsStr::new(r"\\?\UNC\server.exe\share.exe");
+        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+    }
+
+    /// Generates Windows path forms combining path prefixes, directories,
+    /// separators and quotes around the file `name`.
+    fn windows_path_forms(name: &str) -> Vec<String> {
+        let prefixes = [
+            "",
+            r"C:\",
+            "c:/",
+            "/",
+            r"\\?\C:\",
+            r"\\.\C:\",
+            r"\??\C:\",
+            r"\\server\share\",
+            r"\\?\UNC\server\share\",
+        ];
+        let dirs = ["", r"Program Files (x86)\", r"Games/App\", r"App.exe\"];
+        let quotes = ["", "\"", "'"];
+
+        let mut paths = Vec::new();
+        for prefix in prefixes {
+            for dir in dirs {
+                for quote in quotes {
+                    paths.push(format!("{}{}{}{}{}", quote, prefix, dir, name, quote));
+                    paths.push(format!("  {}{}{}{}", quote, prefix, dir, name));
+                }
+            }
+        }
+        paths
+    }
+
+    #[test]
+    fn get_wine_exe_from_path_returns_exe_from_generated_windows_path_forms() {
+        for name in ["App.exe", "APP.EXE", "My App.v2.exe", "exe.exe"] {
+            for path in windows_path_forms(name) {
+                assert_eq!(
+                    Some(name.into()),
+                    get_wine_exe_from_path(OsStr::new(&path)),
+                    "path: {}",
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn get_wine_exe_from_path_returns_none_from_generated_non_exe_path_forms() {
+        for name in ["App", "App.txt", "exe", ".exe", "App.exe.txt", ""] {
+            for path in windows_path_forms(name) {
+                assert_eq!(
+                    None,
+                    get_wine_exe_from_path(OsStr::new(&path)),
+                    "path: {}",
+                    path
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! Resolver for programs launched through wrappers.
+
+use std::{
+    ffi::{OsStr, OsString},
+    path::Path,
+    sync::OnceLock,
+};
+
+use super::{target_file_name, Resolver};
+use crate::solver::{ExecutedFileName, PCmdLine, PExe, WineExecutables};
+
+/// Resolves programs launched through wrappers (`gamescope`, `gamemoderun`,
+/// `mangohud`...) to the target program.
+///
+/// Targets run through wine are resolved to the Windows program they run.
+pub struct WrapperResolver(WineExecutables);
+
+impl WrapperResolver {
+    /// Creates a resolver for wrapper processes. `wine_executables` are used
+    /// to identify targets run through wine.
+    pub fn new(wine_executables: WineExecutables) -> Self {
+        WrapperResolver(wine_executables)
+    }
+}
+
+impl Resolver for WrapperResolver {
+    fn resolve(&self, _pexe: &PExe, cmdline: &PCmdLine) -> Option<ExecutedFileName> {
+        wrapper_target_file_name(cmdline.as_ref(), &self.0).map(|name| PExe::from(name).into())
+    }
+
+    fn priority(&self) -> i32 {
+        40
+    }
+}
+
+fn wrapper_executables() -> &'static Vec<&'static OsStr> {
+    static WRAPPER_EXECUTABLES: OnceLock<Vec<&'static OsStr>> = OnceLock::new();
+    WRAPPER_EXECUTABLES.get_or_init(|| {
+        vec![
+            OsStr::new("gamescope"),
+            OsStr::new("gamemoderun"),
+            OsStr::new("mangohud"),
+            OsStr::new("umu-run"),
+        ]
+    })
+}
+
+fn is_wrapper_executable(cmd: &OsStr) -> bool {
+    Path::new(cmd)
+        .file_name()
+        .is_some_and(|file_name| wrapper_executables().contains(&file_name))
+}
+
+fn wrapper_target_file_name(
+    cmdline: &[OsString],
+    wine_executables: &WineExecutables,
+) -> Option<OsString> {
+    // Wrappers can be either executables or scripts run by an interpreter
+    let position = cmdline
+        .iter()
+        .take(2)
+        .position(|cmd| is_wrapper_executable(cmd))?;
+
+    target_file_name(unwrap_wrappers(&cmdline[position..]), wine_executables)
+}
+
+fn unwrap_wrappers(mut cmdline: &[OsString]) -> &[OsString] {
+    while let Some(wrapper) = cmdline.first().filter(|cmd| is_wrapper_executable(cmd)) {
+        let args = &cmdline[1..];
+        cmdline = if Path::new(wrapper).ends_with("gamescope") {
+            // gamescope options are followed by -- and the target command line
+            match args.iter().position(|arg| arg == "--") {
+                Some(pos) => &args[pos + 1..],
+                None => &[],
+            }
+        } else {
+            let options = args
+                .iter()
+                .take_while(|arg| arg.to_string_lossy().starts_with('-'))
+                .count();
+            &args[options..]
+        };
+    }
+
+    cmdline
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapper_target_file_name_returns_none_from_non_wrapper_cmdline() {
+        let cmdline = vec![
+            OsString::from("/usr/bin/vim"),
+            OsString::from("/usr/bin/gamemoderun"),
+        ];
+        assert_eq!(
+            None,
+            wrapper_target_file_name(&cmdline, &WineExecutables::default())
+        );
+
+        let cmdline = vec![
+            OsString::from("/usr/bin/vim"),
+            OsString::from("README.md"),
+            OsString::from("/usr/bin/mangohud"),
+        ];
+        assert_eq!(
+            None,
+            wrapper_target_file_name(&cmdline, &WineExecutables::default())
+        );
+    }
+
+    #[test]
+    fn wrapper_target_file_name_returns_none_from_wrapper_cmdline_without_target() {
+        let cmdline = vec![OsString::from("/usr/bin/gamescope"), OsString::from("-f")];
+        assert_eq!(
+            None,
+            wrapper_target_file_name(&cmdline, &WineExecutables::default())
+        );
+
+        let cmdline = vec![
+            OsString::from("/bin/sh"),
+            OsString::from("/usr/bin/mangohud"),
+        ];
+        assert_eq!(
+            None,
+            wrapper_target_file_name(&cmdline, &WineExecutables::default())
+        );
+    }
+
+    #[test]
+    fn wrapper_target_file_name_returns_target_from_wrapper_cmdline() {
+        // executable wrapper
+        let cmdline = vec![
+            OsString::from("/usr/bin/gamescope"),
+            OsString::from("-w"),
+            OsString::from("1920"),
+            OsString::from("-f"),
+            OsString::from("--"),
+            OsString::from("/home/user/games/game"),
+            OsString::from("-fullscreen"),
+        ];
+        assert_eq!(
+            Some("game".into()),
+            wrapper_target_file_name(&cmdline, &WineExecutables::default())
+        );
+
+        // script wrapper
+        let cmdline = vec![
+            OsString::from("/bin/sh"),
+            OsString::from("/usr/bin/mangohud"),
+            OsString::from("--dlsym"),
+            OsString::from("/home/user/games/game"),
+        ];
+        assert_eq!(
+            Some("game".into()),
+            wrapper_target_file_name(&cmdline, &WineExecutables::default())
+        );
+
+        // windows program
+        let cmdline = vec![
+            OsString::from("python3"),
+            OsString::from("/usr/bin/umu-run"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wrapper_target_file_name(&cmdline, &WineExecutables::default())
+        );
+    }
+
+    #[test]
+    fn wrapper_target_file_name_returns_target_from_wrapper_chains() {
+        let cmdline = vec![
+            OsString::from("/usr/bin/gamescope"),
+            OsString::from("--"),
+            OsString::from("gamemoderun"),
+            OsString::from("mangohud"),
+            OsString::from("/usr/bin/wine"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ];
+        assert_eq!(
+            Some("App.exe".into()),
+            wrapper_target_file_name(&cmdline, &WineExecutables::default())
+        );
+    }
+}
@@ -3,214 +3,312 @@
 
 //! Process executable solver workflows.
 
-use std::{
-    ffi::{OsStr, OsString},
-    path::Path,
-    sync::OnceLock,
-};
+use std::{ffi::OsStr, sync::OnceLock};
 
-use super::{ExecutedFileName, PCmdLine, PExe};
+use super::{ExecutedFileName, PCmdLine, PEnviron, PExe, Resolvers, SteamAppId};
 
 /// Returns the file name of the executable that started a process.
-pub fn get_process_executed_file(pexe: PExe, cmdline: &PCmdLine) -> ExecutedFileName {
-    if wine_executables().contains(&pexe.as_ref()) {
-        if let Some(name) = wine_executed_file_name(cmdline.as_ref()) {
-            return PExe::from(name).into();
-        }
-    }
-
-    pexe.into()
-}
-
-fn wine_executables() -> &'static Vec<&'static OsStr> {
-    static WINE_EXECUTABLES: OnceLock<Vec<&'static OsStr>> = OnceLock::new();
-    WINE_EXECUTABLES.get_or_init(|| {
-        vec![
-            OsStr::new("wine-preloader"),
-            OsStr::new("wine64-preloader"),
-            OsStr::new("wine"),
-            OsStr::new("wine64"),
-            OsStr::new("wineloader"),
-            OsStr::new("wineloader64"),
-        ]
-    })
+///
+/// The `resolvers` are applied in priority order until one of them resolves
+/// the executed file. When none of them does, the process executable name is
+/// returned.
+pub fn get_process_executed_file(
+    pexe: PExe,
+    cmdline: &PCmdLine,
+    resolvers: &Resolvers,
+) -> ExecutedFileName {
+    resolvers
+        .iter()
+        .find_map(|resolver| resolver.resolve(&pexe, cmdline))
+        .unwrap_or_else(|| pexe.into())
 }
 
-fn wine_executed_file_name(cmdline: &[OsString]) -> Option<OsString> {
+/// Returns `true` when the process is a helper process of a Chromium based
+/// application, such as Electron applications.
+///
+/// Helper processes (renderers, GPU processes, utilities...) are started by
+/// the application main process with a `--type` argument.
+pub fn is_helper_process(cmdline: &PCmdLine) -> bool {
     cmdline
+        .args()
         .iter()
-        .skip_while(|cmd| is_wine_executable(cmd))
-        .take(1)
-        .flat_map(|cmd| get_wine_exe_from_path(cmd))
-        .last()
+        .any(|arg| arg.to_string_lossy().starts_with("--type="))
 }
 
-fn is_wine_executable(cmd: &OsStr) -> bool {
-    let path = Path::new(cmd);
-    let file_name = path.file_name();
+/// Returns `true` when the executed file is a wine service, that is, one of
+/// the background processes started by wine on a wine prefix.
+pub fn is_wine_service(exe: &ExecutedFileName) -> bool {
+    let exe = exe.as_ref();
+    wine_services()
+        .iter()
+        .any(|service| service.eq_ignore_ascii_case(exe))
+}
 
-    path.is_absolute()
-        && file_name.is_some()
-        && wine_executables().contains(file_name.as_ref().unwrap())
+fn wine_services() -> &'static Vec<&'static OsStr> {
+    static WINE_SERVICES: OnceLock<Vec<&'static OsStr>> = OnceLock::new();
+    WINE_SERVICES.get_or_init(|| {
+        vec![
+            OsStr::new("wineserver"),
+            OsStr::new("wineboot.exe"),
+            OsStr::new("winemenubuilder.exe"),
+            OsStr::new("services.exe"),
+            OsStr::new("winedevice.exe"),
+            OsStr::new("explorer.exe"),
+            OsStr::new("plugplay.exe"),
+            OsStr::new("svchost.exe"),
+            OsStr::new("rpcss.exe"),
+            OsStr::new("tabtip.exe"),
+            OsStr::new("conhost.exe"),
+            OsStr::new("start.exe"),
+        ]
+    })
 }
 
-fn get_wine_exe_from_path(cmd: &OsStr) -> Option<OsString> {
-    // Try to get the last path component, which should be the app .exe
-    match cmd
-        .to_string_lossy()
-        .split(|c| c == '\\' || c == '/')
-        .last()
-    {
-        Some(app_name) => {
-            // Look for the .exe extension
-            match app_name.split('.').last() {
-                Some(extension) if extension.eq_ignore_ascii_case("exe") => Some(app_name.into()),
-                _ => None,
-            }
-        }
-        _ => None,
-    }
+/// Returns the Steam application ID of a process started by the Steam client.
+pub fn get_steam_app_id(environ: &PEnviron) -> Option<SteamAppId> {
+    // Non Steam games added to the library have SteamAppId set to 0 and a
+    // 64 bits SteamGameId, so they are ignored.
+    ["SteamAppId", "SteamGameId"]
+        .iter()
+        .filter_map(|key| environ.get(key))
+        .filter_map(|value| value.to_str()?.parse::<u32>().ok())
+        .find(|app_id| *app_id != 0)
+        .map(SteamAppId::from)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::ffi::OsString;
+
     use super::*;
+    use crate::solver::{Resolver, WineExecutables};
 
     #[test]
-    fn is_wine_executable_returns_true_for_wine_executables_on_absolute_paths() {
-        assert!(is_wine_executable(OsStr::new("/some/path/wine")));
-        assert!(is_wine_executable(OsStr::new("/some/path/wine64")));
-        assert!(is_wine_executable(OsStr::new("/some/path/wine-preloader")));
-        assert!(is_wine_executable(OsStr::new(
-            "/some/path/wine64-preloader"
-        )));
-        assert!(is_wine_executable(OsStr::new("/some/path/wineloader")));
-        assert!(is_wine_executable(OsStr::new("/some/path/wineloader64")));
+    fn is_helper_process_returns_true_for_helper_processes() {
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/opt/Slack/slack"),
+            OsString::from("--type=renderer"),
+            OsString::from("--enable-crash-reporter"),
+        ]);
+        assert!(is_helper_process(&cmdline));
     }
 
     #[test]
-    fn is_wine_executable_returns_false_for_wine_executables_on_relative_paths() {
-        assert!(!is_wine_executable(OsStr::new("wineloader64")));
+    fn is_helper_process_returns_false_for_main_processes() {
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/opt/Slack/slack"),
+            OsString::from("--enable-crash-reporter"),
+        ]);
+        assert!(!is_helper_process(&cmdline));
     }
 
     #[test]
-    fn is_wine_executable_returns_false_for_non_wine_executables() {
-        assert!(!is_wine_executable(OsStr::new("/some/path/executable")));
-        assert!(!is_wine_executable(OsStr::new("executable")));
+    fn get_process_executed_file_returns_the_process_executable_name_for_regular_processes() {
+        let exe = PExe::from(OsString::from("cat"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/cat"),
+            OsString::from("test.log"),
+        ]);
+        assert_eq!(
+            ExecutedFileName::from(exe.clone()),
+            get_process_executed_file(exe, &cmdline, &Resolvers::default())
+        );
     }
 
     #[test]
-    fn get_wine_exe_from_path_returns_none_from_non_valid_wine_app_paths() {
-        let wine_cmd = OsStr::new("\\");
-        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+    fn get_process_executed_file_returns_executed_windows_exe_for_wine_processes_with_preloader() {
+        let exe = PExe::from(OsString::from("wine-preloader"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/wine-preloader"),
+            OsString::from("/usr/bin/wine"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ]);
+        assert_eq!(
+            ExecutedFileName::from(PExe::from(OsString::from("App.exe"))),
+            get_process_executed_file(exe, &cmdline, &Resolvers::default())
+        );
+    }
 
-        let wine_cmd = OsStr::new("C:\\");
-        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+    #[test]
+    fn get_process_executed_file_returns_executed_windows_exe_for_wine_processes_without_preloader()
+    {
+        let exe = PExe::from(OsString::from("wine"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/lib/wine/wine64"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ]);
+        assert_eq!(
+            ExecutedFileName::from(PExe::from(OsString::from("App.exe"))),
+            get_process_executed_file(exe, &cmdline, &Resolvers::default())
+        );
+    }
 
-        let wine_cmd = OsStr::new("C:\\no_extension");
-        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+    #[test]
+    fn get_process_executed_file_returns_script_file_name_for_shell_scripts() {
+        let exe = PExe::from(OsString::from("bash"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/bin/bash"),
+            OsString::from("/usr/bin/launcher.sh"),
+        ]);
+        assert_eq!(
+            ExecutedFileName::from(PExe::from(OsString::from("launcher.sh"))),
+            get_process_executed_file(exe, &cmdline, &Resolvers::default())
+        );
+    }
 
-        let wine_cmd = OsStr::new("C:\\no_exe_extension.txt");
-        assert_eq!(None, get_wine_exe_from_path(wine_cmd));
+    #[test]
+    fn get_process_executed_file_returns_the_shell_name_for_interactive_shells() {
+        let exe = PExe::from(OsString::from("bash"));
+        let cmdline = PCmdLine::from(vec![OsString::from("-bash")]);
+        assert_eq!(
+            ExecutedFileName::from(exe.clone()),
+            get_process_executed_file(exe, &cmdline, &Resolvers::default())
+        );
     }
 
     #[test]
-    fn get_wine_exe_from_path_returns_exe_from_valid_wine_app_paths() {
-        // back slash
-        let wine_cmd = OsStr::new("C:\\Program Files (x86)\\App\\App.exe");
-        assert_eq!(Some("App.exe".into()), get_wine_exe_from_path(wine_cmd));
+    fn get_process_executed_file_returns_app_name_for_electron_apps() {
+        let exe = PExe::from(OsString::from("electron25"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/lib/electron25/electron"),
+            OsString::from("/usr/lib/signal-desktop/resources/app.asar"),
+        ]);
+        assert_eq!(
+            ExecutedFileName::from(PExe::from(OsString::from("signal-desktop"))),
+            get_process_executed_file(exe, &cmdline, &Resolvers::default())
+        );
+    }
 
-        // slash
-        let wine_cmd = OsStr::new("C:/Program Files (x86)/App/App.exe");
-        assert_eq!(Some("App.exe".into()), get_wine_exe_from_path(wine_cmd));
+    #[test]
+    fn get_process_executed_file_returns_executed_windows_exe_for_custom_wine_executables() {
+        let mut wine_executables = WineExecutables::default();
+        wine_executables.extend([OsString::from("/opt/wine-tkg/bin/wine-tkg")]);
 
-        // slash + back slash
-        let wine_cmd = OsStr::new("C:\\Program Files (x86)\\App/Binaries/App.exe");
-        assert_eq!(Some("App.exe".into()), get_wine_exe_from_path(wine_cmd));
+        let exe = PExe::from(OsString::from("wine-tkg"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/opt/wine-tkg/bin/wine-tkg"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ]);
+        assert_eq!(
+            ExecutedFileName::from(PExe::from(OsString::from("App.exe"))),
+            get_process_executed_file(exe, &cmdline, &Resolvers::with_builtin(wine_executables))
+        );
+    }
+
+    #[test]
+    fn get_process_executed_file_returns_the_process_executable_name_for_non_wine_executables() {
+        let wine_executables = WineExecutables::from(vec![OsString::from("wine-tkg")]);
 
-        // unix path
-        let wine_cmd = OsStr::new("/Program Files (x86)/App/Binaries/App.exe");
-        assert_eq!(Some("App.exe".into()), get_wine_exe_from_path(wine_cmd));
+        let exe = PExe::from(OsString::from("wine"));
+        let cmdline = PCmdLine::from(vec![
+            OsString::from("/usr/bin/wine"),
+            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+        ]);
+        assert_eq!(
+            ExecutedFileName::from(exe.clone()),
+            get_process_executed_file(exe, &cmdline, &Resolvers::with_builtin(wine_executables))
+        );
     }
 
     #[test]
-    fn wine_executed_file_name_returns_none_from_non_wine_launch_cmdline() {
-        let cmdline = vec![OsString::from("/usr/bin/cat")];
-        assert_eq!(None, wine_executed_file_name(&cmdline));
+    fn is_wine_service_returns_true_for_wine_services() {
+        let exe = ExecutedFileName::from(PExe::from(OsString::from("wineserver")));
+        assert!(is_wine_service(&exe));
+
+        let exe = ExecutedFileName::from(PExe::from(OsString::from("services.exe")));
+        assert!(is_wine_service(&exe));
+
+        let exe = ExecutedFileName::from(PExe::from(OsString::from("WineDevice.exe")));
+        assert!(is_wine_service(&exe));
     }
 
     #[test]
-    fn wine_executed_file_name_returns_executed_windows_exe_from_wine_launch_cmdline() {
-        // traditional launch
-        let cmdline = vec![
-            OsString::from("/usr/bin/wine-preloader"),
-            OsString::from("/usr/bin/wine"),
-            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
-        ];
-        assert_eq!(Some("App.exe".into()), wine_executed_file_name(&cmdline));
+    fn is_wine_service_returns_false_for_non_wine_services() {
+        let exe = ExecutedFileName::from(PExe::from(OsString::from("App.exe")));
+        assert!(!is_wine_service(&exe));
 
-        // without preloader
-        let cmdline = vec![
-            OsString::from("/usr/bin/wine"),
-            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
-        ];
-        assert_eq!(Some("App.exe".into()), wine_executed_file_name(&cmdline));
+        let exe = ExecutedFileName::from(PExe::from(OsString::from("services")));
+        assert!(!is_wine_service(&exe));
+    }
 
-        // wine64 launch
-        let cmdline = vec![
-            OsString::from("/usr/bin/wine64-preloader"),
-            OsString::from("/usr/bin/wine64"),
-            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
-        ];
-        assert_eq!(Some("App.exe".into()), wine_executed_file_name(&cmdline));
+    #[test]
+    fn get_steam_app_id_returns_none_for_processes_not_started_by_steam() {
+        let environ = PEnviron::from(vec![(OsString::from("HOME"), OsString::from("/home/user"))]);
+        assert_eq!(None, get_steam_app_id(&environ));
+    }
 
-        // unix path launch app argument
-        let cmdline = vec![
-            OsString::from("/usr/bin/wine-preloader"),
-            OsString::from("/usr/bin/wine"),
-            OsString::from("/Program Files (x86)/App/Binaries/App.exe"),
-        ];
-        assert_eq!(Some("App.exe".into()), wine_executed_file_name(&cmdline));
+    #[test]
+    fn get_steam_app_id_returns_app_id_for_processes_started_by_steam() {
+        let environ = PEnviron::from(vec![
+            (OsString::from("SteamAppId"), OsString::from("870780")),
+            (OsString::from("SteamGameId"), OsString::from("870780")),
+        ]);
+        assert_eq!(Some(SteamAppId::from(870780)), get_steam_app_id(&environ));
+
+        let environ = PEnviron::from(vec![(
+            OsString::from("SteamGameId"),
+            OsString::from("870780"),
+        )]);
+        assert_eq!(Some(SteamAppId::from(870780)), get_steam_app_id(&environ));
     }
 
     #[test]
-    fn get_process_executed_file_returns_the_process_executable_name_for_regular_processes() {
-        let exe = PExe::from(OsString::from("cat"));
-        let cmdline = PCmdLine::from(vec![
-            OsString::from("/usr/bin/cat"),
-            OsString::from("test.log"),
+    fn get_steam_app_id_returns_none_for_non_steam_games() {
+        let environ = PEnviron::from(vec![
+            (OsString::from("SteamAppId"), OsString::from("0")),
+            (
+                OsString::from("SteamGameId"),
+                OsString::from("13528596929149779968"),
+            ),
         ]);
-        assert_eq!(
-            ExecutedFileName::from(exe.clone()),
-            get_process_executed_file(exe, &cmdline)
-        );
+        assert_eq!(None, get_steam_app_id(&environ));
     }
 
     #[test]
-    fn get_process_executed_file_returns_executed_windows_exe_for_wine_processes_with_preloader() {
-        let exe = PExe::from(OsString::from("wine-preloader"));
+    fn get_process_executed_file_returns_app_id_for_flatpak_apps() {
+        let exe = PExe::from(OsString::from("flatpak"));
         let cmdline = PCmdLine::from(vec![
-            OsString::from("/usr/bin/wine-preloader"),
-            OsString::from("/usr/bin/wine"),
-            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+            OsString::from("/usr/bin/flatpak"),
+            OsString::from("run"),
+            OsString::from("com.valvesoftware.Steam"),
         ]);
         assert_eq!(
-            ExecutedFileName::from(PExe::from(OsString::from("App.exe"))),
-            get_process_executed_file(exe, &cmdline)
+            ExecutedFileName::from(PExe::from(OsString::from("com.valvesoftware.Steam"))),
+            get_process_executed_file(exe, &cmdline, &Resolvers::default())
         );
     }
 
     #[test]
-    fn get_process_executed_file_returns_executed_windows_exe_for_wine_processes_without_preloader()
-    {
-        let exe = PExe::from(OsString::from("wine"));
+    fn get_process_executed_file_applies_registered_resolvers() {
+        struct CatResolver;
+
+        impl Resolver for CatResolver {
+            fn resolve(&self, pexe: &PExe, cmdline: &PCmdLine) -> Option<ExecutedFileName> {
+                if pexe.as_ref() != "cat" {
+                    return None;
+                }
+                cmdline
+                    .as_ref()
+                    .get(1)
+                    .map(|file| PExe::from(file.clone()).into())
+            }
+
+            fn priority(&self) -> i32 {
+                100
+            }
+        }
+
+        let mut resolvers = Resolvers::default();
+        resolvers.register(CatResolver);
+
+        let exe = PExe::from(OsString::from("cat"));
         let cmdline = PCmdLine::from(vec![
-            OsString::from("/usr/lib/wine/wine64"),
-            OsString::from("C:\\Program Files (x86)\\App\\App.exe"),
+            OsString::from("/usr/bin/cat"),
+            OsString::from("test.log"),
         ]);
         assert_eq!(
-            ExecutedFileName::from(PExe::from(OsString::from("App.exe"))),
-            get_process_executed_file(exe, &cmdline)
+            ExecutedFileName::from(PExe::from(OsString::from("test.log"))),
+            get_process_executed_file(exe, &cmdline, &resolvers)
         );
     }
 }
@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright 2022 Juan Palacios <jpalaciosdev@gmail.com>
+
+//! End-to-end tests of the process events connector.
+//!
+//! Listening to process events requires root privileges or the
+//! `CAP_NET_ADMIN` capability on Linux 6.5 and earlier versions. The tests
+//! are skipped when the connector can't be set up.
+
+use std::{
+    io,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use copes::{
+    io::connector::{MonitoredEvents, ProcessEventsConnector},
+    solver::{PEvent, PID},
+};
+
+/// Maximum time to wait for the events of a child process.
+const EVENTS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns a connector monitoring the given `events`, or `None` when the
+/// process events can't be monitored.
+fn connector(events: MonitoredEvents) -> Option<ProcessEventsConnector> {
+    match ProcessEventsConnector::try_new_with(events) {
+        Ok(connector) => {
+            connector.set_timeout(Duration::from_millis(100)).unwrap();
+            Some(connector)
+        }
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            eprintln!("skipped: process events can't be monitored without privileges");
+            None
+        }
+        Err(e) => panic!("{}: {:?}", e, e.io_error()),
+    }
+}
+
+/// Runs `sh -c script` and returns its PID and the events received for it.
+///
+/// The events of the child process are queued on the connector once it
+/// exits, so they are read until its exit event is received or no more
+/// events are queued.
+fn run_child(connector: &ProcessEventsConnector, script: &str) -> (PID, Vec<PEvent>) {
+    let mut child = Command::new("sh").args(["-c", script]).spawn().unwrap();
+    let pid = PID::from(child.id() as i32);
+    child.wait().unwrap();
+
+    let deadline = Instant::now() + EVENTS_TIMEOUT;
+    let mut records = connector.records();
+    let mut events = Vec::new();
+    while Instant::now() < deadline {
+        let Some(record) = records.next() else {
+            break;
+        };
+        let event = record.unwrap().event;
+        if event.pid() != pid {
+            continue;
+        }
+
+        let exited = matches!(event, PEvent::Exit { .. });
+        events.push(event);
+        if exited {
+            break;
+        }
+    }
+
+    (pid, events)
+}
+
+#[test]
+fn connector_reports_exec_and_exit_events_of_child_processes() {
+    let Some(connector) = connector(MonitoredEvents::All) else {
+        return;
+    };
+
+    let (pid, events) = run_child(&connector, "exit 3");
+    assert!(
+        matches!(events.first(), Some(PEvent::Exec { pid: exec_pid, .. }) if *exec_pid == pid),
+        "no exec event: {:?}",
+        events
+    );
+    match events.last() {
+        Some(PEvent::Exit { exit_code, .. }) => assert_eq!(Some(3), exit_code.code()),
+        _ => panic!("no exit event: {:?}", events),
+    }
+}
+
+#[test]
+fn connector_only_reports_the_monitored_events() {
+    let Some(connector) = connector(MonitoredEvents::Exec) else {
+        return;
+    };
+
+    let (pid, events) = run_child(&connector, "exit 0");
+    assert!(
+        !events.is_empty()
+            && events
+                .iter()
+                .all(|event| matches!(event, PEvent::Exec { .. }) && event.pid() == pid),
+        "unexpected events: {:?}",
+        events
+    );
+}
+
+#[test]
+fn reconnected_connector_keeps_reporting_the_monitored_events() {
+    let Some(mut connector) = connector(MonitoredEvents::Exit) else {
+        return;
+    };
+
+    connector.reconnect().unwrap();
+    let (_, events) = run_child(&connector, "exit 5");
+    match events.as_slice() {
+        [PEvent::Exit { exit_code, .. }] => assert_eq!(Some(5), exit_code.code()),
+        _ => panic!("unexpected events: {:?}", events),
+    }
+}
+
+#[test]
+fn paused_connector_reports_no_events_until_resumed() {
+    let Some(connector) = connector(MonitoredEvents::Exit) else {
+        return;
+    };
+
+    connector.pause().unwrap();
+    assert!(connector.is_paused());
+    let (_, events) = run_child(&connector, "exit 0");
+    assert!(events.is_empty(), "unexpected events: {:?}", events);
+
+    connector.resume().unwrap();
+    let (_, events) = run_child(&connector, "exit 0");
+    assert_eq!(1, events.len(), "unexpected events: {:?}", events);
+}